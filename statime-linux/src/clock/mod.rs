@@ -187,6 +187,12 @@ impl PortTimestampToTime for LinuxClock {
     }
 }
 
+impl<T: PortTimestampToTime + ?Sized> PortTimestampToTime for Box<T> {
+    fn port_timestamp_to_time(&self, ts: timestamped_socket::socket::Timestamp) -> Time {
+        self.as_ref().port_timestamp_to_time(ts)
+    }
+}
+
 impl PortTimestampToTime for OverlayClock<LinuxClock> {
     fn port_timestamp_to_time(&self, ts: timestamped_socket::socket::Timestamp) -> Time {
         let roclock_time = self.underlying().port_timestamp_to_time(ts);
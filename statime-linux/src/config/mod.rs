@@ -1,14 +1,24 @@
 use std::{
     fs::read_to_string,
     net::SocketAddr,
+    ops::RangeInclusive,
     os::unix::fs::PermissionsExt,
     path::{Path, PathBuf},
 };
 
+use arrayvec::ArrayString;
 use log::warn;
 use serde::{Deserialize, Deserializer};
 use statime::{
-    config::{ClockIdentity, DelayMechanism, PtpMinorVersion},
+    config::{
+        AcceptableMaster, AlternateTimescale, ClockIdentity, ClockQuality, DatasetComparison,
+        DelayMechanism, DesiredState, GptpConfig, HoldoverClasses, ManagementSetPolicy,
+        PortAddress, PtpMinorVersion, SecurityAssociation, SecurityKey, UnicastGrantorConfig,
+        UnicastMasterTable, MAX_ALTERNATE_MASTERS, MAX_ALTERNATE_TIMESCALES, MAX_DISPLAY_NAME_LEN,
+        MAX_MONITORING_RECORDS, MAX_SECURITY_KEYS, MAX_SECURITY_KEY_LEN, MAX_UNICAST_GRANTEES,
+        MAX_UNICAST_MASTERS,
+    },
+    filters::KalmanConfiguration,
     time::{Duration, Interval},
 };
 use timestamped_socket::interface::InterfaceName;
@@ -20,10 +30,14 @@ use crate::tracing::LogLevel;
 pub struct Config {
     #[serde(default)]
     pub loglevel: LogLevel,
+    #[serde(default)]
+    pub clock_type: ClockType,
+    #[serde(default)]
+    pub profile: Profile,
     #[serde(default = "default_sdo_id")]
     pub sdo_id: u16,
-    #[serde(default = "default_domain")]
-    pub domain: u8,
+    #[serde(default)]
+    pub domain: Option<u8>,
     #[serde(default = "default_slave_only")]
     pub slave_only: bool,
     #[serde(default, deserialize_with = "deserialize_clock_identity")]
@@ -32,16 +46,143 @@ pub struct Config {
     pub priority1: u8,
     #[serde(default = "default_priority2")]
     pub priority2: u8,
+    #[serde(default = "default_local_priority")]
+    pub local_priority: u8,
     #[serde(default)]
     pub path_trace: bool,
+    #[serde(default)]
+    pub external_port_configuration: bool,
+    #[serde(default)]
+    pub allow_management_set: Vec<ManagementSetField>,
+    #[serde(default, rename = "alternate-timescale")]
+    pub alternate_timescales: Vec<AlternateTimescaleConfig>,
     #[serde(rename = "port")]
     pub ports: Vec<PortConfig>,
+    #[serde(default, rename = "instance")]
+    pub instances: Vec<DomainInstanceConfig>,
     #[serde(default)]
     pub observability: ObservabilityConfig,
     #[serde(default)]
+    pub control: ControlConfig,
+    #[serde(default)]
+    pub authentication: Option<AuthenticationConfig>,
+    #[serde(default)]
+    pub holdover: Option<HoldoverConfig>,
+    #[serde(default)]
+    pub power_profile: Option<PowerProfileConfig>,
+    #[serde(default)]
+    pub synchronization_metadata: Option<SynchronizationMetadataConfig>,
+    #[serde(default)]
+    pub gpsd: Option<GpsdConfig>,
+    #[serde(default)]
+    pub leap_seconds_file: Option<PathBuf>,
+    #[serde(default)]
     pub virtual_system_clock: bool,
 }
 
+/// A PTP instance in another domain, run next to the instance configured at
+/// the top level
+///
+/// Its ports share the sockets of the ports on the same interface, received
+/// messages are passed to the instance of their domain. These instances keep
+/// time on overlay clocks, so only the top level instance steers clocks.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DomainInstanceConfig {
+    pub domain: u8,
+    #[serde(default = "default_sdo_id")]
+    pub sdo_id: u16,
+    #[serde(default = "default_slave_only")]
+    pub slave_only: bool,
+    #[serde(default = "default_priority1")]
+    pub priority1: u8,
+    #[serde(default = "default_priority2")]
+    pub priority2: u8,
+    #[serde(default)]
+    pub path_trace: bool,
+    #[serde(rename = "port")]
+    pub ports: Vec<PortConfig>,
+}
+
+/// The kind of PTP instance the daemon runs
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClockType {
+    /// An ordinary or boundary clock, depending on the number of ports
+    #[default]
+    Ordinary,
+    /// An end-to-end transparent clock, forwarding messages between its ports
+    E2eTransparent,
+    /// A peer-to-peer transparent clock, forwarding messages between its ports
+    /// and measuring the delay of the link on each of them
+    P2pTransparent,
+}
+
+impl ClockType {
+    /// Whether this clock type runs a transparent clock
+    pub fn is_transparent(self) -> bool {
+        matches!(self, ClockType::E2eTransparent | ClockType::P2pTransparent)
+    }
+}
+
+/// The PTP profile the instance follows
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Profile {
+    /// The default profile of IEEE 1588, with all settings as configured
+    #[default]
+    Default,
+    /// IEEE 802.1AS (gPTP), which runs over ethernet with the peer delay
+    /// mechanism
+    Gptp,
+    /// The ITU-T G.8275.1 telecom profile with full timing support from the
+    /// network, which runs over ethernet with the alternate BMCA
+    #[serde(rename = "g8275-1")]
+    G8275_1,
+    /// The ITU-T G.8275.2 telecom profile with partial timing support from the
+    /// network, which runs over unicast IPv4 or IPv6 with the alternate BMCA
+    #[serde(rename = "g8275-2")]
+    G8275_2,
+    /// The IEC/IEEE 61850-9-3 power utility profile, which runs over ethernet
+    /// with the peer delay mechanism
+    #[serde(rename = "power-61850-9-3")]
+    Power61850_9_3,
+    /// The AES67 media profile for audio over IP networks
+    Aes67,
+    /// The SMPTE ST 2059-2 profile for professional broadcast media
+    #[serde(rename = "smpte-2059-2")]
+    Smpte2059_2,
+    /// The enterprise profile for IP networks of IETF RFC 8173
+    Enterprise,
+}
+
+impl Profile {
+    /// The clock quality of an instance following the profile
+    pub fn clock_quality(self, slave_only: bool) -> ClockQuality {
+        match self {
+            // A telecom time slave clock announces class 255
+            Profile::G8275_1 | Profile::G8275_2 if slave_only => ClockQuality {
+                clock_class: 255,
+                ..Default::default()
+            },
+            _ => ClockQuality::default(),
+        }
+    }
+
+    /// The filter settings of the ports of an instance following the profile
+    pub fn kalman_config(self) -> KalmanConfiguration {
+        match self {
+            // Only use the least delayed of the last 16 measurements, as paths
+            // with partial timing support can add much packet delay variation
+            Profile::G8275_2 => KalmanConfiguration {
+                packet_selection_window: 16,
+                ..Default::default()
+            },
+            _ => KalmanConfiguration::default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum HardwareClock {
     /// Automatically use the (default) hardware clock for the interface
@@ -87,30 +228,134 @@ impl<'de> Deserialize<'de> for HardwareClock {
 pub struct PortConfig {
     pub interface: InterfaceName,
     #[serde(default, deserialize_with = "deserialize_acceptable_master_list")]
-    pub acceptable_master_list: Option<Vec<ClockIdentity>>,
+    pub acceptable_master_list: Option<Vec<AcceptableMaster>>,
     #[serde(default)]
     pub hardware_clock: HardwareClock,
     #[serde(default)]
     pub network_mode: NetworkMode,
-    #[serde(default = "default_announce_interval")]
-    pub announce_interval: i8,
-    #[serde(default = "default_sync_interval")]
-    pub sync_interval: i8,
-    #[serde(default = "default_announce_receipt_timeout")]
-    pub announce_receipt_timeout: u8,
     #[serde(default)]
+    pub announce_interval: Option<i8>,
+    #[serde(default)]
+    pub sync_interval: Option<i8>,
+    #[serde(default)]
+    pub announce_receipt_timeout: Option<u8>,
+    #[serde(default, alias = "not-slave")]
     pub master_only: bool,
     #[serde(default = "default_delay_asymmetry")]
     pub delay_asymmetry: i64,
     #[serde(default)]
-    pub delay_mechanism: DelayType,
-    #[serde(default = "default_delay_interval")]
-    pub delay_interval: i8,
+    pub delay_mechanism: Option<DelayType>,
+    #[serde(default)]
+    pub delay_interval: Option<i8>,
     #[serde(
         default = "default_minor_ptp_version",
         deserialize_with = "deserialize_minor_version"
     )]
     pub minor_ptp_version: PtpMinorVersion,
+    #[serde(default)]
+    pub unicast_max_grantees: usize,
+    #[serde(default = "default_unicast_min_interval")]
+    pub unicast_min_interval: i8,
+    #[serde(default = "default_unicast_max_duration")]
+    pub unicast_max_duration: u64,
+    #[serde(default, deserialize_with = "deserialize_unicast_master_table")]
+    pub unicast_master_table: Vec<PortAddress>,
+    #[serde(default = "default_unicast_query_interval")]
+    pub unicast_query_interval: i8,
+    #[serde(default = "default_unicast_grant_duration")]
+    pub unicast_grant_duration: u64,
+    #[serde(default)]
+    pub one_step: bool,
+    #[serde(default)]
+    pub slave_monitoring: Option<SlaveMonitoringConfig>,
+    #[serde(default)]
+    pub desired_state: Option<DesiredPortState>,
+    #[serde(default)]
+    pub number_alternate_masters: u8,
+    #[serde(default = "default_local_priority")]
+    pub local_priority: u8,
+    #[serde(default)]
+    pub multicast_address: MulticastAddress,
+    #[serde(default)]
+    pub hybrid_e2e: bool,
+    #[serde(skip)]
+    pub unicast_only: bool,
+}
+
+/// The multicast address of the messages sent over ethernet
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MulticastAddress {
+    /// 01-1B-19-00-00-00, which bridges forward
+    #[default]
+    Forwardable,
+    /// 01-80-C2-00-00-0E, which bridges do not forward
+    NonForwardable,
+}
+
+/// State of a port when the instance uses external port configuration
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DesiredPortState {
+    Master,
+    Slave,
+    Passive,
+}
+
+impl From<DesiredPortState> for DesiredState {
+    fn from(state: DesiredPortState) -> Self {
+        match state {
+            DesiredPortState::Master => DesiredState::Master,
+            DesiredPortState::Slave => DesiredState::Slave,
+            DesiredPortState::Passive => DesiredState::Passive,
+        }
+    }
+}
+
+/// Slave event monitoring records sent to a collector while the port is slave
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SlaveMonitoringConfig {
+    #[serde(deserialize_with = "deserialize_collector")]
+    pub collector: PortAddress,
+    #[serde(default = "default_monitor_records")]
+    pub rx_sync_timing_data: bool,
+    #[serde(default = "default_monitor_records")]
+    pub rx_sync_computed_data: bool,
+    #[serde(default = "default_monitor_records")]
+    pub tx_event_timestamps: bool,
+    #[serde(default = "default_records_per_message")]
+    pub records_per_message: usize,
+}
+
+impl From<&SlaveMonitoringConfig> for statime::config::SlaveMonitoringConfig {
+    fn from(config: &SlaveMonitoringConfig) -> Self {
+        if config.records_per_message > MAX_MONITORING_RECORDS {
+            warn!(
+                "Too many records per monitoring message, sending {} instead",
+                MAX_MONITORING_RECORDS
+            );
+        }
+
+        Self {
+            collector: config.collector,
+            rx_sync_timing_data: config.rx_sync_timing_data,
+            rx_sync_computed_data: config.rx_sync_computed_data,
+            tx_event_timestamps: config.tx_event_timestamps,
+            records_per_message: config.records_per_message.clamp(1, MAX_MONITORING_RECORDS),
+        }
+    }
+}
+
+fn deserialize_collector<'de, D>(deserializer: D) -> Result<PortAddress, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let raw: String = Deserialize::deserialize(deserializer)?;
+    parse_port_address(&raw)
+        .ok_or_else(|| D::Error::custom(format!("Invalid collector address: {}", raw)))
 }
 
 fn deserialize_minor_version<'de, D>(deserializer: D) -> Result<PtpMinorVersion, D::Error>
@@ -122,27 +367,97 @@ where
     raw.try_into().map_err(D::Error::custom)
 }
 
+/// An entry of the acceptable master list, either just a clock identity or a
+/// table that also selects a port and alternate priority 1
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawAcceptableMaster {
+    Identity(String),
+    Entry(RawAcceptableMasterEntry),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct RawAcceptableMasterEntry {
+    identity: String,
+    port: Option<u16>,
+    #[serde(default)]
+    alternate_priority1: u8,
+}
+
 fn deserialize_acceptable_master_list<'de, D>(
     deserializer: D,
-) -> Result<Option<Vec<ClockIdentity>>, D::Error>
+) -> Result<Option<Vec<AcceptableMaster>>, D::Error>
 where
     D: Deserializer<'de>,
 {
     use hex::FromHex;
     use serde::de::Error;
 
-    let raw: Vec<String> = Deserialize::deserialize(deserializer)?;
+    let raw: Vec<RawAcceptableMaster> = Deserialize::deserialize(deserializer)?;
     let mut result = Vec::with_capacity(raw.len());
 
-    for identity in raw {
-        result.push(ClockIdentity(<[u8; 8]>::from_hex(identity).map_err(
-            |e| D::Error::custom(format!("Invalid clock identifier: {}", e)),
-        )?));
+    for entry in raw {
+        let (identity, port_number, alternate_priority_1) = match entry {
+            RawAcceptableMaster::Identity(identity) => (identity, None, 0),
+            RawAcceptableMaster::Entry(entry) => {
+                (entry.identity, entry.port, entry.alternate_priority1)
+            }
+        };
+
+        result.push(AcceptableMaster {
+            clock_identity: ClockIdentity(
+                <[u8; 8]>::from_hex(identity)
+                    .map_err(|e| D::Error::custom(format!("Invalid clock identifier: {}", e)))?,
+            ),
+            port_number,
+            alternate_priority_1,
+        });
     }
 
     Ok(Some(result))
 }
 
+fn deserialize_unicast_master_table<'de, D>(deserializer: D) -> Result<Vec<PortAddress>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let raw: Vec<String> = Deserialize::deserialize(deserializer)?;
+    raw.iter()
+        .map(|address| {
+            parse_port_address(address)
+                .ok_or_else(|| D::Error::custom(format!("Invalid master address: {}", address)))
+        })
+        .collect()
+}
+
+/// Parse an IPv4, IPv6 or ethernet (`aa:bb:cc:dd:ee:ff`) address
+fn parse_port_address(raw: &str) -> Option<PortAddress> {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    if let Ok(address) = raw.parse::<Ipv4Addr>() {
+        return Some(PortAddress::Ipv4(address.octets()));
+    }
+
+    if let Ok(address) = raw.parse::<Ipv6Addr>() {
+        return Some(PortAddress::Ipv6(address.octets()));
+    }
+
+    let mut mac = [0; 6];
+    let mut parts = raw.split(':');
+    for byte in &mut mac {
+        let part = parts.next()?;
+        if part.len() != 2 {
+            return None;
+        }
+        *byte = u8::from_str_radix(part, 16).ok()?;
+    }
+
+    parts.next().is_none().then_some(PortAddress::Ethernet(mac))
+}
+
 fn deserialize_clock_identity<'de, D>(deserializer: D) -> Result<Option<ClockIdentity>, D::Error>
 where
     D: Deserializer<'de>,
@@ -155,28 +470,333 @@ where
     )?)))
 }
 
-impl From<PortConfig> for statime::config::PortConfig<Option<Vec<ClockIdentity>>> {
+impl PortConfig {
+    /// The interval of delay or peer delay requests
+    pub fn delay_interval(&self) -> Interval {
+        Interval::from_log_2(self.delay_interval.unwrap_or_else(default_delay_interval))
+    }
+
+    /// Fail when unicast is configured for a profile that only uses multicast
+    fn check_multicast_only(&self, profile: &str) -> Result<(), ConfigError> {
+        if self.unicast_max_grantees > 0 {
+            return Err(ConfigError::Profile(format!(
+                "the {profile} profile requires unicast-max-grantees 0, not {} on {}",
+                self.unicast_max_grantees, self.interface
+            )));
+        }
+
+        if !self.unicast_master_table.is_empty() {
+            return Err(ConfigError::Profile(format!(
+                "the {profile} profile requires 0 unicast-master-table entries, not {} on {}",
+                self.unicast_master_table.len(),
+                self.interface
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl From<PortConfig> for statime::config::PortConfig<Option<Vec<AcceptableMaster>>> {
     fn from(pc: PortConfig) -> Self {
         Self {
-            acceptable_master_list: pc.acceptable_master_list,
-            announce_interval: Interval::from_log_2(pc.announce_interval),
-            sync_interval: Interval::from_log_2(pc.sync_interval),
-            announce_receipt_timeout: pc.announce_receipt_timeout,
+            announce_interval: Interval::from_log_2(
+                pc.announce_interval
+                    .unwrap_or_else(default_announce_interval),
+            ),
+            sync_interval: Interval::from_log_2(
+                pc.sync_interval.unwrap_or_else(default_sync_interval),
+            ),
+            announce_receipt_timeout: pc
+                .announce_receipt_timeout
+                .unwrap_or_else(default_announce_receipt_timeout),
             master_only: pc.master_only,
             delay_asymmetry: Duration::from_nanos(pc.delay_asymmetry),
-            delay_mechanism: match pc.delay_mechanism {
+            delay_mechanism: match pc.delay_mechanism.unwrap_or_default() {
                 DelayType::E2E => DelayMechanism::E2E {
-                    interval: Interval::from_log_2(pc.delay_interval),
+                    interval: pc.delay_interval(),
                 },
                 DelayType::P2P => DelayMechanism::P2P {
-                    interval: Interval::from_log_2(pc.delay_interval),
+                    interval: pc.delay_interval(),
                 },
             },
+            acceptable_master_list: pc.acceptable_master_list,
             minor_ptp_version: pc.minor_ptp_version,
+            unicast_grantor: (pc.unicast_max_grantees > 0).then(|| UnicastGrantorConfig {
+                max_grantees: pc.unicast_max_grantees,
+                min_interval: Interval::from_log_2(pc.unicast_min_interval),
+                max_duration: std::time::Duration::from_secs(pc.unicast_max_duration),
+            }),
+            unicast_master_table: UnicastMasterTable {
+                masters: pc
+                    .unicast_master_table
+                    .iter()
+                    .copied()
+                    .take(MAX_UNICAST_MASTERS)
+                    .collect(),
+                query_interval: Interval::from_log_2(pc.unicast_query_interval),
+                grant_duration: std::time::Duration::from_secs(pc.unicast_grant_duration),
+            },
+            one_step: pc.one_step,
+            authentication: None,
+            slave_monitoring: pc.slave_monitoring.as_ref().map(Into::into),
+            gptp: None,
+            desired_state: pc.desired_state.map(Into::into),
+            number_alternate_masters: pc.number_alternate_masters,
+            local_priority: pc.local_priority,
+            link_local: pc.multicast_address == MulticastAddress::NonForwardable,
+            hybrid_e2e: pc.hybrid_e2e,
+            unicast_only: pc.unicast_only,
+        }
+    }
+}
+
+/// An alternate timescale distributed by the instance while it is grandmaster
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct AlternateTimescaleConfig {
+    pub key: u8,
+    pub offset: i32,
+    #[serde(default)]
+    pub jump_seconds: i32,
+    #[serde(default)]
+    pub next_jump: u64,
+    #[serde(deserialize_with = "deserialize_display_name")]
+    pub name: ArrayString<MAX_DISPLAY_NAME_LEN>,
+}
+
+fn deserialize_display_name<'de, D>(
+    deserializer: D,
+) -> Result<ArrayString<MAX_DISPLAY_NAME_LEN>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+    let raw: String = Deserialize::deserialize(deserializer)?;
+    ArrayString::from(&raw).map_err(|_| {
+        D::Error::custom(format!(
+            "Alternate timescale name {raw:?} is longer than {MAX_DISPLAY_NAME_LEN} bytes"
+        ))
+    })
+}
+
+impl From<&AlternateTimescaleConfig> for AlternateTimescale {
+    fn from(atc: &AlternateTimescaleConfig) -> Self {
+        Self {
+            key_field: atc.key,
+            current_offset: atc.offset,
+            jump_seconds: atc.jump_seconds,
+            time_of_next_jump: atc.next_jump,
+            display_name: atc.name,
+        }
+    }
+}
+
+/// Clock class degradation when the instance loses its time reference
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct HoldoverConfig {
+    /// Largest time error in nanoseconds within the holdover specification
+    #[serde(default = "default_holdover_budget")]
+    pub budget: u64,
+    #[serde(default)]
+    pub clock_classes: HoldoverClassSet,
+}
+
+impl Default for HoldoverConfig {
+    fn default() -> Self {
+        Self {
+            budget: default_holdover_budget(),
+            clock_classes: Default::default(),
+        }
+    }
+}
+
+const fn default_holdover_budget() -> u64 {
+    1000
+}
+
+/// The clock classes announced in the holdover states
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HoldoverClassSet {
+    #[default]
+    DegradationA,
+    DegradationB,
+    G8275Grandmaster,
+    #[serde(rename = "g8275-grandmaster-category-2")]
+    G8275GrandmasterCategory2,
+    #[serde(rename = "g8275-grandmaster-category-3")]
+    G8275GrandmasterCategory3,
+    G8275BoundaryClock,
+}
+
+impl From<&HoldoverConfig> for statime::config::HoldoverConfig {
+    fn from(hc: &HoldoverConfig) -> Self {
+        Self {
+            classes: match hc.clock_classes {
+                HoldoverClassSet::DegradationA => HoldoverClasses::DEGRADATION_A,
+                HoldoverClassSet::DegradationB => HoldoverClasses::DEGRADATION_B,
+                HoldoverClassSet::G8275Grandmaster => HoldoverClasses::G8275_GRANDMASTER,
+                HoldoverClassSet::G8275GrandmasterCategory2 => {
+                    HoldoverClasses::G8275_GRANDMASTER_CATEGORY_2
+                }
+                HoldoverClassSet::G8275GrandmasterCategory3 => {
+                    HoldoverClasses::G8275_GRANDMASTER_CATEGORY_3
+                }
+                HoldoverClassSet::G8275BoundaryClock => HoldoverClasses::G8275_BOUNDARY_CLOCK,
+            },
+            budget: Duration::from_nanos(hc.budget.try_into().unwrap_or(i64::MAX)),
+        }
+    }
+}
+
+/// The IEEE C37.238 power profile TLV in announce messages
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PowerProfileConfig {
+    pub grandmaster_id: u16,
+    /// Time inaccuracy in nanoseconds added by this clock
+    #[serde(default)]
+    pub time_inaccuracy: u32,
+}
+
+impl From<&PowerProfileConfig> for statime::config::PowerProfileConfig {
+    fn from(pc: &PowerProfileConfig) -> Self {
+        Self {
+            grandmaster_id: pc.grandmaster_id,
+            time_inaccuracy: pc.time_inaccuracy,
+        }
+    }
+}
+
+/// The SMPTE ST 2059-2 synchronization metadata sent as grandmaster
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SynchronizationMetadataConfig {
+    pub frame_rate_numerator: u32,
+    #[serde(default = "default_frame_rate_denominator")]
+    pub frame_rate_denominator: u32,
+    #[serde(default)]
+    pub master_locking_status: MasterLockingStatus,
+    #[serde(default)]
+    pub drop_frame: bool,
+    #[serde(default)]
+    pub color_frame_identification: bool,
+    /// Offset in seconds of local time from PTP time
+    #[serde(default)]
+    pub current_local_offset: i32,
+    #[serde(default)]
+    pub jump_seconds: i32,
+    #[serde(default)]
+    pub time_of_next_jump: u64,
+    /// Local time of day in seconds of the daily jam
+    #[serde(default, deserialize_with = "deserialize_time_of_day")]
+    pub daily_jam: Option<u32>,
+    #[serde(default)]
+    pub daylight_saving: bool,
+    #[serde(default)]
+    pub daylight_saving_at_next_jump: bool,
+    #[serde(default)]
+    pub leap_second_jump: bool,
+}
+
+const fn default_frame_rate_denominator() -> u32 {
+    1
+}
+
+/// Complementary locking status of the grandmaster
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MasterLockingStatus {
+    #[default]
+    NotInUse,
+    FreeRun,
+    ColdLocking,
+    WarmLocking,
+    Locked,
+}
+
+fn deserialize_time_of_day<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let raw: String = Deserialize::deserialize(deserializer)?;
+    parse_time_of_day(&raw)
+        .map(Some)
+        .ok_or_else(|| D::Error::custom(format!("Invalid time of day: {}", raw)))
+}
+
+/// Parse a time of day as HH:MM or HH:MM:SS into seconds since midnight
+fn parse_time_of_day(raw: &str) -> Option<u32> {
+    let mut parts = raw.split(':');
+    let hours: u32 = parts.next()?.parse().ok()?;
+    let minutes: u32 = parts.next()?.parse().ok()?;
+    let seconds: u32 = match parts.next() {
+        Some(seconds) => seconds.parse().ok()?,
+        None => 0,
+    };
+
+    (parts.next().is_none() && hours < 24 && minutes < 60 && seconds < 60)
+        .then_some(hours * 3600 + minutes * 60 + seconds)
+}
+
+impl From<&SynchronizationMetadataConfig> for statime::config::SynchronizationMetadataConfig {
+    fn from(smc: &SynchronizationMetadataConfig) -> Self {
+        use statime::config::MasterLockingStatus as Status;
+
+        let metadata = statime::config::SynchronizationMetadata {
+            frame_rate_numerator: smc.frame_rate_numerator,
+            frame_rate_denominator: smc.frame_rate_denominator,
+            master_locking_status: match smc.master_locking_status {
+                MasterLockingStatus::NotInUse => Status::NotInUse,
+                MasterLockingStatus::FreeRun => Status::FreeRun,
+                MasterLockingStatus::ColdLocking => Status::ColdLocking,
+                MasterLockingStatus::WarmLocking => Status::WarmLocking,
+                MasterLockingStatus::Locked => Status::Locked,
+            },
+            drop_frame: smc.drop_frame,
+            color_frame_identification: smc.color_frame_identification,
+            current_local_offset: smc.current_local_offset,
+            jump_seconds: smc.jump_seconds,
+            time_of_next_jump: smc.time_of_next_jump,
+            daylight_saving: smc.daylight_saving,
+            daylight_saving_at_next_jump: smc.daylight_saving_at_next_jump,
+            leap_second_jump: smc.leap_second_jump,
+            ..Default::default()
+        };
+
+        Self {
+            metadata,
+            daily_jam: smc.daily_jam,
         }
     }
 }
 
+/// A GNSS receiver managed by gpsd, used as the time source of the grandmaster
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct GpsdConfig {
+    #[serde(default = "default_gpsd_address")]
+    pub address: SocketAddr,
+}
+
+fn default_gpsd_address() -> SocketAddr {
+    "127.0.0.1:2947".parse().unwrap()
+}
+
+/// Values that may be changed by PTP management SET messages
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ManagementSetField {
+    Priority1,
+    Priority2,
+    Domain,
+    SlaveOnly,
+}
+
 #[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum NetworkMode {
@@ -195,6 +815,11 @@ pub enum DelayType {
 }
 
 impl Config {
+    /// The domain of the instance configured at the top level
+    pub fn domain(&self) -> u8 {
+        self.domain.unwrap_or_else(default_domain)
+    }
+
     /// Parse config from file
     pub fn from_file(file: &Path) -> Result<Config, ConfigError> {
         let meta = std::fs::metadata(file).map_err(ConfigError::Io)?;
@@ -205,41 +830,600 @@ impl Config {
         }
 
         let contents = read_to_string(file).map_err(ConfigError::Io)?;
-        let config: Config = toml::de::from_str(&contents).map_err(ConfigError::Toml)?;
+        let mut config: Config = toml::de::from_str(&contents).map_err(ConfigError::Toml)?;
+        config.check_instances()?;
+        config.apply_profile()?;
         config.warn_when_unreasonable();
         Ok(config)
     }
 
-    /// Warns about unreasonable config values
-    pub fn warn_when_unreasonable(&self) {
-        if self.ports.is_empty() {
-            warn!("No ports configured.");
+    /// Check that every instance has a domain of its own
+    ///
+    /// Received messages are passed to an instance by their domain, so two
+    /// instances cannot share one.
+    pub fn check_instances(&self) -> Result<(), ConfigError> {
+        let mut domains = vec![(self.sdo_id, self.domain())];
+
+        for instance in &self.instances {
+            let domain = (instance.sdo_id, instance.domain);
+            if domains.contains(&domain) {
+                return Err(ConfigError::Instance(format!(
+                    "domain {} with sdo-id {:#x} is configured for more than one instance",
+                    instance.domain, instance.sdo_id
+                )));
+            }
+            domains.push(domain);
         }
 
-        if self.ports.len() > 16 {
-            warn!("Too many ports are configured.");
+        Ok(())
+    }
+
+    /// Give the settings that are not configured the default of the profile
+    ///
+    /// Fails when a configured setting conflicts with the profile.
+    pub fn apply_profile(&mut self) -> Result<(), ConfigError> {
+        match self.profile {
+            Profile::Default => Ok(()),
+            Profile::Gptp => self.apply_gptp_profile(),
+            Profile::G8275_1 => self.apply_g8275_1_profile(),
+            Profile::G8275_2 => self.apply_g8275_2_profile(),
+            Profile::Power61850_9_3 => self.apply_power_profile(),
+            Profile::Aes67 => self.apply_ip_profile("aes67", &AES67),
+            Profile::Smpte2059_2 => self.apply_ip_profile("smpte-2059-2", &SMPTE_2059_2),
+            Profile::Enterprise => self.apply_ip_profile("enterprise", &ENTERPRISE),
         }
     }
-}
 
-#[derive(Debug)]
-pub enum ConfigError {
-    Io(std::io::Error),
-    Toml(toml::de::Error),
-}
+    /// Move the domains of the instances into the range of a profile
+    ///
+    /// A domain that is not configured becomes the default domain of the
+    /// profile.
+    fn apply_profile_domains(
+        &mut self,
+        profile: &str,
+        ranges: &ProfileRanges,
+    ) -> Result<(), ConfigError> {
+        let domain = *self.domain.get_or_insert(ranges.default_domain);
+        let domains = &ranges.domains;
 
-impl std::fmt::Display for ConfigError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ConfigError::Io(e) => writeln!(f, "io error while reading config: {e}"),
-            ConfigError::Toml(e) => writeln!(f, "config toml parsing error: {e}"),
+        let instance_domains = self.instances.iter().map(|instance| instance.domain);
+        for domain in std::iter::once(domain).chain(instance_domains) {
+            if !domains.contains(&domain) {
+                return Err(ConfigError::Profile(format!(
+                    "the {profile} profile requires a domain between {} and {}, not {domain}",
+                    domains.start(),
+                    domains.end()
+                )));
+            }
         }
+
+        Ok(())
     }
-}
 
-impl std::error::Error for ConfigError {}
+    fn apply_power_profile(&mut self) -> Result<(), ConfigError> {
+        if self.clock_type == ClockType::E2eTransparent {
+            return Err(ConfigError::Profile(
+                "the power-61850-9-3 profile requires clock-type \"ordinary\" or \
+                 \"p2p-transparent\""
+                    .into(),
+            ));
+        }
 
-fn default_domain() -> u8 {
+        self.apply_profile_domains("power-61850-9-3", &POWER_61850_9_3)?;
+
+        let instance_ports = self
+            .instances
+            .iter_mut()
+            .flat_map(|instance| instance.ports.iter_mut());
+        for port in self.ports.iter_mut().chain(instance_ports) {
+            if port.network_mode != NetworkMode::Ethernet {
+                return Err(ConfigError::Profile(format!(
+                    "the power-61850-9-3 profile requires network-mode \"ethernet\" on {}",
+                    port.interface
+                )));
+            }
+
+            POWER_61850_9_3.apply("power-61850-9-3", port)?;
+            port.check_multicast_only("power-61850-9-3")?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply a media or enterprise profile, which run over IPv4 or IPv6
+    fn apply_ip_profile(
+        &mut self,
+        profile: &str,
+        ranges: &ProfileRanges,
+    ) -> Result<(), ConfigError> {
+        self.apply_profile_domains(profile, ranges)?;
+
+        let instance_ports = self
+            .instances
+            .iter_mut()
+            .flat_map(|instance| instance.ports.iter_mut());
+        for port in self.ports.iter_mut().chain(instance_ports) {
+            if port.network_mode == NetworkMode::Ethernet
+                || (!ranges.ipv6 && port.network_mode == NetworkMode::Ipv6)
+            {
+                let modes = if ranges.ipv6 {
+                    "\"ipv4\" or \"ipv6\""
+                } else {
+                    "\"ipv4\""
+                };
+                return Err(ConfigError::Profile(format!(
+                    "the {profile} profile requires network-mode {modes} on {}",
+                    port.interface
+                )));
+            }
+
+            ranges.apply(profile, port)?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_gptp_profile(&mut self) -> Result<(), ConfigError> {
+        if self.clock_type != ClockType::Ordinary {
+            return Err(ConfigError::Profile(
+                "the gptp profile requires clock-type \"ordinary\"".into(),
+            ));
+        }
+
+        // gPTP uses transportSpecific 1, the major part of the sdoId
+        let sdo_ids = std::iter::once(self.sdo_id)
+            .chain(self.instances.iter().map(|instance| instance.sdo_id));
+        for sdo_id in sdo_ids {
+            if sdo_id != default_sdo_id() && sdo_id != GPTP_SDO_ID {
+                return Err(ConfigError::Profile(format!(
+                    "the gptp profile requires sdo-id {GPTP_SDO_ID:#x}, not {sdo_id:#x}"
+                )));
+            }
+        }
+
+        self.sdo_id = GPTP_SDO_ID;
+        self.path_trace = true;
+
+        for instance in &mut self.instances {
+            instance.sdo_id = GPTP_SDO_ID;
+            instance.path_trace = true;
+        }
+
+        self.apply_profile_domains("gptp", &GPTP)?;
+
+        let instance_ports = self
+            .instances
+            .iter_mut()
+            .flat_map(|instance| instance.ports.iter_mut());
+        for port in self.ports.iter_mut().chain(instance_ports) {
+            if port.network_mode != NetworkMode::Ethernet {
+                return Err(ConfigError::Profile(format!(
+                    "the gptp profile requires network-mode \"ethernet\" on {}",
+                    port.interface
+                )));
+            }
+
+            GPTP.apply("gptp", port)?;
+            port.check_multicast_only("gptp")?;
+
+            if port.one_step {
+                return Err(ConfigError::Profile(format!(
+                    "the gptp profile requires one-step false, not true on {}",
+                    port.interface
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply the clock settings shared by the ITU-T G.8275 telecom profiles
+    fn apply_g8275_clock(
+        &mut self,
+        profile: &str,
+        ranges: &ProfileRanges,
+    ) -> Result<(), ConfigError> {
+        if self.clock_type != ClockType::Ordinary {
+            return Err(ConfigError::Profile(format!(
+                "the {profile} profile requires clock-type \"ordinary\""
+            )));
+        }
+
+        let clocks = std::iter::once((self.sdo_id, self.priority1)).chain(
+            self.instances
+                .iter()
+                .map(|instance| (instance.sdo_id, instance.priority1)),
+        );
+        for (sdo_id, priority1) in clocks {
+            if sdo_id != default_sdo_id() {
+                return Err(ConfigError::Profile(format!(
+                    "the {profile} profile requires sdo-id {:#x}, not {sdo_id:#x}",
+                    default_sdo_id()
+                )));
+            }
+
+            if priority1 != default_priority1() {
+                return Err(ConfigError::Profile(format!(
+                    "the {profile} profile requires priority1 {}, not {priority1}",
+                    default_priority1()
+                )));
+            }
+        }
+
+        self.apply_profile_domains(profile, ranges)?;
+
+        if self.local_priority == 0 {
+            return Err(ConfigError::Profile(format!(
+                "the {profile} profile requires a local-priority between 1 and 255"
+            )));
+        }
+
+        if let Some(holdover) = &self.holdover {
+            if matches!(
+                holdover.clock_classes,
+                HoldoverClassSet::DegradationA | HoldoverClassSet::DegradationB
+            ) {
+                return Err(ConfigError::Profile(format!(
+                    "the {profile} profile requires the clock-classes of a telecom grandmaster or \
+                     boundary clock"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_g8275_1_profile(&mut self) -> Result<(), ConfigError> {
+        self.apply_g8275_clock("g8275-1", &G8275_1)?;
+
+        let instance_ports = self
+            .instances
+            .iter_mut()
+            .flat_map(|instance| instance.ports.iter_mut());
+        for port in self.ports.iter_mut().chain(instance_ports) {
+            if port.network_mode != NetworkMode::Ethernet {
+                return Err(ConfigError::Profile(format!(
+                    "the g8275-1 profile requires network-mode \"ethernet\" on {}",
+                    port.interface
+                )));
+            }
+
+            if port.local_priority == 0 {
+                return Err(ConfigError::Profile(format!(
+                    "the g8275-1 profile requires a local-priority between 1 and 255 on {}",
+                    port.interface
+                )));
+            }
+
+            G8275_1.apply("g8275-1", port)?;
+            port.check_multicast_only("g8275-1")?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_g8275_2_profile(&mut self) -> Result<(), ConfigError> {
+        self.apply_g8275_clock("g8275-2", &G8275_2)?;
+
+        let instance_ports = self
+            .instances
+            .iter_mut()
+            .flat_map(|instance| instance.ports.iter_mut());
+        for port in self.ports.iter_mut().chain(instance_ports) {
+            if port.network_mode == NetworkMode::Ethernet {
+                return Err(ConfigError::Profile(format!(
+                    "the g8275-2 profile requires network-mode \"ipv4\" or \"ipv6\" on {}",
+                    port.interface
+                )));
+            }
+
+            if port.local_priority == 0 {
+                return Err(ConfigError::Profile(format!(
+                    "the g8275-2 profile requires a local-priority between 1 and 255 on {}",
+                    port.interface
+                )));
+            }
+
+            // 1 announce and 16 sync and delay request messages per second by default
+            G8275_2.apply("g8275-2", port)?;
+
+            // Masters only send to the slaves they granted unicast transmission
+            port.unicast_only = true;
+
+            let durations = [
+                ("unicast-grant-duration", port.unicast_grant_duration),
+                ("unicast-max-duration", port.unicast_max_duration),
+            ];
+            for (name, duration) in durations {
+                if !G8275_2_GRANT_DURATIONS.contains(&duration) {
+                    return Err(ConfigError::Profile(format!(
+                        "the g8275-2 profile requires {name} to be between 60 and 1000 seconds on \
+                         {}",
+                        port.interface
+                    )));
+                }
+            }
+
+            // Masters grant sync and delay responses at up to 128 per second
+            if port.unicast_min_interval < -7 {
+                return Err(ConfigError::Profile(format!(
+                    "the g8275-2 profile requires a unicast-min-interval of at least -7 on {}",
+                    port.interface
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The data set comparison of the instances
+    pub fn dataset_comparison(&self) -> DatasetComparison {
+        match self.profile {
+            Profile::Default
+            | Profile::Gptp
+            | Profile::Power61850_9_3
+            | Profile::Aes67
+            | Profile::Smpte2059_2
+            | Profile::Enterprise => DatasetComparison::Ieee1588,
+            Profile::G8275_1 | Profile::G8275_2 => DatasetComparison::G8275 {
+                local_priority: self.local_priority,
+            },
+        }
+    }
+
+    /// gPTP settings of the ports when running the gptp profile
+    pub fn gptp_config(&self) -> Option<GptpConfig> {
+        (self.profile == Profile::Gptp).then(GptpConfig::default)
+    }
+
+    /// Warns about unreasonable config values
+    pub fn warn_when_unreasonable(&self) {
+        if self.ports.is_empty() {
+            warn!("No ports configured.");
+        }
+
+        if self.ports.len() > 16 {
+            warn!("Too many ports are configured.");
+        }
+
+        if self.clock_type.is_transparent() {
+            if self.ports.len() < 2 {
+                warn!("A transparent clock needs at least two ports to forward messages.");
+            }
+
+            if !self.instances.is_empty() {
+                warn!("A transparent clock forwards all domains, instance sections are ignored.");
+            }
+
+            if self.holdover.is_some() {
+                warn!("A transparent clock has no clock class, the holdover section is ignored.");
+            }
+
+            if self.gpsd.is_some() {
+                warn!("A transparent clock does not keep time, the gpsd section is ignored.");
+            }
+
+            if self.power_profile.is_some() {
+                warn!(
+                    "A transparent clock forwards announce messages as is, the power-profile \
+                     section is ignored."
+                );
+            }
+
+            if self.leap_seconds_file.is_some() {
+                warn!("A transparent clock does not announce time, leap-seconds-file is ignored.");
+            }
+
+            if self.synchronization_metadata.is_some() {
+                warn!(
+                    "A transparent clock forwards management messages as is, the \
+                     synchronization-metadata section is ignored."
+                );
+            }
+        }
+
+        if self.clock_type.is_transparent() && self.authentication.is_some() {
+            warn!(
+                "The transparent clock does not support authentication, messages are forwarded as \
+                 is."
+            );
+        }
+
+        if let Some(power_profile) = &self.power_profile {
+            if !self.slave_only && !(3..=254).contains(&power_profile.grandmaster_id) {
+                warn!(
+                    "IEEE C37.238 uses grandmaster-id values from 3 to 254, {} may not be \
+                     accepted by other clocks.",
+                    power_profile.grandmaster_id
+                );
+            }
+        }
+
+        if let Some(metadata) = &self.synchronization_metadata {
+            if self.slave_only {
+                warn!(
+                    "A slave-only instance never becomes grandmaster, the \
+                     synchronization-metadata section is ignored."
+                );
+            }
+
+            if metadata.frame_rate_denominator == 0 {
+                warn!("A frame-rate-denominator of 0 does not describe a frame rate.");
+            }
+
+            if metadata.time_of_next_jump >= 1 << 48 {
+                warn!("time-of-next-jump is sent as a 48 bit value.");
+            }
+        }
+
+        if self.slave_only && self.gpsd.is_some() {
+            warn!(
+                "A slave-only instance never becomes grandmaster, gpsd does not steer the clock."
+            );
+        }
+
+        if self.alternate_timescales.len() > MAX_ALTERNATE_TIMESCALES {
+            warn!(
+                "More than the supported {} alternate timescales are configured, only the first \
+                 {} are distributed.",
+                MAX_ALTERNATE_TIMESCALES, MAX_ALTERNATE_TIMESCALES
+            );
+        }
+
+        if self.external_port_configuration
+            && self
+                .ports
+                .iter()
+                .filter(|port| port.desired_state == Some(DesiredPortState::Slave))
+                .count()
+                > 1
+        {
+            warn!(
+                "More than one port has desired-state slave, only the first becomes slave and the \
+                 others stay passive."
+            );
+        }
+
+        for port in &self.ports {
+            if self.external_port_configuration && port.desired_state.is_none() {
+                warn!(
+                    "No desired-state configured for {}, the port stays passive.",
+                    port.interface
+                );
+            }
+
+            if !self.external_port_configuration && port.desired_state.is_some() {
+                warn!(
+                    "desired-state of {} is ignored without external-port-configuration.",
+                    port.interface
+                );
+            }
+
+            if port.hybrid_e2e && port.delay_mechanism == Some(DelayType::P2P) {
+                warn!(
+                    "hybrid-e2e of {} is ignored with the P2P delay mechanism.",
+                    port.interface
+                );
+            }
+        }
+
+        for instance in &self.instances {
+            if instance.ports.is_empty() {
+                warn!(
+                    "No ports configured for the instance in domain {}.",
+                    instance.domain
+                );
+            }
+
+            if instance
+                .ports
+                .iter()
+                .any(|port| port.desired_state.is_some())
+            {
+                warn!(
+                    "The instance in domain {} uses the best master clock algorithm, its \
+                     desired-state settings are ignored.",
+                    instance.domain
+                );
+            }
+        }
+
+        let instance_ports = self
+            .instances
+            .iter()
+            .flat_map(|instance| instance.ports.iter());
+        for port in self.ports.iter().chain(instance_ports) {
+            if port.number_alternate_masters as usize > MAX_ALTERNATE_MASTERS {
+                warn!(
+                    "number-alternate-masters of {} is larger than the supported {}, at most {} \
+                     alternate masters are measured.",
+                    port.interface, MAX_ALTERNATE_MASTERS, MAX_ALTERNATE_MASTERS
+                );
+            }
+
+            if port.unicast_max_grantees > MAX_UNICAST_GRANTEES {
+                warn!(
+                    "unicast-max-grantees of {} is larger than the supported {}.",
+                    port.interface, MAX_UNICAST_GRANTEES
+                );
+            }
+
+            if port.unicast_master_table.len() > MAX_UNICAST_MASTERS {
+                warn!(
+                    "unicast-master-table of {} has more than the supported {} entries, only the \
+                     first {} are used.",
+                    port.interface, MAX_UNICAST_MASTERS, MAX_UNICAST_MASTERS
+                );
+            }
+
+            let mismatched = port.unicast_master_table.iter().any(|address| {
+                !matches!(
+                    (port.network_mode, address),
+                    (NetworkMode::Ipv4, PortAddress::Ipv4(_))
+                        | (NetworkMode::Ipv6, PortAddress::Ipv6(_))
+                        | (NetworkMode::Ethernet, PortAddress::Ethernet(_))
+                )
+            });
+            if mismatched {
+                warn!(
+                    "unicast-master-table of {} contains addresses that do not match its \
+                     network-mode, these masters are never contacted.",
+                    port.interface
+                );
+            }
+
+            if self.profile == Profile::G8275_2
+                && !port.master_only
+                && port.unicast_master_table.is_empty()
+            {
+                warn!(
+                    "The g8275-2 profile only synchronizes to the masters in the \
+                     unicast-master-table, {} has none configured.",
+                    port.interface
+                );
+            }
+        }
+    }
+
+    /// Which values may be changed through PTP management messages
+    pub fn management_set_policy(&self) -> ManagementSetPolicy {
+        let mut policy = ManagementSetPolicy::default();
+        for field in &self.allow_management_set {
+            match field {
+                ManagementSetField::Priority1 => policy.priority_1 = true,
+                ManagementSetField::Priority2 => policy.priority_2 = true,
+                ManagementSetField::Domain => policy.domain_number = true,
+                ManagementSetField::SlaveOnly => policy.slave_only = true,
+            }
+        }
+        policy
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    KeyFile(String),
+    Profile(String),
+    Instance(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => writeln!(f, "io error while reading config: {e}"),
+            ConfigError::Toml(e) => writeln!(f, "config toml parsing error: {e}"),
+            ConfigError::KeyFile(e) => writeln!(f, "invalid key file: {e}"),
+            ConfigError::Profile(e) => writeln!(f, "invalid configuration for profile: {e}"),
+            ConfigError::Instance(e) => writeln!(f, "invalid instance configuration: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn default_domain() -> u8 {
     0
 }
 
@@ -247,6 +1431,174 @@ fn default_sdo_id() -> u16 {
     0x000
 }
 
+const GPTP_SDO_ID: u16 = 0x100;
+
+const G8275_2_GRANT_DURATIONS: RangeInclusive<u64> = 60..=1000;
+
+/// IEEE 802.1AS-2020 sections 10.7.2 and 11.5.2
+const GPTP: ProfileRanges = ProfileRanges {
+    announce_interval: (0, -3..=3),
+    sync_interval: (-3, -7..=0),
+    delay_interval: (0, -3..=3),
+    announce_receipt_timeout: (3, 2..=10),
+    delay_mechanism: Some(DelayType::P2P),
+    default_domain: 0,
+    domains: 0..=127,
+    ipv6: false,
+};
+
+/// ITU-T G.8275.1 section 6.2 sends 8 announce and 16 sync and delay request
+/// messages per second
+const G8275_1: ProfileRanges = ProfileRanges {
+    announce_interval: (-3, -3..=-3),
+    sync_interval: (-4, -4..=-4),
+    delay_interval: (-4, -4..=-4),
+    announce_receipt_timeout: (3, 3..=3),
+    delay_mechanism: Some(DelayType::E2E),
+    default_domain: 24,
+    domains: 24..=43,
+    ipv6: false,
+};
+
+/// ITU-T G.8275.2 section 6.2.8 and appendix V
+const G8275_2: ProfileRanges = ProfileRanges {
+    announce_interval: (0, -3..=0),
+    sync_interval: (-4, -7..=0),
+    delay_interval: (-4, -7..=0),
+    announce_receipt_timeout: (3, 2..=10),
+    delay_mechanism: Some(DelayType::E2E),
+    default_domain: 44,
+    domains: 44..=63,
+    ipv6: true,
+};
+
+/// The defaults of a profile and the ranges of settings it permits, with
+/// intervals as the log2 of their duration in seconds
+struct ProfileRanges {
+    announce_interval: (i8, RangeInclusive<i8>),
+    sync_interval: (i8, RangeInclusive<i8>),
+    delay_interval: (i8, RangeInclusive<i8>),
+    announce_receipt_timeout: (u8, RangeInclusive<u8>),
+    /// The only delay mechanism the profile permits, if it restricts it
+    delay_mechanism: Option<DelayType>,
+    default_domain: u8,
+    domains: RangeInclusive<u8>,
+    /// Whether the profile runs over IPv6 next to IPv4
+    ipv6: bool,
+}
+
+impl ProfileRanges {
+    /// Give the settings that are not configured the default of the profile,
+    /// and check all of them against its ranges
+    fn apply(&self, profile: &str, port: &mut PortConfig) -> Result<(), ConfigError> {
+        let intervals = [
+            (
+                "announce-interval",
+                &mut port.announce_interval,
+                &self.announce_interval,
+            ),
+            (
+                "sync-interval",
+                &mut port.sync_interval,
+                &self.sync_interval,
+            ),
+            (
+                "delay-interval",
+                &mut port.delay_interval,
+                &self.delay_interval,
+            ),
+        ];
+        for (name, interval, (default, range)) in intervals {
+            let interval = *interval.get_or_insert(*default);
+            if !range.contains(&interval) {
+                return Err(ConfigError::Profile(format!(
+                    "the {profile} profile requires {name} to be between {} and {}, not {} on {}",
+                    range.start(),
+                    range.end(),
+                    interval,
+                    port.interface
+                )));
+            }
+        }
+
+        let (default, range) = &self.announce_receipt_timeout;
+        let announce_receipt_timeout = *port.announce_receipt_timeout.get_or_insert(*default);
+        if !range.contains(&announce_receipt_timeout) {
+            return Err(ConfigError::Profile(format!(
+                "the {profile} profile requires announce-receipt-timeout to be between {} and {}, \
+                 not {} on {}",
+                range.start(),
+                range.end(),
+                announce_receipt_timeout,
+                port.interface
+            )));
+        }
+
+        if let Some(delay_mechanism) = self.delay_mechanism {
+            match port.delay_mechanism {
+                Some(configured) if configured != delay_mechanism => {
+                    return Err(ConfigError::Profile(format!(
+                        "the {profile} profile requires delay-mechanism {delay_mechanism:?}, not \
+                         {configured:?} on {}",
+                        port.interface
+                    )));
+                }
+                _ => port.delay_mechanism = Some(delay_mechanism),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// IEC/IEEE 61850-9-3 section 5.5 sends every message once per second
+const POWER_61850_9_3: ProfileRanges = ProfileRanges {
+    announce_interval: (0, 0..=0),
+    sync_interval: (0, 0..=0),
+    delay_interval: (0, 0..=0),
+    announce_receipt_timeout: (3, 3..=3),
+    delay_mechanism: Some(DelayType::P2P),
+    default_domain: 0,
+    domains: 0..=u8::MAX,
+    ipv6: false,
+};
+
+/// AES67-2018 annex A
+const AES67: ProfileRanges = ProfileRanges {
+    announce_interval: (1, 0..=4),
+    sync_interval: (-3, -4..=1),
+    delay_interval: (0, -3..=5),
+    announce_receipt_timeout: (3, 2..=10),
+    delay_mechanism: None,
+    default_domain: 0,
+    domains: 0..=127,
+    ipv6: false,
+};
+
+/// SMPTE ST 2059-2:2021 section 6
+const SMPTE_2059_2: ProfileRanges = ProfileRanges {
+    announce_interval: (-2, -3..=1),
+    sync_interval: (-3, -7..=-1),
+    delay_interval: (-3, -7..=4),
+    announce_receipt_timeout: (3, 2..=10),
+    delay_mechanism: None,
+    default_domain: 127,
+    domains: 0..=127,
+    ipv6: true,
+};
+
+/// IETF RFC 8173 section 9
+const ENTERPRISE: ProfileRanges = ProfileRanges {
+    announce_interval: (0, 0..=0),
+    sync_interval: (0, -7..=1),
+    delay_interval: (0, -7..=1),
+    announce_receipt_timeout: (3, 3..=3),
+    delay_mechanism: None,
+    default_domain: 0,
+    domains: 0..=127,
+    ipv6: true,
+};
+
 fn default_slave_only() -> bool {
     false
 }
@@ -271,6 +1623,10 @@ fn default_priority2() -> u8 {
     128
 }
 
+fn default_local_priority() -> u8 {
+    128
+}
+
 fn default_delay_asymmetry() -> i64 {
     0
 }
@@ -283,6 +1639,30 @@ fn default_minor_ptp_version() -> PtpMinorVersion {
     PtpMinorVersion::One
 }
 
+fn default_unicast_min_interval() -> i8 {
+    -7
+}
+
+fn default_unicast_max_duration() -> u64 {
+    300
+}
+
+fn default_unicast_query_interval() -> i8 {
+    1
+}
+
+fn default_unicast_grant_duration() -> u64 {
+    300
+}
+
+fn default_monitor_records() -> bool {
+    true
+}
+
+fn default_records_per_message() -> usize {
+    MAX_MONITORING_RECORDS
+}
+
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct ObservabilityConfig {
@@ -312,15 +1692,158 @@ fn default_metrics_exporter_listen() -> SocketAddr {
     "127.0.0.1:9975".parse().unwrap()
 }
 
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ControlConfig {
+    #[serde(default)]
+    pub control_path: Option<PathBuf>,
+    #[serde(default = "default_control_permissions")]
+    pub control_permissions: u32,
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            control_path: Default::default(),
+            control_permissions: default_control_permissions(),
+        }
+    }
+}
+
+const fn default_control_permissions() -> u32 {
+    0o600
+}
+
+/// Authentication of PTP messages with the AUTHENTICATION TLV
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct AuthenticationConfig {
+    pub key_file: PathBuf,
+    #[serde(default)]
+    pub spp: u8,
+    pub send_key_id: u32,
+}
+
+impl AuthenticationConfig {
+    /// Load the keys from the key file into a security association
+    pub fn security_association(&self) -> Result<SecurityAssociation, ConfigError> {
+        let meta = std::fs::metadata(&self.key_file).map_err(ConfigError::Io)?;
+        if meta.permissions().mode() as libc::mode_t & libc::S_IROTH != 0 {
+            warn!("Unrestricted key file permissions: Others can read.");
+        }
+
+        let contents = read_to_string(&self.key_file).map_err(ConfigError::Io)?;
+        self.parse_key_file(&contents)
+    }
+
+    fn parse_key_file(&self, contents: &str) -> Result<SecurityAssociation, ConfigError> {
+        let key_file: KeyFile = toml::de::from_str(contents).map_err(ConfigError::Toml)?;
+
+        if key_file.keys.len() > MAX_SECURITY_KEYS {
+            return Err(ConfigError::KeyFile(format!(
+                "more than the supported {MAX_SECURITY_KEYS} keys"
+            )));
+        }
+
+        for (index, key) in key_file.keys.iter().enumerate() {
+            if key_file.keys[..index]
+                .iter()
+                .any(|other| other.id == key.id)
+            {
+                return Err(ConfigError::KeyFile(format!(
+                    "key {} appears more than once",
+                    key.id
+                )));
+            }
+        }
+
+        let keys = key_file
+            .keys
+            .into_iter()
+            .map(|key| {
+                let secret = hex::decode(&key.key).map_err(|e| {
+                    ConfigError::KeyFile(format!("key {} is not hexadecimal: {e}", key.id))
+                })?;
+                if secret.is_empty() || secret.len() > MAX_SECURITY_KEY_LEN {
+                    return Err(ConfigError::KeyFile(format!(
+                        "key {} must be between 1 and {MAX_SECURITY_KEY_LEN} bytes",
+                        key.id
+                    )));
+                }
+                if key.icv_length == 0 || key.icv_length > 32 || key.icv_length % 2 != 0 {
+                    return Err(ConfigError::KeyFile(format!(
+                        "icv-length of key {} must be an even number of at most 32 bytes",
+                        key.id
+                    )));
+                }
+
+                Ok(SecurityKey {
+                    id: key.id,
+                    key: secret.into_iter().collect(),
+                    icv_length: key.icv_length,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let security_association = SecurityAssociation {
+            spp: self.spp,
+            keys,
+            send_key_id: self.send_key_id,
+        };
+
+        if !security_association
+            .keys
+            .iter()
+            .any(|key| key.id == self.send_key_id)
+        {
+            return Err(ConfigError::KeyFile(format!(
+                "send-key-id {} is not in the key file",
+                self.send_key_id
+            )));
+        }
+
+        Ok(security_association)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct KeyFile {
+    #[serde(rename = "key")]
+    keys: Vec<KeyConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct KeyConfig {
+    id: u32,
+    key: String,
+    #[serde(default = "default_icv_length")]
+    icv_length: usize,
+}
+
+const fn default_icv_length() -> usize {
+    16
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
-    use statime::config::PtpMinorVersion;
+    use arrayvec::ArrayString;
+    use statime::{
+        config::{
+            AcceptableMaster, AlternateTimescale, ClockIdentity, DatasetComparison, DelayMechanism,
+            DesiredState, GptpConfig, HoldoverClasses, PortAddress, PtpMinorVersion,
+        },
+        time::Duration,
+    };
     use timestamped_socket::interface::InterfaceName;
 
     use crate::{
-        config::{HardwareClock, ObservabilityConfig},
+        config::{
+            ClockType, ConfigError, ControlConfig, HardwareClock, ObservabilityConfig, Profile,
+        },
         tracing::LogLevel,
     };
 
@@ -337,27 +1860,55 @@ interface = "enp0s31f6"
             acceptable_master_list: None,
             hardware_clock: HardwareClock::Auto,
             network_mode: crate::config::NetworkMode::Ipv4,
-            announce_interval: 1,
-            sync_interval: 0,
-            announce_receipt_timeout: 3,
+            announce_interval: None,
+            sync_interval: None,
+            announce_receipt_timeout: None,
             master_only: false,
             delay_asymmetry: 0,
-            delay_mechanism: crate::config::DelayType::E2E,
-            delay_interval: 0,
+            delay_mechanism: None,
+            delay_interval: None,
             minor_ptp_version: PtpMinorVersion::One,
+            unicast_max_grantees: 0,
+            unicast_min_interval: -7,
+            unicast_max_duration: 300,
+            unicast_master_table: vec![],
+            unicast_query_interval: 1,
+            unicast_grant_duration: 300,
+            one_step: false,
+            slave_monitoring: None,
+            desired_state: None,
+            number_alternate_masters: 0,
+            local_priority: 128,
+            multicast_address: crate::config::MulticastAddress::Forwardable,
+            hybrid_e2e: false,
+            unicast_only: false,
         };
 
         let expected = crate::config::Config {
             loglevel: LogLevel::Info,
+            clock_type: ClockType::Ordinary,
+            profile: Profile::Default,
             sdo_id: 0x000,
-            domain: 0,
+            domain: None,
             slave_only: false,
             identity: None,
             priority1: 128,
             priority2: 128,
+            local_priority: 128,
             path_trace: false,
+            external_port_configuration: false,
+            allow_management_set: vec![],
+            alternate_timescales: vec![],
             ports: vec![expected_port],
+            instances: vec![],
             observability: ObservabilityConfig::default(),
+            control: ControlConfig::default(),
+            authentication: None,
+            holdover: None,
+            power_profile: None,
+            synchronization_metadata: None,
+            gpsd: None,
+            leap_seconds_file: None,
             virtual_system_clock: false,
         };
 
@@ -365,4 +1916,1057 @@ interface = "enp0s31f6"
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn management_set_policy() {
+        const CONFIG: &str = r#"
+allow-management-set = ["priority1", "slave-only"]
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        let policy = config.management_set_policy();
+        assert!(policy.priority_1);
+        assert!(!policy.priority_2);
+        assert!(!policy.domain_number);
+        assert!(policy.slave_only);
+    }
+
+    #[test]
+    fn unicast_grantor_config() {
+        const CONFIG: &str = r#"
+[[port]]
+interface = "enp0s31f6"
+
+[[port]]
+interface = "enp0s31f7"
+unicast-max-grantees = 4
+unicast-min-interval = -3
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        let mut ports = config
+            .ports
+            .into_iter()
+            .map(statime::config::PortConfig::from);
+
+        assert_eq!(ports.next().unwrap().unicast_grantor, None);
+        assert_eq!(
+            ports.next().unwrap().unicast_grantor,
+            Some(statime::config::UnicastGrantorConfig {
+                max_grantees: 4,
+                min_interval: statime::time::Interval::from_log_2(-3),
+                max_duration: std::time::Duration::from_secs(300),
+            })
+        );
+    }
+
+    #[test]
+    fn domain_instances() {
+        const CONFIG: &str = r#"
+domain = 0
+
+[[port]]
+interface = "enp0s31f6"
+
+[[instance]]
+domain = 1
+slave-only = true
+
+[[instance.port]]
+interface = "enp0s31f6"
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        assert!(config.check_instances().is_ok());
+        assert_eq!(config.instances.len(), 1);
+
+        let instance = &config.instances[0];
+        assert_eq!(instance.domain, 1);
+        assert_eq!(instance.sdo_id, 0);
+        assert!(instance.slave_only);
+        assert_eq!(instance.priority1, 128);
+        assert_eq!(instance.ports, config.ports);
+
+        // Messages are passed to instances by their domain
+        const DUPLICATE: &str = r#"
+domain = 1
+
+[[port]]
+interface = "enp0s31f6"
+
+[[instance]]
+domain = 1
+
+[[instance.port]]
+interface = "enp0s31f6"
+"#;
+        let config: crate::config::Config = toml::from_str(DUPLICATE).unwrap();
+        assert!(config.check_instances().is_err());
+    }
+
+    #[test]
+    fn acceptable_master_list_config() {
+        const CONFIG: &str = r#"
+[[port]]
+interface = "enp0s31f6"
+acceptable-master-list = [
+    "00FFFFFFFFFFFFFB",
+    { identity = "00FFFFFFFFFFFFFC", port = 2, alternate-priority1 = 100 },
+]
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        assert_eq!(
+            config.ports[0].acceptable_master_list.as_deref(),
+            Some(
+                &[
+                    AcceptableMaster::new(ClockIdentity([0, 255, 255, 255, 255, 255, 255, 251])),
+                    AcceptableMaster {
+                        clock_identity: ClockIdentity([0, 255, 255, 255, 255, 255, 255, 252]),
+                        port_number: Some(2),
+                        alternate_priority_1: 100,
+                    },
+                ][..]
+            )
+        );
+
+        const INVALID: &str = r#"
+[[port]]
+interface = "enp0s31f6"
+acceptable-master-list = [{ identity = "00FFFFFFFFFFFFFC", priority1 = 100 }]
+"#;
+        assert!(toml::from_str::<crate::config::Config>(INVALID).is_err());
+    }
+
+    #[test]
+    fn holdover_config() {
+        const CONFIG: &str = r#"
+[holdover]
+budget = 1500
+clock-classes = "g8275-grandmaster"
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        let holdover = statime::config::HoldoverConfig::from(config.holdover.as_ref().unwrap());
+        assert_eq!(holdover.classes, HoldoverClasses::G8275_GRANDMASTER);
+        assert_eq!(holdover.budget, Duration::from_nanos(1500));
+
+        const DEFAULT_CLASSES: &str = r#"
+[holdover]
+budget = 1000
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+
+        let config: crate::config::Config = toml::from_str(DEFAULT_CLASSES).unwrap();
+        let holdover = statime::config::HoldoverConfig::from(config.holdover.as_ref().unwrap());
+        assert_eq!(holdover.classes, HoldoverClasses::DEGRADATION_A);
+    }
+
+    #[test]
+    fn power_profile_config() {
+        const CONFIG: &str = r#"
+[power-profile]
+grandmaster-id = 3
+time-inaccuracy = 50
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        let power_profile =
+            statime::config::PowerProfileConfig::from(config.power_profile.as_ref().unwrap());
+        assert_eq!(
+            power_profile,
+            statime::config::PowerProfileConfig {
+                grandmaster_id: 3,
+                time_inaccuracy: 50,
+            }
+        );
+
+        // The grandmaster id is required
+        const MISSING_ID: &str = r#"
+[power-profile]
+time-inaccuracy = 50
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+        assert!(toml::from_str::<crate::config::Config>(MISSING_ID).is_err());
+    }
+
+    #[test]
+    fn synchronization_metadata_config() {
+        const CONFIG: &str = r#"
+[synchronization-metadata]
+frame-rate-numerator = 30000
+frame-rate-denominator = 1001
+master-locking-status = "locked"
+drop-frame = true
+current-local-offset = 3600
+daylight-saving = true
+daily-jam = "02:30"
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        let metadata_config = statime::config::SynchronizationMetadataConfig::from(
+            config.synchronization_metadata.as_ref().unwrap(),
+        );
+        assert_eq!(metadata_config.daily_jam, Some(9000));
+        assert_eq!(
+            metadata_config.metadata,
+            statime::config::SynchronizationMetadata {
+                frame_rate_numerator: 30000,
+                frame_rate_denominator: 1001,
+                master_locking_status: statime::config::MasterLockingStatus::Locked,
+                drop_frame: true,
+                current_local_offset: 3600,
+                daylight_saving: true,
+                ..Default::default()
+            }
+        );
+
+        // Jams are at a time of day
+        const INVALID_JAM: &str = r#"
+[synchronization-metadata]
+frame-rate-numerator = 25
+daily-jam = "24:00"
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+        assert!(toml::from_str::<crate::config::Config>(INVALID_JAM).is_err());
+
+        // The frame rate is required
+        const MISSING_FRAME_RATE: &str = r#"
+[synchronization-metadata]
+master-locking-status = "free-run"
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+        assert!(toml::from_str::<crate::config::Config>(MISSING_FRAME_RATE).is_err());
+    }
+
+    #[test]
+    fn gpsd_config() {
+        const CONFIG: &str = r#"
+[gpsd]
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        assert_eq!(
+            config.gpsd.unwrap().address,
+            "127.0.0.1:2947".parse().unwrap()
+        );
+
+        const ADDRESS: &str = r#"
+[gpsd]
+address = "[::1]:2948"
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+
+        let config: crate::config::Config = toml::from_str(ADDRESS).unwrap();
+        assert_eq!(config.gpsd.unwrap().address, "[::1]:2948".parse().unwrap());
+    }
+
+    #[test]
+    fn unicast_master_table_config() {
+        const CONFIG: &str = r#"
+[[port]]
+interface = "enp0s31f6"
+unicast-master-table = ["192.0.2.1", "2001:db8::1", "00:1b:21:aa:bb:0c"]
+unicast-query-interval = 0
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        let port = statime::config::PortConfig::from(config.ports[0].clone());
+        let table = port.unicast_master_table;
+
+        assert_eq!(
+            table.masters.as_slice(),
+            [
+                PortAddress::Ipv4([192, 0, 2, 1]),
+                PortAddress::Ipv6([0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]),
+                PortAddress::Ethernet([0x00, 0x1b, 0x21, 0xaa, 0xbb, 0x0c]),
+            ]
+        );
+        assert_eq!(table.query_interval, statime::time::Interval::ONE_SECOND);
+        assert_eq!(table.grant_duration, std::time::Duration::from_secs(300));
+
+        const INVALID: &str = r#"
+[[port]]
+interface = "enp0s31f6"
+unicast-master-table = ["00:1b:21:aa:bb"]
+"#;
+        assert!(toml::from_str::<crate::config::Config>(INVALID).is_err());
+    }
+
+    #[test]
+    fn external_port_configuration() {
+        const CONFIG: &str = r#"
+external-port-configuration = true
+
+[[port]]
+interface = "enp0s31f6"
+desired-state = "slave"
+
+[[port]]
+interface = "enp0s31f7"
+desired-state = "master"
+
+[[port]]
+interface = "enp0s31f8"
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        assert!(config.external_port_configuration);
+
+        let desired_states: Vec<_> = config
+            .ports
+            .into_iter()
+            .map(|port| statime::config::PortConfig::from(port).desired_state)
+            .collect();
+        assert_eq!(
+            desired_states,
+            [Some(DesiredState::Slave), Some(DesiredState::Master), None]
+        );
+
+        const INVALID: &str = r#"
+[[port]]
+interface = "enp0s31f6"
+desired-state = "listening"
+"#;
+        assert!(toml::from_str::<crate::config::Config>(INVALID).is_err());
+    }
+
+    #[test]
+    fn control_config() {
+        const CONFIG: &str = r#"
+[[port]]
+interface = "enp0s31f6"
+
+[control]
+control-path = "/run/statime/control"
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        assert_eq!(
+            config.control.control_path.as_deref(),
+            Some(std::path::Path::new("/run/statime/control"))
+        );
+        assert_eq!(config.control.control_permissions, 0o600);
+    }
+
+    #[test]
+    fn clock_type_config() {
+        const CONFIG: &str = r#"
+clock-type = "e2e-transparent"
+
+[[port]]
+interface = "enp0s31f6"
+
+[[port]]
+interface = "enp0s31f7"
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        assert_eq!(config.clock_type, ClockType::E2eTransparent);
+
+        let config: crate::config::Config =
+            toml::from_str(&CONFIG.replace("e2e-transparent", "p2p-transparent")).unwrap();
+        assert_eq!(config.clock_type, ClockType::P2pTransparent);
+
+        const INVALID: &str = r#"
+clock-type = "boundary"
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+        assert!(toml::from_str::<crate::config::Config>(INVALID).is_err());
+    }
+
+    #[test]
+    fn alternate_timescale_config() {
+        const CONFIG: &str = r#"
+[[alternate-timescale]]
+key = 1
+offset = 3600
+jump-seconds = 3600
+next-jump = 1711846800
+name = "CET"
+
+[[alternate-timescale]]
+key = 2
+offset = -18000
+name = "EST"
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        let alternate_timescales: Vec<AlternateTimescale> =
+            config.alternate_timescales.iter().map(Into::into).collect();
+        assert_eq!(
+            alternate_timescales,
+            [
+                AlternateTimescale {
+                    key_field: 1,
+                    current_offset: 3600,
+                    jump_seconds: 3600,
+                    time_of_next_jump: 1711846800,
+                    display_name: ArrayString::from("CET").unwrap(),
+                },
+                AlternateTimescale {
+                    key_field: 2,
+                    current_offset: -18000,
+                    jump_seconds: 0,
+                    time_of_next_jump: 0,
+                    display_name: ArrayString::from("EST").unwrap(),
+                },
+            ]
+        );
+
+        const TOO_LONG: &str = r#"
+[[alternate-timescale]]
+key = 1
+offset = 0
+name = "Central European"
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+        assert!(toml::from_str::<crate::config::Config>(TOO_LONG).is_err());
+    }
+
+    #[test]
+    fn authentication_config() {
+        const CONFIG: &str = r#"
+[authentication]
+key-file = "/etc/statime/keys.toml"
+spp = 2
+send-key-id = 5
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        let authentication = config.authentication.unwrap();
+        assert_eq!(authentication.spp, 2);
+        assert_eq!(authentication.send_key_id, 5);
+
+        const KEY_FILE: &str = r#"
+[[key]]
+id = 5
+key = "00112233445566778899aabbccddeeff"
+
+[[key]]
+id = 6
+key = "ffeeddccbbaa99887766554433221100"
+icv-length = 32
+"#;
+
+        let security_association = authentication.parse_key_file(KEY_FILE).unwrap();
+        assert_eq!(security_association.spp, 2);
+        assert_eq!(security_association.send_key_id, 5);
+        assert_eq!(security_association.keys.len(), 2);
+        assert_eq!(security_association.keys[0].id, 5);
+        assert_eq!(security_association.keys[0].key[..2], [0x00, 0x11]);
+        assert_eq!(security_association.keys[0].icv_length, 16);
+        assert_eq!(security_association.keys[1].icv_length, 32);
+
+        // Send key missing
+        assert!(authentication
+            .parse_key_file(&KEY_FILE.replace("id = 5", "id = 4"))
+            .is_err());
+        // Invalid ICV length
+        assert!(authentication
+            .parse_key_file(&KEY_FILE.replace("icv-length = 32", "icv-length = 17"))
+            .is_err());
+        // Invalid key
+        assert!(authentication
+            .parse_key_file(&KEY_FILE.replace("ffeedd", "not hex"))
+            .is_err());
+        // Duplicate key id
+        assert!(matches!(
+            authentication.parse_key_file(&KEY_FILE.replace("id = 6", "id = 5")),
+            Err(ConfigError::KeyFile(_))
+        ));
+    }
+
+    #[test]
+    fn slave_monitoring_config() {
+        const CONFIG: &str = r#"
+[[port]]
+interface = "enp0s31f6"
+
+[port.slave-monitoring]
+collector = "192.0.2.10"
+tx-event-timestamps = false
+records-per-message = 4
+"#;
+
+        let config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        let port_config: statime::config::PortConfig<_> = config.ports[0].clone().into();
+        assert_eq!(
+            port_config.slave_monitoring,
+            Some(statime::config::SlaveMonitoringConfig {
+                collector: PortAddress::Ipv4([192, 0, 2, 10]),
+                rx_sync_timing_data: true,
+                rx_sync_computed_data: true,
+                tx_event_timestamps: false,
+                records_per_message: 4,
+            })
+        );
+
+        const INVALID: &str = r#"
+[[port]]
+interface = "enp0s31f6"
+
+[port.slave-monitoring]
+collector = "collector.example"
+"#;
+        assert!(toml::from_str::<crate::config::Config>(INVALID).is_err());
+    }
+
+    #[test]
+    fn gptp_profile() {
+        const CONFIG: &str = r#"
+profile = "gptp"
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ethernet"
+"#;
+
+        let mut config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        config.apply_profile().unwrap();
+        assert_eq!(config.sdo_id, 0x100);
+        assert!(config.path_trace);
+        assert_eq!(config.gptp_config(), Some(GptpConfig::default()));
+
+        let port_config: statime::config::PortConfig<_> = config.ports[0].clone().into();
+        assert!(matches!(
+            port_config.delay_mechanism,
+            DelayMechanism::P2P { .. }
+        ));
+        assert!(!port_config.one_step);
+
+        // gPTP only runs over ethernet
+        const IPV4: &str = r#"
+profile = "gptp"
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+        let mut config: crate::config::Config = toml::from_str(IPV4).unwrap();
+        assert!(matches!(
+            config.apply_profile(),
+            Err(crate::config::ConfigError::Profile(_))
+        ));
+
+        // The default profile leaves everything as configured
+        const DEFAULT: &str = r#"
+[[port]]
+interface = "enp0s31f6"
+"#;
+        let mut config: crate::config::Config = toml::from_str(DEFAULT).unwrap();
+        config.apply_profile().unwrap();
+        assert_eq!(config.sdo_id, 0);
+        assert_eq!(config.gptp_config(), None);
+    }
+
+    #[test]
+    fn g8275_1_profile() {
+        const CONFIG: &str = r#"
+profile = "g8275-1"
+local-priority = 100
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ethernet"
+not-slave = true
+local-priority = 64
+multicast-address = "non-forwardable"
+"#;
+
+        let mut config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        config.apply_profile().unwrap();
+        assert_eq!(config.domain(), 24);
+        assert_eq!(
+            config.dataset_comparison(),
+            DatasetComparison::G8275 {
+                local_priority: 100
+            }
+        );
+
+        let port_config: statime::config::PortConfig<_> = config.ports[0].clone().into();
+        assert!(port_config.master_only);
+        assert!(port_config.link_local);
+        assert_eq!(port_config.local_priority, 64);
+        assert_eq!(port_config.announce_interval.as_log_2(), -3);
+        assert_eq!(port_config.sync_interval.as_log_2(), -4);
+
+        // Domains outside of 24 to 43 are not allowed
+        const DOMAIN: &str = r#"
+profile = "g8275-1"
+domain = 4
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ethernet"
+"#;
+        let mut config: crate::config::Config = toml::from_str(DOMAIN).unwrap();
+        assert!(matches!(
+            config.apply_profile(),
+            Err(crate::config::ConfigError::Profile(_))
+        ));
+    }
+
+    #[test]
+    fn g8275_2_profile() {
+        const CONFIG: &str = r#"
+profile = "g8275-2"
+slave-only = true
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ipv6"
+sync-interval = -6
+delay-interval = -6
+unicast-master-table = ["2001:db8::1"]
+"#;
+
+        let mut config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        config.apply_profile().unwrap();
+        assert_eq!(config.domain(), 44);
+        assert_eq!(config.profile.clock_quality(true).clock_class, 255);
+        assert_eq!(config.profile.kalman_config().packet_selection_window, 16);
+        assert_eq!(
+            config.dataset_comparison(),
+            DatasetComparison::G8275 {
+                local_priority: 128
+            }
+        );
+
+        let port_config: statime::config::PortConfig<_> = config.ports[0].clone().into();
+        assert_eq!(port_config.sync_interval.as_log_2(), -6);
+        assert_eq!(port_config.unicast_master_table.masters.len(), 1);
+        assert!(port_config.unicast_only);
+
+        // The profile runs over IP only
+        const ETHERNET: &str = r#"
+profile = "g8275-2"
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ethernet"
+"#;
+        let mut config: crate::config::Config = toml::from_str(ETHERNET).unwrap();
+        assert!(matches!(
+            config.apply_profile(),
+            Err(crate::config::ConfigError::Profile(_))
+        ));
+
+        // Message rates are limited to the ranges of the profile
+        const SYNC_INTERVAL: &str = r#"
+profile = "g8275-2"
+
+[[port]]
+interface = "enp0s31f6"
+sync-interval = 1
+"#;
+        let mut config: crate::config::Config = toml::from_str(SYNC_INTERVAL).unwrap();
+        assert!(matches!(
+            config.apply_profile(),
+            Err(crate::config::ConfigError::Profile(_))
+        ));
+
+        // As are the durations of unicast grants
+        const GRANT_DURATION: &str = r#"
+profile = "g8275-2"
+
+[[port]]
+interface = "enp0s31f6"
+unicast-grant-duration = 30
+"#;
+        let mut config: crate::config::Config = toml::from_str(GRANT_DURATION).unwrap();
+        assert!(matches!(
+            config.apply_profile(),
+            Err(crate::config::ConfigError::Profile(_))
+        ));
+    }
+
+    #[test]
+    fn power_61850_9_3_profile() {
+        const CONFIG: &str = r#"
+profile = "power-61850-9-3"
+domain = 93
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ethernet"
+"#;
+
+        let mut config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        config.apply_profile().unwrap();
+        assert_eq!(config.domain(), 93);
+
+        let port_config: statime::config::PortConfig<_> = config.ports[0].clone().into();
+        assert!(matches!(
+            port_config.delay_mechanism,
+            DelayMechanism::P2P { .. }
+        ));
+        assert_eq!(port_config.announce_interval.as_log_2(), 0);
+        assert_eq!(port_config.sync_interval.as_log_2(), 0);
+        assert_eq!(port_config.announce_receipt_timeout, 3);
+
+        // Every message is sent once per second
+        const SYNC_INTERVAL: &str = r#"
+profile = "power-61850-9-3"
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ethernet"
+sync-interval = -3
+"#;
+        let mut config: crate::config::Config = toml::from_str(SYNC_INTERVAL).unwrap();
+        let Err(crate::config::ConfigError::Profile(error)) = config.apply_profile() else {
+            panic!("Expected a profile error");
+        };
+        assert_eq!(
+            error,
+            "the power-61850-9-3 profile requires sync-interval to be between 0 and 0, not -3 on \
+             enp0s31f6"
+        );
+
+        // Transparent clocks measure the peer delay
+        const E2E_TRANSPARENT: &str = r#"
+profile = "power-61850-9-3"
+clock-type = "e2e-transparent"
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ethernet"
+"#;
+        let mut config: crate::config::Config = toml::from_str(E2E_TRANSPARENT).unwrap();
+        assert!(matches!(
+            config.apply_profile(),
+            Err(crate::config::ConfigError::Profile(_))
+        ));
+    }
+
+    #[test]
+    fn media_profiles() {
+        const AES67: &str = r#"
+profile = "aes67"
+
+[[port]]
+interface = "enp0s31f6"
+delay-interval = -2
+"#;
+
+        let mut config: crate::config::Config = toml::from_str(AES67).unwrap();
+        config.apply_profile().unwrap();
+        assert_eq!(config.domain(), 0);
+        assert_eq!(config.dataset_comparison(), DatasetComparison::Ieee1588);
+
+        let port_config: statime::config::PortConfig<_> = config.ports[0].clone().into();
+        assert_eq!(port_config.announce_interval.as_log_2(), 1);
+        assert_eq!(port_config.sync_interval.as_log_2(), -3);
+        assert_eq!(port_config.min_delay_req_interval().as_log_2(), -2);
+
+        // AES67 runs over IPv4 only
+        const AES67_IPV6: &str = r#"
+profile = "aes67"
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ipv6"
+"#;
+        let mut config: crate::config::Config = toml::from_str(AES67_IPV6).unwrap();
+        assert!(matches!(
+            config.apply_profile(),
+            Err(crate::config::ConfigError::Profile(_))
+        ));
+
+        const SMPTE_2059_2: &str = r#"
+profile = "smpte-2059-2"
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ipv6"
+"#;
+        let mut config: crate::config::Config = toml::from_str(SMPTE_2059_2).unwrap();
+        config.apply_profile().unwrap();
+        assert_eq!(config.domain(), 127);
+
+        let port_config: statime::config::PortConfig<_> = config.ports[0].clone().into();
+        assert_eq!(port_config.announce_interval.as_log_2(), -2);
+        assert_eq!(port_config.sync_interval.as_log_2(), -3);
+
+        // Domains above 127 are reserved
+        const DOMAIN: &str = r#"
+profile = "smpte-2059-2"
+domain = 128
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+        let mut config: crate::config::Config = toml::from_str(DOMAIN).unwrap();
+        let Err(crate::config::ConfigError::Profile(error)) = config.apply_profile() else {
+            panic!("Expected a profile error");
+        };
+        assert_eq!(
+            error,
+            "the smpte-2059-2 profile requires a domain between 0 and 127, not 128"
+        );
+    }
+
+    #[test]
+    fn profile_explicit_settings() {
+        // Configured values are checked even when equal to the generic default
+        let rejected = [
+            ("enterprise", "announce-interval = 1"),
+            ("power-61850-9-3", "announce-interval = 1"),
+            ("smpte-2059-2", "sync-interval = 0"),
+            ("g8275-1", "sync-interval = 0"),
+            ("g8275-1", "delay-mechanism = \"P2P\""),
+            ("gptp", "delay-mechanism = \"E2E\""),
+            ("gptp", "sync-interval = 1"),
+        ];
+        for (profile, setting) in rejected {
+            let network_mode = match profile {
+                "power-61850-9-3" | "g8275-1" | "gptp" => "ethernet",
+                _ => "ipv4",
+            };
+            let config = format!(
+                "profile = \"{profile}\"\n\n[[port]]\ninterface = \"enp0s31f6\"\nnetwork-mode = \
+                 \"{network_mode}\"\n{setting}\n"
+            );
+            let mut config: crate::config::Config = toml::from_str(&config).unwrap();
+            assert!(
+                matches!(
+                    config.apply_profile(),
+                    Err(crate::config::ConfigError::Profile(_))
+                ),
+                "{profile} accepted {setting}"
+            );
+        }
+
+        // And are not replaced by the default of the profile
+        const AES67: &str = r#"
+profile = "aes67"
+
+[[port]]
+interface = "enp0s31f6"
+sync-interval = 0
+"#;
+        let mut config: crate::config::Config = toml::from_str(AES67).unwrap();
+        config.apply_profile().unwrap();
+        let port_config: statime::config::PortConfig<_> = config.ports[0].clone().into();
+        assert_eq!(port_config.sync_interval.as_log_2(), 0);
+
+        const SMPTE_2059_2: &str = r#"
+profile = "smpte-2059-2"
+domain = 0
+
+[[port]]
+interface = "enp0s31f6"
+"#;
+        let mut config: crate::config::Config = toml::from_str(SMPTE_2059_2).unwrap();
+        config.apply_profile().unwrap();
+        assert_eq!(config.domain(), 0);
+
+        // Settings that are not configured get the default of the profile
+        const GPTP: &str = r#"
+profile = "gptp"
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ethernet"
+"#;
+        let mut config: crate::config::Config = toml::from_str(GPTP).unwrap();
+        config.apply_profile().unwrap();
+        let port_config: statime::config::PortConfig<_> = config.ports[0].clone().into();
+        assert_eq!(port_config.announce_interval.as_log_2(), 0);
+        assert_eq!(port_config.sync_interval.as_log_2(), -3);
+        assert!(matches!(
+            port_config.delay_mechanism,
+            DelayMechanism::P2P { .. }
+        ));
+    }
+
+    #[test]
+    fn profile_conflicting_settings() {
+        // Settings fixed by a profile are rejected instead of replaced
+        let rejected = [
+            (
+                "gptp",
+                "sdo-id = 0x200",
+                "",
+                "the gptp profile requires sdo-id 0x100, not 0x200",
+            ),
+            (
+                "gptp",
+                "",
+                "one-step = true",
+                "the gptp profile requires one-step false, not true on enp0s31f6",
+            ),
+            (
+                "gptp",
+                "",
+                "unicast-max-grantees = 2",
+                "the gptp profile requires unicast-max-grantees 0, not 2 on enp0s31f6",
+            ),
+            (
+                "gptp",
+                "",
+                "unicast-master-table = [\"00:11:22:33:44:55\"]",
+                "the gptp profile requires 0 unicast-master-table entries, not 1 on enp0s31f6",
+            ),
+            (
+                "g8275-1",
+                "sdo-id = 1",
+                "",
+                "the g8275-1 profile requires sdo-id 0x0, not 0x1",
+            ),
+            (
+                "g8275-1",
+                "priority1 = 64",
+                "",
+                "the g8275-1 profile requires priority1 128, not 64",
+            ),
+            (
+                "g8275-1",
+                "",
+                "[[instance]]\ndomain = 25\npriority1 = 64\n\n[[instance.port]]\ninterface = \
+                 \"enp0s31f7\"",
+                "the g8275-1 profile requires priority1 128, not 64",
+            ),
+            (
+                "g8275-1",
+                "",
+                "unicast-max-grantees = 2",
+                "the g8275-1 profile requires unicast-max-grantees 0, not 2 on enp0s31f6",
+            ),
+            (
+                "g8275-1",
+                "",
+                "unicast-master-table = [\"00:11:22:33:44:55\"]",
+                "the g8275-1 profile requires 0 unicast-master-table entries, not 1 on enp0s31f6",
+            ),
+            (
+                "power-61850-9-3",
+                "",
+                "unicast-max-grantees = 2",
+                "the power-61850-9-3 profile requires unicast-max-grantees 0, not 2 on enp0s31f6",
+            ),
+            (
+                "power-61850-9-3",
+                "",
+                "unicast-master-table = [\"00:11:22:33:44:55\"]",
+                "the power-61850-9-3 profile requires 0 unicast-master-table entries, not 1 on \
+                 enp0s31f6",
+            ),
+        ];
+        for (profile, clock_setting, port_setting, expected) in rejected {
+            let config = format!(
+                "profile = \"{profile}\"\n{clock_setting}\n\n[[port]]\ninterface = \
+                 \"enp0s31f6\"\nnetwork-mode = \"ethernet\"\n{port_setting}\n"
+            );
+            let mut config: crate::config::Config = toml::from_str(&config).unwrap();
+            let Err(crate::config::ConfigError::Profile(error)) = config.apply_profile() else {
+                panic!("{profile} accepted {clock_setting}{port_setting}");
+            };
+            assert_eq!(error, expected);
+        }
+
+        // Values equal to those of the profile are accepted
+        const GPTP: &str = r#"
+profile = "gptp"
+sdo-id = 0x100
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ethernet"
+one-step = false
+"#;
+        let mut config: crate::config::Config = toml::from_str(GPTP).unwrap();
+        config.apply_profile().unwrap();
+        assert_eq!(config.sdo_id, 0x100);
+    }
+
+    #[test]
+    fn enterprise_profile() {
+        const CONFIG: &str = r#"
+profile = "enterprise"
+
+[[port]]
+interface = "enp0s31f6"
+sync-interval = -4
+
+[[port]]
+interface = "enp0s31f7"
+hybrid-e2e = true
+"#;
+
+        let mut config: crate::config::Config = toml::from_str(CONFIG).unwrap();
+        config.apply_profile().unwrap();
+
+        let port_config: statime::config::PortConfig<_> = config.ports[0].clone().into();
+        assert_eq!(port_config.announce_interval.as_log_2(), 0);
+        assert_eq!(port_config.sync_interval.as_log_2(), -4);
+        assert_eq!(port_config.announce_receipt_timeout, 3);
+        assert!(!port_config.hybrid_e2e);
+
+        let port_config: statime::config::PortConfig<_> = config.ports[1].clone().into();
+        assert!(port_config.hybrid_e2e);
+
+        // Announce messages are sent once per second
+        const ANNOUNCE_INTERVAL: &str = r#"
+profile = "enterprise"
+
+[[port]]
+interface = "enp0s31f6"
+announce-interval = 2
+"#;
+        let mut config: crate::config::Config = toml::from_str(ANNOUNCE_INTERVAL).unwrap();
+        assert!(matches!(
+            config.apply_profile(),
+            Err(crate::config::ConfigError::Profile(_))
+        ));
+
+        // And only over IP
+        const ETHERNET: &str = r#"
+profile = "enterprise"
+
+[[port]]
+interface = "enp0s31f6"
+network-mode = "ethernet"
+"#;
+        let mut config: crate::config::Config = toml::from_str(ETHERNET).unwrap();
+        assert!(matches!(
+            config.apply_profile(),
+            Err(crate::config::ConfigError::Profile(_))
+        ));
+    }
 }
@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+
+use super::{read_json, write_json, ControlRequest, ControlResponse, MAX_RESPONSE_SIZE};
+use crate::initialize_logging_parse_config;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+pub(crate) struct Args {
+    /// Configuration file to use
+    #[clap(
+        long = "config",
+        short = 'c',
+        default_value = "/etc/statime/statime.toml"
+    )]
+    config: PathBuf,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+enum Command {
+    /// Show information about the running daemon
+    #[clap(subcommand)]
+    Show(Show),
+    /// Change a setting of the running daemon
+    #[clap(subcommand)]
+    Set(Set),
+    /// Enable a port, given by its port number
+    EnablePort { port: u16 },
+    /// Disable a port, given by its port number
+    DisablePort { port: u16 },
+}
+
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+enum Show {
+    /// The default, current, parent, time properties and path trace datasets
+    Datasets,
+    /// The foreign masters known to each port
+    ForeignMasters,
+    /// The port dataset of each port
+    PortState,
+}
+
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+enum Set {
+    /// The priority1 of the instance
+    Priority1 { value: u8 },
+    /// The priority2 of the instance
+    Priority2 { value: u8 },
+    /// Whether the instance may never become master
+    SlaveOnly {
+        #[clap(action = clap::ArgAction::Set)]
+        value: bool,
+    },
+}
+
+impl From<Command> for ControlRequest {
+    fn from(command: Command) -> Self {
+        match command {
+            Command::Show(Show::Datasets) => ControlRequest::ShowDatasets,
+            Command::Show(Show::ForeignMasters) => ControlRequest::ShowForeignMasters,
+            Command::Show(Show::PortState) => ControlRequest::ShowPortState,
+            Command::Set(Set::Priority1 { value }) => ControlRequest::SetPriority1 { value },
+            Command::Set(Set::Priority2 { value }) => ControlRequest::SetPriority2 { value },
+            Command::Set(Set::SlaveOnly { value }) => ControlRequest::SetSlaveOnly { value },
+            Command::EnablePort { port } => ControlRequest::EnablePort { port },
+            Command::DisablePort { port } => ControlRequest::DisablePort { port },
+        }
+    }
+}
+
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let options = Args::parse();
+
+    let config = initialize_logging_parse_config(&options.config);
+
+    let control_socket_path = match config.control.control_path {
+        Some(path) => path,
+        None => {
+            eprintln!(
+                "A control socket path must be configured using the control-path option in the \
+                 [control] section of the configuration"
+            );
+            std::process::exit(1);
+        }
+    };
+
+    match request(&control_socket_path, options.command.into()).await? {
+        ControlResponse::Datasets { instance } => {
+            println!("{}", serde_json::to_string_pretty(&instance)?)
+        }
+        ControlResponse::ForeignMasters { ports } => {
+            println!("{}", serde_json::to_string_pretty(&ports)?)
+        }
+        ControlResponse::PortState { ports } => {
+            println!("{}", serde_json::to_string_pretty(&ports)?)
+        }
+        ControlResponse::Done => {}
+        ControlResponse::Error { message } => {
+            eprintln!("{message}");
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+async fn request(
+    control_socket_path: &Path,
+    request: ControlRequest,
+) -> std::io::Result<ControlResponse> {
+    let mut stream = tokio::net::UnixStream::connect(control_socket_path).await?;
+    write_json(&mut stream, &request).await?;
+    read_json(&mut stream, MAX_RESPONSE_SIZE).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use clap::Parser;
+
+    use super::{Args, Command, Set, Show};
+    use crate::control::ControlRequest;
+
+    const BINARY: &str = "/usr/bin/statime-ctl";
+
+    #[test]
+    fn cli_config() {
+        let config_str = "/foo/bar/statime.toml";
+        let config = Path::new(config_str);
+        let arguments = &[BINARY, "-c", config_str, "show", "datasets"];
+
+        let options = Args::try_parse_from(arguments).unwrap();
+        assert_eq!(options.config.as_path(), config);
+        assert_eq!(options.command, Command::Show(Show::Datasets));
+    }
+
+    #[test]
+    fn cli_commands() {
+        let options = Args::try_parse_from([BINARY, "set", "slave-only", "false"]).unwrap();
+        assert_eq!(
+            options.command,
+            Command::Set(Set::SlaveOnly { value: false })
+        );
+
+        let options = Args::try_parse_from([BINARY, "set", "priority1", "12"]).unwrap();
+        assert_eq!(
+            ControlRequest::from(options.command),
+            ControlRequest::SetPriority1 { value: 12 }
+        );
+
+        let options = Args::try_parse_from([BINARY, "disable-port", "2"]).unwrap();
+        assert_eq!(
+            ControlRequest::from(options.command),
+            ControlRequest::DisablePort { port: 2 }
+        );
+
+        assert!(Args::try_parse_from([BINARY, "set", "priority2", "256"]).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+//! Runtime management of a running statime daemon
+//!
+//! The daemon listens on the Unix socket configured in the `[control]`
+//! section. A client connects, writes a single JSON encoded [`ControlRequest`]
+//! and closes its side of the connection, after which the daemon answers with
+//! a single JSON encoded [`ControlResponse`] and closes the connection.
+
+use serde::{Deserialize, Serialize};
+use statime::observability::port::{ForeignMaster, PortDS};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+
+use crate::observer::ObservableInstanceState;
+
+pub mod client;
+pub mod server;
+
+/// A request sent to the daemon over the control socket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum ControlRequest {
+    /// Show the datasets of the instance
+    ShowDatasets,
+    /// Show the foreign masters known to each port
+    ShowForeignMasters,
+    /// Show the port dataset of each port
+    ShowPortState,
+    /// Change the priority1 of the instance
+    SetPriority1 { value: u8 },
+    /// Change the priority2 of the instance
+    SetPriority2 { value: u8 },
+    /// Change whether the instance is slave only
+    SetSlaveOnly { value: bool },
+    /// Enable the port with the given port number
+    EnablePort { port: u16 },
+    /// Disable the port with the given port number
+    DisablePort { port: u16 },
+}
+
+/// The answer of the daemon to a [`ControlRequest`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum ControlResponse {
+    Datasets {
+        instance: Box<ObservableInstanceState>,
+    },
+    ForeignMasters {
+        ports: Vec<PortForeignMasters>,
+    },
+    PortState {
+        ports: Vec<PortDS>,
+    },
+    Done,
+    Error {
+        message: String,
+    },
+}
+
+/// The foreign masters known to a single port
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortForeignMasters {
+    /// Port number of the port, as also found in its port dataset
+    pub port: u16,
+    pub foreign_masters: Vec<ForeignMaster>,
+}
+
+/// Maximum size of an encoded [`ControlRequest`]
+const MAX_REQUEST_SIZE: u64 = 4 * 1024;
+
+/// Maximum size of an encoded [`ControlResponse`]
+const MAX_RESPONSE_SIZE: u64 = 1024 * 1024;
+
+/// Read a JSON value of at most `limit` bytes from the stream, up to the point
+/// where the other side closes its side of the connection.
+async fn read_json<T>(stream: &mut UnixStream, limit: u64) -> std::io::Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let mut buffer = Vec::with_capacity(4 * 1024);
+    // One byte more than the limit tells whether the value is too large
+    stream.take(limit + 1).read_to_end(&mut buffer).await?;
+    if buffer.len() as u64 > limit {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("larger than {limit} bytes"),
+        ));
+    }
+    serde_json::from_slice(&buffer)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+}
+
+/// Write a JSON value to the stream and close our side of the connection.
+async fn write_json<T>(stream: &mut UnixStream, value: &T) -> std::io::Result<()>
+where
+    T: serde::Serialize,
+{
+    crate::observer::write_json(stream, value).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_format() {
+        let request = ControlRequest::SetPriority1 { value: 12 };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(json, r#"{"command":"set-priority1","value":12}"#);
+        assert_eq!(
+            serde_json::from_str::<ControlRequest>(&json).unwrap(),
+            request
+        );
+
+        let json = serde_json::to_string(&ControlRequest::ShowForeignMasters).unwrap();
+        assert_eq!(json, r#"{"command":"show-foreign-masters"}"#);
+    }
+
+    #[tokio::test]
+    async fn request_response_roundtrip() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+
+        write_json(&mut client, &ControlRequest::DisablePort { port: 2 })
+            .await
+            .unwrap();
+        let request: ControlRequest = read_json(&mut server, MAX_REQUEST_SIZE).await.unwrap();
+        assert_eq!(request, ControlRequest::DisablePort { port: 2 });
+
+        let response = ControlResponse::Error {
+            message: "no port with number 2".into(),
+        };
+        write_json(&mut server, &response).await.unwrap();
+        let ControlResponse::Error { message } =
+            read_json(&mut client, MAX_RESPONSE_SIZE).await.unwrap()
+        else {
+            panic!("Unexpected response");
+        };
+        assert_eq!(message, "no port with number 2");
+    }
+
+    #[tokio::test]
+    async fn request_too_large() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+
+        let padding = " ".repeat(MAX_REQUEST_SIZE as usize);
+        let request = format!(r#"{{"command":"show-foreign-masters"}}{padding}"#);
+        client.write_all(request.as_bytes()).await.unwrap();
+        client.shutdown().await.unwrap();
+
+        let error = read_json::<ControlRequest>(&mut server, MAX_REQUEST_SIZE)
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,203 @@
+use std::{os::unix::prelude::PermissionsExt, sync::RwLock};
+
+use statime::{filters::KalmanFilter, PtpInstance, PtpInstanceState};
+use tokio::{
+    net::UnixStream,
+    sync::{mpsc, oneshot, watch},
+    task::JoinHandle,
+};
+
+use super::{
+    read_json, write_json, ControlRequest, ControlResponse, PortForeignMasters, MAX_REQUEST_SIZE,
+};
+use crate::{
+    config::Config,
+    observer::{create_unix_socket_with_permissions, ObservableInstanceState},
+};
+
+type Instance = PtpInstance<KalmanFilter, RwLock<PtpInstanceState>>;
+
+/// Request to enable or disable a port
+///
+/// Ports can only be changed while they are in the BMCA, so these are handled
+/// by the main task during the next BMCA run. Whether the port number was
+/// valid is reported back over `reply`.
+#[derive(Debug)]
+pub struct PortCommand {
+    pub port: u16,
+    pub enabled: bool,
+    pub reply: oneshot::Sender<Result<(), String>>,
+}
+
+pub async fn spawn(
+    config: &Config,
+    instance: &'static Instance,
+    instance_state_receiver: watch::Receiver<ObservableInstanceState>,
+    foreign_masters_receiver: watch::Receiver<Vec<PortForeignMasters>>,
+    port_command_sender: mpsc::Sender<PortCommand>,
+) -> JoinHandle<std::io::Result<()>> {
+    let config = config.clone();
+    tokio::spawn(async move {
+        let result = control(
+            config,
+            instance,
+            instance_state_receiver,
+            foreign_masters_receiver,
+            port_command_sender,
+        )
+        .await;
+        if let Err(ref e) = result {
+            log::warn!("Abnormal termination of the control socket: {e}");
+            log::warn!("The control socket will not be available");
+        }
+        result
+    })
+}
+
+async fn control(
+    config: Config,
+    instance: &'static Instance,
+    instance_state_receiver: watch::Receiver<ObservableInstanceState>,
+    foreign_masters_receiver: watch::Receiver<Vec<PortForeignMasters>>,
+    port_command_sender: mpsc::Sender<PortCommand>,
+) -> std::io::Result<()> {
+    let path = match config.control.control_path {
+        Some(ref path) => path,
+        None => return Ok(()),
+    };
+
+    // The control socket allows changing the behaviour of the daemon, so unlike
+    // the observation socket it is only accessible to root by default.
+    let permissions: std::fs::Permissions =
+        PermissionsExt::from_mode(config.control.control_permissions);
+
+    let listener = create_unix_socket_with_permissions(path, permissions)?;
+
+    loop {
+        let (stream, _addr) = listener.accept().await?;
+
+        // Port commands wait for the next BMCA, so don't let them block other
+        // clients.
+        tokio::spawn(handle_connection(
+            stream,
+            instance,
+            instance_state_receiver.clone(),
+            foreign_masters_receiver.clone(),
+            port_command_sender.clone(),
+        ));
+    }
+}
+
+async fn handle_connection(
+    mut stream: UnixStream,
+    instance: &'static Instance,
+    instance_state_receiver: watch::Receiver<ObservableInstanceState>,
+    foreign_masters_receiver: watch::Receiver<Vec<PortForeignMasters>>,
+    port_command_sender: mpsc::Sender<PortCommand>,
+) {
+    let response = match read_json(&mut stream, MAX_REQUEST_SIZE).await {
+        Ok(request) => {
+            handle_request(
+                request,
+                instance,
+                &instance_state_receiver,
+                &foreign_masters_receiver,
+                &port_command_sender,
+            )
+            .await
+        }
+        Err(e) => ControlResponse::Error {
+            message: format!("invalid request: {e}"),
+        },
+    };
+
+    if let Err(e) = write_json(&mut stream, &response).await {
+        log::warn!("Could not answer control request: {e}");
+    }
+}
+
+async fn handle_request(
+    request: ControlRequest,
+    instance: &'static Instance,
+    instance_state_receiver: &watch::Receiver<ObservableInstanceState>,
+    foreign_masters_receiver: &watch::Receiver<Vec<PortForeignMasters>>,
+    port_command_sender: &mpsc::Sender<PortCommand>,
+) -> ControlResponse {
+    log::debug!("Control request: {request:?}");
+
+    match request {
+        ControlRequest::ShowDatasets => {
+            // The current and port datasets need the ports, so those are from the last
+            // BMCA run. Everything else is read directly to include recent changes.
+            let snapshot = instance_state_receiver.borrow().to_owned();
+            ControlResponse::Datasets {
+                instance: Box::new(ObservableInstanceState {
+                    default_ds: instance.default_ds(),
+                    current_ds: snapshot.current_ds,
+                    parent_ds: instance.parent_ds(),
+                    time_properties_ds: instance.time_properties_ds(),
+                    path_trace_ds: instance.path_trace_ds(),
+                    alternate_timescale_offsets_ds: instance.alternate_timescale_offsets_ds(),
+                    holdover_state: instance.holdover_state(),
+                    power_profile: instance.power_profile(),
+                    synchronization_metadata: instance.synchronization_metadata(),
+                    port_ds: snapshot.port_ds,
+                    slave_reports: snapshot.slave_reports,
+                    alternate_masters: snapshot.alternate_masters,
+                }),
+            }
+        }
+        ControlRequest::ShowForeignMasters => ControlResponse::ForeignMasters {
+            ports: foreign_masters_receiver.borrow().to_owned(),
+        },
+        ControlRequest::ShowPortState => ControlResponse::PortState {
+            ports: instance_state_receiver.borrow().port_ds.clone(),
+        },
+        ControlRequest::SetPriority1 { value } => {
+            log::info!("Setting priority1 to {value}");
+            instance.set_priority_1(value);
+            ControlResponse::Done
+        }
+        ControlRequest::SetPriority2 { value } => {
+            log::info!("Setting priority2 to {value}");
+            instance.set_priority_2(value);
+            ControlResponse::Done
+        }
+        ControlRequest::SetSlaveOnly { value } => {
+            log::info!("Setting slave-only to {value}");
+            instance.set_slave_only(value);
+            ControlResponse::Done
+        }
+        ControlRequest::EnablePort { port } => port_command(port_command_sender, port, true).await,
+        ControlRequest::DisablePort { port } => {
+            port_command(port_command_sender, port, false).await
+        }
+    }
+}
+
+async fn port_command(
+    port_command_sender: &mpsc::Sender<PortCommand>,
+    port: u16,
+    enabled: bool,
+) -> ControlResponse {
+    let (reply, reply_receiver) = oneshot::channel();
+    let command = PortCommand {
+        port,
+        enabled,
+        reply,
+    };
+
+    if port_command_sender.send(command).await.is_err() {
+        return ControlResponse::Error {
+            message: "daemon is shutting down".into(),
+        };
+    }
+
+    match reply_receiver.await {
+        Ok(Ok(())) => ControlResponse::Done,
+        Ok(Err(message)) => ControlResponse::Error { message },
+        Err(_) => ControlResponse::Error {
+            message: "daemon is shutting down".into(),
+        },
+    }
+}
@@ -0,0 +1,454 @@
+//! A GNSS receiver managed by gpsd as the time source of the grandmaster
+//!
+//! The TPV reports of gpsd tell whether the receiver has a fix and the number
+//! of leap seconds, the PPS reports give the offset of the system clock at
+//! each pulse. While the instance is the grandmaster those offsets discipline
+//! the system clock.
+//!
+//! A leap seconds file, when configured, takes precedence over the receiver
+//! for the UTC offset and announces upcoming leap seconds.
+
+use std::net::SocketAddr;
+
+use serde::Deserialize;
+use statime::{
+    config::{LeapIndicator, TimePropertiesDS, TimeSource},
+    filters::{Filter, KalmanConfiguration, KalmanFilter},
+    port::Measurement,
+    time::{Duration, Time},
+    Clock, PtpInstance, PtpInstanceStateMutex,
+};
+use timestamped_socket::socket::Timestamp;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::TcpStream,
+};
+
+use crate::{clock::PortTimestampToTime, config::GpsdConfig, leap_seconds::LeapSeconds};
+
+/// Request reports in JSON, including those of the PPS
+const WATCH: &[u8] = b"?WATCH={\"enable\":true,\"json\":true,\"pps\":true};\n";
+
+/// Offset of TAI to GPS time, which counts its leap seconds from 1980 on
+const TAI_GPS_OFFSET: i16 = 19;
+
+/// The reference is lost when no PPS arrives for this long
+const PPS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+const RECONNECT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// A report of gpsd, see gpsd_json(5)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(tag = "class")]
+pub enum Report {
+    #[serde(rename = "TPV")]
+    Tpv(Tpv),
+    #[serde(rename = "PPS")]
+    Pps(Pps),
+    #[serde(other)]
+    Other,
+}
+
+/// A time-position-velocity report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Tpv {
+    /// 0 when unknown, 1 without fix, 2 for a 2D and 3 for a 3D fix
+    #[serde(default)]
+    pub mode: u8,
+    /// Leap seconds of GPS time to UTC
+    #[serde(default)]
+    pub leapseconds: Option<i16>,
+}
+
+/// The UTC time of a pulse and the system (`CLOCK_REALTIME`) time at which it
+/// arrived
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Pps {
+    pub real_sec: i64,
+    pub real_nsec: u32,
+    pub clock_sec: i64,
+    pub clock_nsec: u32,
+}
+
+pub struct GpsdClient {
+    lines: Lines<BufReader<TcpStream>>,
+}
+
+impl GpsdClient {
+    /// Connect to gpsd and start watching the receivers
+    pub async fn connect(address: SocketAddr) -> std::io::Result<Self> {
+        let mut stream = TcpStream::connect(address).await?;
+        stream.write_all(WATCH).await?;
+
+        Ok(Self {
+            lines: BufReader::new(stream).lines(),
+        })
+    }
+
+    /// The next TPV or PPS report, or `None` when gpsd closes the connection
+    pub async fn next_report(&mut self) -> std::io::Result<Option<Report>> {
+        while let Some(line) = self.lines.next_line().await? {
+            match serde_json::from_str(&line) {
+                Ok(Report::Other) => {}
+                Ok(report) => return Ok(Some(report)),
+                Err(error) => log::warn!("Invalid gpsd report: {error}"),
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// What is known of the receiver
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GnssState {
+    /// The receiver has a 2D or 3D fix
+    pub fix: bool,
+    /// A PPS arrived recently
+    pub pps: bool,
+    /// Offset of TAI to UTC in seconds
+    pub utc_offset: Option<i16>,
+}
+
+impl GnssState {
+    pub fn update(&mut self, tpv: &Tpv) {
+        self.fix = tpv.mode >= 2;
+        if let Some(leapseconds) = tpv.leapseconds {
+            self.utc_offset = Some(leapseconds + TAI_GPS_OFFSET);
+        }
+    }
+
+    /// The receiver provides traceable time
+    pub fn is_locked(&self) -> bool {
+        self.fix && self.pps && self.utc_offset.is_some()
+    }
+
+    /// The time properties of the grandmaster
+    pub fn time_properties(&self) -> TimePropertiesDS {
+        TimePropertiesDS::new_ptp_time(
+            self.utc_offset,
+            LeapIndicator::NoLeap,
+            self.is_locked(),
+            self.is_locked(),
+            TimeSource::Gnss,
+        )
+    }
+
+    /// The measurement of a clock against the pulse in `pps`, given the time
+    /// `local` of that clock at which the pulse arrived
+    ///
+    /// Returns `None` while the UTC offset is unknown, as the PTP timescale is
+    /// TAI.
+    pub fn measurement(&self, pps: &Pps, local: Time) -> Option<Measurement> {
+        let reference = Time::from_fixed_nanos(
+            (pps.real_sec + self.utc_offset? as i64) as i128 * 1_000_000_000
+                + pps.real_nsec as i128,
+        );
+        let offset = local - reference;
+
+        // The pulse has no path delay, so both directions see the offset
+        Some(Measurement {
+            event_time: local,
+            offset: Some(offset),
+            delay: Some(Duration::ZERO),
+            peer_delay: None,
+            raw_sync_offset: Some(offset),
+            raw_delay_offset: Some(offset),
+        })
+    }
+}
+
+/// Follow the receiver of gpsd, reconnecting when the connection is lost
+///
+/// The time properties and external reference of `instance` follow the state
+/// of the receiver. Only while `instance` is the grandmaster does the PPS
+/// steer `clock`, as otherwise a port does.
+pub async fn run<F, S, C>(
+    config: GpsdConfig,
+    instance: &PtpInstance<F, S>,
+    mut clock: C,
+    mut leap_seconds: Option<LeapSeconds>,
+) where
+    F: Filter,
+    S: PtpInstanceStateMutex,
+    C: Clock + PortTimestampToTime,
+    C::Error: core::fmt::Debug,
+{
+    let mut state = GnssState::default();
+    let mut time_properties = grandmaster_time_properties(&state, &mut leap_seconds);
+    instance.set_time_properties(time_properties);
+
+    loop {
+        match GpsdClient::connect(config.address).await {
+            Ok(mut client) => {
+                log::info!("Connected to gpsd at {}", config.address);
+                let result = follow(
+                    &mut client,
+                    &mut state,
+                    &mut time_properties,
+                    instance,
+                    &mut clock,
+                    &mut leap_seconds,
+                )
+                .await;
+                match result {
+                    Ok(()) => log::warn!("gpsd closed the connection"),
+                    Err(error) => log::warn!("Lost connection to gpsd: {error}"),
+                }
+            }
+            Err(error) => log::warn!("Could not connect to gpsd at {}: {error}", config.address),
+        }
+
+        state.fix = false;
+        state.pps = false;
+        instance.set_external_reference(None);
+        time_properties = grandmaster_time_properties(&state, &mut leap_seconds);
+        instance.set_time_properties(time_properties);
+
+        tokio::time::sleep(RECONNECT_INTERVAL).await;
+    }
+}
+
+/// The time properties of the receiver, with the UTC offset and leap second
+/// of the leap seconds file if there is one
+fn grandmaster_time_properties(
+    state: &GnssState,
+    leap_seconds: &mut Option<LeapSeconds>,
+) -> TimePropertiesDS {
+    let mut time_properties = state.time_properties();
+    if let Some(leap_seconds) = leap_seconds {
+        leap_seconds.update(&mut time_properties);
+    }
+    time_properties
+}
+
+async fn follow<F, S, C>(
+    client: &mut GpsdClient,
+    state: &mut GnssState,
+    time_properties: &mut TimePropertiesDS,
+    instance: &PtpInstance<F, S>,
+    clock: &mut C,
+    leap_seconds: &mut Option<LeapSeconds>,
+) -> std::io::Result<()>
+where
+    F: Filter,
+    S: PtpInstanceStateMutex,
+    C: Clock + PortTimestampToTime,
+    C::Error: core::fmt::Debug,
+{
+    let mut filter = KalmanFilter::new(KalmanConfiguration::default());
+    let mut steering = false;
+
+    let pps_timeout = tokio::time::sleep(PPS_TIMEOUT);
+    let update_timer = tokio::time::sleep(std::time::Duration::ZERO);
+    tokio::pin!(pps_timeout, update_timer);
+    let mut update_pending = false;
+
+    loop {
+        let previous = *state;
+        let was_steering = steering;
+
+        tokio::select! {
+            report = client.next_report() => match report? {
+                Some(Report::Tpv(tpv)) => state.update(&tpv),
+                Some(Report::Pps(pps)) => {
+                    state.pps = true;
+                    pps_timeout
+                        .as_mut()
+                        .reset(tokio::time::Instant::now() + PPS_TIMEOUT);
+
+                    let is_grandmaster = instance.parent_ds().grandmaster_identity
+                        == instance.default_ds().clock_identity;
+                    if steering && !is_grandmaster {
+                        // A port steers the clock now
+                        let old_filter = std::mem::replace(
+                            &mut filter,
+                            KalmanFilter::new(KalmanConfiguration::default()),
+                        );
+                        old_filter.demobilize(clock);
+                        update_pending = false;
+                    }
+                    steering = is_grandmaster;
+
+                    let local = clock.port_timestamp_to_time(Timestamp {
+                        seconds: pps.clock_sec,
+                        nanos: pps.clock_nsec,
+                    });
+                    if let Some(measurement) = state.measurement(&pps, local) {
+                        if steering && state.fix {
+                            let update = filter.measurement(measurement, clock);
+                            if let Some(timeout) = update.next_update {
+                                update_timer
+                                    .as_mut()
+                                    .reset(tokio::time::Instant::now() + timeout);
+                                update_pending = true;
+                            }
+                        }
+                    }
+                }
+                Some(Report::Other) => {}
+                None => return Ok(()),
+            },
+            () = &mut pps_timeout, if state.pps => {
+                log::warn!("No PPS received from gpsd");
+                state.pps = false;
+            }
+            () = &mut update_timer, if update_pending => {
+                update_pending = false;
+                let update = filter.update(clock);
+                if let Some(timeout) = update.next_update {
+                    update_timer
+                        .as_mut()
+                        .reset(tokio::time::Instant::now() + timeout);
+                    update_pending = true;
+                }
+            }
+        }
+
+        if *state != previous {
+            log::info!(
+                "GNSS receiver: fix {}, PPS {}, UTC offset {:?}",
+                state.fix,
+                state.pps,
+                state.utc_offset
+            );
+        }
+
+        let previous_time_properties = *time_properties;
+        *time_properties = grandmaster_time_properties(state, leap_seconds);
+        if *time_properties != previous_time_properties {
+            instance.set_time_properties(*time_properties);
+        }
+
+        // The kernel needs the UTC offset to keep CLOCK_TAI, and the leap
+        // indicator to insert or delete the leap second. These are often known
+        // before the first PPS, so pass them on once steering starts as well.
+        if steering
+            && (!was_steering
+                || time_properties.current_utc_offset
+                    != previous_time_properties.current_utc_offset
+                || time_properties.leap_indicator != previous_time_properties.leap_indicator)
+        {
+            if let Err(error) = clock.set_properties(time_properties) {
+                log::error!("Could not update clock: {:?}", error);
+            }
+        }
+
+        instance.set_external_reference(if state.is_locked() {
+            filter.frequency_uncertainty()
+        } else {
+            None
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn fake_gpsd() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+
+            let mut watch = String::new();
+            stream.read_line(&mut watch).await.unwrap();
+            assert!(watch.starts_with("?WATCH="));
+
+            stream
+                .write_all(
+                    concat!(
+                        r#"{"class":"VERSION","release":"3.25","proto_major":3,"proto_minor":15}"#,
+                        "\n",
+                        r#"{"class":"TPV","device":"/dev/ttyACM0","mode":3,"time":"2024-05-01T12:00:00.000Z","leapseconds":18,"lat":52.0}"#,
+                        "\n",
+                        "not json\n",
+                        r#"{"class":"PPS","device":"/dev/pps0","real_sec":1714564801,"real_nsec":0,"clock_sec":1714564801,"clock_nsec":1500,"precision":-20}"#,
+                        "\n",
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+        });
+
+        let mut client = GpsdClient::connect(address).await.unwrap();
+
+        assert_eq!(
+            client.next_report().await.unwrap(),
+            Some(Report::Tpv(Tpv {
+                mode: 3,
+                leapseconds: Some(18),
+            }))
+        );
+        assert_eq!(
+            client.next_report().await.unwrap(),
+            Some(Report::Pps(Pps {
+                real_sec: 1714564801,
+                real_nsec: 0,
+                clock_sec: 1714564801,
+                clock_nsec: 1500,
+            }))
+        );
+
+        server.await.unwrap();
+        assert_eq!(client.next_report().await.unwrap(), None);
+    }
+
+    #[test]
+    fn gnss_state() {
+        let mut state = GnssState::default();
+        let pps = Pps {
+            real_sec: 100,
+            real_nsec: 0,
+            clock_sec: 100,
+            clock_nsec: 1500,
+        };
+        let local = Time::from_fixed_nanos(137_000_001_500i64);
+
+        // Without the leap seconds the PTP time is unknown
+        state.pps = true;
+        state.update(&Tpv {
+            mode: 3,
+            leapseconds: None,
+        });
+        assert!(!state.is_locked());
+        assert!(state.measurement(&pps, local).is_none());
+
+        state.update(&Tpv {
+            mode: 3,
+            leapseconds: Some(18),
+        });
+        assert!(state.is_locked());
+        let time_properties = state.time_properties();
+        assert_eq!(time_properties.current_utc_offset, Some(37));
+        assert_eq!(time_properties.time_source, TimeSource::Gnss);
+        assert!(time_properties.time_traceable);
+        assert!(time_properties.frequency_traceable);
+        assert!(time_properties.is_ptp());
+
+        let measurement = state.measurement(&pps, local).unwrap();
+        assert_eq!(measurement.offset, Some(Duration::from_nanos(1500)));
+        assert_eq!(
+            measurement.raw_delay_offset,
+            Some(Duration::from_nanos(1500))
+        );
+
+        state.update(&Tpv {
+            mode: 1,
+            leapseconds: None,
+        });
+        assert!(!state.is_locked());
+        assert_eq!(state.time_properties().current_utc_offset, Some(37));
+        assert!(!state.time_properties().time_traceable);
+    }
+}
@@ -0,0 +1,405 @@
+//! The leap seconds of UTC from the `leap-seconds.list` file published by
+//! IANA and NIST
+//!
+//! The file lists the offset of TAI to UTC from each leap second on. The
+//! grandmaster announces the current offset, and an upcoming leap second in
+//! the 12 hours before it happens.
+
+use std::{
+    fmt::Display,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use sha1::{Digest, Sha1};
+use statime::{
+    config::{LeapIndicator, TimePropertiesDS},
+    filters::Filter,
+    Clock, PtpInstance, PtpInstanceStateMutex,
+};
+
+/// Seconds from the NTP epoch (1900) to the Unix epoch (1970)
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+/// An upcoming leap second is announced this many seconds before it happens
+const LEAP_WINDOW: i64 = 12 * 60 * 60;
+
+const UPDATE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum LeapSecondsError {
+    Io(std::io::Error),
+    Syntax(String),
+    MissingHash,
+    HashMismatch,
+}
+
+impl Display for LeapSecondsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error while reading leap seconds file: {e}"),
+            Self::Syntax(e) => write!(f, "invalid leap seconds file: {e}"),
+            Self::MissingHash => write!(f, "leap seconds file has no hash"),
+            Self::HashMismatch => write!(f, "hash of leap seconds file does not match"),
+        }
+    }
+}
+
+impl std::error::Error for LeapSecondsError {}
+
+/// The contents of a `leap-seconds.list` file, with all times in seconds
+/// since the Unix epoch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeapSecondsList {
+    updated: i64,
+    expires: i64,
+    /// The offset of TAI to UTC from each time on
+    offsets: Vec<(i64, i16)>,
+}
+
+impl LeapSecondsList {
+    pub fn from_file(path: &Path) -> Result<Self, LeapSecondsError> {
+        let contents = std::fs::read_to_string(path).map_err(LeapSecondsError::Io)?;
+        Self::parse(&contents)
+    }
+
+    /// Parse the contents of a leap seconds file and check its hash
+    pub fn parse(contents: &str) -> Result<Self, LeapSecondsError> {
+        let mut updated = None;
+        let mut expires = None;
+        let mut hash = None;
+        let mut offsets: Vec<(u64, i16)> = Vec::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let syntax_error =
+                |what: &str| LeapSecondsError::Syntax(format!("line {}: {what}", index + 1));
+            let parse_timestamp = |value: &str| {
+                value
+                    .split_whitespace()
+                    .next()
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .ok_or_else(|| syntax_error("invalid timestamp"))
+            };
+
+            if let Some(value) = line.strip_prefix("#$") {
+                updated = Some(parse_timestamp(value)?);
+            } else if let Some(value) = line.strip_prefix("#@") {
+                expires = Some(parse_timestamp(value)?);
+            } else if let Some(value) = line.strip_prefix("#h") {
+                // Leading zeros of the words may be left out
+                let words = value
+                    .split_whitespace()
+                    .map(|word| u32::from_str_radix(word, 16))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| syntax_error("invalid hash"))?;
+                if words.len() != 5 {
+                    return Err(syntax_error("invalid hash"));
+                }
+                hash = Some(words);
+            } else if !line.starts_with('#') {
+                let data = line.split('#').next().unwrap_or_default();
+                let mut fields = data.split_whitespace();
+                let Some(time) = fields.next() else {
+                    continue;
+                };
+
+                let time: u64 = time.parse().map_err(|_| syntax_error("invalid time"))?;
+                let offset: i16 = fields
+                    .next()
+                    .and_then(|offset| offset.parse().ok())
+                    .ok_or_else(|| syntax_error("invalid offset"))?;
+                if fields.next().is_some() {
+                    return Err(syntax_error("unexpected data after offset"));
+                }
+                if offsets.last().map_or(false, |&(last, _)| last >= time) {
+                    return Err(syntax_error("times are not increasing"));
+                }
+
+                offsets.push((time, offset));
+            }
+        }
+
+        let updated = updated.ok_or(LeapSecondsError::Syntax("missing update time".into()))?;
+        let expires = expires.ok_or(LeapSecondsError::Syntax("missing expiration time".into()))?;
+        let hash = hash.ok_or(LeapSecondsError::MissingHash)?;
+
+        // The hash covers the digits of the data only, without whitespace or
+        // comments
+        let mut hasher = Sha1::new();
+        hasher.update(updated.to_string());
+        hasher.update(expires.to_string());
+        for (time, offset) in &offsets {
+            hasher.update(time.to_string());
+            hasher.update(offset.to_string());
+        }
+        let expected: Vec<u8> = hash.iter().flat_map(|word| word.to_be_bytes()).collect();
+        if hasher.finalize()[..] != expected[..] {
+            return Err(LeapSecondsError::HashMismatch);
+        }
+
+        Ok(Self {
+            updated: updated as i64 - NTP_UNIX_OFFSET,
+            expires: expires as i64 - NTP_UNIX_OFFSET,
+            offsets: offsets
+                .into_iter()
+                .map(|(time, offset)| (time as i64 - NTP_UNIX_OFFSET, offset))
+                .collect(),
+        })
+    }
+
+    /// The time at which the file was last updated
+    pub fn updated(&self) -> i64 {
+        self.updated
+    }
+
+    /// The file should not be used for leap seconds after this time
+    pub fn expires(&self) -> i64 {
+        self.expires
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.expires
+    }
+
+    /// The offset of TAI to UTC at `now`, or `None` before the first entry
+    pub fn utc_offset(&self, now: i64) -> Option<i16> {
+        self.offsets
+            .iter()
+            .take_while(|&&(time, _)| time <= now)
+            .last()
+            .map(|&(_, offset)| offset)
+    }
+
+    /// The leap second at the end of the UTC day of `now`, announced in the
+    /// last 12 hours before it
+    pub fn leap_indicator(&self, now: i64) -> LeapIndicator {
+        let next = self.offsets.iter().find(|&&(time, _)| time > now);
+
+        match (self.utc_offset(now), next) {
+            (Some(current), Some(&(time, next))) if time - now <= LEAP_WINDOW => {
+                if next > current {
+                    LeapIndicator::Leap61
+                } else {
+                    LeapIndicator::Leap59
+                }
+            }
+            _ => LeapIndicator::NoLeap,
+        }
+    }
+}
+
+/// Applies a leap seconds list to the time properties of the grandmaster
+pub struct LeapSeconds {
+    list: LeapSecondsList,
+    expired: bool,
+}
+
+impl LeapSeconds {
+    pub fn new(list: LeapSecondsList) -> Self {
+        Self {
+            list,
+            expired: false,
+        }
+    }
+
+    /// Set the UTC offset and leap indicator of `time_properties` to those
+    /// of the current time
+    pub fn update(&mut self, time_properties: &mut TimePropertiesDS) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs() as i64);
+        self.update_at(now, time_properties);
+    }
+
+    fn update_at(&mut self, now: i64, time_properties: &mut TimePropertiesDS) {
+        if !self.expired && self.list.is_expired(now) {
+            log::warn!(
+                "The leap seconds file expired, upcoming leap seconds may not be announced. \
+                 Please update the file."
+            );
+        }
+        self.expired = self.list.is_expired(now);
+
+        time_properties.current_utc_offset = self.list.utc_offset(now);
+        time_properties.leap_indicator = self.list.leap_indicator(now);
+    }
+}
+
+/// Keep the UTC offset and leap indicator of `instance` up to date
+///
+/// While `instance` is the grandmaster these are also passed to `clock`, so
+/// the kernel inserts or deletes the leap second. Otherwise a port does so
+/// for the leap seconds announced by its master.
+pub async fn run<F, S, C>(
+    mut leap_seconds: LeapSeconds,
+    time_properties: TimePropertiesDS,
+    instance: &PtpInstance<F, S>,
+    mut clock: C,
+) where
+    F: Filter,
+    S: PtpInstanceStateMutex,
+    C: Clock,
+    C::Error: core::fmt::Debug,
+{
+    let mut interval = tokio::time::interval(UPDATE_INTERVAL);
+    let mut announced = None;
+    let mut on_clock = None;
+
+    loop {
+        interval.tick().await;
+
+        let mut current = time_properties;
+        leap_seconds.update(&mut current);
+
+        if announced != Some(current) {
+            if current.leap_indicator != LeapIndicator::NoLeap {
+                log::info!("Announcing leap second: {:?}", current.leap_indicator);
+            }
+            instance.set_time_properties(current);
+            announced = Some(current);
+        }
+
+        let is_grandmaster =
+            instance.parent_ds().grandmaster_identity == instance.default_ds().clock_identity;
+        if !is_grandmaster {
+            on_clock = None;
+        } else if on_clock != Some(current) {
+            if let Err(error) = clock.set_properties(&current) {
+                log::error!("Could not update clock: {:?}", error);
+            }
+            on_clock = Some(current);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The leap-seconds.list of 7 July 2025, without most comments
+    const LEAP_SECONDS_LIST: &str = "\
+#	File expires on 28 June 2026
+#$	 3960835200
+#@	3991593600
+2272060800	10	# 1 Jan 1972
+2287785600	11	# 1 Jul 1972
+2303683200	12	# 1 Jan 1973
+2335219200	13	# 1 Jan 1974
+2366755200	14	# 1 Jan 1975
+2398291200	15	# 1 Jan 1976
+2429913600	16	# 1 Jan 1977
+2461449600	17	# 1 Jan 1978
+2492985600	18	# 1 Jan 1979
+2524521600	19	# 1 Jan 1980
+2571782400	20	# 1 Jul 1981
+2603318400	21	# 1 Jul 1982
+2634854400	22	# 1 Jul 1983
+2698012800	23	# 1 Jul 1985
+2776982400	24	# 1 Jan 1988
+2840140800	25	# 1 Jan 1990
+2871676800	26	# 1 Jan 1991
+2918937600	27	# 1 Jul 1992
+2950473600	28	# 1 Jul 1993
+2982009600	29	# 1 Jul 1994
+3029443200	30	# 1 Jan 1996
+3076704000	31	# 1 Jul 1997
+3124137600	32	# 1 Jan 1999
+3345062400	33	# 1 Jan 2006
+3439756800	34	# 1 Jan 2009
+3550089600	35	# 1 Jul 2012
+3644697600	36	# 1 Jul 2015
+3692217600	37	# 1 Jan 2017
+#
+#h	49db2447 571e5e1b 2f002a53 9c8da8e4 39b8e49e
+";
+
+    // 1 January 2017, 00:00:00 UTC
+    const LEAP_2017: i64 = 1_483_228_800;
+
+    #[test]
+    fn parse_leap_seconds_list() {
+        let list = LeapSecondsList::parse(LEAP_SECONDS_LIST).unwrap();
+
+        assert_eq!(list.updated(), 1_751_846_400);
+        // 28 June 2026
+        assert_eq!(list.expires(), 1_782_604_800);
+        assert!(!list.is_expired(1_782_604_799));
+        assert!(list.is_expired(1_782_604_800));
+
+        assert_eq!(list.utc_offset(0), None);
+        assert_eq!(list.utc_offset(63_072_000), Some(10));
+        assert_eq!(list.utc_offset(LEAP_2017 - 1), Some(36));
+        assert_eq!(list.utc_offset(LEAP_2017), Some(37));
+        assert_eq!(list.utc_offset(1_782_604_800), Some(37));
+    }
+
+    #[test]
+    fn invalid_leap_seconds_list() {
+        assert!(matches!(
+            LeapSecondsList::parse(&LEAP_SECONDS_LIST.replace("\t37\t", "\t38\t")),
+            Err(LeapSecondsError::HashMismatch)
+        ));
+        assert!(matches!(
+            LeapSecondsList::parse(&LEAP_SECONDS_LIST.replace("#h", "#")),
+            Err(LeapSecondsError::MissingHash)
+        ));
+        assert!(matches!(
+            LeapSecondsList::parse(&LEAP_SECONDS_LIST.replace("#@", "#")),
+            Err(LeapSecondsError::Syntax(_))
+        ));
+        assert!(matches!(
+            LeapSecondsList::parse(&LEAP_SECONDS_LIST.replace("\t37\t", "\tx\t")),
+            Err(LeapSecondsError::Syntax(_))
+        ));
+
+        // Leading zeros of the hash may be left out
+        let list = "#$ 1\n#@ 7\n100 10\n#h 8236bdcf bbd53308 eb06a10c a9f4c4 513c6f04\n";
+        assert!(LeapSecondsList::parse(list).is_ok());
+    }
+
+    #[test]
+    fn announce_leap_seconds() {
+        let list = LeapSecondsList::parse(LEAP_SECONDS_LIST).unwrap();
+
+        assert_eq!(
+            list.leap_indicator(LEAP_2017 - LEAP_WINDOW - 1),
+            LeapIndicator::NoLeap
+        );
+        assert_eq!(
+            list.leap_indicator(LEAP_2017 - LEAP_WINDOW),
+            LeapIndicator::Leap61
+        );
+        assert_eq!(list.leap_indicator(LEAP_2017 - 1), LeapIndicator::Leap61);
+        assert_eq!(list.leap_indicator(LEAP_2017), LeapIndicator::NoLeap);
+
+        let mut leap_seconds = LeapSeconds::new(list);
+        let mut time_properties = TimePropertiesDS::default();
+        leap_seconds.update_at(LEAP_2017 - 1, &mut time_properties);
+        assert_eq!(time_properties.current_utc_offset, Some(36));
+        assert_eq!(time_properties.leap_indicator, LeapIndicator::Leap61);
+        assert!(!leap_seconds.expired);
+
+        // The offset advances at the leap second
+        leap_seconds.update_at(LEAP_2017, &mut time_properties);
+        assert_eq!(time_properties.current_utc_offset, Some(37));
+        assert_eq!(time_properties.leap_indicator, LeapIndicator::NoLeap);
+
+        // An expired list still provides the offset
+        leap_seconds.update_at(1_782_604_800, &mut time_properties);
+        assert_eq!(time_properties.current_utc_offset, Some(37));
+        assert!(leap_seconds.expired);
+    }
+
+    #[test]
+    fn negative_leap_second() {
+        let list = LeapSecondsList {
+            updated: 0,
+            expires: 2000,
+            offsets: vec![(0, 37), (1000, 36)],
+        };
+
+        assert_eq!(list.leap_indicator(999), LeapIndicator::Leap59);
+        assert_eq!(list.utc_offset(999), Some(37));
+        assert_eq!(list.utc_offset(1000), Some(36));
+    }
+}
@@ -2,15 +2,20 @@ extern crate core;
 
 pub mod clock;
 pub mod config;
+pub mod control;
+pub mod gpsd;
+pub mod leap_seconds;
 pub mod metrics;
 pub mod observer;
 pub mod socket;
 pub mod tlvforwarder;
 pub mod tracing;
+pub mod transparent;
 
 use std::path::Path;
 
 use config::Config;
+pub use control::client::main as ctl_main;
 pub use metrics::exporter::main as metrics_exporter_main;
 use tracing::LogLevel;
 use tracing_log::LogTracer;
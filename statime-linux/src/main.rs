@@ -1,6 +1,6 @@
 use std::{
     collections::HashMap,
-    future::Future,
+    future::{poll_fn, Future},
     path::PathBuf,
     pin::{pin, Pin},
     sync::RwLock,
@@ -9,20 +9,25 @@ use std::{
 use clap::Parser;
 use rand::{rngs::StdRng, SeedableRng};
 use statime::{
-    config::{ClockIdentity, ClockQuality, InstanceConfig, SdoId, TimePropertiesDS, TimeSource},
+    config::{
+        AcceptableMaster, AlternateTimescale, ClockIdentity, InstanceConfig, LeapIndicator, SdoId,
+        TimePropertiesDS, TimeSource, MAX_ALTERNATE_TIMESCALES,
+    },
     filters::{Filter, KalmanConfiguration, KalmanFilter},
     port::{
         is_message_buffer_compatible, InBmca, Measurement, Port, PortAction, PortActionIterator,
-        TimestampContext, MAX_DATA_LEN,
+        Running, TimestampContext, MAX_DATA_LEN,
     },
     time::Time,
     Clock, OverlayClock, PtpInstance, PtpInstanceState, SharedClock,
 };
 use statime_linux::{
     clock::{LinuxClock, PortTimestampToTime},
-    config::HardwareClock,
+    config::{HardwareClock, PortConfig},
+    control::{server::PortCommand, PortForeignMasters},
     initialize_logging_parse_config,
-    observer::ObservableInstanceState,
+    leap_seconds::{LeapSeconds, LeapSecondsList},
+    observer::{ObservableInstanceState, PortAlternateMasters, PortSlaveReports},
     socket::{
         open_ethernet_socket, open_ipv4_event_socket, open_ipv4_general_socket,
         open_ipv6_event_socket, open_ipv6_general_socket, PtpTargetAddress,
@@ -269,19 +274,46 @@ async fn actual_main() {
 
     log::info!("Clock identity: {}", hex::encode(clock_identity.0));
 
+    if config.clock_type.is_transparent() {
+        log::info!("Running as transparent clock");
+        statime_linux::transparent::run(&config, clock_identity, |clock| {
+            start_clock_task(clock, SystemClock::Linux(LinuxClock::CLOCK_TAI))
+        })
+        .await
+        .expect("Could not open sockets");
+        return;
+    }
+
     let instance_config = InstanceConfig {
         clock_identity,
         priority_1: config.priority1,
         priority_2: config.priority2,
-        domain_number: config.domain,
+        domain_number: config.domain(),
         slave_only: config.slave_only,
         sdo_id: SdoId::try_from(config.sdo_id).expect("sdo-id should be between 0 and 4095"),
         path_trace: config.path_trace,
-        clock_quality: ClockQuality::default(),
+        external_port_configuration: config.external_port_configuration,
+        clock_quality: config.profile.clock_quality(config.slave_only),
+        management_set_policy: config.management_set_policy(),
+        dataset_comparison: config.dataset_comparison(),
     };
 
-    let time_properties_ds =
-        TimePropertiesDS::new_arbitrary_time(false, false, TimeSource::InternalOscillator);
+    // With gpsd or a leap seconds file the clock keeps PTP time, with gpsd the
+    // time source is known as well, but not yet traceable
+    let time_properties_ds = if config.gpsd.is_some() || config.leap_seconds_file.is_some() {
+        TimePropertiesDS::new_ptp_time(
+            None,
+            LeapIndicator::NoLeap,
+            false,
+            false,
+            match config.gpsd {
+                Some(_) => TimeSource::Gnss,
+                None => TimeSource::InternalOscillator,
+            },
+        )
+    } else {
+        TimePropertiesDS::new_arbitrary_time(false, false, TimeSource::InternalOscillator)
+    };
 
     let system_clock = if config.virtual_system_clock {
         SystemClock::Overlay(SharedClock::new(OverlayClock::new(LinuxClock::CLOCK_TAI)))
@@ -291,11 +323,75 @@ async fn actual_main() {
 
     // Leak to get a static reference, the ptp instance will be around for the rest
     // of the program anyway
-    let instance = Box::leak(Box::new(PtpInstance::new(
+    let instance: &'static Instance = Box::leak(Box::new(PtpInstance::new(
         instance_config,
         time_properties_ds,
     )));
 
+    let alternate_timescales: Vec<AlternateTimescale> = config
+        .alternate_timescales
+        .iter()
+        .take(MAX_ALTERNATE_TIMESCALES)
+        .map(Into::into)
+        .collect();
+    instance.set_alternate_timescales(&alternate_timescales);
+    // The fix of the receiver drives the clock class
+    let holdover = config
+        .holdover
+        .or_else(|| config.gpsd.is_some().then(Default::default));
+    instance.set_holdover(holdover.as_ref().map(Into::into));
+    instance.set_power_profile(config.power_profile.as_ref().map(Into::into));
+    instance.set_synchronization_metadata(config.synchronization_metadata.as_ref().map(Into::into));
+
+    let leap_seconds = config.leap_seconds_file.as_ref().map(|path| {
+        let list = LeapSecondsList::from_file(path).unwrap_or_else(|e| {
+            log::error!("{e}");
+            std::process::exit(1);
+        });
+        LeapSeconds::new(list)
+    });
+
+    if let Some(gpsd_config) = config.gpsd.clone() {
+        tokio::spawn(statime_linux::gpsd::run(
+            gpsd_config,
+            instance,
+            system_clock.clone_boxed(),
+            leap_seconds,
+        ));
+    } else if let Some(leap_seconds) = leap_seconds {
+        tokio::spawn(statime_linux::leap_seconds::run(
+            leap_seconds,
+            time_properties_ds,
+            instance,
+            system_clock.clone_boxed(),
+        ));
+    }
+
+    // The instances in other domains follow the top level instance
+    let mut instances = vec![instance];
+    for domain_instance in &config.instances {
+        log::info!("Running instance in domain {}", domain_instance.domain);
+
+        let instance_config = InstanceConfig {
+            clock_identity,
+            priority_1: domain_instance.priority1,
+            priority_2: domain_instance.priority2,
+            domain_number: domain_instance.domain,
+            slave_only: domain_instance.slave_only,
+            sdo_id: SdoId::try_from(domain_instance.sdo_id)
+                .expect("sdo-id should be between 0 and 4095"),
+            path_trace: domain_instance.path_trace,
+            external_port_configuration: false,
+            clock_quality: config.profile.clock_quality(domain_instance.slave_only),
+            management_set_policy: Default::default(),
+            dataset_comparison: config.dataset_comparison(),
+        };
+        instances.push(Box::leak(Box::new(PtpInstance::new(
+            instance_config,
+            time_properties_ds,
+        ))));
+    }
+
     // The observer for the metrics exporter
     let (instance_state_sender, instance_state_receiver) =
         tokio::sync::watch::channel(ObservableInstanceState {
@@ -304,85 +400,179 @@ async fn actual_main() {
             parent_ds: instance.parent_ds(),
             time_properties_ds: instance.time_properties_ds(),
             path_trace_ds: instance.path_trace_ds(),
+            alternate_timescale_offsets_ds: instance.alternate_timescale_offsets_ds(),
+            holdover_state: instance.holdover_state(),
+            power_profile: instance.power_profile(),
+            synchronization_metadata: instance.synchronization_metadata(),
             port_ds: vec![],
+            slave_reports: vec![],
+            alternate_masters: vec![],
         });
-    statime_linux::observer::spawn(&config, instance_state_receiver).await;
+    statime_linux::observer::spawn(&config, instance_state_receiver.clone()).await;
+
+    // The control socket for statime-ctl
+    let (foreign_masters_sender, foreign_masters_receiver) = tokio::sync::watch::channel(vec![]);
+    let (port_command_sender, port_command_receiver) = tokio::sync::mpsc::channel(16);
+    statime_linux::control::server::spawn(
+        &config,
+        instance,
+        instance_state_receiver,
+        foreign_masters_receiver,
+        port_command_sender,
+    )
+    .await;
 
     let (bmca_notify_sender, bmca_notify_receiver) = tokio::sync::watch::channel(false);
 
-    let mut main_task_senders = Vec::with_capacity(config.ports.len());
-    let mut main_task_receivers = Vec::with_capacity(config.ports.len());
+    // Ports of different instances on the same interface share its sockets, so
+    // every port task runs a group of ports with at most one per instance
+    let mut port_groups: Vec<Vec<(usize, PortConfig)>> = vec![];
+    let domain_instance_ports = config
+        .instances
+        .iter()
+        .enumerate()
+        .flat_map(|(index, instance)| instance.ports.iter().map(move |port| (index + 1, port)));
+    for (instance_index, port_config) in config
+        .ports
+        .iter()
+        .map(|port| (0, port))
+        .chain(domain_instance_ports)
+    {
+        let group = port_groups.iter_mut().find(|group| {
+            let (_, first) = &group[0];
+            first.interface == port_config.interface
+                && first.network_mode == port_config.network_mode
+                && group.iter().all(|(index, _)| *index != instance_index)
+        });
+
+        match group {
+            Some(group) => group.push((instance_index, port_config.clone())),
+            None => port_groups.push(vec![(instance_index, port_config.clone())]),
+        }
+    }
+
+    let mut main_task_senders = Vec::with_capacity(port_groups.len());
+    let mut main_task_receivers = Vec::with_capacity(port_groups.len());
 
     let mut internal_sync_senders = vec![];
 
     let mut clock_name_map = HashMap::new();
-    let mut clock_port_map = Vec::with_capacity(config.ports.len());
+    let mut clock_port_map = vec![];
+    let mut port_instances = vec![];
 
-    let mut ports = Vec::with_capacity(config.ports.len());
+    let mut ports = Vec::with_capacity(port_groups.len());
 
-    let tlv_forwarder = TlvForwarder::new();
+    let tlv_forwarders: Vec<_> = instances.iter().map(|_| TlvForwarder::new()).collect();
 
-    let mut add_hw_clock = |idx: u32, clock_port_map: &mut Vec<Option<usize>>| {
+    let mut add_hw_clock = |idx: u32| {
         let mut clock = LinuxClock::open_idx(idx).expect("Unable to open clock");
-        if let Some(id) = clock_name_map.get(&idx) {
-            clock_port_map.push(Some(*id));
-        } else {
-            clock.init().expect("Unable to initialize clock");
-            let id = internal_sync_senders.len();
-            clock_port_map.push(Some(id));
-            clock_name_map.insert(idx, id);
-            internal_sync_senders.push(start_clock_task(clock.clone(), system_clock.clone()));
-        }
+        let id = match clock_name_map.get(&idx) {
+            Some(id) => *id,
+            None => {
+                clock.init().expect("Unable to initialize clock");
+                let id = internal_sync_senders.len();
+                clock_name_map.insert(idx, id);
+                internal_sync_senders.push(start_clock_task(clock.clone(), system_clock.clone()));
+                id
+            }
+        };
         (
             Some(idx),
-            Box::new(clock) as BoxedClock,
+            Some(id),
+            clock,
             InterfaceTimestampMode::HardwarePTPAll,
         )
     };
 
-    let add_sw_clock = |clock_port_map: &mut Vec<Option<usize>>| {
-        clock_port_map.push(None);
+    let add_sw_clock = || {
         (
             None,
-            system_clock.clone_boxed(),
+            None,
+            LinuxClock::CLOCK_TAI,
             InterfaceTimestampMode::SoftwareAll,
         )
     };
 
-    for port_config in config.ports {
-        let interface = port_config.interface;
-        let network_mode = port_config.network_mode;
-        let (bind_phc, port_clock, timestamping) = match port_config.hardware_clock {
+    // Instances in other domains keep their time on an overlay of the clock
+    let mut overlay_clocks: HashMap<(usize, Option<u32>), SharedOverlayClock> = HashMap::new();
+
+    let security_association = config.authentication.as_ref().map(|authentication| {
+        authentication.security_association().unwrap_or_else(|e| {
+            log::error!("{e}");
+            std::process::exit(1);
+        })
+    });
+
+    let gptp_config = config.gptp_config();
+
+    for group in port_groups {
+        // The first port of the group determines the sockets and clock
+        let interface = group[0].1.interface;
+        let network_mode = group[0].1.network_mode;
+        let (bind_phc, clock_id, linux_clock, timestamping) = match group[0].1.hardware_clock {
             HardwareClock::Auto => match interface.lookup_phc() {
-                Some(idx) => add_hw_clock(idx, &mut clock_port_map),
+                Some(idx) => add_hw_clock(idx),
                 None => {
                     log::info!("No hardware clock found, falling back to software timestamping");
-                    add_sw_clock(&mut clock_port_map)
+                    add_sw_clock()
                 }
             },
             HardwareClock::Required => {
                 let idx = interface.lookup_phc().expect("No hardware clock found");
-                add_hw_clock(idx, &mut clock_port_map)
+                add_hw_clock(idx)
             }
-            HardwareClock::Specific(idx) => add_hw_clock(idx, &mut clock_port_map),
-            HardwareClock::None => add_sw_clock(&mut clock_port_map),
+            HardwareClock::Specific(idx) => add_hw_clock(idx),
+            HardwareClock::None => add_sw_clock(),
         };
 
-        let rng = StdRng::from_entropy();
-        let port = instance.add_port(
-            port_config.into(),
-            KalmanConfiguration::default(),
-            port_clock.clone_box(),
-            rng,
-        );
+        let mut group_ports = Vec::with_capacity(group.len());
+        let mut port_clocks = Vec::with_capacity(group.len());
+        let mut port_tlv_forwarders = Vec::with_capacity(group.len());
+
+        for (instance_index, port_config) in group {
+            let port_clock: BoxedClock = if instance_index == 0 {
+                clock_port_map.push(clock_id);
+                match bind_phc {
+                    Some(_) => Box::new(linux_clock.clone()),
+                    None => system_clock.clone_boxed(),
+                }
+            } else {
+                // Only the top level instance steers the clocks
+                clock_port_map.push(None);
+                Box::new(
+                    overlay_clocks
+                        .entry((instance_index, bind_phc))
+                        .or_insert_with(|| SharedClock::new(OverlayClock::new(linux_clock.clone())))
+                        .clone(),
+                )
+            };
+            port_instances.push(instance_index);
+
+            let rng = StdRng::from_entropy();
+            let port_config = statime::config::PortConfig {
+                authentication: security_association.clone(),
+                gptp: gptp_config,
+                ..port_config.into()
+            };
+            let port = instances[instance_index].add_port(
+                port_config,
+                config.profile.kalman_config(),
+                port_clock.clone_box(),
+                rng,
+            );
+
+            group_ports.push(port);
+            port_clocks.push(port_clock);
+            port_tlv_forwarders.push(tlv_forwarders[instance_index].duplicate());
+        }
 
         let (main_task_sender, port_task_receiver) = tokio::sync::mpsc::channel(1);
         let (port_task_sender, main_task_receiver) = tokio::sync::mpsc::channel(1);
 
-        // We can't send the port yet, since that may start running on the port,
-        // inhibiting write access to the instance and making it impossible to
+        // We can't send the ports yet, since that may start running on the ports,
+        // inhibiting write access to the instances and making it impossible to
         // create more ports.
-        ports.push(port);
+        ports.push(group_ports);
         main_task_senders.push(main_task_sender);
         main_task_receivers.push(main_task_receiver);
 
@@ -399,8 +589,8 @@ async fn actual_main() {
                     event_socket,
                     general_socket,
                     bmca_notify_receiver.clone(),
-                    tlv_forwarder.duplicate(),
-                    port_clock,
+                    port_tlv_forwarders,
+                    port_clocks,
                 ));
             }
             statime_linux::config::NetworkMode::Ipv6 => {
@@ -415,8 +605,8 @@ async fn actual_main() {
                     event_socket,
                     general_socket,
                     bmca_notify_receiver.clone(),
-                    tlv_forwarder.duplicate(),
-                    port_clock,
+                    port_tlv_forwarders,
+                    port_clocks,
                 ));
             }
             statime_linux::config::NetworkMode::Ethernet => {
@@ -431,52 +621,71 @@ async fn actual_main() {
                         .expect("Unable to get network interface index") as _,
                     socket,
                     bmca_notify_receiver.clone(),
-                    tlv_forwarder.duplicate(),
-                    port_clock,
+                    port_tlv_forwarders,
+                    port_clocks,
                 ));
             }
         }
     }
 
-    // Drop the forwarder so we don't keep an unneeded subscriber.
-    drop(tlv_forwarder);
+    // Drop the forwarders so we don't keep unneeded subscribers.
+    drop(tlv_forwarders);
 
     // All ports created, so we can start running them.
-    for (i, port) in ports.into_iter().enumerate() {
+    for (i, group_ports) in ports.into_iter().enumerate() {
         main_task_senders[i]
-            .send(port)
+            .send(group_ports)
             .await
             .expect("space in channel buffer");
     }
 
     run(
-        instance,
+        instances,
         bmca_notify_sender,
         instance_state_sender,
+        foreign_masters_sender,
+        port_command_receiver,
         main_task_receivers,
         main_task_senders,
         internal_sync_senders,
         clock_port_map,
+        port_instances,
     )
     .await
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run(
-    instance: &'static PtpInstance<KalmanFilter, RwLock<PtpInstanceState>>,
+    instances: Vec<&'static Instance>,
     bmca_notify_sender: tokio::sync::watch::Sender<bool>,
     instance_state_sender: tokio::sync::watch::Sender<ObservableInstanceState>,
-    mut main_task_receivers: Vec<Receiver<BmcaPort>>,
-    main_task_senders: Vec<Sender<BmcaPort>>,
+    foreign_masters_sender: tokio::sync::watch::Sender<Vec<PortForeignMasters>>,
+    mut port_command_receiver: Receiver<PortCommand>,
+    mut main_task_receivers: Vec<Receiver<Vec<BmcaPort>>>,
+    main_task_senders: Vec<Sender<Vec<BmcaPort>>>,
     internal_sync_senders: Vec<tokio::sync::watch::Sender<ClockSyncMode>>,
     clock_port_map: Vec<Option<usize>>,
+    port_instances: Vec<usize>,
 ) -> ! {
     // run bmca over all of the ports at the same time. The ports don't perform
     // their normal actions at this time: bmca is stop-the-world!
     let mut bmca_timer = pin!(Timer::new());
 
+    // The top level instance is the one that is observed and controlled
+    let instance = instances[0];
+
+    // Instances with a longer bmca interval skip some of the runs
+    let mut since_bmca = vec![std::time::Duration::ZERO; instances.len()];
+
     loop {
+        let bmca_interval = instances
+            .iter()
+            .map(|instance| instance.bmca_interval())
+            .min()
+            .unwrap_or_default();
+
         // reset bmca timer
-        bmca_timer.as_mut().reset(instance.bmca_interval());
+        bmca_timer.as_mut().reset(bmca_interval);
 
         // wait until the next BMCA
         bmca_timer.as_mut().await;
@@ -486,11 +695,13 @@ async fn run(
             .send(true)
             .expect("Bmca notification failed");
 
-        let mut bmca_ports = Vec::with_capacity(main_task_receivers.len());
-        let mut mut_bmca_ports = Vec::with_capacity(main_task_receivers.len());
+        let mut group_sizes = Vec::with_capacity(main_task_receivers.len());
+        let mut bmca_ports = Vec::with_capacity(port_instances.len());
 
         for receiver in main_task_receivers.iter_mut() {
-            bmca_ports.push(receiver.recv().await.unwrap());
+            let group_ports = receiver.recv().await.unwrap();
+            group_sizes.push(group_ports.len());
+            bmca_ports.extend(group_ports);
         }
 
         // have all ports so deassert stop
@@ -498,11 +709,54 @@ async fn run(
             .send(false)
             .expect("Bmca notification failed");
 
-        for mut_bmca_port in bmca_ports.iter_mut() {
-            mut_bmca_ports.push(mut_bmca_port);
+        let mut instance_ports: Vec<Vec<&mut BmcaPort>> =
+            instances.iter().map(|_| vec![]).collect();
+        for (port, instance_index) in bmca_ports.iter_mut().zip(&port_instances) {
+            instance_ports[*instance_index].push(port);
+        }
+
+        let mut_bmca_ports = &mut instance_ports[0];
+
+        // Apply port changes requested over the control socket
+        while let Ok(command) = port_command_receiver.try_recv() {
+            let result = match mut_bmca_ports
+                .iter_mut()
+                .find(|port| port.port_ds().port_identity.port_number == command.port)
+            {
+                Some(port) => {
+                    port.set_enabled(command.enabled);
+                    Ok(())
+                }
+                None => Err(format!("no port with number {}", command.port)),
+            };
+            // The client might have gone away, which is fine
+            let _ = command.reply.send(result);
+        }
+
+        // Taken before the BMCA, since that consumes the announce messages
+        let _ = foreign_masters_sender.send(
+            mut_bmca_ports
+                .iter()
+                .map(|v| PortForeignMasters {
+                    port: v.port_ds().port_identity.port_number,
+                    foreign_masters: v.foreign_masters().collect(),
+                })
+                .collect(),
+        );
+
+        for ((instance, ports), since_bmca) in instances
+            .iter()
+            .zip(instance_ports.iter_mut())
+            .zip(since_bmca.iter_mut())
+        {
+            *since_bmca += bmca_interval;
+            if *since_bmca >= instance.bmca_interval() {
+                *since_bmca = std::time::Duration::ZERO;
+                instance.bmca(ports);
+            }
         }
 
-        instance.bmca(&mut mut_bmca_ports);
+        let mut_bmca_ports = &instance_ports[0];
 
         // Update instance state for observability
         // We don't care if isn't anybody on the other side
@@ -517,11 +771,31 @@ async fn run(
             parent_ds: instance.parent_ds(),
             time_properties_ds: instance.time_properties_ds(),
             path_trace_ds: instance.path_trace_ds(),
+            alternate_timescale_offsets_ds: instance.alternate_timescale_offsets_ds(),
+            holdover_state: instance.holdover_state(),
+            power_profile: instance.power_profile(),
+            synchronization_metadata: instance.synchronization_metadata(),
             port_ds: mut_bmca_ports.iter().map(|v| v.port_ds()).collect(),
+            slave_reports: mut_bmca_ports
+                .iter()
+                .map(|v| PortSlaveReports {
+                    port: v.port_ds().port_identity.port_number,
+                    slaves: v.slave_reports().collect(),
+                })
+                .collect(),
+            alternate_masters: mut_bmca_ports
+                .iter()
+                .map(|v| PortAlternateMasters {
+                    port: v.port_ds().port_identity.port_number,
+                    alternate_masters: v.alternate_masters().collect(),
+                })
+                .collect(),
         });
 
+        drop(instance_ports);
+
         let mut clock_states = vec![ClockSyncMode::FromSystem; internal_sync_senders.len()];
-        for (idx, port) in mut_bmca_ports.iter().enumerate() {
+        for (idx, port) in bmca_ports.iter().enumerate() {
             if port.is_steering() {
                 if let Some(id) = clock_port_map[idx] {
                     clock_states[id] = ClockSyncMode::ToSystem;
@@ -532,18 +806,22 @@ async fn run(
             sender.send(mode).expect("Clock mode change failed");
         }
 
-        drop(mut_bmca_ports);
-
-        for (port, sender) in bmca_ports.into_iter().zip(main_task_senders.iter()) {
-            sender.send(port).await.unwrap();
+        let mut bmca_ports = bmca_ports.into_iter();
+        for (group_size, sender) in group_sizes.into_iter().zip(main_task_senders.iter()) {
+            sender
+                .send(bmca_ports.by_ref().take(group_size).collect())
+                .await
+                .unwrap();
         }
     }
 }
 
+type Instance = PtpInstance<KalmanFilter, RwLock<PtpInstanceState>>;
+
 type BmcaPort = Port<
     'static,
     InBmca,
-    Option<Vec<ClockIdentity>>,
+    Option<Vec<AcceptableMaster>>,
     StdRng,
     BoxedClock,
     KalmanFilter,
@@ -552,93 +830,101 @@ type BmcaPort = Port<
 
 // the Port task
 //
-// This task waits for a new port (in the bmca state) to arrive on its Receiver.
-// It will then move the port into the running state, and process actions. When
-// the task is notified of a BMCA, it will stop running, move the port into the
-// bmca state, and send it on its Sender
+// This task waits for new ports (in the bmca state) to arrive on its Receiver.
+// These are the ports of all instances that share the sockets of an interface.
+// It will then move the ports into the running state, and process actions,
+// passing each received message to the port of its domain. When the task is
+// notified of a BMCA, it will stop running, move the ports into the bmca
+// state, and send them on its Sender
 async fn port_task<A: NetworkAddress + PtpTargetAddress>(
-    mut port_task_receiver: Receiver<BmcaPort>,
-    port_task_sender: Sender<BmcaPort>,
+    mut port_task_receiver: Receiver<Vec<BmcaPort>>,
+    port_task_sender: Sender<Vec<BmcaPort>>,
     mut event_socket: Socket<A, Open>,
     mut general_socket: Socket<A, Open>,
     mut bmca_notify: tokio::sync::watch::Receiver<bool>,
-    mut tlv_forwarder: TlvForwarder,
-    clock: BoxedClock,
+    mut tlv_forwarders: Vec<TlvForwarder>,
+    clocks: Vec<BoxedClock>,
 ) {
-    let mut timers = Timers {
-        port_sync_timer: pin!(Timer::new()),
-        port_announce_timer: pin!(Timer::new()),
-        port_announce_timeout_timer: pin!(Timer::new()),
-        delay_request_timer: pin!(Timer::new()),
-        filter_update_timer: pin!(Timer::new()),
-    };
+    let mut timers: Vec<Timers> = clocks.iter().map(|_| Timers::new()).collect();
 
     loop {
-        let port_in_bmca = port_task_receiver.recv().await.unwrap();
-
-        // handle post-bmca actions
-        let (mut port, actions) = port_in_bmca.end_bmca();
-
-        let mut pending_timestamp = handle_actions(
-            actions,
-            &mut event_socket,
-            &mut general_socket,
-            &mut timers,
-            &tlv_forwarder,
-            &clock,
-        )
-        .await;
+        let ports_in_bmca = port_task_receiver.recv().await.unwrap();
+        let mut ports = Vec::with_capacity(ports_in_bmca.len());
 
-        while let Some((context, timestamp)) = pending_timestamp {
-            pending_timestamp = handle_actions(
-                port.handle_send_timestamp(context, timestamp),
+        for (index, port_in_bmca) in ports_in_bmca.into_iter().enumerate() {
+            // handle post-bmca actions
+            let (mut port, actions) = port_in_bmca.end_bmca();
+
+            let mut pending_timestamp = handle_actions(
+                actions,
                 &mut event_socket,
                 &mut general_socket,
-                &mut timers,
-                &tlv_forwarder,
-                &clock,
+                &mut timers[index],
+                &tlv_forwarders[index],
+                &clocks[index],
             )
             .await;
+
+            while let Some((context, timestamp)) = pending_timestamp {
+                pending_timestamp = handle_actions(
+                    port.handle_send_timestamp(context, timestamp),
+                    &mut event_socket,
+                    &mut general_socket,
+                    &mut timers[index],
+                    &tlv_forwarders[index],
+                    &clocks[index],
+                )
+                .await;
+            }
+
+            ports.push(port);
         }
 
         let mut event_buffer = [0; MAX_DATA_LEN];
         let mut general_buffer = [0; 2048];
 
         loop {
-            let mut actions = tokio::select! {
+            let (index, mut actions) = tokio::select! {
                 result = event_socket.recv(&mut event_buffer) => match result {
                     Ok(packet) => {
-                        if !is_message_buffer_compatible(&event_buffer[..packet.bytes_read]) {
+                        let data = &event_buffer[..packet.bytes_read];
+                        let index = ports.iter().position(|port| port.is_in_domain(data));
+                        match (index, packet.timestamp) {
                             // do not spam with missing timestamp error in mixed-version PTPv1+v2 networks
-                            PortActionIterator::empty()
-                        } else if let Some(timestamp) = packet.timestamp {
-                            log::trace!("Recv timestamp: {:?}", packet.timestamp);
-                            port.handle_event_receive(&event_buffer[..packet.bytes_read], clock.port_timestamp_to_time(timestamp))
-                        } else {
-                            log::error!("Missing recv timestamp");
-                            PortActionIterator::empty()
+                            _ if !is_message_buffer_compatible(data) => (0, PortActionIterator::empty()),
+                            // a message in the domain of none of our instances
+                            (None, _) => (0, PortActionIterator::empty()),
+                            (Some(index), Some(timestamp)) => {
+                                log::trace!("Recv timestamp: {:?}", packet.timestamp);
+                                (index, ports[index].handle_event_receive_from(
+                                    data,
+                                    clocks[index].port_timestamp_to_time(timestamp),
+                                    Some(packet.remote_addr.port_address()),
+                                ))
+                            }
+                            (Some(_), None) => {
+                                log::error!("Missing recv timestamp");
+                                (0, PortActionIterator::empty())
+                            }
                         }
                     }
                     Err(error) => panic!("Error receiving: {error:?}"),
                 },
                 result = general_socket.recv(&mut general_buffer) => match result {
-                    Ok(packet) => port.handle_general_receive(&general_buffer[..packet.bytes_read]),
+                    Ok(packet) => {
+                        let data = &general_buffer[..packet.bytes_read];
+                        match ports.iter().position(|port| port.is_in_domain(data)) {
+                            Some(index) => (index, ports[index].handle_general_receive_from(
+                                data,
+                                Some(packet.remote_addr.port_address()),
+                            )),
+                            None => (0, PortActionIterator::empty()),
+                        }
+                    }
                     Err(error) => panic!("Error receiving: {error:?}"),
                 },
-                () = &mut timers.port_announce_timer => {
-                    port.handle_announce_timer(&mut tlv_forwarder)
-                },
-                () = &mut timers.port_sync_timer => {
-                    port.handle_sync_timer()
-                },
-                () = &mut timers.port_announce_timeout_timer => {
-                    port.handle_announce_receipt_timer()
-                },
-                () = &mut timers.delay_request_timer => {
-                    port.handle_delay_request_timer()
-                },
-                () = &mut timers.filter_update_timer => {
-                    port.handle_filter_update_timer()
+                (index, timer) = poll_fn(|cx| Timers::poll_expired(&mut timers, cx)) => {
+                    (index, handle_timer(&mut ports[index], timer, &mut tlv_forwarders[index]))
                 },
                 result = bmca_notify.wait_for(|v| *v) => match result {
                     Ok(_) => break,
@@ -651,111 +937,114 @@ async fn port_task<A: NetworkAddress + PtpTargetAddress>(
                     actions,
                     &mut event_socket,
                     &mut general_socket,
-                    &mut timers,
-                    &tlv_forwarder,
-                    &clock,
+                    &mut timers[index],
+                    &tlv_forwarders[index],
+                    &clocks[index],
                 )
                 .await;
 
                 // there might be more actions to handle based on the current action
                 actions = match pending_timestamp {
-                    Some((context, timestamp)) => port.handle_send_timestamp(context, timestamp),
+                    Some((context, timestamp)) => {
+                        ports[index].handle_send_timestamp(context, timestamp)
+                    }
                     None => break,
                 };
             }
         }
 
-        let port_in_bmca = port.start_bmca();
-        port_task_sender.send(port_in_bmca).await.unwrap();
+        let ports_in_bmca = ports.into_iter().map(|port| port.start_bmca()).collect();
+        port_task_sender.send(ports_in_bmca).await.unwrap();
     }
 }
 
 // the Port task for ethernet transport
 //
-// This task waits for a new port (in the bmca state) to arrive on its Receiver.
-// It will then move the port into the running state, and process actions. When
-// the task is notified of a BMCA, it will stop running, move the port into the
-// bmca state, and send it on its Sender
+// This task waits for new ports (in the bmca state) to arrive on its Receiver.
+// These are the ports of all instances that share the socket of an interface.
+// It will then move the ports into the running state, and process actions,
+// passing each received message to the port of its domain. When the task is
+// notified of a BMCA, it will stop running, move the ports into the bmca
+// state, and send them on its Sender
 async fn ethernet_port_task(
-    mut port_task_receiver: Receiver<BmcaPort>,
-    port_task_sender: Sender<BmcaPort>,
+    mut port_task_receiver: Receiver<Vec<BmcaPort>>,
+    port_task_sender: Sender<Vec<BmcaPort>>,
     interface: libc::c_int,
     mut socket: Socket<EthernetAddress, Open>,
     mut bmca_notify: tokio::sync::watch::Receiver<bool>,
-    mut tlv_forwarder: TlvForwarder,
-    clock: BoxedClock,
+    mut tlv_forwarders: Vec<TlvForwarder>,
+    clocks: Vec<BoxedClock>,
 ) {
-    let mut timers = Timers {
-        port_sync_timer: pin!(Timer::new()),
-        port_announce_timer: pin!(Timer::new()),
-        port_announce_timeout_timer: pin!(Timer::new()),
-        delay_request_timer: pin!(Timer::new()),
-        filter_update_timer: pin!(Timer::new()),
-    };
+    let mut timers: Vec<Timers> = clocks.iter().map(|_| Timers::new()).collect();
 
     loop {
-        let port_in_bmca = port_task_receiver.recv().await.unwrap();
-
-        // Clear out old tlvs if we are not in the master state, so we don't keep em too
-        // long.
-        if port_in_bmca.is_master() {
-            tlv_forwarder.empty()
-        }
-
-        // handle post-bmca actions
-        let (mut port, actions) = port_in_bmca.end_bmca();
+        let ports_in_bmca = port_task_receiver.recv().await.unwrap();
+        let mut ports = Vec::with_capacity(ports_in_bmca.len());
+
+        for (index, port_in_bmca) in ports_in_bmca.into_iter().enumerate() {
+            // Clear out old tlvs if we are not in the master state, so we don't keep em
+            // too long.
+            if port_in_bmca.is_master() {
+                tlv_forwarders[index].empty()
+            }
 
-        let mut pending_timestamp = handle_actions_ethernet(
-            actions,
-            interface,
-            &mut socket,
-            &mut timers,
-            &tlv_forwarder,
-            &clock,
-        )
-        .await;
+            // handle post-bmca actions
+            let (mut port, actions) = port_in_bmca.end_bmca();
 
-        while let Some((context, timestamp)) = pending_timestamp {
-            pending_timestamp = handle_actions_ethernet(
-                port.handle_send_timestamp(context, timestamp),
+            let mut pending_timestamp = handle_actions_ethernet(
+                actions,
                 interface,
                 &mut socket,
-                &mut timers,
-                &tlv_forwarder,
-                &clock,
+                &mut timers[index],
+                &tlv_forwarders[index],
+                &clocks[index],
             )
             .await;
+
+            while let Some((context, timestamp)) = pending_timestamp {
+                pending_timestamp = handle_actions_ethernet(
+                    port.handle_send_timestamp(context, timestamp),
+                    interface,
+                    &mut socket,
+                    &mut timers[index],
+                    &tlv_forwarders[index],
+                    &clocks[index],
+                )
+                .await;
+            }
+
+            ports.push(port);
         }
 
         let mut event_buffer = [0; MAX_DATA_LEN];
 
         loop {
-            let mut actions = tokio::select! {
+            let (index, mut actions) = tokio::select! {
                 result = socket.recv(&mut event_buffer) => match result {
                     Ok(packet) => {
-                        if let Some(timestamp) = packet.timestamp {
-                            log::trace!("Recv timestamp: {:?}", packet.timestamp);
-                            port.handle_event_receive(&event_buffer[..packet.bytes_read], clock.port_timestamp_to_time(timestamp))
-                        } else {
-                            port.handle_general_receive(&event_buffer[..packet.bytes_read])
+                        let data = &event_buffer[..packet.bytes_read];
+                        match ports.iter().position(|port| port.is_in_domain(data)) {
+                            Some(index) => (index, if let Some(timestamp) = packet.timestamp {
+                                log::trace!("Recv timestamp: {:?}", packet.timestamp);
+                                ports[index].handle_event_receive_from(
+                                    data,
+                                    clocks[index].port_timestamp_to_time(timestamp),
+                                    Some(packet.remote_addr.port_address()),
+                                )
+                            } else {
+                                ports[index].handle_general_receive_from(
+                                    data,
+                                    Some(packet.remote_addr.port_address()),
+                                )
+                            }),
+                            // a message in the domain of none of our instances
+                            None => (0, PortActionIterator::empty()),
                         }
                     }
                     Err(error) => panic!("Error receiving: {error:?}"),
                 },
-                () = &mut timers.port_announce_timer => {
-                    port.handle_announce_timer(&mut tlv_forwarder)
-                },
-                () = &mut timers.port_sync_timer => {
-                    port.handle_sync_timer()
-                },
-                () = &mut timers.port_announce_timeout_timer => {
-                    port.handle_announce_receipt_timer()
-                },
-                () = &mut timers.delay_request_timer => {
-                    port.handle_delay_request_timer()
-                },
-                () = &mut timers.filter_update_timer => {
-                    port.handle_filter_update_timer()
+                (index, timer) = poll_fn(|cx| Timers::poll_expired(&mut timers, cx)) => {
+                    (index, handle_timer(&mut ports[index], timer, &mut tlv_forwarders[index]))
                 },
                 result = bmca_notify.wait_for(|v| *v) => match result {
                     Ok(_) => break,
@@ -768,38 +1057,126 @@ async fn ethernet_port_task(
                     actions,
                     interface,
                     &mut socket,
-                    &mut timers,
-                    &tlv_forwarder,
-                    &clock,
+                    &mut timers[index],
+                    &tlv_forwarders[index],
+                    &clocks[index],
                 )
                 .await;
 
                 // there might be more actions to handle based on the current action
                 actions = match pending_timestamp {
-                    Some((context, timestamp)) => port.handle_send_timestamp(context, timestamp),
+                    Some((context, timestamp)) => {
+                        ports[index].handle_send_timestamp(context, timestamp)
+                    }
                     None => break,
                 };
             }
         }
 
-        let port_in_bmca = port.start_bmca();
-        port_task_sender.send(port_in_bmca).await.unwrap();
+        let ports_in_bmca = ports.into_iter().map(|port| port.start_bmca()).collect();
+        port_task_sender.send(ports_in_bmca).await.unwrap();
     }
 }
 
-struct Timers<'a> {
-    port_sync_timer: Pin<&'a mut Timer>,
-    port_announce_timer: Pin<&'a mut Timer>,
-    port_announce_timeout_timer: Pin<&'a mut Timer>,
-    delay_request_timer: Pin<&'a mut Timer>,
-    filter_update_timer: Pin<&'a mut Timer>,
+type RunningPort = Port<
+    'static,
+    Running,
+    Option<Vec<AcceptableMaster>>,
+    StdRng,
+    BoxedClock,
+    KalmanFilter,
+    RwLock<PtpInstanceState>,
+>;
+
+fn handle_timer<'a>(
+    port: &'a mut RunningPort,
+    timer: PortTimer,
+    tlv_forwarder: &mut TlvForwarder,
+) -> PortActionIterator<'a> {
+    match timer {
+        PortTimer::Announce => port.handle_announce_timer(tlv_forwarder),
+        PortTimer::Sync => port.handle_sync_timer(),
+        PortTimer::AnnounceTimeout => port.handle_announce_receipt_timer(),
+        PortTimer::DelayRequest => port.handle_delay_request_timer(),
+        PortTimer::FilterUpdate => port.handle_filter_update_timer(),
+        PortTimer::Unicast => port.handle_unicast_timer(),
+        PortTimer::SynchronizationMetadata => port.handle_synchronization_metadata_timer(),
+    }
+}
+
+/// The timers of a port
+#[derive(Debug, Clone, Copy)]
+enum PortTimer {
+    Sync,
+    Announce,
+    AnnounceTimeout,
+    DelayRequest,
+    FilterUpdate,
+    Unicast,
+    SynchronizationMetadata,
+}
+
+struct Timers {
+    port_sync_timer: Pin<Box<Timer>>,
+    port_announce_timer: Pin<Box<Timer>>,
+    port_announce_timeout_timer: Pin<Box<Timer>>,
+    delay_request_timer: Pin<Box<Timer>>,
+    filter_update_timer: Pin<Box<Timer>>,
+    unicast_timer: Pin<Box<Timer>>,
+    synchronization_metadata_timer: Pin<Box<Timer>>,
+}
+
+impl Timers {
+    fn new() -> Self {
+        Timers {
+            port_sync_timer: Box::pin(Timer::new()),
+            port_announce_timer: Box::pin(Timer::new()),
+            port_announce_timeout_timer: Box::pin(Timer::new()),
+            delay_request_timer: Box::pin(Timer::new()),
+            filter_update_timer: Box::pin(Timer::new()),
+            unicast_timer: Box::pin(Timer::new()),
+            synchronization_metadata_timer: Box::pin(Timer::new()),
+        }
+    }
+
+    /// Poll the timers of all ports, returning the port and timer that expired
+    fn poll_expired(
+        timers: &mut [Timers],
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<(usize, PortTimer)> {
+        for (index, timers) in timers.iter_mut().enumerate() {
+            let port_timers = [
+                (timers.port_announce_timer.as_mut(), PortTimer::Announce),
+                (timers.port_sync_timer.as_mut(), PortTimer::Sync),
+                (
+                    timers.port_announce_timeout_timer.as_mut(),
+                    PortTimer::AnnounceTimeout,
+                ),
+                (timers.delay_request_timer.as_mut(), PortTimer::DelayRequest),
+                (timers.filter_update_timer.as_mut(), PortTimer::FilterUpdate),
+                (timers.unicast_timer.as_mut(), PortTimer::Unicast),
+                (
+                    timers.synchronization_metadata_timer.as_mut(),
+                    PortTimer::SynchronizationMetadata,
+                ),
+            ];
+
+            for (timer, kind) in port_timers {
+                if timer.poll(cx).is_ready() {
+                    return std::task::Poll::Ready((index, kind));
+                }
+            }
+        }
+
+        std::task::Poll::Pending
+    }
 }
 
 async fn handle_actions<A: NetworkAddress + PtpTargetAddress>(
     actions: PortActionIterator<'_>,
     event_socket: &mut Socket<A, Open>,
     general_socket: &mut Socket<A, Open>,
-    timers: &mut Timers<'_>,
+    timers: &mut Timers,
     tlv_forwarder: &TlvForwarder,
     clock: &BoxedClock,
 ) -> Option<(TimestampContext, Time)> {
@@ -846,6 +1223,60 @@ async fn handle_actions<A: NetworkAddress + PtpTargetAddress>(
                     .await
                     .expect("Failed to send general message");
             }
+            PortAction::SendEventUnicast {
+                context,
+                data,
+                address,
+            } => {
+                let Some(address) = A::unicast_event(address) else {
+                    log::error!("Unicast address {:?} does not match the transport", address);
+                    continue;
+                };
+
+                // send timestamp of the send
+                let time = event_socket
+                    .send_to(data, address)
+                    .await
+                    .expect("Failed to send event message");
+
+                // anything we send later will have a later pending (send) timestamp
+                if let Some(time) = time {
+                    log::trace!("Send timestamp {:?}", time);
+                    pending_timestamp = Some((context, clock.port_timestamp_to_time(time)));
+                } else {
+                    log::error!("Missing send timestamp");
+                }
+            }
+            PortAction::SendEventOneStep {
+                data,
+                launch_time,
+                link_local,
+            } => {
+                wait_for_launch(clock, launch_time).await;
+                let time = event_socket
+                    .send_to(
+                        data,
+                        if link_local {
+                            A::PDELAY_EVENT
+                        } else {
+                            A::PRIMARY_EVENT
+                        },
+                    )
+                    .await
+                    .expect("Failed to send event message");
+                log_launch_error(clock, launch_time, time);
+            }
+            PortAction::SendGeneralUnicast { data, address } => {
+                let Some(address) = A::unicast_general(address) else {
+                    log::error!("Unicast address {:?} does not match the transport", address);
+                    continue;
+                };
+
+                general_socket
+                    .send_to(data, address)
+                    .await
+                    .expect("Failed to send general message");
+            }
             PortAction::ResetAnnounceTimer { duration } => {
                 timers.port_announce_timer.as_mut().reset(duration);
             }
@@ -861,6 +1292,15 @@ async fn handle_actions<A: NetworkAddress + PtpTargetAddress>(
             PortAction::ResetFilterUpdateTimer { duration } => {
                 timers.filter_update_timer.as_mut().reset(duration);
             }
+            PortAction::ResetUnicastTimer { duration } => {
+                timers.unicast_timer.as_mut().reset(duration);
+            }
+            PortAction::ResetSynchronizationMetadataTimer { duration } => {
+                timers
+                    .synchronization_metadata_timer
+                    .as_mut()
+                    .reset(duration);
+            }
             PortAction::ForwardTLV { tlv } => {
                 tlv_forwarder.forward(tlv.into_owned());
             }
@@ -870,11 +1310,45 @@ async fn handle_actions<A: NetworkAddress + PtpTargetAddress>(
     pending_timestamp
 }
 
+/// Wait until the clock of the port reaches `launch_time`
+///
+/// Without hardware support for one-step messages, the timestamp in the message
+/// is only accurate when it is sent at exactly that time. Sleeping is not
+/// precise enough for that, so the last part of the wait is spent yielding.
+async fn wait_for_launch(clock: &BoxedClock, launch_time: Time) {
+    const SPIN_TIME: std::time::Duration = std::time::Duration::from_millis(2);
+
+    loop {
+        let now = clock.now();
+        if now >= launch_time {
+            break;
+        }
+
+        let remaining: std::time::Duration = (launch_time - now).into();
+        if remaining > SPIN_TIME {
+            tokio::time::sleep(remaining - SPIN_TIME).await;
+        } else {
+            tokio::task::yield_now().await;
+        }
+    }
+}
+
+fn log_launch_error(
+    clock: &BoxedClock,
+    launch_time: Time,
+    time: Option<timestamped_socket::socket::Timestamp>,
+) {
+    if let Some(time) = time {
+        let error = clock.port_timestamp_to_time(time) - launch_time;
+        log::trace!("One-step message sent {} after its launch time", error);
+    }
+}
+
 async fn handle_actions_ethernet(
     actions: PortActionIterator<'_>,
     interface: libc::c_int,
     socket: &mut Socket<EthernetAddress, Open>,
-    timers: &mut Timers<'_>,
+    timers: &mut Timers,
     tlv_forwarder: &TlvForwarder,
     clock: &BoxedClock,
 ) -> Option<(TimestampContext, Time)> {
@@ -937,6 +1411,74 @@ async fn handle_actions_ethernet(
                     .await
                     .expect("Failed to send general message");
             }
+            PortAction::SendEventUnicast {
+                context,
+                data,
+                address,
+            } => {
+                let Some(address) = EthernetAddress::unicast_event(address) else {
+                    log::error!("Unicast address {:?} does not match the transport", address);
+                    continue;
+                };
+
+                // send timestamp of the send
+                let time = socket
+                    .send_to(
+                        data,
+                        EthernetAddress::new(address.protocol(), address.mac(), interface),
+                    )
+                    .await
+                    .expect("Failed to send event message");
+
+                // anything we send later will have a later pending (send) timestamp
+                if let Some(time) = time {
+                    log::trace!("Send timestamp {:?}", time);
+                    pending_timestamp = Some((context, clock.port_timestamp_to_time(time)));
+                } else {
+                    log::error!("Missing send timestamp");
+                }
+            }
+            PortAction::SendEventOneStep {
+                data,
+                launch_time,
+                link_local,
+            } => {
+                wait_for_launch(clock, launch_time).await;
+                let time = socket
+                    .send_to(
+                        data,
+                        EthernetAddress::new(
+                            if link_local {
+                                EthernetAddress::PDELAY_EVENT.protocol()
+                            } else {
+                                EthernetAddress::PRIMARY_EVENT.protocol()
+                            },
+                            if link_local {
+                                EthernetAddress::PDELAY_EVENT.mac()
+                            } else {
+                                EthernetAddress::PRIMARY_EVENT.mac()
+                            },
+                            interface,
+                        ),
+                    )
+                    .await
+                    .expect("Failed to send event message");
+                log_launch_error(clock, launch_time, time);
+            }
+            PortAction::SendGeneralUnicast { data, address } => {
+                let Some(address) = EthernetAddress::unicast_general(address) else {
+                    log::error!("Unicast address {:?} does not match the transport", address);
+                    continue;
+                };
+
+                socket
+                    .send_to(
+                        data,
+                        EthernetAddress::new(address.protocol(), address.mac(), interface),
+                    )
+                    .await
+                    .expect("Failed to send general message");
+            }
             PortAction::ResetAnnounceTimer { duration } => {
                 timers.port_announce_timer.as_mut().reset(duration);
             }
@@ -952,6 +1494,15 @@ async fn handle_actions_ethernet(
             PortAction::ResetFilterUpdateTimer { duration } => {
                 timers.filter_update_timer.as_mut().reset(duration);
             }
+            PortAction::ResetUnicastTimer { duration } => {
+                timers.unicast_timer.as_mut().reset(duration);
+            }
+            PortAction::ResetSynchronizationMetadataTimer { duration } => {
+                timers
+                    .synchronization_metadata_timer
+                    .as_mut()
+                    .reset(duration);
+            }
             PortAction::ForwardTLV { tlv } => tlv_forwarder.forward(tlv.into_owned()),
         }
     }
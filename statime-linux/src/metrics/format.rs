@@ -2,10 +2,17 @@ use std::fmt::Write;
 
 use statime::{
     config::TimePropertiesDS,
-    observability::{current::CurrentDS, default::DefaultDS, parent::ParentDS},
+    observability::{
+        current::CurrentDS,
+        default::DefaultDS,
+        parent::ParentDS,
+        port::{AlternateMaster, SlaveReport},
+        PowerProfile,
+    },
 };
 
 use super::exporter::ObservableState;
+use crate::observer::{PortAlternateMasters, PortSlaveReports};
 
 macro_rules! format_bool {
     ($value:expr) => {
@@ -424,6 +431,173 @@ fn format_port_ds(
     Ok(())
 }
 
+fn format_slave_reports(
+    w: &mut impl Write,
+    slave_reports: &[PortSlaveReports],
+    labels: Vec<(&'static str, String)>,
+) -> std::fmt::Result {
+    let slave_labels = |port: u16, slave: &SlaveReport| {
+        let mut labels = labels.clone();
+        labels.push(("port", format!("{}", port)));
+        labels.push((
+            "slave_clock_identity",
+            format!("{}", slave.slave_port_identity.clock_identity),
+        ));
+        labels.push((
+            "slave_port",
+            format!("{}", slave.slave_port_identity.port_number),
+        ));
+        labels
+    };
+
+    format_metric(
+        w,
+        "slave_offset_from_master",
+        "The offset from this master reported by a slave",
+        MetricType::Gauge,
+        Some(Unit::Nanoseconds),
+        slave_reports
+            .iter()
+            .flat_map(|port| {
+                port.slaves.iter().filter_map(move |slave| {
+                    Some(Measurement {
+                        labels: slave_labels(port.port, slave),
+                        value: slave.offset_from_master?.to_nanos(),
+                    })
+                })
+            })
+            .collect(),
+    )?;
+
+    format_metric(
+        w,
+        "slave_mean_path_delay",
+        "The mean path delay between this master and a slave reported by the slave",
+        MetricType::Gauge,
+        Some(Unit::Nanoseconds),
+        slave_reports
+            .iter()
+            .flat_map(|port| {
+                port.slaves.iter().filter_map(move |slave| {
+                    Some(Measurement {
+                        labels: slave_labels(port.port, slave),
+                        value: slave.mean_path_delay?.to_nanos(),
+                    })
+                })
+            })
+            .collect(),
+    )?;
+
+    Ok(())
+}
+
+fn format_alternate_masters(
+    w: &mut impl Write,
+    alternate_masters: &[PortAlternateMasters],
+    labels: Vec<(&'static str, String)>,
+) -> std::fmt::Result {
+    let alternate_master_labels = |port: u16, alternate_master: &AlternateMaster| {
+        let mut labels = labels.clone();
+        labels.push(("port", format!("{}", port)));
+        labels.push((
+            "alternate_master_clock_identity",
+            format!(
+                "{}",
+                alternate_master
+                    .alternate_master_port_identity
+                    .clock_identity
+            ),
+        ));
+        labels.push((
+            "alternate_master_port",
+            format!(
+                "{}",
+                alternate_master.alternate_master_port_identity.port_number
+            ),
+        ));
+        labels
+    };
+
+    format_metric(
+        w,
+        "alternate_master_offset",
+        "The offset from an alternate master",
+        MetricType::Gauge,
+        Some(Unit::Nanoseconds),
+        alternate_masters
+            .iter()
+            .flat_map(|port| {
+                port.alternate_masters
+                    .iter()
+                    .filter_map(move |alternate_master| {
+                        Some(Measurement {
+                            labels: alternate_master_labels(port.port, alternate_master),
+                            value: alternate_master.offset_from_alternate_master?.to_nanos(),
+                        })
+                    })
+            })
+            .collect(),
+    )?;
+
+    format_metric(
+        w,
+        "alternate_master_mean_offset",
+        "The moving average of the offset from an alternate master",
+        MetricType::Gauge,
+        Some(Unit::Nanoseconds),
+        alternate_masters
+            .iter()
+            .flat_map(|port| {
+                port.alternate_masters
+                    .iter()
+                    .filter_map(move |alternate_master| {
+                        Some(Measurement {
+                            labels: alternate_master_labels(port.port, alternate_master),
+                            value: alternate_master
+                                .mean_offset_from_alternate_master?
+                                .to_nanos(),
+                        })
+                    })
+            })
+            .collect(),
+    )?;
+
+    Ok(())
+}
+
+fn format_power_profile(
+    w: &mut impl Write,
+    power_profile: Option<&PowerProfile>,
+    labels: Vec<(&'static str, String)>,
+) -> std::fmt::Result {
+    let labels = |power_profile: &PowerProfile| {
+        let mut labels = labels.clone();
+        labels.push((
+            "grandmaster_id",
+            format!("{}", power_profile.grandmaster_id),
+        ));
+        labels
+    };
+
+    format_metric(
+        w,
+        "power_profile_total_time_inaccuracy",
+        "The IEEE C37.238 total time inaccuracy of the grandmaster and the boundary clocks up to \
+         this clock",
+        MetricType::Gauge,
+        Some(Unit::Nanoseconds),
+        power_profile
+            .iter()
+            .map(|power_profile| Measurement {
+                labels: labels(power_profile),
+                value: power_profile.total_time_inaccuracy,
+            })
+            .collect(),
+    )?;
+
+    Ok(())
+}
+
 pub fn format_state(w: &mut impl std::fmt::Write, state: &ObservableState) -> std::fmt::Result {
     format_metric(
         w,
@@ -452,6 +626,9 @@ pub fn format_state(w: &mut impl std::fmt::Write, state: &ObservableState) -> st
     format_time_properties_ds(w, &state.instance.time_properties_ds, labels.clone())?;
     format_path_trace_ds(w, &state.instance.path_trace_ds, labels.clone())?;
     format_port_ds(w, &state.instance.port_ds, labels.clone())?;
+    format_slave_reports(w, &state.instance.slave_reports, labels.clone())?;
+    format_alternate_masters(w, &state.instance.alternate_masters, labels.clone())?;
+    format_power_profile(w, state.instance.power_profile.as_ref(), labels.clone())?;
 
     w.write_str("# EOF\n")?;
     Ok(())
@@ -3,7 +3,12 @@ use std::{fs::Permissions, os::unix::prelude::PermissionsExt, path::Path, time::
 use statime::{
     config::TimePropertiesDS,
     observability::{
-        current::CurrentDS, default::DefaultDS, parent::ParentDS, port::PortDS, PathTraceDS,
+        current::CurrentDS,
+        default::DefaultDS,
+        parent::ParentDS,
+        port::{AlternateMaster, PortDS, SlaveReport},
+        AlternateTimescaleOffsetsDS, HoldoverState, PathTraceDS, PowerProfile,
+        SynchronizationMetadata,
     },
 };
 use tokio::{io::AsyncWriteExt, net::UnixStream, task::JoinHandle};
@@ -31,8 +36,38 @@ pub struct ObservableInstanceState {
     /// A concrete implementation of the PTP Path Trace dataset (IEEE1588-2019
     /// section 16.2.2)
     pub path_trace_ds: PathTraceDS,
+    /// A concrete implementation of the PTP Alternate Timescale Offsets dataset
+    /// (IEEE1588-2019 section 16.3.2)
+    pub alternate_timescale_offsets_ds: AlternateTimescaleOffsetsDS,
+    /// State of the holdover state machine, if enabled
+    pub holdover_state: Option<HoldoverState>,
+    /// IEEE C37.238 power profile information of the time of the instance,
+    /// including the total time inaccuracy received from the parent
+    pub power_profile: Option<PowerProfile>,
+    /// SMPTE ST 2059-2 synchronization metadata of the time of the instance
+    pub synchronization_metadata: Option<SynchronizationMetadata>,
     /// Port datasets for all the ports.
     pub port_ds: Vec<PortDS>,
+    /// Slave event monitoring reports received by each of the ports
+    pub slave_reports: Vec<PortSlaveReports>,
+    /// Alternate masters measured by each of the ports
+    pub alternate_masters: Vec<PortAlternateMasters>,
+}
+
+/// The slave event monitoring reports received by a single port
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PortSlaveReports {
+    /// Port number of the port, as also found in its port dataset
+    pub port: u16,
+    pub slaves: Vec<SlaveReport>,
+}
+
+/// The alternate masters measured by a single port
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PortAlternateMasters {
+    /// Port number of the port, as also found in its port dataset
+    pub port: u16,
+    pub alternate_masters: Vec<AlternateMaster>,
 }
 
 pub async fn spawn(
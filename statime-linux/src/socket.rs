@@ -4,6 +4,7 @@
 
 use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
 
+use statime::config::PortAddress;
 use timestamped_socket::{
     interface::InterfaceName,
     networkaddress::{EthernetAddress, MacAddress},
@@ -24,11 +25,20 @@ const GENERAL_PORT: u16 = 320;
 
 const PTP_ETHERTYPE: u16 = 0x88f7;
 
-pub trait PtpTargetAddress {
+pub trait PtpTargetAddress: Sized {
     const PRIMARY_EVENT: Self;
     const PRIMARY_GENERAL: Self;
     const PDELAY_EVENT: Self;
     const PDELAY_GENERAL: Self;
+
+    /// Address for unicast event messages to the given port, if it uses the
+    /// same transport
+    fn unicast_event(address: PortAddress) -> Option<Self>;
+    /// Address for unicast general messages to the given port, if it uses the
+    /// same transport
+    fn unicast_general(address: PortAddress) -> Option<Self>;
+    /// The port this address belongs to
+    fn port_address(&self) -> PortAddress;
 }
 
 impl PtpTargetAddress for SocketAddrV4 {
@@ -36,6 +46,24 @@ impl PtpTargetAddress for SocketAddrV4 {
     const PRIMARY_GENERAL: Self = SocketAddrV4::new(IPV4_PRIMARY_MULTICAST, GENERAL_PORT);
     const PDELAY_EVENT: Self = SocketAddrV4::new(IPV4_PDELAY_MULTICAST, EVENT_PORT);
     const PDELAY_GENERAL: Self = SocketAddrV4::new(IPV4_PDELAY_MULTICAST, GENERAL_PORT);
+
+    fn unicast_event(address: PortAddress) -> Option<Self> {
+        match address {
+            PortAddress::Ipv4(ip) => Some(SocketAddrV4::new(ip.into(), EVENT_PORT)),
+            _ => None,
+        }
+    }
+
+    fn unicast_general(address: PortAddress) -> Option<Self> {
+        match address {
+            PortAddress::Ipv4(ip) => Some(SocketAddrV4::new(ip.into(), GENERAL_PORT)),
+            _ => None,
+        }
+    }
+
+    fn port_address(&self) -> PortAddress {
+        PortAddress::Ipv4(self.ip().octets())
+    }
 }
 
 impl PtpTargetAddress for SocketAddrV6 {
@@ -43,6 +71,24 @@ impl PtpTargetAddress for SocketAddrV6 {
     const PRIMARY_GENERAL: Self = SocketAddrV6::new(IPV6_PRIMARY_MULTICAST, GENERAL_PORT, 0, 0);
     const PDELAY_EVENT: Self = SocketAddrV6::new(IPV6_PDELAY_MULTICAST, EVENT_PORT, 0, 0);
     const PDELAY_GENERAL: Self = SocketAddrV6::new(IPV6_PDELAY_MULTICAST, GENERAL_PORT, 0, 0);
+
+    fn unicast_event(address: PortAddress) -> Option<Self> {
+        match address {
+            PortAddress::Ipv6(ip) => Some(SocketAddrV6::new(ip.into(), EVENT_PORT, 0, 0)),
+            _ => None,
+        }
+    }
+
+    fn unicast_general(address: PortAddress) -> Option<Self> {
+        match address {
+            PortAddress::Ipv6(ip) => Some(SocketAddrV6::new(ip.into(), GENERAL_PORT, 0, 0)),
+            _ => None,
+        }
+    }
+
+    fn port_address(&self) -> PortAddress {
+        PortAddress::Ipv6(self.ip().octets())
+    }
 }
 
 impl PtpTargetAddress for EthernetAddress {
@@ -58,6 +104,26 @@ impl PtpTargetAddress for EthernetAddress {
         0,
     );
     const PDELAY_GENERAL: Self = Self::PDELAY_EVENT;
+
+    // The interface index is filled in when sending
+    fn unicast_event(address: PortAddress) -> Option<Self> {
+        match address {
+            PortAddress::Ethernet(mac) => {
+                Some(EthernetAddress::new(PTP_ETHERTYPE, MacAddress::new(mac), 0))
+            }
+            _ => None,
+        }
+    }
+
+    fn unicast_general(address: PortAddress) -> Option<Self> {
+        Self::unicast_event(address)
+    }
+
+    fn port_address(&self) -> PortAddress {
+        let mut mac = [0; 6];
+        mac.copy_from_slice(self.mac().as_ref());
+        PortAddress::Ethernet(mac)
+    }
 }
 
 pub fn open_ipv4_event_socket(
@@ -0,0 +1,461 @@
+//! Running statime as a transparent clock
+//!
+//! All ports are handled by a single task, which forwards every message it
+//! receives through the [`TransparentClock`]. Timestamps are taken by the
+//! hardware clock of each port when it has one, and in software otherwise. The
+//! residence time only needs the timestamps of one interface to be consistent
+//! with those of another, so when the ports use more than one clock, the
+//! hardware clocks are kept synchronized to the system clock.
+
+use std::{
+    future::Future,
+    net::{SocketAddrV4, SocketAddrV6},
+    pin::Pin,
+    task::Poll,
+};
+
+use statime::{
+    config::ClockIdentity,
+    time::Time,
+    transparent_clock::{
+        TransparentClockAction, TransparentClockActionIterator, TransparentTimestampContext,
+    },
+    TransparentClock,
+};
+use timestamped_socket::{
+    networkaddress::EthernetAddress,
+    socket::{InterfaceTimestampMode, Open, Socket, Timestamp},
+};
+use tokio::time::{Instant, Sleep};
+
+use crate::{
+    clock::{LinuxClock, PortTimestampToTime},
+    config::{ClockType, Config, HardwareClock, NetworkMode},
+    socket::{
+        open_ethernet_socket, open_ipv4_event_socket, open_ipv4_general_socket,
+        open_ipv6_event_socket, open_ipv6_general_socket, PtpTargetAddress,
+    },
+};
+
+const BUFFER_SIZE: usize = 2048;
+
+/// The sockets of a single transparent clock port
+enum TransparentPort {
+    Ipv4 {
+        event: Socket<SocketAddrV4, Open>,
+        general: Socket<SocketAddrV4, Open>,
+    },
+    Ipv6 {
+        event: Socket<SocketAddrV6, Open>,
+        general: Socket<SocketAddrV6, Open>,
+    },
+    Ethernet {
+        socket: Socket<EthernetAddress, Open>,
+        interface: libc::c_int,
+    },
+}
+
+/// A packet received on a [`TransparentPort`]
+struct Received {
+    from_event_socket: bool,
+    bytes_read: usize,
+    timestamp: Option<Timestamp>,
+}
+
+impl TransparentPort {
+    fn open(
+        port_config: &crate::config::PortConfig,
+        bind_phc: Option<u32>,
+    ) -> std::io::Result<Self> {
+        let interface = port_config.interface;
+        let timestamping = match bind_phc {
+            Some(_) => InterfaceTimestampMode::HardwarePTPAll,
+            None => InterfaceTimestampMode::SoftwareAll,
+        };
+
+        Ok(match port_config.network_mode {
+            NetworkMode::Ipv4 => TransparentPort::Ipv4 {
+                event: open_ipv4_event_socket(interface, timestamping, bind_phc)?,
+                general: open_ipv4_general_socket(interface)?,
+            },
+            NetworkMode::Ipv6 => TransparentPort::Ipv6 {
+                event: open_ipv6_event_socket(interface, timestamping, bind_phc)?,
+                general: open_ipv6_general_socket(interface)?,
+            },
+            NetworkMode::Ethernet => TransparentPort::Ethernet {
+                socket: open_ethernet_socket(interface, timestamping, bind_phc)?,
+                interface: interface
+                    .get_index()
+                    .expect("Unable to get network interface index")
+                    as _,
+            },
+        })
+    }
+
+    async fn recv(
+        &self,
+        event_buffer: &mut [u8],
+        general_buffer: &mut [u8],
+    ) -> std::io::Result<Received> {
+        match self {
+            TransparentPort::Ipv4 { event, general } => {
+                Self::recv_ip(event, general, event_buffer, general_buffer).await
+            }
+            TransparentPort::Ipv6 { event, general } => {
+                Self::recv_ip(event, general, event_buffer, general_buffer).await
+            }
+            TransparentPort::Ethernet { socket, .. } => {
+                // Ethernet has a single socket, only event messages get a timestamp
+                let packet = socket.recv(event_buffer).await?;
+                Ok(Received {
+                    from_event_socket: true,
+                    bytes_read: packet.bytes_read,
+                    timestamp: packet.timestamp,
+                })
+            }
+        }
+    }
+
+    async fn recv_ip<A: timestamped_socket::networkaddress::NetworkAddress>(
+        event: &Socket<A, Open>,
+        general: &Socket<A, Open>,
+        event_buffer: &mut [u8],
+        general_buffer: &mut [u8],
+    ) -> std::io::Result<Received> {
+        tokio::select! {
+            result = event.recv(event_buffer) => result.map(|packet| Received {
+                from_event_socket: true,
+                bytes_read: packet.bytes_read,
+                timestamp: packet.timestamp,
+            }),
+            result = general.recv(general_buffer) => result.map(|packet| Received {
+                from_event_socket: false,
+                bytes_read: packet.bytes_read,
+                timestamp: None,
+            }),
+        }
+    }
+
+    async fn send_event(
+        &mut self,
+        data: &[u8],
+        link_local: bool,
+    ) -> std::io::Result<Option<Timestamp>> {
+        match self {
+            TransparentPort::Ipv4 { event, .. } => {
+                event
+                    .send_to(
+                        data,
+                        target(
+                            link_local,
+                            SocketAddrV4::PDELAY_EVENT,
+                            SocketAddrV4::PRIMARY_EVENT,
+                        ),
+                    )
+                    .await
+            }
+            TransparentPort::Ipv6 { event, .. } => {
+                event
+                    .send_to(
+                        data,
+                        target(
+                            link_local,
+                            SocketAddrV6::PDELAY_EVENT,
+                            SocketAddrV6::PRIMARY_EVENT,
+                        ),
+                    )
+                    .await
+            }
+            TransparentPort::Ethernet { socket, interface } => {
+                let address = target(
+                    link_local,
+                    EthernetAddress::PDELAY_EVENT,
+                    EthernetAddress::PRIMARY_EVENT,
+                );
+                socket
+                    .send_to(
+                        data,
+                        EthernetAddress::new(address.protocol(), address.mac(), *interface),
+                    )
+                    .await
+            }
+        }
+    }
+
+    async fn send_general(&mut self, data: &[u8], link_local: bool) -> std::io::Result<()> {
+        match self {
+            TransparentPort::Ipv4 { general, .. } => {
+                general
+                    .send_to(
+                        data,
+                        target(
+                            link_local,
+                            SocketAddrV4::PDELAY_GENERAL,
+                            SocketAddrV4::PRIMARY_GENERAL,
+                        ),
+                    )
+                    .await?;
+            }
+            TransparentPort::Ipv6 { general, .. } => {
+                general
+                    .send_to(
+                        data,
+                        target(
+                            link_local,
+                            SocketAddrV6::PDELAY_GENERAL,
+                            SocketAddrV6::PRIMARY_GENERAL,
+                        ),
+                    )
+                    .await?;
+            }
+            TransparentPort::Ethernet { socket, interface } => {
+                let address = target(
+                    link_local,
+                    EthernetAddress::PDELAY_GENERAL,
+                    EthernetAddress::PRIMARY_GENERAL,
+                );
+                socket
+                    .send_to(
+                        data,
+                        EthernetAddress::new(address.protocol(), address.mac(), *interface),
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The hardware clock a port timestamps with, or `None` for software
+/// timestamping
+fn port_phc(port_config: &crate::config::PortConfig) -> Option<u32> {
+    match port_config.hardware_clock {
+        HardwareClock::Auto => {
+            let phc = port_config.interface.lookup_phc();
+            if phc.is_none() {
+                log::info!("No hardware clock found, falling back to software timestamping");
+            }
+            phc
+        }
+        HardwareClock::Required => Some(
+            port_config
+                .interface
+                .lookup_phc()
+                .expect("No hardware clock found"),
+        ),
+        HardwareClock::Specific(idx) => Some(idx),
+        HardwareClock::None => None,
+    }
+}
+
+fn target<A>(link_local: bool, pdelay: A, primary: A) -> A {
+    if link_local {
+        pdelay
+    } else {
+        primary
+    }
+}
+
+/// Something that happened on one of the ports
+enum PortEvent {
+    Received(std::io::Result<Received>),
+    PeerDelayTimer,
+}
+
+/// Run a transparent clock on the ports in the configuration
+///
+/// When the ports timestamp with more than one clock, `sync_clock` is called
+/// for each hardware clock to keep it synchronized to the system clock. What
+/// it returns is kept until the transparent clock stops.
+///
+/// This only returns when opening the sockets fails.
+pub async fn run<T>(
+    config: &Config,
+    clock_identity: ClockIdentity,
+    mut sync_clock: impl FnMut(LinuxClock) -> T,
+) -> std::io::Result<()> {
+    let phcs: Vec<_> = config.ports.iter().map(port_phc).collect();
+
+    let mut ports = config
+        .ports
+        .iter()
+        .zip(phcs.iter())
+        .map(|(port_config, phc)| TransparentPort::open(port_config, *phc))
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let mut clocks = Vec::with_capacity(phcs.len());
+    let mut hardware_clocks: Vec<(u32, LinuxClock)> = vec![];
+    for phc in &phcs {
+        clocks.push(match phc {
+            Some(idx) => match hardware_clocks.iter().find(|(other, _)| other == idx) {
+                Some((_, clock)) => clock.clone(),
+                None => {
+                    let mut clock = LinuxClock::open_idx(*idx)?;
+                    clock.init().expect("Unable to initialize clock");
+                    hardware_clocks.push((*idx, clock.clone()));
+                    clock
+                }
+            },
+            None => LinuxClock::CLOCK_TAI,
+        });
+    }
+
+    // Timestamps of different clocks are only comparable while these clocks
+    // are synchronized
+    let _clock_syncs: Vec<T> = if phcs.windows(2).all(|pair| pair[0] == pair[1]) {
+        vec![]
+    } else {
+        hardware_clocks
+            .into_iter()
+            .map(|(_, clock)| sync_clock(clock))
+            .collect()
+    };
+
+    let mut transparent_clock = match config.clock_type {
+        ClockType::P2pTransparent => {
+            let pdelay_intervals: Vec<_> = config
+                .ports
+                .iter()
+                .map(|port| port.delay_interval())
+                .collect();
+            TransparentClock::new_p2p(clock_identity, &pdelay_intervals)
+        }
+        _ => TransparentClock::new(ports.len()),
+    };
+
+    // Only peer-to-peer transparent clocks use timers, these expire
+    // immediately to start the first measurement
+    let mut timers: Vec<_> = ports
+        .iter()
+        .map(|_| {
+            (config.clock_type == ClockType::P2pTransparent)
+                .then(|| Box::pin(tokio::time::sleep(std::time::Duration::ZERO)))
+        })
+        .collect();
+
+    let mut buffers = vec![([0; BUFFER_SIZE], [0; BUFFER_SIZE]); ports.len()];
+
+    loop {
+        let (index, event) = {
+            let mut receives: Vec<_> = ports
+                .iter()
+                .zip(buffers.iter_mut())
+                .map(|(port, (event_buffer, general_buffer))| {
+                    Box::pin(port.recv(event_buffer, general_buffer))
+                })
+                .collect();
+
+            std::future::poll_fn(|cx| {
+                for (index, receive) in receives.iter_mut().enumerate() {
+                    if let Poll::Ready(result) = receive.as_mut().poll(cx) {
+                        return Poll::Ready((index, PortEvent::Received(result)));
+                    }
+                }
+                for (index, timer) in timers.iter_mut().enumerate() {
+                    if let Some(Poll::Ready(())) = timer.as_mut().map(|t| t.as_mut().poll(cx)) {
+                        return Poll::Ready((index, PortEvent::PeerDelayTimer));
+                    }
+                }
+                Poll::Pending
+            })
+            .await
+        };
+
+        let received = match event {
+            PortEvent::Received(Ok(received)) => received,
+            PortEvent::Received(Err(error)) => panic!("Error receiving: {error:?}"),
+            PortEvent::PeerDelayTimer => {
+                let actions = transparent_clock.handle_peer_delay_timer(index);
+                let mut send_timestamps =
+                    handle_actions(actions, &mut ports, &mut timers, &clocks).await;
+                while let Some((context, timestamp)) = send_timestamps.pop() {
+                    let actions = transparent_clock.handle_send_timestamp(context, timestamp);
+                    send_timestamps
+                        .extend(handle_actions(actions, &mut ports, &mut timers, &clocks).await);
+                }
+                continue;
+            }
+        };
+
+        let (event_buffer, general_buffer) = &buffers[index];
+        let actions = if received.from_event_socket {
+            let data = &event_buffer[..received.bytes_read];
+            match received.timestamp {
+                Some(timestamp) => transparent_clock.handle_event_receive(
+                    index,
+                    data,
+                    clocks[index].port_timestamp_to_time(timestamp),
+                ),
+                // Only event messages are timestamped on ethernet
+                None if matches!(ports[index], TransparentPort::Ethernet { .. }) => {
+                    transparent_clock.handle_general_receive(index, data)
+                }
+                None => {
+                    log::error!("Missing recv timestamp");
+                    continue;
+                }
+            }
+        } else {
+            transparent_clock.handle_general_receive(index, &general_buffer[..received.bytes_read])
+        };
+
+        let mut send_timestamps = handle_actions(actions, &mut ports, &mut timers, &clocks).await;
+
+        // Timestamps must be handled before the next received message, as its
+        // correction may depend on them
+        while let Some((context, timestamp)) = send_timestamps.pop() {
+            let actions = transparent_clock.handle_send_timestamp(context, timestamp);
+            send_timestamps.extend(handle_actions(actions, &mut ports, &mut timers, &clocks).await);
+        }
+    }
+}
+
+async fn handle_actions(
+    actions: TransparentClockActionIterator<'_>,
+    ports: &mut [TransparentPort],
+    timers: &mut [Option<Pin<Box<Sleep>>>],
+    clocks: &[LinuxClock],
+) -> Vec<(TransparentTimestampContext, Time)> {
+    let mut send_timestamps = vec![];
+
+    for action in actions {
+        match action {
+            TransparentClockAction::SendEvent {
+                port,
+                context,
+                data,
+                link_local,
+            } => {
+                let time = ports[port]
+                    .send_event(data, link_local)
+                    .await
+                    .expect("Failed to send event message");
+
+                if let Some(time) = time {
+                    log::trace!("Send timestamp {:?}", time);
+                    send_timestamps.push((context, clocks[port].port_timestamp_to_time(time)));
+                } else {
+                    log::error!("Missing send timestamp");
+                }
+            }
+            TransparentClockAction::SendGeneral {
+                port,
+                data,
+                link_local,
+            } => {
+                ports[port]
+                    .send_general(data, link_local)
+                    .await
+                    .expect("Failed to send general message");
+            }
+            TransparentClockAction::ResetPeerDelayTimer { port, duration } => {
+                if let Some(timer) = &mut timers[port] {
+                    timer.as_mut().reset(Instant::now() + duration);
+                }
+            }
+        }
+    }
+
+    send_timestamps
+}
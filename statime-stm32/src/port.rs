@@ -175,6 +175,14 @@ impl Port {
                 }
                 // Single port implementation, so no need to forward TLVs
                 PortAction::ForwardTLV { .. } => {}
+                // Unicast negotiation is not enabled, see `unicast_grantor` below
+                PortAction::SendEventUnicast { .. }
+                | PortAction::SendGeneralUnicast { .. }
+                | PortAction::ResetUnicastTimer { .. } => {}
+                // One-step sync is not enabled, see `one_step` below
+                PortAction::SendEventOneStep { .. } => {}
+                // No synchronization metadata is configured on the instance
+                PortAction::ResetSynchronizationMetadataTimer { .. } => {}
             }
         }
     }
@@ -287,7 +295,10 @@ pub fn setup_statime(
         slave_only: false,
         sdo_id: SdoId::default(),
         path_trace: false,
+        external_port_configuration: false,
         clock_quality: ClockQuality::default(),
+        management_set_policy: Default::default(),
+        dataset_comparison: Default::default(),
     };
     let time_properties_ds =
         TimePropertiesDS::new_arbitrary_time(false, false, TimeSource::InternalOscillator);
@@ -305,6 +316,18 @@ pub fn setup_statime(
         master_only: false,
         delay_asymmetry: Duration::ZERO,
         minor_ptp_version: PtpMinorVersion::One,
+        unicast_grantor: None,
+        unicast_master_table: Default::default(),
+        one_step: false,
+        authentication: None,
+        slave_monitoring: None,
+        gptp: None,
+        desired_state: None,
+        number_alternate_masters: 0,
+        local_priority: 128,
+        link_local: false,
+        hybrid_e2e: false,
+        unicast_only: false,
     };
 
     let ptp_port = ptp_instance.add_port(port_config, Default::default(), ptp_clock, rng);
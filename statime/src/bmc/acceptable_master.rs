@@ -1,10 +1,59 @@
-use crate::config::ClockIdentity;
+use crate::config::{ClockIdentity, PortIdentity};
 
 /// A list of [`ClockIdentity`]s a [`Port`](`crate::port::Port`) may accept as a
 /// master clock.
 pub trait AcceptableMasterList {
     /// Return whether the clock with `identity` may be a master to this `Port`
     fn is_acceptable(&self, identity: ClockIdentity) -> bool;
+
+    /// Return whether the port with `identity` may be a master to this `Port`
+    ///
+    /// By default any port of an acceptable clock is accepted.
+    fn is_acceptable_port(&self, identity: PortIdentity) -> bool {
+        self.is_acceptable(identity.clock_identity)
+    }
+
+    /// Return the priority 1 that replaces the grandmaster priority 1 of
+    /// announce messages of the port with `identity` during the dataset
+    /// comparison, if any
+    fn alternate_priority_1(&self, _identity: PortIdentity) -> Option<u8> {
+        None
+    }
+}
+
+/// An entry of an acceptable master table
+///
+/// See *IEEE1588-2019 section 17.5*
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AcceptableMaster {
+    /// Identity of the clock that is accepted as a master
+    pub clock_identity: ClockIdentity,
+    /// Number of the port of the clock that is accepted as a master, or `None`
+    /// to accept all of its ports
+    pub port_number: Option<u16>,
+    /// Value that replaces the grandmaster priority 1 of announce messages of
+    /// this master in the dataset comparison, which allows ranking masters
+    /// locally. The default of 0 keeps the announced value.
+    pub alternate_priority_1: u8,
+}
+
+impl AcceptableMaster {
+    /// Accept all ports of the clock with `clock_identity`, without changing
+    /// their priority
+    pub fn new(clock_identity: ClockIdentity) -> Self {
+        Self {
+            clock_identity,
+            port_number: None,
+            alternate_priority_1: 0,
+        }
+    }
+
+    fn matches(&self, identity: PortIdentity) -> bool {
+        self.clock_identity == identity.clock_identity
+            && self
+                .port_number
+                .map_or(true, |port_number| port_number == identity.port_number)
+    }
 }
 
 /// An [`AcceptableMasterList`] that accepts any [`ClockIdentity`] as a master
@@ -49,6 +98,52 @@ impl AcceptableMasterList for std::collections::HashSet<ClockIdentity> {
     }
 }
 
+impl AcceptableMasterList for &[AcceptableMaster] {
+    fn is_acceptable(&self, identity: ClockIdentity) -> bool {
+        self.iter().any(|entry| entry.clock_identity == identity)
+    }
+
+    fn is_acceptable_port(&self, identity: PortIdentity) -> bool {
+        self.iter().any(|entry| entry.matches(identity))
+    }
+
+    fn alternate_priority_1(&self, identity: PortIdentity) -> Option<u8> {
+        self.iter()
+            .find(|entry| entry.matches(identity))
+            .map(|entry| entry.alternate_priority_1)
+            .filter(|&priority_1| priority_1 != 0)
+    }
+}
+
+impl<const CAP: usize> AcceptableMasterList for arrayvec::ArrayVec<AcceptableMaster, CAP> {
+    fn is_acceptable(&self, identity: ClockIdentity) -> bool {
+        self.as_slice().is_acceptable(identity)
+    }
+
+    fn is_acceptable_port(&self, identity: PortIdentity) -> bool {
+        self.as_slice().is_acceptable_port(identity)
+    }
+
+    fn alternate_priority_1(&self, identity: PortIdentity) -> Option<u8> {
+        self.as_slice().alternate_priority_1(identity)
+    }
+}
+
+#[cfg(feature = "std")]
+impl AcceptableMasterList for std::vec::Vec<AcceptableMaster> {
+    fn is_acceptable(&self, identity: ClockIdentity) -> bool {
+        self.as_slice().is_acceptable(identity)
+    }
+
+    fn is_acceptable_port(&self, identity: PortIdentity) -> bool {
+        self.as_slice().is_acceptable_port(identity)
+    }
+
+    fn alternate_priority_1(&self, identity: PortIdentity) -> Option<u8> {
+        self.as_slice().alternate_priority_1(identity)
+    }
+}
+
 impl<T: AcceptableMasterList> AcceptableMasterList for Option<T> {
     fn is_acceptable(&self, identity: ClockIdentity) -> bool {
         match self {
@@ -56,4 +151,58 @@ impl<T: AcceptableMasterList> AcceptableMasterList for Option<T> {
             None => true,
         }
     }
+
+    fn is_acceptable_port(&self, identity: PortIdentity) -> bool {
+        match self {
+            Some(list) => list.is_acceptable_port(identity),
+            None => true,
+        }
+    }
+
+    fn alternate_priority_1(&self, identity: PortIdentity) -> Option<u8> {
+        self.as_ref()
+            .and_then(|list| list.alternate_priority_1(identity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY_A: ClockIdentity = ClockIdentity([1; 8]);
+    const IDENTITY_B: ClockIdentity = ClockIdentity([2; 8]);
+
+    fn port(clock_identity: ClockIdentity, port_number: u16) -> PortIdentity {
+        PortIdentity {
+            clock_identity,
+            port_number,
+        }
+    }
+
+    #[test]
+    fn acceptable_master_table() {
+        let table = [
+            AcceptableMaster {
+                port_number: Some(1),
+                alternate_priority_1: 10,
+                ..AcceptableMaster::new(IDENTITY_A)
+            },
+            AcceptableMaster::new(IDENTITY_B),
+        ];
+        let table = &table[..];
+
+        assert!(table.is_acceptable(IDENTITY_A));
+        assert!(table.is_acceptable_port(port(IDENTITY_A, 1)));
+        assert!(!table.is_acceptable_port(port(IDENTITY_A, 2)));
+        assert!(table.is_acceptable_port(port(IDENTITY_B, 2)));
+        assert!(!table.is_acceptable_port(port(ClockIdentity([3; 8]), 1)));
+
+        assert_eq!(table.alternate_priority_1(port(IDENTITY_A, 1)), Some(10));
+        assert_eq!(table.alternate_priority_1(port(IDENTITY_B, 1)), None);
+
+        // No table accepts everything without changing priorities
+        let table: Option<&[AcceptableMaster]> = None;
+        assert!(table.is_acceptable_port(port(IDENTITY_A, 2)));
+        assert_eq!(table.alternate_priority_1(port(IDENTITY_A, 1)), None);
+    }
 }
@@ -8,6 +8,7 @@ use super::{
     foreign_master::ForeignMasterList,
 };
 use crate::{
+    config::DesiredState,
     datastructures::{
         common::{PortIdentity, TimeInterval},
         datasets::InternalDefaultDS,
@@ -35,6 +36,9 @@ pub(crate) struct Bmca<A> {
     foreign_master_list: ForeignMasterList,
     acceptable_master_list: A,
     own_port_identity: PortIdentity,
+    /// localPriority of the port, when using the alternate BMCA of ITU-T
+    /// G.8275.1
+    local_priority: Option<u8>,
 }
 
 impl<A> Bmca<A> {
@@ -50,6 +54,16 @@ impl<A> Bmca<A> {
             ),
             acceptable_master_list,
             own_port_identity,
+            local_priority: None,
+        }
+    }
+
+    /// Compare the announce messages of this port with the alternate BMCA of
+    /// ITU-T G.8275.1, with `local_priority` as the localPriority of the port
+    pub(crate) fn with_local_priority(self, local_priority: Option<u8>) -> Self {
+        Self {
+            local_priority,
+            ..self
         }
     }
 
@@ -57,6 +71,12 @@ impl<A> Bmca<A> {
         self.foreign_master_list.step_age(step);
     }
 
+    pub(crate) fn foreign_masters(
+        &self,
+    ) -> impl Iterator<Item = crate::observability::port::ForeignMaster> + '_ {
+        self.foreign_master_list.foreign_masters()
+    }
+
     /// Finds the best announce message in the given iterator.
     /// The port identity in the tuple is the identity of the port that received
     /// the announce message.
@@ -74,16 +94,11 @@ impl<A> Bmca<A> {
     ) -> MessageComparison {
         match opt_best {
             None => MessageComparison::Better,
-            Some(best) => {
-                let dataset =
-                    ComparisonDataset::from_announce_message(&best.message, &best.identity);
-
-                match d0.compare(&dataset).as_ordering() {
-                    Ordering::Less => MessageComparison::Worse(best),
-                    Ordering::Equal => MessageComparison::Same,
-                    Ordering::Greater => MessageComparison::Better,
-                }
-            }
+            Some(best) => match d0.compare(&best.comparison_dataset()).as_ordering() {
+                Ordering::Less => MessageComparison::Worse(best),
+                Ordering::Equal => MessageComparison::Same,
+                Ordering::Greater => MessageComparison::Better,
+            },
         }
     }
 
@@ -128,6 +143,29 @@ impl<A> Bmca<A> {
         }
     }
 
+    /// Recommended state for a port with the given desired state when the
+    /// instance uses external port configuration
+    ///
+    /// Master ports distribute the time of the master of the slave port, if
+    /// any. Returns `None` for passive ports and for slave ports that have not
+    /// received an announce message. See *IEEE1588-2019 section 17.6.5.3*.
+    pub(crate) fn calculate_desired_recommended_state(
+        own_data: &InternalDefaultDS,
+        desired_state: DesiredState,
+        slave_port_announce_message: Option<BestAnnounceMessage>,
+        best_port_announce_message: Option<BestAnnounceMessage>,
+    ) -> Option<RecommendedState> {
+        match desired_state {
+            DesiredState::Master => Some(match slave_port_announce_message {
+                Some(slave_message) => RecommendedState::M3(slave_message.message),
+                None => RecommendedState::M1(*own_data),
+            }),
+            DesiredState::Slave => best_port_announce_message
+                .map(|port_message| RecommendedState::S1(port_message.message)),
+            DesiredState::Passive => None,
+        }
+    }
+
     fn calculate_recommended_state_low_class(
         own_data: &InternalDefaultDS,
         best_port_announce_message: Option<BestAnnounceMessage>,
@@ -166,15 +204,8 @@ impl<A> Bmca<A> {
             // effectively, E_best == E_rbest
             RecommendedState::S1(global_message.message)
         } else {
-            let ebest = ComparisonDataset::from_announce_message(
-                &global_message.message,
-                &global_message.identity,
-            );
-
-            let erbest = ComparisonDataset::from_announce_message(
-                &port_message.message,
-                &port_message.identity,
-            );
+            let ebest = global_message.comparison_dataset();
+            let erbest = port_message.comparison_dataset();
 
             // E_best better by topology than E_rbest
             if matches!(ebest.compare(&erbest), DatasetOrdering::BetterByTopology) {
@@ -187,17 +218,43 @@ impl<A> Bmca<A> {
 }
 
 impl<A: AcceptableMasterList> Bmca<A> {
+    /// Number of qualified foreign masters that are better than `own_data`
+    ///
+    /// This has to be called before the announce messages are taken with
+    /// [Bmca::take_best_port_announce_message].
+    pub(crate) fn count_better_foreign_masters(&self, own_data: &InternalDefaultDS) -> usize {
+        let d0 = ComparisonDataset::from_own_data(own_data);
+
+        self.foreign_master_list
+            .qualified_announce_messages()
+            .filter(|announce| {
+                let dataset = ComparisonDataset::from_announce_message(
+                    &announce.message,
+                    &self.own_port_identity,
+                )
+                .with_alternate_priority_1(
+                    self.acceptable_master_list
+                        .alternate_priority_1(announce.header.source_port_identity),
+                )
+                .with_local_priority(self.local_priority);
+                d0.compare(&dataset).as_ordering() == Ordering::Less
+            })
+            .count()
+    }
+
     /// Register a received announce message to the BMC algorithm
     pub(crate) fn register_announce_message(
         &mut self,
         header: &Header,
         announce_message: &AnnounceMessage,
     ) -> bool {
-        // Ignore messages comming from the same port
+        // Ignore messages comming from the same port, and those of alternate
+        // masters, which are not qualified (IEEE 1588-2019 section 17.3)
         if announce_message.header.source_port_identity != self.own_port_identity
+            && !header.alternate_master_flag
             && self
                 .acceptable_master_list
-                .is_acceptable(announce_message.header.source_port_identity.clock_identity)
+                .is_acceptable_port(announce_message.header.source_port_identity)
         {
             self.foreign_master_list.register_announce_message(
                 header,
@@ -216,11 +273,13 @@ impl<A: AcceptableMasterList> Bmca<A> {
         announce_message: &AnnounceMessage,
         age: Duration,
     ) {
-        // Ignore messages comming from the same port
+        // Ignore messages comming from the same port, and those of alternate
+        // masters
         if announce_message.header.source_port_identity != self.own_port_identity
+            && !header.alternate_master_flag
             && self
                 .acceptable_master_list
-                .is_acceptable(announce_message.header.source_port_identity.clock_identity)
+                .is_acceptable_port(announce_message.header.source_port_identity)
         {
             self.foreign_master_list
                 .register_announce_message(header, announce_message, age);
@@ -240,6 +299,10 @@ impl<A: AcceptableMasterList> Bmca<A> {
                 message: message.message,
                 age: message.age,
                 identity: self.own_port_identity,
+                alternate_priority_1: self
+                    .acceptable_master_list
+                    .alternate_priority_1(message.header.source_port_identity),
+                local_priority: self.local_priority,
             }
         }));
 
@@ -267,6 +330,11 @@ pub(crate) struct BestAnnounceMessage {
     message: AnnounceMessage,
     age: Duration,
     identity: PortIdentity,
+    /// Priority 1 of the sender in the acceptable master table
+    alternate_priority_1: Option<u8>,
+    /// localPriority of the receiving port, for the alternate BMCA of ITU-T
+    /// G.8275.1
+    local_priority: Option<u8>,
 }
 
 impl BestAnnounceMessage {
@@ -277,10 +345,14 @@ impl BestAnnounceMessage {
     }
 
     fn compare_dataset(&self, other: &Self) -> DatasetOrdering {
-        let data1 = ComparisonDataset::from_announce_message(&self.message, &self.identity);
-        let data2 = ComparisonDataset::from_announce_message(&other.message, &other.identity);
+        self.comparison_dataset()
+            .compare(&other.comparison_dataset())
+    }
 
-        data1.compare(&data2)
+    fn comparison_dataset(&self) -> ComparisonDataset {
+        ComparisonDataset::from_announce_message(&self.message, &self.identity)
+            .with_alternate_priority_1(self.alternate_priority_1)
+            .with_local_priority(self.local_priority)
     }
 }
 
@@ -298,7 +370,7 @@ pub(crate) enum RecommendedState {
 mod tests {
     use super::*;
     use crate::{
-        bmc::acceptable_master::AcceptAnyMaster,
+        bmc::acceptable_master::{AcceptAnyMaster, AcceptableMaster},
         config::{ClockIdentity, ClockQuality, InstanceConfig},
         datastructures::messages::{Header, PtpVersion},
     };
@@ -355,6 +427,8 @@ mod tests {
             message,
             age: Duration::ZERO,
             identity,
+            alternate_priority_1: None,
+            local_priority: None,
         }
     }
 
@@ -376,6 +450,24 @@ mod tests {
         assert!(bmca.take_best_port_announce_message().is_some());
     }
 
+    #[test]
+    fn test_alternate_master_not_registered() {
+        let mut bmca = Bmca::new(
+            AcceptAnyMaster,
+            TimeInterval(100.into()),
+            PortIdentity::default(),
+        );
+        let mut announce = default_announce_message();
+        announce.header.source_port_identity.clock_identity.0 = [1, 2, 3, 4, 5, 6, 7, 8];
+        announce.header.alternate_master_flag = true;
+
+        assert!(!bmca.register_announce_message(&announce.header, &announce));
+        assert!(!bmca.register_announce_message(&announce.header, &announce));
+        bmca.reregister_announce_message(&announce.header, &announce, Duration::ZERO);
+
+        assert!(bmca.take_best_port_announce_message().is_none());
+    }
+
     #[test]
     fn test_master_registration_rollover() {
         let mut bmca = Bmca::new(
@@ -425,7 +517,7 @@ mod tests {
     #[test]
     fn test_acceptable_master_filter() {
         let mut bmca = Bmca::new(
-            std::vec![],
+            std::vec::Vec::<ClockIdentity>::new(),
             TimeInterval(100.into()),
             PortIdentity::default(),
         );
@@ -439,6 +531,65 @@ mod tests {
         assert!(bmca.take_best_port_announce_message().is_none());
     }
 
+    #[test]
+    fn test_acceptable_master_table() {
+        let identity_a = ClockIdentity([1; 8]);
+        let identity_b = ClockIdentity([2; 8]);
+        let mut bmca = Bmca::new(
+            std::vec![
+                AcceptableMaster::new(identity_a),
+                AcceptableMaster {
+                    port_number: Some(1),
+                    alternate_priority_1: 5,
+                    ..AcceptableMaster::new(identity_b)
+                },
+            ],
+            TimeInterval(100.into()),
+            PortIdentity::default(),
+        );
+
+        let mut announce_a = default_announce_message();
+        announce_a.header.source_port_identity.clock_identity = identity_a;
+        announce_a.grandmaster_identity = identity_a;
+        announce_a.grandmaster_priority_1 = 10;
+
+        // Only the listed port of clock B is acceptable
+        let mut announce_b = default_announce_message();
+        announce_b.header.source_port_identity = PortIdentity {
+            clock_identity: identity_b,
+            port_number: 2,
+        };
+        announce_b.grandmaster_identity = identity_b;
+        announce_b.grandmaster_priority_1 = 20;
+        assert!(!bmca.register_announce_message(&announce_b.header, &announce_b));
+
+        // Clock B wins by its alternate priority 1
+        announce_b.header.source_port_identity.port_number = 1;
+        for _ in 0..2 {
+            assert!(bmca.register_announce_message(&announce_a.header, &announce_a));
+            assert!(bmca.register_announce_message(&announce_b.header, &announce_b));
+        }
+
+        let best = bmca.take_best_port_announce_message().unwrap();
+        assert_eq!(best.message, announce_b);
+        assert_eq!(best.alternate_priority_1, Some(5));
+
+        // But only when compared, the announced priority is kept
+        let own_data = InternalDefaultDS {
+            priority_1: 8,
+            ..default_own_data()
+        };
+        assert!(matches!(
+            Bmca::<AcceptAnyMaster>::calculate_recommended_state(
+                &own_data,
+                Some(best),
+                Some(best),
+                &PortState::Listening,
+            ),
+            Some(RecommendedState::S1(message)) if message.grandmaster_priority_1 == 20
+        ));
+    }
+
     #[test]
     fn best_announce_message_compare_equal() {
         let message1 = default_best_announce_message();
@@ -504,7 +655,10 @@ mod tests {
             slave_only,
             sdo_id,
             path_trace,
+            external_port_configuration: false,
             clock_quality: ClockQuality::default(),
+            management_set_policy: Default::default(),
+            dataset_comparison: Default::default(),
         })
     }
 
@@ -553,7 +707,10 @@ mod tests {
             slave_only,
             sdo_id,
             path_trace,
+            external_port_configuration: false,
             clock_quality,
+            management_set_policy: Default::default(),
+            dataset_comparison: Default::default(),
         });
 
         // D0 is the same as E_rbest; this is unreachable in practice, but we return M1
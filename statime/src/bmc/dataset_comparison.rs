@@ -2,10 +2,13 @@
 
 use core::cmp::Ordering;
 
-use crate::datastructures::{
-    common::{ClockIdentity, ClockQuality, PortIdentity},
-    datasets::InternalDefaultDS,
-    messages::AnnounceMessage,
+use crate::{
+    config::DatasetComparison,
+    datastructures::{
+        common::{ClockIdentity, ClockQuality, PortIdentity},
+        datasets::InternalDefaultDS,
+        messages::AnnounceMessage,
+    },
 };
 
 /// A collection of data that is gathered from other sources (mainly announce
@@ -13,6 +16,9 @@ use crate::datastructures::{
 /// [compare](crate::bmc::dataset_comparison::ComparisonDataset) method can be
 /// used to find out which source is better according to the dataset comparison
 /// algorithm.
+///
+/// When both datasets have a local priority, they are compared with the
+/// alternate BMCA of ITU-T G.8275.1 instead.
 #[derive(Eq, PartialEq, Default, Debug)]
 pub(crate) struct ComparisonDataset {
     gm_priority_1: u8,
@@ -22,6 +28,7 @@ pub(crate) struct ComparisonDataset {
     steps_removed: u16,
     identity_of_senders: ClockIdentity,
     identity_of_receiver: PortIdentity,
+    local_priority: Option<u8>,
 }
 
 impl ComparisonDataset {
@@ -39,6 +46,27 @@ impl ComparisonDataset {
             steps_removed: message.steps_removed,
             identity_of_senders: message.header.source_port_identity.clock_identity,
             identity_of_receiver: *port_receiver_identity,
+            local_priority: None,
+        }
+    }
+
+    /// Replace the grandmaster priority 1 by the alternatePriority1 of the
+    /// acceptable master table entry of the sender, if any. See
+    /// *IEEE1588-2019 section 17.5.4*.
+    pub(crate) fn with_alternate_priority_1(self, alternate_priority_1: Option<u8>) -> Self {
+        Self {
+            gm_priority_1: alternate_priority_1.unwrap_or(self.gm_priority_1),
+            ..self
+        }
+    }
+
+    /// Set the localPriority of the port that received the announce message,
+    /// which is only compared by the alternate BMCA of ITU-T G.8275.1. See
+    /// *ITU-T G.8275.1 section 6.3.2*.
+    pub(crate) fn with_local_priority(self, local_priority: Option<u8>) -> Self {
+        Self {
+            local_priority,
+            ..self
         }
     }
 
@@ -54,6 +82,10 @@ impl ComparisonDataset {
                 clock_identity: data.clock_identity,
                 port_number: 0,
             },
+            local_priority: match data.dataset_comparison {
+                DatasetComparison::Ieee1588 => None,
+                DatasetComparison::G8275 { local_priority } => Some(local_priority),
+            },
         }
     }
 
@@ -61,6 +93,15 @@ impl ComparisonDataset {
     pub(crate) fn compare(&self, other: &Self) -> DatasetOrdering {
         if self.gm_identity == other.gm_identity {
             Self::compare_same_identity(self, other)
+        } else if let (Some(local_priority), Some(other_local_priority)) =
+            (self.local_priority, other.local_priority)
+        {
+            Self::compare_different_identity_g8275(
+                self,
+                other,
+                local_priority,
+                other_local_priority,
+            )
         } else {
             Self::compare_different_identity(self, other)
         }
@@ -87,6 +128,65 @@ impl ComparisonDataset {
         }
     }
 
+    /// PTP grandmaster instances are different, compared by the alternate
+    /// BMCA of ITU-T G.8275.1
+    fn compare_different_identity_g8275(
+        &self,
+        other: &Self,
+        local_priority: u8,
+        other_local_priority: u8,
+    ) -> DatasetOrdering {
+        let self_quality = self.gm_clock_quality;
+        let other_quality = other.gm_clock_quality;
+
+        // G.8275.1 figure 4, priority1 is not used
+        let ordering = (self_quality.clock_class.cmp(&other_quality.clock_class))
+            .then_with(|| {
+                self_quality
+                    .clock_accuracy
+                    .cmp_numeric(&other_quality.clock_accuracy)
+            })
+            .then_with(|| {
+                self_quality
+                    .offset_scaled_log_variance
+                    .cmp(&other_quality.offset_scaled_log_variance)
+            })
+            .then_with(|| self.gm_priority_2.cmp(&other.gm_priority_2))
+            .then_with(|| local_priority.cmp(&other_local_priority));
+
+        // Grandmasters locked to a primary reference are equivalent, otherwise
+        // the closest one is preferred
+        if ordering == Ordering::Equal && self_quality.clock_class > 127 {
+            let ordering = self.steps_removed.cmp(&other.steps_removed);
+            if ordering != Ordering::Equal {
+                return match ordering {
+                    Ordering::Greater => DatasetOrdering::Worse,
+                    _ => DatasetOrdering::Better,
+                };
+            }
+
+            let receivers = Ord::cmp(
+                &self.identity_of_receiver.port_number,
+                &other.identity_of_receiver.port_number,
+            );
+            match self
+                .identity_of_senders
+                .cmp(&other.identity_of_senders)
+                .then(receivers)
+            {
+                Ordering::Less => return DatasetOrdering::BetterByTopology,
+                Ordering::Equal => {}
+                Ordering::Greater => return DatasetOrdering::WorseByTopology,
+            }
+        }
+
+        match ordering.then_with(|| self.gm_identity.cmp(&other.gm_identity)) {
+            Ordering::Equal => unreachable!("gm_identity is guaranteed to be different"),
+            Ordering::Greater => DatasetOrdering::Worse,
+            Ordering::Less => DatasetOrdering::Better,
+        }
+    }
+
     /// Potentially the same PTP grandmaster instance
     fn compare_same_identity(&self, other: &Self) -> DatasetOrdering {
         let steps_removed_difference = self.steps_removed as i32 - other.steps_removed as i32;
@@ -264,4 +364,51 @@ mod tests {
         assert_eq!(a.compare(&b), DatasetOrdering::Better);
         assert_eq!(b.compare(&a), DatasetOrdering::Worse);
     }
+
+    #[test]
+    fn g8275_comparison() {
+        let (mut a, mut b) = get_default_test_pair();
+        a.gm_identity = IDENTITY_A;
+        b.gm_identity = IDENTITY_B;
+        a.gm_clock_quality.clock_class = 165;
+        b.gm_clock_quality.clock_class = 165;
+        a.local_priority = Some(128);
+        b.local_priority = Some(128);
+
+        // Between equal grandmasters the closest one is preferred
+        a.steps_removed = 2;
+        b.steps_removed = 1;
+        assert_eq!(a.compare(&b), DatasetOrdering::Worse);
+        assert_eq!(b.compare(&a), DatasetOrdering::Better);
+
+        // unless they are locked to a primary reference
+        a.gm_clock_quality.clock_class = 6;
+        b.gm_clock_quality.clock_class = 6;
+        assert_eq!(a.compare(&b), DatasetOrdering::Better);
+        assert_eq!(b.compare(&a), DatasetOrdering::Worse);
+
+        a.local_priority = Some(129);
+        assert_eq!(a.compare(&b), DatasetOrdering::Worse);
+        assert_eq!(b.compare(&a), DatasetOrdering::Better);
+
+        a.gm_priority_2 = 0;
+        b.gm_priority_2 = 1;
+        assert_eq!(a.compare(&b), DatasetOrdering::Better);
+        assert_eq!(b.compare(&a), DatasetOrdering::Worse);
+
+        // Priority 1 is not used
+        a.gm_priority_1 = 255;
+        assert_eq!(a.compare(&b), DatasetOrdering::Better);
+        assert_eq!(b.compare(&a), DatasetOrdering::Worse);
+
+        a.gm_clock_quality.clock_class = 7;
+        assert_eq!(a.compare(&b), DatasetOrdering::Worse);
+        assert_eq!(b.compare(&a), DatasetOrdering::Better);
+
+        // Without a local priority the default comparison applies
+        a.local_priority = None;
+        a.gm_clock_quality.clock_class = 6;
+        assert_eq!(a.compare(&b), DatasetOrdering::Worse);
+        assert_eq!(b.compare(&a), DatasetOrdering::Better);
+    }
 }
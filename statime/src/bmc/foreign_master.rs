@@ -7,6 +7,7 @@ use crate::{
         common::{PortIdentity, TimeInterval},
         messages::{AnnounceMessage, Header},
     },
+    observability,
     time::Duration,
 };
 
@@ -59,6 +60,21 @@ impl ForeignMaster {
         self.foreign_master_port_identity
     }
 
+    fn observable(&self) -> Option<observability::port::ForeignMaster> {
+        let last = self.announce_messages.last()?;
+
+        Some(observability::port::ForeignMaster {
+            foreign_master_port_identity: self.foreign_master_port_identity,
+            announce_messages: self.announce_messages.len(),
+            qualified: self.announce_messages.len() >= FOREIGN_MASTER_THRESHOLD,
+            grandmaster_identity: last.message.grandmaster_identity,
+            grandmaster_clock_quality: last.message.grandmaster_clock_quality,
+            grandmaster_priority_1: last.message.grandmaster_priority_1,
+            grandmaster_priority_2: last.message.grandmaster_priority_2,
+            steps_removed: last.message.steps_removed,
+        })
+    }
+
     /// Removes all messages that fall outside of the
     /// [FOREIGN_MASTER_TIME_WINDOW].
     ///
@@ -124,6 +140,15 @@ impl ForeignMasterList {
         }
     }
 
+    /// The foreign masters currently in the list
+    pub(crate) fn foreign_masters(
+        &self,
+    ) -> impl Iterator<Item = observability::port::ForeignMaster> + '_ {
+        self.foreign_masters
+            .iter()
+            .filter_map(ForeignMaster::observable)
+    }
+
     pub(crate) fn step_age(&mut self, step: Duration) {
         for i in (0..self.foreign_masters.len()).rev() {
             // Purge the old timestamps so we can check the FOREIGN_MASTER_THRESHOLD
@@ -135,6 +160,17 @@ impl ForeignMasterList {
         }
     }
 
+    /// The most recent announce message of all qualified foreign masters,
+    /// without taking them
+    pub(crate) fn qualified_announce_messages(
+        &self,
+    ) -> impl Iterator<Item = &ForeignAnnounceMessage> {
+        self.foreign_masters
+            .iter()
+            .filter(|fm| fm.announce_messages.len() >= FOREIGN_MASTER_THRESHOLD)
+            .filter_map(|fm| fm.announce_messages.last())
+    }
+
     /// Takes the qualified announce message of all foreign masters that have
     /// one
     pub(crate) fn take_qualified_announce_messages(
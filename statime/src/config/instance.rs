@@ -1,13 +1,13 @@
 use crate::config::{ClockIdentity, ClockQuality, SdoId};
 #[cfg(doc)]
-use crate::PtpInstance;
+use crate::{config::PortConfig, PtpInstance};
 
 /// Configuration for a [`PtpInstance`]
 ///
 /// # Example
 /// A configuration with common default values:
 /// ```
-/// # use statime::config::{ClockIdentity, InstanceConfig, SdoId, ClockQuality};
+/// # use statime::config::{ClockIdentity, InstanceConfig, SdoId, ClockQuality, DatasetComparison, ManagementSetPolicy};
 /// let config = InstanceConfig {
 ///     clock_identity: ClockIdentity::from_mac_address([1,2,3,4,5,6]),
 ///     priority_1: 128,
@@ -16,7 +16,10 @@ use crate::PtpInstance;
 ///     sdo_id: SdoId::default(),
 ///     slave_only: false,
 ///     path_trace: false,
+///     external_port_configuration: false,
 ///     clock_quality: ClockQuality::default(),
+///     management_set_policy: ManagementSetPolicy::default(),
+///     dataset_comparison: DatasetComparison::Ieee1588,
 /// };
 /// ```
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
@@ -51,6 +54,60 @@ pub struct InstanceConfig {
     /// Whether the path trace option is enabled
     pub path_trace: bool,
 
+    /// Whether the state of each port is set through its
+    /// [`PortConfig::desired_state`] instead of by the best master clock
+    /// algorithm (externalPortConfigurationEnabled).
+    ///
+    /// See *IEEE1588-2019 section 17.6*.
+    pub external_port_configuration: bool,
+
     /// A description of the accuracy and type of the local clock.
     pub clock_quality: ClockQuality,
+
+    /// Which values may be changed by other nodes through PTP management
+    /// messages.
+    pub management_set_policy: ManagementSetPolicy,
+
+    /// How the best master clock algorithm compares the data sets of
+    /// masters.
+    pub dataset_comparison: DatasetComparison,
+}
+
+/// The data set comparison algorithm of the best master clock algorithm
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum DatasetComparison {
+    /// The data set comparison of *IEEE1588-2019 section 9.3.4*
+    #[default]
+    Ieee1588,
+    /// The alternate BMCA of the ITU-T G.8275.1 and G.8275.2 telecom
+    /// profiles
+    ///
+    /// Priority1 is not compared. After priority2, the localPriority of the
+    /// port that received each announce message decides, see
+    /// [`PortConfig::local_priority`]. Between equal masters that are not
+    /// locked to a primary reference, the one with the fewest steps removed
+    /// is preferred. See *ITU-T G.8275.1 section 6.3*.
+    G8275 {
+        /// The localPriority of this instance, compared with that of the
+        /// announce messages when deciding whether to become the grandmaster
+        local_priority: u8,
+    },
+}
+
+/// Values of a [`PtpInstance`] that may be changed with a management SET
+///
+/// Management messages are not authenticated, so by default nothing can be
+/// changed remotely. GET requests are always answered.
+///
+/// See *IEEE1588-2019 clause 15* for the management mechanism.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct ManagementSetPolicy {
+    /// Allow changing [`InstanceConfig::priority_1`] (PRIORITY1)
+    pub priority_1: bool,
+    /// Allow changing [`InstanceConfig::priority_2`] (PRIORITY2)
+    pub priority_2: bool,
+    /// Allow changing [`InstanceConfig::domain_number`] (DOMAIN)
+    pub domain_number: bool,
+    /// Allow changing [`InstanceConfig::slave_only`] (SLAVE_ONLY)
+    pub slave_only: bool,
 }
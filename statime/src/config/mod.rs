@@ -12,14 +12,24 @@
 mod instance;
 mod port;
 
-pub use instance::InstanceConfig;
-pub use port::{DelayMechanism, PortConfig, PtpMinorVersion};
+pub use instance::{DatasetComparison, InstanceConfig, ManagementSetPolicy};
+pub use port::{
+    DelayMechanism, DesiredState, GptpConfig, PortAddress, PortConfig, PtpMinorVersion,
+    SecurityAssociation, SecurityKey, SlaveMonitoringConfig, UnicastGrantorConfig,
+    UnicastMasterTable, MAX_ALTERNATE_MASTERS, MAX_MONITORING_RECORDS, MAX_SECURITY_KEYS,
+    MAX_SECURITY_KEY_LEN, MAX_UNICAST_GRANTEES, MAX_UNICAST_MASTERS,
+};
 
 pub use crate::{
-    bmc::acceptable_master::{AcceptAnyMaster, AcceptableMasterList},
+    bmc::acceptable_master::{AcceptAnyMaster, AcceptableMaster, AcceptableMasterList},
     datastructures::{
-        common::{ClockAccuracy, ClockIdentity, ClockQuality, LeapIndicator, TimeSource},
-        datasets::TimePropertiesDS,
+        common::{
+            AlternateTimescale, ClockAccuracy, ClockIdentity, ClockQuality, LeapIndicator,
+            MasterLockingStatus, PortIdentity, PowerProfileConfig, SynchronizationMetadata,
+            SynchronizationMetadataConfig, TimeSource, MAX_DISPLAY_NAME_LEN,
+        },
+        datasets::{TimePropertiesDS, MAX_ALTERNATE_TIMESCALES},
         messages::SdoId,
     },
+    holdover::{HoldoverClasses, HoldoverConfig},
 };
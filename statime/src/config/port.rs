@@ -1,8 +1,12 @@
+use arrayvec::ArrayVec;
 use rand::Rng;
 
 use crate::time::{Duration, Interval};
 #[cfg(doc)]
-use crate::{config::AcceptableMasterList, port::Port};
+use crate::{
+    config::{AcceptableMasterList, DatasetComparison},
+    port::{Port, PortAction},
+};
 
 /// Which delay mechanism a port is using.
 ///
@@ -52,9 +56,204 @@ impl From<PtpMinorVersion> for u8 {
     }
 }
 
+/// Network address of another PTP port, used for unicast messages
+///
+/// This does not include the UDP port or network interface, those follow from
+/// the transport used by the [`Port`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum PortAddress {
+    /// Address of a port using PTP over UDP/IPv4
+    Ipv4([u8; 4]),
+    /// Address of a port using PTP over UDP/IPv6
+    Ipv6([u8; 16]),
+    /// MAC address of a port using PTP over ethernet
+    Ethernet([u8; 6]),
+}
+
+/// Limits on the unicast transmission a [`Port`] grants to other ports
+///
+/// For more information see *IEEE1588-2019 section 16.1*
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct UnicastGrantorConfig {
+    /// Maximum number of ports that can be granted unicast transmission at
+    /// the same time. This is capped at [`MAX_UNICAST_GRANTEES`].
+    pub max_grantees: usize,
+
+    /// Shortest time between messages that is granted. Requests for more
+    /// frequent messages are denied.
+    pub min_interval: Interval,
+
+    /// Longest duration of a single grant. Requests for longer grants are
+    /// granted for this duration instead.
+    pub max_duration: core::time::Duration,
+}
+
+/// Maximum number of ports a single [`Port`] can grant unicast transmission
+pub const MAX_UNICAST_GRANTEES: usize = 16;
+
+/// Masters a [`Port`] requests unicast transmission from
+///
+/// For more information see *IEEE1588-2019 section 16.3*
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct UnicastMasterTable {
+    /// Addresses of the masters. When empty, the port does not request
+    /// unicast transmission.
+    pub masters: ArrayVec<PortAddress, MAX_UNICAST_MASTERS>,
+
+    /// Time between requests to a master that did not (yet) grant them
+    pub query_interval: Interval,
+
+    /// Duration of the grants requested from the masters. Grants are renewed
+    /// halfway through.
+    pub grant_duration: core::time::Duration,
+}
+
+impl Default for UnicastMasterTable {
+    /// An empty table, with the default query interval of 2 seconds and grants
+    /// of 300 seconds
+    fn default() -> Self {
+        Self {
+            masters: ArrayVec::new(),
+            query_interval: Interval::TWO_SECONDS,
+            grant_duration: core::time::Duration::from_secs(300),
+        }
+    }
+}
+
+/// Maximum number of masters in a [`UnicastMasterTable`]
+pub const MAX_UNICAST_MASTERS: usize = 8;
+
+/// A key used to authenticate PTP messages with HMAC-SHA256
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct SecurityKey {
+    /// Identifies the key in the AUTHENTICATION TLV (keyID)
+    pub id: u32,
+
+    /// The secret key itself
+    pub key: ArrayVec<u8, MAX_SECURITY_KEY_LEN>,
+
+    /// Number of bytes of the HMAC-SHA256 output sent as integrity check value
+    /// (ICV). Must be even and at most 32.
+    pub icv_length: usize,
+}
+
+/// Maximum length in bytes of the secret of a [`SecurityKey`]
+pub const MAX_SECURITY_KEY_LEN: usize = 64;
+
+/// Security association used to authenticate the messages of a [`Port`]
+///
+/// Messages are protected with immediate security processing: every message
+/// sent carries an AUTHENTICATION TLV with an integrity check value, and
+/// received messages without a valid one are dropped. For more information see
+/// *IEEE1588-2019 section 16.14 and annex P*
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct SecurityAssociation {
+    /// Security parameter pointer (SPP) identifying this association
+    pub spp: u8,
+
+    /// Keys that received messages may be authenticated with
+    pub keys: ArrayVec<SecurityKey, MAX_SECURITY_KEYS>,
+
+    /// Identifier of the key in [`keys`](`Self::keys`) used to authenticate
+    /// the messages sent
+    pub send_key_id: u32,
+}
+
+impl SecurityAssociation {
+    pub(crate) fn key(&self, id: u32) -> Option<&SecurityKey> {
+        self.keys.iter().find(|key| key.id == id)
+    }
+
+    /// Size of the AUTHENTICATION TLV added to the messages sent
+    pub(crate) fn tlv_size(&self) -> usize {
+        // TLV header, SPP, secParamIndicator and keyID, followed by the ICV
+        10 + self.key(self.send_key_id).map_or(0, |key| key.icv_length)
+    }
+}
+
+/// Maximum number of keys in a [`SecurityAssociation`]
+pub const MAX_SECURITY_KEYS: usize = 8;
+
+/// Slave event monitoring records a [`Port`] sends while it is slave
+///
+/// The records are collected and sent in batches, in signaling messages to the
+/// address of a collector. For more information see *IEEE1588-2019 section
+/// 16.11*
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct SlaveMonitoringConfig {
+    /// Address of the collector the signaling messages are sent to
+    pub collector: PortAddress,
+
+    /// Send the timestamps and correction of the received sync messages
+    /// (SLAVE_RX_SYNC_TIMING_DATA)
+    pub rx_sync_timing_data: bool,
+
+    /// Send the offset from master and mean path delay computed for the
+    /// received sync messages (SLAVE_RX_SYNC_COMPUTED_DATA)
+    pub rx_sync_computed_data: bool,
+
+    /// Send the transmit timestamps of the (peer) delay requests
+    /// (SLAVE_TX_EVENT_TIMESTAMPS)
+    pub tx_event_timestamps: bool,
+
+    /// Number of records collected before they are sent. This is capped at
+    /// [`MAX_MONITORING_RECORDS`].
+    pub records_per_message: usize,
+}
+
+/// Maximum number of records of each kind in a single slave event monitoring
+/// message
+pub const MAX_MONITORING_RECORDS: usize = 8;
+
+/// IEEE 802.1AS (gPTP) behaviour of a [`Port`]
+///
+/// A gPTP port measures the rate ratio to its neighbor from successive peer
+/// delay exchanges, only exchanges sync and announce messages while it is
+/// capable of running gPTP (asCapable) and sends all messages to the link
+/// local address. For more information see *IEEE802.1AS-2020 section 11*
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct GptpConfig {
+    /// Largest mean link delay for which the neighbor is considered capable
+    /// of running gPTP (neighborPropDelayThresh)
+    pub neighbor_prop_delay_thresh: Duration,
+
+    /// Number of consecutive peer delay requests that may go unanswered
+    /// before the neighbor is no longer considered capable of running gPTP
+    /// (allowedLostResponses)
+    pub allowed_lost_responses: u8,
+}
+
+impl Default for GptpConfig {
+    /// The defaults of 800 nanoseconds and 3 lost responses, suitable for
+    /// copper links
+    fn default() -> Self {
+        Self {
+            neighbor_prop_delay_thresh: Duration::from_nanos(800),
+            allowed_lost_responses: 3,
+        }
+    }
+}
+
+/// State of a [`Port`] when the instance uses external port configuration
+///
+/// See *IEEE1588-2019 section 17.6*.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum DesiredState {
+    /// Distribute the time of this instance
+    Master,
+    /// Synchronize to the best master on this port's link
+    Slave,
+    /// Neither synchronize nor distribute time
+    Passive,
+}
+
+/// Maximum number of alternate masters a [`Port`] keeps statistics for, see
+/// [`PortConfig::number_alternate_masters`]
+pub const MAX_ALTERNATE_MASTERS: usize = 4;
+
 /// Configuration items of the PTP PortDS dataset. Dynamical fields are kept
 /// as part of [crate::port::Port].
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct PortConfig<A> {
     /// A list that contains all nodes that this [`Port`] will accept as a
     /// master.
@@ -83,6 +282,76 @@ pub struct PortConfig<A> {
 
     /// Minor version number to use.
     pub minor_ptp_version: PtpMinorVersion,
+
+    /// Grant unicast transmission of messages to other ports that request it
+    /// while this [`Port`] is master. When `None`, all requests are denied.
+    pub unicast_grantor: Option<UnicastGrantorConfig>,
+
+    /// Masters to request unicast transmission of messages from, which
+    /// allows synchronizing without multicast.
+    pub unicast_master_table: UnicastMasterTable,
+
+    /// Send sync messages as one-step, with the origin timestamp in the sync
+    /// message itself instead of in a follow up message. This requires the
+    /// platform to support [`PortAction::SendEventOneStep`].
+    ///
+    /// Sync messages sent through unicast negotiation are always two-step.
+    pub one_step: bool,
+
+    /// Authenticate all messages sent and received on this [`Port`]. When
+    /// `None`, messages are not authenticated.
+    pub authentication: Option<SecurityAssociation>,
+
+    /// Send slave event monitoring records to a collector while this
+    /// [`Port`] is slave. When `None`, no records are sent.
+    pub slave_monitoring: Option<SlaveMonitoringConfig>,
+
+    /// Run this [`Port`] as an IEEE 802.1AS (gPTP) port. This requires the
+    /// [`P2P`](`DelayMechanism::P2P`) delay mechanism. When `None`, the port
+    /// follows IEEE 1588.
+    pub gptp: Option<GptpConfig>,
+
+    /// State of this [`Port`] when the instance uses
+    /// [`external_port_configuration`](`crate::config::InstanceConfig::external_port_configuration`)
+    /// (desiredState). Ports without a desired state are passive. Ignored when
+    /// the state is decided by the BMCA.
+    pub desired_state: Option<DesiredState>,
+
+    /// Number of alternate masters (numberAlternateMasters). While this
+    /// [`Port`] is passive and at most this many foreign masters on its link
+    /// are better than this instance, it sends announce, sync and follow up
+    /// messages with the alternateMasterFlag set. While slave, it keeps
+    /// statistics of the offset to this many alternate masters, capped at
+    /// [`MAX_ALTERNATE_MASTERS`]. Zero disables the option.
+    ///
+    /// See *IEEE1588-2019 section 17.3*.
+    pub number_alternate_masters: u8,
+
+    /// Priority of the masters on the link of this [`Port`] in the alternate
+    /// BMCA of the telecom profiles (localPriority). Lower values assign a
+    /// higher priority. Ignored by the default data set comparison.
+    ///
+    /// See [`DatasetComparison::G8275`].
+    pub local_priority: u8,
+
+    /// Send all multicast messages to the link local address, which bridges
+    /// do not forward (non-forwardable address), instead of only the peer
+    /// delay messages. gPTP ports always do so.
+    pub link_local: bool,
+
+    /// Send delay requests unicast to the address the master sends its sync
+    /// messages from, instead of multicast, while this [`Port`] is slave with
+    /// the [`E2E`](`DelayMechanism::E2E`) delay mechanism. This is the hybrid
+    /// mode of the enterprise profile. Regardless of this setting, unicast
+    /// delay requests are always answered with a unicast delay response.
+    ///
+    /// See *IETF RFC 8173 section 7*.
+    pub hybrid_e2e: bool,
+
+    /// Only send announce, sync and follow up messages to the ports this
+    /// [`Port`] granted unicast transmission to, never multicast, while it is
+    /// master, as required by the unicast ITU-T G.8275.2 telecom profile.
+    pub unicast_only: bool,
     // Notes:
     // Fields specific for delay mechanism are kept as part of [DelayMechanism].
     // Major version is always 2, so not stored (versionNumber)
@@ -0,0 +1,205 @@
+use arrayvec::ArrayString;
+
+use super::{Tlv, TlvType};
+use crate::datastructures::{WireFormat, WireFormatError};
+
+/// Maximum length in bytes of the display name of an [`AlternateTimescale`]
+///
+/// The standard recommends names of at most 10 characters, see *IEEE1588-2019
+/// section 16.3.3.8*.
+pub const MAX_DISPLAY_NAME_LEN: usize = 10;
+
+/// An alternate timescale, such as a local time, that is distributed by the
+/// grandmaster in ALTERNATE_TIME_OFFSET_INDICATOR TLVs.
+///
+/// See *IEEE1588-2019 section 16.3*.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlternateTimescale {
+    /// Identifies this timescale among those distributed by the grandmaster.
+    ///
+    /// See *IEEE1588-2019 section 16.3.3.4*.
+    pub key_field: u8,
+    /// Offset in seconds of the alternate timescale from the PTP timescale.
+    ///
+    /// See *IEEE1588-2019 section 16.3.3.5*.
+    pub current_offset: i32,
+    /// Size in seconds of the next discontinuity of the alternate timescale,
+    /// for instance 3600 when daylight saving time starts.
+    ///
+    /// See *IEEE1588-2019 section 16.3.3.6*.
+    pub jump_seconds: i32,
+    /// Time in seconds of the PTP timescale at which the next discontinuity
+    /// occurs. Only the lower 48 bits are transmitted.
+    ///
+    /// See *IEEE1588-2019 section 16.3.3.7*.
+    pub time_of_next_jump: u64,
+    /// Name of the alternate timescale, for instance `"CET"`.
+    ///
+    /// See *IEEE1588-2019 section 16.3.3.8*.
+    pub display_name: ArrayString<MAX_DISPLAY_NAME_LEN>,
+}
+
+impl AlternateTimescale {
+    const FIXED_SIZE: usize = 16;
+    const MAX_WIRE_SIZE: usize = Self::FIXED_SIZE + MAX_DISPLAY_NAME_LEN;
+
+    /// Size of the TLV value, padded to an even number of octets
+    pub(crate) fn wire_size(&self) -> usize {
+        let size = Self::FIXED_SIZE + self.display_name.len();
+        size + size % 2
+    }
+
+    /// Serialize into an ALTERNATE_TIME_OFFSET_INDICATOR TLV and pass it to
+    /// `f`
+    pub(crate) fn with_tlv<R>(&self, f: impl FnOnce(Tlv<'_>) -> R) -> R {
+        let mut buffer = [0; Self::MAX_WIRE_SIZE];
+        let size = self.wire_size();
+        // Cannot fail as the buffer fits the largest display name
+        self.serialize(&mut buffer[..size]).unwrap();
+
+        f(Tlv {
+            tlv_type: TlvType::AlternateTimeOffsetIndicator,
+            value: buffer[..size].into(),
+        })
+    }
+
+    /// Parse an ALTERNATE_TIME_OFFSET_INDICATOR TLV
+    pub(crate) fn from_tlv(tlv: &Tlv<'_>) -> Result<Self, WireFormatError> {
+        if tlv.tlv_type != TlvType::AlternateTimeOffsetIndicator {
+            return Err(WireFormatError::Invalid);
+        }
+
+        Self::deserialize(&tlv.value)
+    }
+}
+
+impl WireFormat for AlternateTimescale {
+    fn serialize(&self, buffer: &mut [u8]) -> Result<(), WireFormatError> {
+        let buffer = buffer
+            .get_mut(..self.wire_size())
+            .ok_or(WireFormatError::BufferTooShort)?;
+
+        buffer[0] = self.key_field;
+        buffer[1..5].copy_from_slice(&self.current_offset.to_be_bytes());
+        buffer[5..9].copy_from_slice(&self.jump_seconds.to_be_bytes());
+        buffer[9..15].copy_from_slice(&self.time_of_next_jump.to_be_bytes()[2..8]);
+        buffer[15] = self.display_name.len() as u8;
+        buffer[16..16 + self.display_name.len()].copy_from_slice(self.display_name.as_bytes());
+        buffer[16 + self.display_name.len()..].fill(0);
+
+        Ok(())
+    }
+
+    fn deserialize(buffer: &[u8]) -> Result<Self, WireFormatError> {
+        if buffer.len() < Self::FIXED_SIZE {
+            return Err(WireFormatError::BufferTooShort);
+        }
+
+        let mut time_of_next_jump = [0; 8];
+        time_of_next_jump[2..8].copy_from_slice(&buffer[9..15]);
+
+        let name_length = buffer[15] as usize;
+        let name = buffer
+            .get(16..16 + name_length)
+            .ok_or(WireFormatError::BufferTooShort)?;
+        let name = core::str::from_utf8(name).map_err(|_| WireFormatError::Invalid)?;
+
+        Ok(Self {
+            key_field: buffer[0],
+            current_offset: i32::from_be_bytes(buffer[1..5].try_into().unwrap()),
+            jump_seconds: i32::from_be_bytes(buffer[5..9].try_into().unwrap()),
+            time_of_next_jump: u64::from_be_bytes(time_of_next_jump),
+            display_name: ArrayString::from(name).map_err(|_| WireFormatError::CapacityError)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alternate_timescale_wireformat() {
+        let representations = [
+            (
+                &[
+                    0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x00, 0x65, 0x1d,
+                    0x5e, 0x90, 0x03, b'C', b'E', b'T', 0x00,
+                ][..],
+                AlternateTimescale {
+                    key_field: 1,
+                    current_offset: 3600,
+                    jump_seconds: 3600,
+                    time_of_next_jump: 0x651d5e90,
+                    display_name: ArrayString::from("CET").unwrap(),
+                },
+            ),
+            (
+                &[
+                    0xff, 0xff, 0xff, 0xc7, 0xc0, 0xff, 0xff, 0xf1, 0xf0, 0x12, 0x34, 0x56, 0x78,
+                    0x9a, 0xbc, 0x02, b'E', b'T',
+                ][..],
+                AlternateTimescale {
+                    key_field: 255,
+                    current_offset: -14400,
+                    jump_seconds: -3600,
+                    time_of_next_jump: 0x123456789abc,
+                    display_name: ArrayString::from("ET").unwrap(),
+                },
+            ),
+        ];
+
+        for (byte_representation, object_representation) in representations {
+            // Test the serialization output
+            assert_eq!(object_representation.wire_size(), byte_representation.len());
+            let mut serialization_buffer = [0xaa; 32];
+            object_representation
+                .serialize(&mut serialization_buffer)
+                .unwrap();
+            assert_eq!(
+                &serialization_buffer[..byte_representation.len()],
+                byte_representation
+            );
+
+            // Test the deserialization output
+            let deserialized_data = AlternateTimescale::deserialize(byte_representation).unwrap();
+            assert_eq!(deserialized_data, object_representation);
+
+            // And through a TLV
+            object_representation.with_tlv(|tlv| {
+                assert_eq!(tlv.tlv_type, TlvType::AlternateTimeOffsetIndicator);
+                assert_eq!(&*tlv.value, byte_representation);
+                assert_eq!(
+                    AlternateTimescale::from_tlv(&tlv).unwrap(),
+                    object_representation
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn alternate_timescale_invalid() {
+        // Name runs past the end of the TLV
+        let data = [
+            0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x00, 0x65, 0x1d, 0x5e,
+            0x90, 0x05, b'C', b'E', b'T', 0x00,
+        ];
+        assert!(AlternateTimescale::deserialize(&data).is_err());
+
+        // Name is longer than supported
+        let mut data = [0; 28];
+        data[15] = 12;
+        data[16..28].copy_from_slice(b"ABCDEFGHIJKL");
+        assert!(AlternateTimescale::deserialize(&data).is_err());
+
+        // Name is not valid UTF-8
+        let mut data = [0; 18];
+        data[15] = 2;
+        data[16..18].copy_from_slice(&[0xc3, 0x28]);
+        assert!(AlternateTimescale::deserialize(&data).is_err());
+
+        // Too short for the fixed fields
+        assert!(AlternateTimescale::deserialize(&[0; 15]).is_err());
+    }
+}
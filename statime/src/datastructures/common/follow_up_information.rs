@@ -0,0 +1,188 @@
+use super::{Tlv, TlvType};
+use crate::datastructures::{WireFormat, WireFormatError};
+
+/// Organization identifier of IEEE 802.1 (00-80-C2)
+const IEEE_802_1_ORGANIZATION_ID: [u8; 3] = [0x00, 0x80, 0xc2];
+
+/// Organization subtype of the Follow_Up information TLV
+const FOLLOW_UP_INFORMATION_SUBTYPE: [u8; 3] = [0x00, 0x00, 0x01];
+
+/// Rate and phase information about the grandmaster, sent by IEEE 802.1AS
+/// (gPTP) masters in the Follow_Up information TLV of their follow up
+/// messages.
+///
+/// See *IEEE802.1AS-2020 section 11.4.4.3*.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub(crate) struct FollowUpInformation {
+    /// Ratio of the grandmaster frequency to the frequency of the sender,
+    /// minus one and multiplied by 2^41 (cumulativeScaledRateOffset)
+    pub(crate) cumulative_scaled_rate_offset: i32,
+    /// Identifies the time base of the grandmaster (gmTimeBaseIndicator)
+    pub(crate) gm_time_base_indicator: u16,
+    /// Phase change of the grandmaster time base at the last change of time
+    /// base, in units of 2^-16 nanoseconds. Only the lower 96 bits are
+    /// transmitted (lastGmPhaseChange)
+    pub(crate) last_gm_phase_change: i128,
+    /// Frequency change of the grandmaster time base at the last change of
+    /// time base, multiplied by 2^41 (scaledLastGmFreqChange)
+    pub(crate) scaled_last_gm_freq_change: i32,
+}
+
+impl FollowUpInformation {
+    const WIRE_SIZE: usize = 28;
+
+    /// Serialize into a Follow_Up information TLV and pass it to `f`
+    pub(crate) fn with_tlv<R>(&self, f: impl FnOnce(Tlv<'_>) -> R) -> R {
+        let mut buffer = [0; Self::WIRE_SIZE];
+        // Cannot fail as the buffer has the exact size needed
+        self.serialize(&mut buffer).unwrap();
+
+        f(Tlv {
+            tlv_type: TlvType::OrganizationExtension,
+            value: buffer[..].into(),
+        })
+    }
+
+    /// Parse a Follow_Up information TLV
+    ///
+    /// The CUMULATIVE_RATE_RATIO TLV of *IEEE1588-2019 section 16.10* carries
+    /// the same rate offset and is accepted as well.
+    pub(crate) fn from_tlv(tlv: &Tlv<'_>) -> Result<Self, WireFormatError> {
+        match tlv.tlv_type {
+            TlvType::OrganizationExtension => Self::deserialize(&tlv.value),
+            TlvType::CumulativeRateRatio => {
+                let value = tlv.value.get(0..4).ok_or(WireFormatError::BufferTooShort)?;
+
+                Ok(Self {
+                    cumulative_scaled_rate_offset: i32::from_be_bytes(value.try_into().unwrap()),
+                    ..Default::default()
+                })
+            }
+            _ => Err(WireFormatError::Invalid),
+        }
+    }
+}
+
+impl WireFormat for FollowUpInformation {
+    fn serialize(&self, buffer: &mut [u8]) -> Result<(), WireFormatError> {
+        let buffer = buffer
+            .get_mut(..Self::WIRE_SIZE)
+            .ok_or(WireFormatError::BufferTooShort)?;
+
+        buffer[0..3].copy_from_slice(&IEEE_802_1_ORGANIZATION_ID);
+        buffer[3..6].copy_from_slice(&FOLLOW_UP_INFORMATION_SUBTYPE);
+        buffer[6..10].copy_from_slice(&self.cumulative_scaled_rate_offset.to_be_bytes());
+        buffer[10..12].copy_from_slice(&self.gm_time_base_indicator.to_be_bytes());
+        buffer[12..24].copy_from_slice(&self.last_gm_phase_change.to_be_bytes()[4..16]);
+        buffer[24..28].copy_from_slice(&self.scaled_last_gm_freq_change.to_be_bytes());
+
+        Ok(())
+    }
+
+    fn deserialize(buffer: &[u8]) -> Result<Self, WireFormatError> {
+        if buffer.len() < Self::WIRE_SIZE {
+            return Err(WireFormatError::BufferTooShort);
+        }
+
+        if buffer[0..3] != IEEE_802_1_ORGANIZATION_ID
+            || buffer[3..6] != FOLLOW_UP_INFORMATION_SUBTYPE
+        {
+            return Err(WireFormatError::Invalid);
+        }
+
+        // Sign extend the 96 bit phase change
+        let mut last_gm_phase_change = [if buffer[12] & 0x80 != 0 { 0xff } else { 0 }; 16];
+        last_gm_phase_change[4..16].copy_from_slice(&buffer[12..24]);
+
+        Ok(Self {
+            cumulative_scaled_rate_offset: i32::from_be_bytes(buffer[6..10].try_into().unwrap()),
+            gm_time_base_indicator: u16::from_be_bytes(buffer[10..12].try_into().unwrap()),
+            last_gm_phase_change: i128::from_be_bytes(last_gm_phase_change),
+            scaled_last_gm_freq_change: i32::from_be_bytes(buffer[24..28].try_into().unwrap()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follow_up_information_wireformat() {
+        let representations = [
+            (
+                [
+                    0x00, 0x80, 0xc2, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00,
+                ],
+                FollowUpInformation::default(),
+            ),
+            (
+                [
+                    0x00, 0x80, 0xc2, 0x00, 0x00, 0x01, 0xff, 0xff, 0xfc, 0x18, 0x00, 0x02, 0xff,
+                    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe, 0x00, 0x00, 0x00, 0x00,
+                    0x01, 0x00,
+                ],
+                FollowUpInformation {
+                    cumulative_scaled_rate_offset: -1000,
+                    gm_time_base_indicator: 2,
+                    last_gm_phase_change: -2 << 16,
+                    scaled_last_gm_freq_change: 256,
+                },
+            ),
+        ];
+
+        for (byte_representation, object_representation) in representations {
+            // Test the serialization output
+            let mut serialization_buffer = [0xaa; 32];
+            object_representation
+                .serialize(&mut serialization_buffer)
+                .unwrap();
+            assert_eq!(serialization_buffer[..28], byte_representation);
+
+            // Test the deserialization output
+            let deserialized_data = FollowUpInformation::deserialize(&byte_representation).unwrap();
+            assert_eq!(deserialized_data, object_representation);
+
+            // And through a TLV
+            object_representation.with_tlv(|tlv| {
+                assert_eq!(tlv.tlv_type, TlvType::OrganizationExtension);
+                assert_eq!(&*tlv.value, byte_representation);
+                assert_eq!(
+                    FollowUpInformation::from_tlv(&tlv).unwrap(),
+                    object_representation
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn follow_up_information_other_tlvs() {
+        // Cumulative rate ratio from IEEE 1588
+        let tlv = Tlv {
+            tlv_type: TlvType::CumulativeRateRatio,
+            value: (&[0x00, 0x00, 0x08, 0x00][..]).into(),
+        };
+        assert_eq!(
+            FollowUpInformation::from_tlv(&tlv).unwrap(),
+            FollowUpInformation {
+                cumulative_scaled_rate_offset: 2048,
+                ..Default::default()
+            }
+        );
+
+        // Another organization
+        let mut data = [0; 28];
+        data[0..3].copy_from_slice(&[0x00, 0x1b, 0x19]);
+        data[5] = 1;
+        let tlv = Tlv {
+            tlv_type: TlvType::OrganizationExtension,
+            value: (&data[..]).into(),
+        };
+        assert!(FollowUpInformation::from_tlv(&tlv).is_err());
+
+        // Too short
+        assert!(FollowUpInformation::deserialize(&[0x00, 0x80, 0xc2, 0x00, 0x00, 0x01]).is_err());
+    }
+}
@@ -1,20 +1,28 @@
 //! Common data structures that are used throughout the protocol
 
+mod alternate_timescale;
 mod clock_accuracy;
 mod clock_identity;
 mod clock_quality;
+mod follow_up_information;
 mod leap_indicator;
 mod port_identity;
+mod power_profile;
+mod synchronization_metadata;
 mod time_interval;
 mod time_source;
 mod timestamp;
 mod tlv;
 
+pub use alternate_timescale::*;
 pub use clock_accuracy::*;
 pub use clock_identity::*;
 pub use clock_quality::*;
+pub(crate) use follow_up_information::*;
 pub use leap_indicator::*;
-pub(crate) use port_identity::*;
+pub use port_identity::*;
+pub use power_profile::*;
+pub use synchronization_metadata::*;
 pub use time_interval::*;
 pub use time_source::*;
 pub use timestamp::*;
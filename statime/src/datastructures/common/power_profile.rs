@@ -0,0 +1,225 @@
+use super::{Tlv, TlvType};
+use crate::datastructures::{WireFormat, WireFormatError};
+
+/// Organization identifier of the IEEE C37 committee (1C-12-9D)
+const IEEE_C37_238_ORGANIZATION_ID: [u8; 3] = [0x1c, 0x12, 0x9d];
+
+/// Organization subtype of the IEEE_C37_238 TLV of C37.238-2011
+const IEEE_C37_238_2011_SUBTYPE: [u8; 3] = [0x00, 0x00, 0x01];
+
+/// Organization subtype of the IEEE_C37_238 TLV of C37.238-2017
+const IEEE_C37_238_2017_SUBTYPE: [u8; 3] = [0x00, 0x00, 0x02];
+
+/// Time inaccuracy information of the IEEE C37.238 power profile, sent by
+/// masters in the IEEE_C37_238 TLV of their announce messages.
+///
+/// See *IEEE C37.238-2017 section 5.12*.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PowerProfile {
+    /// Identifier of the grandmaster within the power profile domain
+    /// (grandmasterID)
+    pub grandmaster_id: u16,
+    /// Worst case inaccuracy in nanoseconds of the time of the grandmaster
+    /// and all boundary clocks between it and the sender
+    /// (totalTimeInaccuracy). Saturates at `u32::MAX`.
+    pub total_time_inaccuracy: u32,
+}
+
+impl PowerProfile {
+    const WIRE_SIZE: usize = 18;
+
+    /// Serialize into a C37.238-2017 IEEE_C37_238 TLV and pass it to `f`
+    pub(crate) fn with_tlv<R>(&self, f: impl FnOnce(Tlv<'_>) -> R) -> R {
+        let mut buffer = [0; Self::WIRE_SIZE];
+        // Cannot fail as the buffer has the exact size needed
+        self.serialize(&mut buffer).unwrap();
+
+        f(Tlv {
+            tlv_type: TlvType::OrganizationExtension,
+            value: buffer[..].into(),
+        })
+    }
+
+    /// Parse an IEEE_C37_238 TLV
+    ///
+    /// The TLV of C37.238-2011 is accepted as well, its grandmaster and
+    /// network time inaccuracies add up to the total time inaccuracy.
+    pub(crate) fn from_tlv(tlv: &Tlv<'_>) -> Result<Self, WireFormatError> {
+        if tlv.tlv_type != TlvType::OrganizationExtension {
+            return Err(WireFormatError::Invalid);
+        }
+
+        Self::deserialize(&tlv.value)
+    }
+
+    /// Whether the TLV is an IEEE_C37_238 TLV of any version
+    pub(crate) fn is_power_profile_tlv(tlv: &Tlv<'_>) -> bool {
+        tlv.tlv_type == TlvType::OrganizationExtension
+            && tlv.value.get(0..3) == Some(&IEEE_C37_238_ORGANIZATION_ID[..])
+    }
+
+    /// The information to send after adding the time inaccuracy of this
+    /// clock
+    pub(crate) fn add_time_inaccuracy(self, time_inaccuracy: u32) -> Self {
+        Self {
+            total_time_inaccuracy: self.total_time_inaccuracy.saturating_add(time_inaccuracy),
+            ..self
+        }
+    }
+}
+
+impl WireFormat for PowerProfile {
+    fn serialize(&self, buffer: &mut [u8]) -> Result<(), WireFormatError> {
+        let buffer = buffer
+            .get_mut(..Self::WIRE_SIZE)
+            .ok_or(WireFormatError::BufferTooShort)?;
+
+        buffer[0..3].copy_from_slice(&IEEE_C37_238_ORGANIZATION_ID);
+        buffer[3..6].copy_from_slice(&IEEE_C37_238_2017_SUBTYPE);
+        buffer[6..8].copy_from_slice(&self.grandmaster_id.to_be_bytes());
+        buffer[8..12].fill(0);
+        buffer[12..16].copy_from_slice(&self.total_time_inaccuracy.to_be_bytes());
+        buffer[16..18].fill(0);
+
+        Ok(())
+    }
+
+    fn deserialize(buffer: &[u8]) -> Result<Self, WireFormatError> {
+        if buffer.len() < Self::WIRE_SIZE {
+            return Err(WireFormatError::BufferTooShort);
+        }
+
+        if buffer[0..3] != IEEE_C37_238_ORGANIZATION_ID {
+            return Err(WireFormatError::Invalid);
+        }
+
+        let grandmaster_id = u16::from_be_bytes(buffer[6..8].try_into().unwrap());
+        let first = u32::from_be_bytes(buffer[8..12].try_into().unwrap());
+        let second = u32::from_be_bytes(buffer[12..16].try_into().unwrap());
+
+        let total_time_inaccuracy = match buffer[3..6].try_into().unwrap() {
+            IEEE_C37_238_2011_SUBTYPE => first.saturating_add(second),
+            IEEE_C37_238_2017_SUBTYPE => second,
+            _ => return Err(WireFormatError::Invalid),
+        };
+
+        Ok(Self {
+            grandmaster_id,
+            total_time_inaccuracy,
+        })
+    }
+}
+
+/// Configuration of the IEEE C37.238 power profile TLV of a
+/// [`PtpInstance`](`crate::PtpInstance`)
+///
+/// See [`PtpInstance::set_power_profile`](`crate::PtpInstance::set_power_profile`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PowerProfileConfig {
+    /// Identifier of this clock when it is the grandmaster
+    pub grandmaster_id: u16,
+    /// Worst case inaccuracy in nanoseconds this clock adds to the time it
+    /// distributes. As grandmaster this includes the inaccuracy of its time
+    /// source.
+    pub time_inaccuracy: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_profile_wireformat() {
+        let representations = [
+            (
+                [
+                    0x1c, 0x12, 0x9d, 0x00, 0x00, 0x02, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    0x00, 0x00, 0xfa, 0x00, 0x00,
+                ],
+                PowerProfile {
+                    grandmaster_id: 3,
+                    total_time_inaccuracy: 250,
+                },
+            ),
+            (
+                [
+                    0x1c, 0x12, 0x9d, 0x00, 0x00, 0x02, 0x00, 0xfe, 0x00, 0x00, 0x00, 0x00, 0xff,
+                    0xff, 0xff, 0xff, 0x00, 0x00,
+                ],
+                PowerProfile {
+                    grandmaster_id: 254,
+                    total_time_inaccuracy: u32::MAX,
+                },
+            ),
+        ];
+
+        for (byte_representation, object_representation) in representations {
+            // Test the serialization output
+            let mut serialization_buffer = [0; 18];
+            object_representation
+                .serialize(&mut serialization_buffer)
+                .unwrap();
+            assert_eq!(serialization_buffer, byte_representation);
+
+            // Test the deserialization output
+            let deserialized_data = PowerProfile::deserialize(&byte_representation).unwrap();
+            assert_eq!(deserialized_data, object_representation);
+
+            // And through a TLV
+            object_representation.with_tlv(|tlv| {
+                assert!(PowerProfile::is_power_profile_tlv(&tlv));
+                assert_eq!(PowerProfile::from_tlv(&tlv).unwrap(), object_representation);
+            });
+        }
+    }
+
+    #[test]
+    fn power_profile_2011() {
+        // Grandmaster time inaccuracy of 100ns, network time inaccuracy of 50ns
+        let data = [
+            0x1c, 0x12, 0x9d, 0x00, 0x00, 0x01, 0x00, 0x03, 0x00, 0x00, 0x00, 0x64, 0x00, 0x00,
+            0x00, 0x32, 0x00, 0x00,
+        ];
+        assert_eq!(
+            PowerProfile::deserialize(&data).unwrap(),
+            PowerProfile {
+                grandmaster_id: 3,
+                total_time_inaccuracy: 150,
+            }
+        );
+    }
+
+    #[test]
+    fn power_profile_invalid() {
+        // Other organization
+        let mut data = [0; 18];
+        data[0..6].copy_from_slice(&[0x00, 0x80, 0xc2, 0x00, 0x00, 0x01]);
+        assert!(PowerProfile::deserialize(&data).is_err());
+
+        // Unknown subtype
+        data[0..6].copy_from_slice(&[0x1c, 0x12, 0x9d, 0x00, 0x00, 0x03]);
+        assert!(PowerProfile::deserialize(&data).is_err());
+
+        // Too short
+        assert!(PowerProfile::deserialize(&data[..16]).is_err());
+    }
+
+    #[test]
+    fn power_profile_add_time_inaccuracy() {
+        let power_profile = PowerProfile {
+            grandmaster_id: 3,
+            total_time_inaccuracy: 250,
+        };
+        assert_eq!(
+            power_profile.add_time_inaccuracy(50).total_time_inaccuracy,
+            300
+        );
+        assert_eq!(
+            power_profile
+                .add_time_inaccuracy(u32::MAX)
+                .total_time_inaccuracy,
+            u32::MAX
+        );
+    }
+}
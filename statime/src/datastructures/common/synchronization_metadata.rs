@@ -0,0 +1,436 @@
+use super::{Tlv, TlvType};
+use crate::datastructures::{WireFormat, WireFormatError};
+
+/// Organization identifier of SMPTE (68-97-E8)
+const SMPTE_ORGANIZATION_ID: [u8; 3] = [0x68, 0x97, 0xe8];
+
+/// Organization subtype of the synchronization metadata TLV
+const SMPTE_SYNCHRONIZATION_METADATA_SUBTYPE: [u8; 3] = [0x00, 0x00, 0x01];
+
+/// Synchronization metadata of SMPTE ST 2059-2, sent by masters in the
+/// organization extension TLV of management messages
+///
+/// Media devices use this to derive timecode from the PTP time. See *SMPTE ST
+/// 2059-2:2021 section 9*.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SynchronizationMetadata {
+    /// Numerator of the default video frame rate of the system
+    /// (defaultSystemFrameRate)
+    pub frame_rate_numerator: u32,
+    /// Denominator of the default video frame rate of the system
+    /// (defaultSystemFrameRate)
+    pub frame_rate_denominator: u32,
+    /// Complementary locking status of the grandmaster (masterLockingStatus)
+    pub master_locking_status: MasterLockingStatus,
+    /// Whether timecode uses drop frame counting (timeAddressFlags)
+    pub drop_frame: bool,
+    /// Whether color frame identification is in use (timeAddressFlags)
+    pub color_frame_identification: bool,
+    /// Offset in seconds of local time from PTP time (currentLocalOffset)
+    pub current_local_offset: i32,
+    /// Size in seconds of the next discontinuity of local time (jumpSeconds)
+    pub jump_seconds: i32,
+    /// PTP time in seconds at which the next discontinuity occurs
+    /// (timeOfNextJump). Only the lower 48 bits are sent.
+    pub time_of_next_jump: u64,
+    /// PTP time in seconds of the next daily jam (timeOfNextJam). Only the
+    /// lower 48 bits are sent.
+    pub time_of_next_jam: u64,
+    /// PTP time in seconds of the previous daily jam (timeOfPreviousJam).
+    /// Only the lower 48 bits are sent.
+    pub time_of_previous_jam: u64,
+    /// Local offset in seconds at the previous daily jam
+    /// (previousJamLocalOffset)
+    pub previous_jam_local_offset: i32,
+    /// Whether daylight saving is currently in effect (daylightSaving)
+    pub daylight_saving: bool,
+    /// Whether daylight saving is in effect after the next discontinuity
+    /// (daylightSaving)
+    pub daylight_saving_at_next_jump: bool,
+    /// Whether daylight saving was in effect at the previous daily jam
+    /// (daylightSaving)
+    pub daylight_saving_at_previous_jam: bool,
+    /// Whether the next discontinuity is caused by a leap second
+    /// (leapSecondJump)
+    pub leap_second_jump: bool,
+}
+
+/// Status of the complementary locking of the grandmaster to its reference
+///
+/// See *SMPTE ST 2059-2:2021 table 4*.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MasterLockingStatus {
+    /// Complementary locking is not in use
+    #[default]
+    NotInUse,
+    /// The grandmaster runs free
+    FreeRun,
+    /// The grandmaster is locking by jumping to the reference
+    ColdLocking,
+    /// The grandmaster is locking by slewing to the reference
+    WarmLocking,
+    /// The grandmaster is locked to the reference
+    Locked,
+}
+
+impl MasterLockingStatus {
+    fn to_primitive(self) -> u8 {
+        match self {
+            Self::NotInUse => 0,
+            Self::FreeRun => 1,
+            Self::ColdLocking => 2,
+            Self::WarmLocking => 3,
+            Self::Locked => 4,
+        }
+    }
+
+    fn from_primitive(value: u8) -> Result<Self, WireFormatError> {
+        match value {
+            0 => Ok(Self::NotInUse),
+            1 => Ok(Self::FreeRun),
+            2 => Ok(Self::ColdLocking),
+            3 => Ok(Self::WarmLocking),
+            4 => Ok(Self::Locked),
+            _ => Err(WireFormatError::EnumConversionError),
+        }
+    }
+}
+
+impl SynchronizationMetadata {
+    const WIRE_SIZE: usize = 48;
+
+    /// Serialize into a synchronization metadata TLV and pass it to `f`
+    pub(crate) fn with_tlv<R>(&self, f: impl FnOnce(Tlv<'_>) -> R) -> R {
+        let mut buffer = [0; Self::WIRE_SIZE];
+        // Cannot fail as the buffer has the exact size needed
+        self.serialize(&mut buffer).unwrap();
+
+        f(Tlv {
+            tlv_type: TlvType::OrganizationExtension,
+            value: buffer[..].into(),
+        })
+    }
+
+    /// Parse a synchronization metadata TLV
+    pub(crate) fn from_tlv(tlv: &Tlv<'_>) -> Result<Self, WireFormatError> {
+        if tlv.tlv_type != TlvType::OrganizationExtension {
+            return Err(WireFormatError::Invalid);
+        }
+
+        Self::deserialize(&tlv.value)
+    }
+}
+
+fn u48_to_be_bytes(value: u64) -> [u8; 6] {
+    value.to_be_bytes()[2..8].try_into().unwrap()
+}
+
+fn u48_from_be_bytes(bytes: &[u8]) -> u64 {
+    let mut buffer = [0; 8];
+    buffer[2..8].copy_from_slice(bytes);
+    u64::from_be_bytes(buffer)
+}
+
+impl WireFormat for SynchronizationMetadata {
+    fn serialize(&self, buffer: &mut [u8]) -> Result<(), WireFormatError> {
+        let buffer = buffer
+            .get_mut(..Self::WIRE_SIZE)
+            .ok_or(WireFormatError::BufferTooShort)?;
+
+        buffer[0..3].copy_from_slice(&SMPTE_ORGANIZATION_ID);
+        buffer[3..6].copy_from_slice(&SMPTE_SYNCHRONIZATION_METADATA_SUBTYPE);
+        buffer[6..10].copy_from_slice(&self.frame_rate_numerator.to_be_bytes());
+        buffer[10..14].copy_from_slice(&self.frame_rate_denominator.to_be_bytes());
+        buffer[14] = self.master_locking_status.to_primitive();
+        buffer[15] = self.drop_frame as u8 | (self.color_frame_identification as u8) << 1;
+        buffer[16..20].copy_from_slice(&self.current_local_offset.to_be_bytes());
+        buffer[20..24].copy_from_slice(&self.jump_seconds.to_be_bytes());
+        buffer[24..30].copy_from_slice(&u48_to_be_bytes(self.time_of_next_jump));
+        buffer[30..36].copy_from_slice(&u48_to_be_bytes(self.time_of_next_jam));
+        buffer[36..42].copy_from_slice(&u48_to_be_bytes(self.time_of_previous_jam));
+        buffer[42..46].copy_from_slice(&self.previous_jam_local_offset.to_be_bytes());
+        buffer[46] = self.daylight_saving as u8
+            | (self.daylight_saving_at_next_jump as u8) << 1
+            | (self.daylight_saving_at_previous_jam as u8) << 2;
+        buffer[47] = self.leap_second_jump as u8;
+
+        Ok(())
+    }
+
+    fn deserialize(buffer: &[u8]) -> Result<Self, WireFormatError> {
+        if buffer.len() < Self::WIRE_SIZE {
+            return Err(WireFormatError::BufferTooShort);
+        }
+
+        if buffer[0..3] != SMPTE_ORGANIZATION_ID
+            || buffer[3..6] != SMPTE_SYNCHRONIZATION_METADATA_SUBTYPE
+        {
+            return Err(WireFormatError::Invalid);
+        }
+
+        Ok(Self {
+            frame_rate_numerator: u32::from_be_bytes(buffer[6..10].try_into().unwrap()),
+            frame_rate_denominator: u32::from_be_bytes(buffer[10..14].try_into().unwrap()),
+            master_locking_status: MasterLockingStatus::from_primitive(buffer[14])?,
+            drop_frame: buffer[15] & 0b1 != 0,
+            color_frame_identification: buffer[15] & 0b10 != 0,
+            current_local_offset: i32::from_be_bytes(buffer[16..20].try_into().unwrap()),
+            jump_seconds: i32::from_be_bytes(buffer[20..24].try_into().unwrap()),
+            time_of_next_jump: u48_from_be_bytes(&buffer[24..30]),
+            time_of_next_jam: u48_from_be_bytes(&buffer[30..36]),
+            time_of_previous_jam: u48_from_be_bytes(&buffer[36..42]),
+            previous_jam_local_offset: i32::from_be_bytes(buffer[42..46].try_into().unwrap()),
+            daylight_saving: buffer[46] & 0b1 != 0,
+            daylight_saving_at_next_jump: buffer[46] & 0b10 != 0,
+            daylight_saving_at_previous_jam: buffer[46] & 0b100 != 0,
+            leap_second_jump: buffer[47] & 0b1 != 0,
+        })
+    }
+}
+
+/// Seconds in a day of local time
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Configuration of the SMPTE ST 2059-2 synchronization metadata of a
+/// [`PtpInstance`](`crate::PtpInstance`)
+///
+/// See [`PtpInstance::set_synchronization_metadata`](`crate::PtpInstance::set_synchronization_metadata`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SynchronizationMetadataConfig {
+    /// The metadata to send. Once `time_of_next_jump` has passed, the jump is
+    /// applied to the local offset and daylight saving.
+    pub metadata: SynchronizationMetadata,
+    /// Local time of day in seconds at which devices jam their timecode
+    /// daily. The times of the next and previous jam are computed from it,
+    /// otherwise those of `metadata` are sent.
+    pub daily_jam: Option<u32>,
+}
+
+impl SynchronizationMetadataConfig {
+    /// The metadata to send at PTP time `now` in seconds
+    pub(crate) fn metadata_at(&self, now: u64) -> SynchronizationMetadata {
+        let mut metadata = self.metadata;
+        let now = now as i64;
+
+        // The local offset and daylight saving before a jump that has passed
+        let mut before_jump = None;
+        if metadata.time_of_next_jump != 0 && now >= metadata.time_of_next_jump as i64 {
+            before_jump = Some((
+                metadata.time_of_next_jump as i64,
+                metadata.current_local_offset,
+                metadata.daylight_saving,
+            ));
+            metadata.current_local_offset = metadata
+                .current_local_offset
+                .saturating_add(metadata.jump_seconds);
+            metadata.daylight_saving = metadata.daylight_saving_at_next_jump;
+            metadata.jump_seconds = 0;
+            metadata.time_of_next_jump = 0;
+            metadata.leap_second_jump = false;
+        }
+
+        if let Some(daily_jam) = self.daily_jam {
+            let offset = metadata.current_local_offset;
+            let local = now + offset as i64;
+            let mut next_jam = local - local.rem_euclid(SECONDS_PER_DAY) + daily_jam as i64;
+            if next_jam <= local {
+                next_jam += SECONDS_PER_DAY;
+            }
+            let previous_jam = next_jam - SECONDS_PER_DAY;
+
+            // The previous jam used the local offset of before the jump when
+            // that was still in effect
+            let (previous_offset, previous_daylight_saving) = match before_jump {
+                Some((jump, old_offset, old_daylight_saving))
+                    if previous_jam - (old_offset as i64) < jump =>
+                {
+                    (old_offset, old_daylight_saving)
+                }
+                _ => (offset, metadata.daylight_saving),
+            };
+
+            metadata.time_of_next_jam = (next_jam - offset as i64).max(0) as u64;
+            metadata.time_of_previous_jam = (previous_jam - previous_offset as i64).max(0) as u64;
+            metadata.previous_jam_local_offset = previous_offset;
+            metadata.daylight_saving_at_previous_jam = previous_daylight_saving;
+        }
+
+        metadata
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synchronization_metadata_wireformat() {
+        let representations = [
+            (
+                [
+                    0x68, 0x97, 0xe8, 0x00, 0x00, 0x01, // organization
+                    0x00, 0x00, 0x75, 0x30, 0x00, 0x00, 0x03, 0xe9, // 30000/1001
+                    0x04, 0x01, // locked, drop frame
+                    0x00, 0x00, 0x1c, 0x20, // current local offset 7200
+                    0xff, 0xff, 0xf1, 0xf0, // jump seconds -3600
+                    0x00, 0x00, 0x67, 0x1b, 0xe3, 0x90, // time of next jump
+                    0x00, 0x00, 0x66, 0xc9, 0x7c, 0x10, // time of next jam
+                    0x00, 0x00, 0x66, 0xc8, 0x2a, 0x90, // time of previous jam
+                    0x00, 0x00, 0x1c, 0x20, // previous jam local offset 7200
+                    0x05, 0x00, // daylight saving now and at previous jam
+                ],
+                SynchronizationMetadata {
+                    frame_rate_numerator: 30000,
+                    frame_rate_denominator: 1001,
+                    master_locking_status: MasterLockingStatus::Locked,
+                    drop_frame: true,
+                    color_frame_identification: false,
+                    current_local_offset: 7200,
+                    jump_seconds: -3600,
+                    time_of_next_jump: 0x671b_e390,
+                    time_of_next_jam: 0x66c9_7c10,
+                    time_of_previous_jam: 0x66c8_2a90,
+                    previous_jam_local_offset: 7200,
+                    daylight_saving: true,
+                    daylight_saving_at_next_jump: false,
+                    daylight_saving_at_previous_jam: true,
+                    leap_second_jump: false,
+                },
+            ),
+            (
+                [
+                    0x68, 0x97, 0xe8, 0x00, 0x00, 0x01, // organization
+                    0x00, 0x00, 0x00, 0x19, 0x00, 0x00, 0x00, 0x01, // 25/1
+                    0x01, 0x02, // free run, color frame identification
+                    0x00, 0x00, 0x00, 0x00, // current local offset 0
+                    0x00, 0x00, 0x00, 0x01, // jump seconds 1
+                    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // time of next jump
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // time of next jam
+                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // time of previous jam
+                    0x00, 0x00, 0x00, 0x00, // previous jam local offset 0
+                    0x02, 0x01, // daylight saving at next jump, leap second
+                ],
+                SynchronizationMetadata {
+                    frame_rate_numerator: 25,
+                    frame_rate_denominator: 1,
+                    master_locking_status: MasterLockingStatus::FreeRun,
+                    drop_frame: false,
+                    color_frame_identification: true,
+                    current_local_offset: 0,
+                    jump_seconds: 1,
+                    time_of_next_jump: 0xffff_ffff_ffff,
+                    time_of_next_jam: 0,
+                    time_of_previous_jam: 0,
+                    previous_jam_local_offset: 0,
+                    daylight_saving: false,
+                    daylight_saving_at_next_jump: true,
+                    daylight_saving_at_previous_jam: false,
+                    leap_second_jump: true,
+                },
+            ),
+        ];
+
+        for (byte_representation, object_representation) in representations {
+            // Test the serialization output
+            let mut serialization_buffer = [0; 48];
+            object_representation
+                .serialize(&mut serialization_buffer)
+                .unwrap();
+            assert_eq!(serialization_buffer, byte_representation);
+
+            // Test the deserialization output
+            let deserialized_data =
+                SynchronizationMetadata::deserialize(&byte_representation).unwrap();
+            assert_eq!(deserialized_data, object_representation);
+
+            // And through a TLV
+            object_representation.with_tlv(|tlv| {
+                assert_eq!(
+                    SynchronizationMetadata::from_tlv(&tlv).unwrap(),
+                    object_representation
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn synchronization_metadata_invalid() {
+        let mut data = [0; 48];
+        SynchronizationMetadata::default()
+            .serialize(&mut data)
+            .unwrap();
+
+        // Unknown locking status
+        data[14] = 5;
+        assert!(SynchronizationMetadata::deserialize(&data).is_err());
+        data[14] = 0;
+
+        // Other organization
+        data[0..6].copy_from_slice(&[0x1c, 0x12, 0x9d, 0x00, 0x00, 0x01]);
+        assert!(SynchronizationMetadata::deserialize(&data).is_err());
+
+        // Unknown subtype
+        data[0..6].copy_from_slice(&[0x68, 0x97, 0xe8, 0x00, 0x00, 0x02]);
+        assert!(SynchronizationMetadata::deserialize(&data).is_err());
+
+        // Too short
+        data[0..6].copy_from_slice(&[0x68, 0x97, 0xe8, 0x00, 0x00, 0x01]);
+        assert!(SynchronizationMetadata::deserialize(&data).is_ok());
+        assert!(SynchronizationMetadata::deserialize(&data[..47]).is_err());
+    }
+
+    #[test]
+    fn synchronization_metadata_daily_jam() {
+        // 2 hours ahead of PTP time, jamming at 02:00 local time
+        let config = SynchronizationMetadataConfig {
+            metadata: SynchronizationMetadata {
+                current_local_offset: 7200,
+                jump_seconds: -3600,
+                time_of_next_jump: 10 * 86400,
+                daylight_saving: true,
+                ..Default::default()
+            },
+            daily_jam: Some(7200),
+        };
+
+        // The first jam is at PTP time 0
+        let metadata = config.metadata_at(1800);
+        assert_eq!(metadata.time_of_next_jam, 86400);
+        assert_eq!(metadata.time_of_previous_jam, 0);
+
+        // The jams advance with the days
+        let metadata = config.metadata_at(3 * 86400 + 3600);
+        assert_eq!(metadata.time_of_next_jam, 4 * 86400);
+        assert_eq!(metadata.time_of_previous_jam, 3 * 86400);
+        assert_eq!(metadata.previous_jam_local_offset, 7200);
+        assert!(metadata.daylight_saving_at_previous_jam);
+        assert_eq!(metadata.time_of_next_jump, 10 * 86400);
+
+        // After the jump the local offset changes, but the previous jam was
+        // still before it
+        let metadata = config.metadata_at(10 * 86400 + 60);
+        assert_eq!(metadata.current_local_offset, 3600);
+        assert!(!metadata.daylight_saving);
+        assert_eq!(metadata.jump_seconds, 0);
+        assert_eq!(metadata.time_of_next_jump, 0);
+        assert_eq!(metadata.time_of_next_jam, 10 * 86400 + 3600);
+        assert_eq!(metadata.time_of_previous_jam, 9 * 86400);
+        assert_eq!(metadata.previous_jam_local_offset, 7200);
+        assert!(metadata.daylight_saving_at_previous_jam);
+
+        // Until the next jam
+        let metadata = config.metadata_at(11 * 86400 + 7200);
+        assert_eq!(metadata.time_of_previous_jam, 11 * 86400 + 3600);
+        assert_eq!(metadata.previous_jam_local_offset, 3600);
+        assert!(!metadata.daylight_saving_at_previous_jam);
+
+        // Without a daily jam the configured times are sent
+        let config = SynchronizationMetadataConfig {
+            daily_jam: None,
+            ..config
+        };
+        assert_eq!(config.metadata_at(86400).time_of_next_jam, 0);
+    }
+}
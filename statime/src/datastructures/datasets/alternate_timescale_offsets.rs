@@ -0,0 +1,17 @@
+use arrayvec::ArrayVec;
+
+use crate::datastructures::common::AlternateTimescale;
+
+/// Maximum number of alternate timescales that can be distributed and
+/// received by an instance
+pub const MAX_ALTERNATE_TIMESCALES: usize = 8;
+
+/// A concrete implementation of the PTP Alternate Timescale Offsets dataset
+/// (IEEE1588-2019 section 16.3.2)
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlternateTimescaleOffsetsDS {
+    /// The alternate timescales distributed by the grandmaster, or configured
+    /// on this instance when it is the grandmaster itself.
+    pub list: ArrayVec<AlternateTimescale, MAX_ALTERNATE_TIMESCALES>,
+}
@@ -1,5 +1,5 @@
 use crate::{
-    config::InstanceConfig,
+    config::{DatasetComparison, InstanceConfig},
     datastructures::{
         common::{ClockIdentity, ClockQuality},
         messages::SdoId,
@@ -15,6 +15,7 @@ use crate::{
 /// [TimePropertiesDS](crate::TimePropertiesDS) dataset.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub(crate) struct InternalDefaultDS {
+    pub(crate) two_step_flag: bool,
     pub(crate) clock_identity: ClockIdentity,
     pub(crate) number_ports: u16,
     pub(crate) clock_quality: ClockQuality,
@@ -23,11 +24,14 @@ pub(crate) struct InternalDefaultDS {
     pub(crate) domain_number: u8,
     pub(crate) slave_only: bool,
     pub(crate) sdo_id: SdoId,
+    pub(crate) external_port_configuration: bool,
+    pub(crate) dataset_comparison: DatasetComparison,
 }
 
 impl InternalDefaultDS {
     pub(crate) fn new(config: InstanceConfig) -> Self {
         Self {
+            two_step_flag: false,
             clock_identity: config.clock_identity,
             number_ports: 0,
             clock_quality: config.clock_quality,
@@ -36,6 +40,8 @@ impl InternalDefaultDS {
             domain_number: config.domain_number,
             slave_only: config.slave_only,
             sdo_id: config.sdo_id,
+            external_port_configuration: config.external_port_configuration,
+            dataset_comparison: config.dataset_comparison,
         }
     }
 }
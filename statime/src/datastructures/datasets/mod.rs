@@ -1,9 +1,11 @@
+pub use alternate_timescale_offsets::{AlternateTimescaleOffsetsDS, MAX_ALTERNATE_TIMESCALES};
 pub(crate) use current::InternalCurrentDS;
 pub(crate) use default::InternalDefaultDS;
 pub(crate) use parent::InternalParentDS;
 pub use path_trace::PathTraceDS;
 pub use time_properties::TimePropertiesDS;
 
+mod alternate_timescale_offsets;
 mod current;
 mod default;
 mod parent;
@@ -0,0 +1,263 @@
+//! Immediate security processing with the AUTHENTICATION TLV
+//!
+//! See *IEEE1588-2019 section 16.14 and annex P*
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::Message;
+use crate::{
+    config::SecurityAssociation,
+    datastructures::{common::TlvType, WireFormatError},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Size of the fixed part of the AUTHENTICATION TLV value: the SPP,
+/// secParamIndicator and keyID
+const FIXED_VALUE_SIZE: usize = 6;
+
+/// Reasons a received message is not authentic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AuthenticationError {
+    /// The last TLV of the message is not an AUTHENTICATION TLV
+    Missing,
+    /// The TLV is for another security association
+    UnknownSpp,
+    /// The key is not part of the security association
+    UnknownKey,
+    /// The TLV uses delayed security processing or optional fields
+    Unsupported,
+    /// The integrity check value does not match the message
+    InvalidIcv,
+}
+
+/// Append an AUTHENTICATION TLV for the message of `length` bytes at the start
+/// of `buffer`, authenticating it with the send key of `security_association`
+///
+/// Returns the length of the authenticated message.
+pub(crate) fn append_authentication_tlv(
+    buffer: &mut [u8],
+    length: usize,
+    security_association: &SecurityAssociation,
+) -> Result<usize, WireFormatError> {
+    let key = security_association
+        .key(security_association.send_key_id)
+        .ok_or(WireFormatError::Invalid)?;
+
+    let value_length = FIXED_VALUE_SIZE + key.icv_length;
+    let icv_start = length + 4 + FIXED_VALUE_SIZE;
+    let total_length = icv_start + key.icv_length;
+    let buffer = buffer
+        .get_mut(..total_length)
+        .ok_or(WireFormatError::BufferTooShort)?;
+
+    buffer[length..length + 2]
+        .copy_from_slice(&TlvType::Authentication.to_primitive().to_be_bytes());
+    buffer[length + 2..length + 4].copy_from_slice(&(value_length as u16).to_be_bytes());
+    buffer[length + 4] = security_association.spp;
+    // Immediate security processing without sequence number or RES
+    buffer[length + 5] = 0;
+    buffer[length + 6..icv_start].copy_from_slice(&key.id.to_be_bytes());
+
+    // The message length covers the ICV, so update it before computing the ICV
+    buffer[2..4].copy_from_slice(&(total_length as u16).to_be_bytes());
+
+    let mut mac = HmacSha256::new_from_slice(&key.key).map_err(|_| WireFormatError::Invalid)?;
+    mac.update(&buffer[..icv_start]);
+    let icv = mac.finalize().into_bytes();
+    buffer[icv_start..].copy_from_slice(icv.get(..key.icv_length).ok_or(WireFormatError::Invalid)?);
+
+    Ok(total_length)
+}
+
+/// Check that `message`, parsed from `data`, ends with an AUTHENTICATION TLV
+/// with a valid integrity check value for `security_association`
+pub(crate) fn verify_authentication(
+    data: &[u8],
+    message: &Message<'_>,
+    security_association: &SecurityAssociation,
+) -> Result<(), AuthenticationError> {
+    let tlv = message
+        .suffix
+        .tlv()
+        .last()
+        .filter(|tlv| tlv.tlv_type == TlvType::Authentication)
+        .ok_or(AuthenticationError::Missing)?;
+
+    let value = &tlv.value;
+    if value.len() < FIXED_VALUE_SIZE {
+        return Err(AuthenticationError::Missing);
+    }
+
+    if value[0] != security_association.spp {
+        return Err(AuthenticationError::UnknownSpp);
+    }
+
+    if value[1] != 0 {
+        return Err(AuthenticationError::Unsupported);
+    }
+
+    let key_id = u32::from_be_bytes(value[2..6].try_into().unwrap());
+    let key = security_association
+        .key(key_id)
+        .ok_or(AuthenticationError::UnknownKey)?;
+
+    let icv = &value[FIXED_VALUE_SIZE..];
+    if icv.len() != key.icv_length {
+        return Err(AuthenticationError::InvalidIcv);
+    }
+
+    // Parsing the message succeeded, so data contains the entire message
+    let message_length = u16::from_be_bytes([data[2], data[3]]) as usize;
+
+    let mut mac =
+        HmacSha256::new_from_slice(&key.key).map_err(|_| AuthenticationError::UnknownKey)?;
+    mac.update(&data[..message_length - icv.len()]);
+    mac.verify_truncated_left(icv)
+        .map_err(|_| AuthenticationError::InvalidIcv)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrayvec::ArrayVec;
+
+    use super::*;
+    use crate::{
+        config::{ClockIdentity, InstanceConfig, SecurityKey},
+        datastructures::{datasets::InternalDefaultDS, messages::MAX_DATA_LEN},
+    };
+
+    fn security_association(icv_length: usize) -> SecurityAssociation {
+        let mut keys = ArrayVec::new();
+        keys.push(SecurityKey {
+            id: 1,
+            key: b"0123456789abcdef0123456789abcdef"
+                .iter()
+                .copied()
+                .collect(),
+            icv_length,
+        });
+        keys.push(SecurityKey {
+            id: 2,
+            key: b"another key".iter().copied().collect(),
+            icv_length: 32,
+        });
+
+        SecurityAssociation {
+            spp: 3,
+            keys,
+            send_key_id: 1,
+        }
+    }
+
+    fn sync_message() -> Message<'static> {
+        let default_ds = InternalDefaultDS::new(InstanceConfig {
+            clock_identity: ClockIdentity([1, 2, 3, 4, 5, 6, 7, 8]),
+            priority_1: 128,
+            priority_2: 128,
+            domain_number: 0,
+            slave_only: false,
+            sdo_id: Default::default(),
+            path_trace: false,
+            external_port_configuration: false,
+            clock_quality: Default::default(),
+            management_set_policy: Default::default(),
+            dataset_comparison: Default::default(),
+        });
+        Message::sync(&default_ds, Default::default(), 7, 1)
+    }
+
+    fn authenticated_sync(buffer: &mut [u8], security_association: &SecurityAssociation) -> usize {
+        let message = sync_message();
+        let length = message.serialize(buffer).unwrap();
+        append_authentication_tlv(buffer, length, security_association).unwrap()
+    }
+
+    #[test]
+    fn test_append_authentication_tlv() {
+        let security_association = security_association(16);
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = authenticated_sync(&mut buffer, &security_association);
+
+        // Sync message, followed by the TLV with a 16 byte ICV
+        assert_eq!(length, 44 + 4 + 6 + 16);
+        assert_eq!(u16::from_be_bytes([buffer[2], buffer[3]]) as usize, length);
+        assert_eq!(buffer[44..54], [0x80, 0x09, 0, 22, 3, 0, 0, 0, 0, 1]);
+
+        let mut mac = HmacSha256::new_from_slice(b"0123456789abcdef0123456789abcdef").unwrap();
+        mac.update(&buffer[..54]);
+        assert_eq!(buffer[54..70], mac.finalize().into_bytes()[..16]);
+
+        let message = Message::deserialize(&buffer[..length]).unwrap();
+        assert_eq!(
+            verify_authentication(&buffer[..length], &message, &security_association),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_verify_authentication() {
+        let security_association = security_association(32);
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = authenticated_sync(&mut buffer, &security_association);
+
+        // Trailing padding is ignored
+        let message = Message::deserialize(&buffer[..length + 4]).unwrap();
+        assert_eq!(
+            verify_authentication(&buffer[..length + 4], &message, &security_association),
+            Ok(())
+        );
+
+        // Modified message
+        let mut modified = buffer;
+        modified[10] ^= 1;
+        let message = Message::deserialize(&modified[..length]).unwrap();
+        assert_eq!(
+            verify_authentication(&modified[..length], &message, &security_association),
+            Err(AuthenticationError::InvalidIcv)
+        );
+
+        // Other key
+        let mut modified = buffer;
+        modified[53] = 2;
+        let message = Message::deserialize(&modified[..length]).unwrap();
+        assert_eq!(
+            verify_authentication(&modified[..length], &message, &security_association),
+            Err(AuthenticationError::InvalidIcv)
+        );
+        modified[53] = 4;
+        let message = Message::deserialize(&modified[..length]).unwrap();
+        assert_eq!(
+            verify_authentication(&modified[..length], &message, &security_association),
+            Err(AuthenticationError::UnknownKey)
+        );
+
+        // Other security association
+        let mut modified = buffer;
+        modified[48] = 4;
+        let message = Message::deserialize(&modified[..length]).unwrap();
+        assert_eq!(
+            verify_authentication(&modified[..length], &message, &security_association),
+            Err(AuthenticationError::UnknownSpp)
+        );
+
+        // Delayed security processing
+        let mut modified = buffer;
+        modified[49] = 1;
+        let message = Message::deserialize(&modified[..length]).unwrap();
+        assert_eq!(
+            verify_authentication(&modified[..length], &message, &security_association),
+            Err(AuthenticationError::Unsupported)
+        );
+
+        // No TLV at all
+        let message = sync_message();
+        let length = message.serialize(&mut buffer).unwrap();
+        let message = Message::deserialize(&buffer[..length]).unwrap();
+        assert_eq!(
+            verify_authentication(&buffer[..length], &message, &security_association),
+            Err(AuthenticationError::Missing)
+        );
+    }
+}
@@ -1,11 +1,23 @@
-use crate::datastructures::{common::PortIdentity, WireFormat, WireFormatError};
+use crate::{
+    datastructures::{
+        common::{PortIdentity, TimeInterval},
+        datasets::TimePropertiesDS,
+        WireFormat, WireFormatError,
+    },
+    observability::{
+        current::CurrentDS,
+        default::DefaultDS,
+        parent::ParentDS,
+        port::{DelayMechanism, PortDS},
+    },
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct ManagementMessage {
-    pub(super) target_port_identity: PortIdentity,
-    pub(super) starting_boundary_hops: u8,
-    pub(super) boundary_hops: u8,
-    pub(super) action: ManagementAction,
+    pub(crate) target_port_identity: PortIdentity,
+    pub(crate) starting_boundary_hops: u8,
+    pub(crate) boundary_hops: u8,
+    pub(crate) action: ManagementAction,
 }
 
 impl ManagementMessage {
@@ -17,10 +29,15 @@ impl ManagementMessage {
         &self,
         buffer: &mut [u8],
     ) -> Result<(), crate::datastructures::WireFormatError> {
+        if buffer.len() < 14 {
+            return Err(WireFormatError::BufferTooShort);
+        }
+
         self.target_port_identity.serialize(&mut buffer[0..10])?;
-        buffer[11] = self.starting_boundary_hops;
-        buffer[12] = self.boundary_hops;
-        buffer[13] = self.action.to_primitive();
+        buffer[10] = self.starting_boundary_hops;
+        buffer[11] = self.boundary_hops;
+        buffer[12] = self.action.to_primitive();
+        buffer[13] = 0;
 
         Ok(())
     }
@@ -33,9 +50,9 @@ impl ManagementMessage {
         }
         Ok(Self {
             target_port_identity: PortIdentity::deserialize(&buffer[0..10])?,
-            starting_boundary_hops: buffer[11],
-            boundary_hops: buffer[12],
-            action: ManagementAction::from_primitive(buffer[13]),
+            starting_boundary_hops: buffer[10],
+            boundary_hops: buffer[11],
+            action: ManagementAction::from_primitive(buffer[12] & 0x0f),
         })
     }
 }
@@ -74,4 +91,567 @@ impl ManagementAction {
             0x5..=u8::MAX => Self::Reserved,
         }
     }
+
+    /// The action used in the reply to a message with this action, if any.
+    pub fn reply(self) -> Option<Self> {
+        match self {
+            Self::GET | Self::SET => Some(Self::RESPONSE),
+            Self::COMMAND => Some(Self::ACKNOWLEDGE),
+            Self::RESPONSE | Self::ACKNOWLEDGE | Self::Reserved => None,
+        }
+    }
+}
+
+/// See: 15.5.2.3 / Table 59
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ManagementId {
+    NullPtpManagement,
+    ClockDescription,
+    UserDescription,
+    SaveInNonVolatileStorage,
+    ResetNonVolatileStorage,
+    Initialize,
+    FaultLog,
+    FaultLogReset,
+    DefaultDataSet,
+    CurrentDataSet,
+    ParentDataSet,
+    TimePropertiesDataSet,
+    PortDataSet,
+    Priority1,
+    Priority2,
+    Domain,
+    SlaveOnly,
+    LogAnnounceInterval,
+    AnnounceReceiptTimeout,
+    LogSyncInterval,
+    VersionNumber,
+    EnablePort,
+    DisablePort,
+    Time,
+    ClockAccuracy,
+    UtcProperties,
+    TraceabilityProperties,
+    TimescaleProperties,
+    Other(u16),
+}
+
+impl ManagementId {
+    pub fn to_primitive(self) -> u16 {
+        match self {
+            Self::NullPtpManagement => 0x0000,
+            Self::ClockDescription => 0x0001,
+            Self::UserDescription => 0x0002,
+            Self::SaveInNonVolatileStorage => 0x0003,
+            Self::ResetNonVolatileStorage => 0x0004,
+            Self::Initialize => 0x0005,
+            Self::FaultLog => 0x0006,
+            Self::FaultLogReset => 0x0007,
+            Self::DefaultDataSet => 0x2000,
+            Self::CurrentDataSet => 0x2001,
+            Self::ParentDataSet => 0x2002,
+            Self::TimePropertiesDataSet => 0x2003,
+            Self::PortDataSet => 0x2004,
+            Self::Priority1 => 0x2005,
+            Self::Priority2 => 0x2006,
+            Self::Domain => 0x2007,
+            Self::SlaveOnly => 0x2008,
+            Self::LogAnnounceInterval => 0x2009,
+            Self::AnnounceReceiptTimeout => 0x200a,
+            Self::LogSyncInterval => 0x200b,
+            Self::VersionNumber => 0x200c,
+            Self::EnablePort => 0x200d,
+            Self::DisablePort => 0x200e,
+            Self::Time => 0x200f,
+            Self::ClockAccuracy => 0x2010,
+            Self::UtcProperties => 0x2011,
+            Self::TraceabilityProperties => 0x2012,
+            Self::TimescaleProperties => 0x2013,
+            Self::Other(value) => value,
+        }
+    }
+
+    pub fn from_primitive(value: u16) -> Self {
+        match value {
+            0x0000 => Self::NullPtpManagement,
+            0x0001 => Self::ClockDescription,
+            0x0002 => Self::UserDescription,
+            0x0003 => Self::SaveInNonVolatileStorage,
+            0x0004 => Self::ResetNonVolatileStorage,
+            0x0005 => Self::Initialize,
+            0x0006 => Self::FaultLog,
+            0x0007 => Self::FaultLogReset,
+            0x2000 => Self::DefaultDataSet,
+            0x2001 => Self::CurrentDataSet,
+            0x2002 => Self::ParentDataSet,
+            0x2003 => Self::TimePropertiesDataSet,
+            0x2004 => Self::PortDataSet,
+            0x2005 => Self::Priority1,
+            0x2006 => Self::Priority2,
+            0x2007 => Self::Domain,
+            0x2008 => Self::SlaveOnly,
+            0x2009 => Self::LogAnnounceInterval,
+            0x200a => Self::AnnounceReceiptTimeout,
+            0x200b => Self::LogSyncInterval,
+            0x200c => Self::VersionNumber,
+            0x200d => Self::EnablePort,
+            0x200e => Self::DisablePort,
+            0x200f => Self::Time,
+            0x2010 => Self::ClockAccuracy,
+            0x2011 => Self::UtcProperties,
+            0x2012 => Self::TraceabilityProperties,
+            0x2013 => Self::TimescaleProperties,
+            _ => Self::Other(value),
+        }
+    }
+
+    /// Whether the id is defined by IEEE 1588-2019 (as opposed to reserved or
+    /// implementation specific ids we know nothing about)
+    pub fn is_known(self) -> bool {
+        !matches!(self, Self::Other(_))
+    }
+}
+
+/// See: 15.5.4.1.4 / Table 109
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ManagementErrorId {
+    ResponseTooBig,
+    NoSuchId,
+    WrongLength,
+    WrongValue,
+    NotSetable,
+    NotSupported,
+    GeneralError,
+    Reserved(u16),
+}
+
+impl ManagementErrorId {
+    pub fn to_primitive(self) -> u16 {
+        match self {
+            Self::ResponseTooBig => 0x0001,
+            Self::NoSuchId => 0x0002,
+            Self::WrongLength => 0x0003,
+            Self::WrongValue => 0x0004,
+            Self::NotSetable => 0x0005,
+            Self::NotSupported => 0x0006,
+            Self::GeneralError => 0xfffe,
+            Self::Reserved(value) => value,
+        }
+    }
+
+    pub fn from_primitive(value: u16) -> Self {
+        match value {
+            0x0001 => Self::ResponseTooBig,
+            0x0002 => Self::NoSuchId,
+            0x0003 => Self::WrongLength,
+            0x0004 => Self::WrongValue,
+            0x0005 => Self::NotSetable,
+            0x0006 => Self::NotSupported,
+            0xfffe => Self::GeneralError,
+            _ => Self::Reserved(value),
+        }
+    }
+}
+
+/// Value of a MANAGEMENT TLV, see 15.5.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ManagementTlv<'a> {
+    pub(crate) management_id: ManagementId,
+    pub(crate) data: &'a [u8],
+}
+
+impl<'a> ManagementTlv<'a> {
+    pub(crate) fn wire_size(&self) -> usize {
+        2 + self.data.len()
+    }
+
+    pub(crate) fn serialize(&self, buffer: &mut [u8]) -> Result<usize, WireFormatError> {
+        let buffer = buffer
+            .get_mut(..self.wire_size())
+            .ok_or(WireFormatError::BufferTooShort)?;
+        buffer[0..2].copy_from_slice(&self.management_id.to_primitive().to_be_bytes());
+        buffer[2..].copy_from_slice(self.data);
+        Ok(self.wire_size())
+    }
+
+    pub(crate) fn deserialize(buffer: &'a [u8]) -> Result<Self, WireFormatError> {
+        if buffer.len() < 2 {
+            return Err(WireFormatError::BufferTooShort);
+        }
+
+        Ok(Self {
+            management_id: ManagementId::from_primitive(u16::from_be_bytes([buffer[0], buffer[1]])),
+            data: &buffer[2..],
+        })
+    }
+}
+
+/// Value of a MANAGEMENT_ERROR_STATUS TLV, see 15.5.4
+///
+/// The optional displayData is never sent by us and ignored on reception.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ManagementErrorStatusTlv {
+    pub(crate) error_id: ManagementErrorId,
+    pub(crate) management_id: ManagementId,
+}
+
+impl ManagementErrorStatusTlv {
+    pub(crate) fn wire_size(&self) -> usize {
+        8
+    }
+
+    pub(crate) fn serialize(&self, buffer: &mut [u8]) -> Result<usize, WireFormatError> {
+        let buffer = buffer
+            .get_mut(..self.wire_size())
+            .ok_or(WireFormatError::BufferTooShort)?;
+        buffer[0..2].copy_from_slice(&self.error_id.to_primitive().to_be_bytes());
+        buffer[2..4].copy_from_slice(&self.management_id.to_primitive().to_be_bytes());
+        buffer[4..8].fill(0);
+        Ok(self.wire_size())
+    }
+
+    #[allow(unused)]
+    pub(crate) fn deserialize(buffer: &[u8]) -> Result<Self, WireFormatError> {
+        if buffer.len() < 8 {
+            return Err(WireFormatError::BufferTooShort);
+        }
+
+        Ok(Self {
+            error_id: ManagementErrorId::from_primitive(u16::from_be_bytes([buffer[0], buffer[1]])),
+            management_id: ManagementId::from_primitive(u16::from_be_bytes([buffer[2], buffer[3]])),
+        })
+    }
+}
+
+/// Typed dataField of a MANAGEMENT TLV, see 15.5.3
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ManagementData {
+    Null,
+    DefaultDataSet(DefaultDS),
+    CurrentDataSet(CurrentDS),
+    ParentDataSet(ParentDS),
+    TimePropertiesDataSet(TimePropertiesDS),
+    PortDataSet(PortDS),
+    Priority1(u8),
+    Priority2(u8),
+    Domain(u8),
+    SlaveOnly(bool),
+}
+
+impl ManagementData {
+    pub(crate) fn management_id(&self) -> ManagementId {
+        match self {
+            Self::Null => ManagementId::NullPtpManagement,
+            Self::DefaultDataSet(_) => ManagementId::DefaultDataSet,
+            Self::CurrentDataSet(_) => ManagementId::CurrentDataSet,
+            Self::ParentDataSet(_) => ManagementId::ParentDataSet,
+            Self::TimePropertiesDataSet(_) => ManagementId::TimePropertiesDataSet,
+            Self::PortDataSet(_) => ManagementId::PortDataSet,
+            Self::Priority1(_) => ManagementId::Priority1,
+            Self::Priority2(_) => ManagementId::Priority2,
+            Self::Domain(_) => ManagementId::Domain,
+            Self::SlaveOnly(_) => ManagementId::SlaveOnly,
+        }
+    }
+
+    pub(crate) fn wire_size(&self) -> usize {
+        match self {
+            Self::Null => 0,
+            Self::DefaultDataSet(_) => 20,
+            Self::CurrentDataSet(_) => 18,
+            Self::ParentDataSet(_) => 32,
+            Self::TimePropertiesDataSet(_) => 4,
+            Self::PortDataSet(_) => 26,
+            Self::Priority1(_) | Self::Priority2(_) | Self::Domain(_) | Self::SlaveOnly(_) => 2,
+        }
+    }
+
+    pub(crate) fn serialize(&self, buffer: &mut [u8]) -> Result<usize, WireFormatError> {
+        let buffer = buffer
+            .get_mut(..self.wire_size())
+            .ok_or(WireFormatError::BufferTooShort)?;
+        buffer.fill(0);
+
+        match self {
+            Self::Null => {}
+            Self::DefaultDataSet(ds) => {
+                buffer[0] = u8::from(ds.two_step_flag) | if ds.slave_only { 0b10 } else { 0 };
+                buffer[2..4].copy_from_slice(&ds.number_ports.to_be_bytes());
+                buffer[4] = ds.priority_1;
+                ds.clock_quality.serialize(&mut buffer[5..9])?;
+                buffer[9] = ds.priority_2;
+                ds.clock_identity.serialize(&mut buffer[10..18])?;
+                buffer[18] = ds.domain_number;
+            }
+            Self::CurrentDataSet(ds) => {
+                buffer[0..2].copy_from_slice(&ds.steps_removed.to_be_bytes());
+                TimeInterval::from(ds.offset_from_master).serialize(&mut buffer[2..10])?;
+                TimeInterval::from(ds.mean_delay).serialize(&mut buffer[10..18])?;
+            }
+            Self::ParentDataSet(ds) => {
+                ds.parent_port_identity.serialize(&mut buffer[0..10])?;
+                // Parent statistics are not computed, so we report the
+                // default values of 8.2.3.4 and 8.2.3.5
+                buffer[12..14].copy_from_slice(&0xffffu16.to_be_bytes());
+                buffer[14..18].copy_from_slice(&0x7fffffffi32.to_be_bytes());
+                buffer[18] = ds.grandmaster_priority_1;
+                ds.grandmaster_clock_quality
+                    .serialize(&mut buffer[19..23])?;
+                buffer[23] = ds.grandmaster_priority_2;
+                ds.grandmaster_identity.serialize(&mut buffer[24..32])?;
+            }
+            Self::TimePropertiesDataSet(ds) => {
+                buffer[0..2]
+                    .copy_from_slice(&ds.current_utc_offset.unwrap_or_default().to_be_bytes());
+                buffer[2] = Self::time_properties_flags(ds);
+                buffer[3] = ds.time_source.to_primitive();
+            }
+            Self::PortDataSet(ds) => {
+                ds.port_identity.serialize(&mut buffer[0..10])?;
+                buffer[10] = ds.port_state as u8;
+                let (log_min_delay_req, peer_mean_delay, mechanism, log_min_pdelay_req) =
+                    match ds.delay_mechanism {
+                        DelayMechanism::E2E {
+                            log_min_delay_req_interval,
+                        } => (
+                            log_min_delay_req_interval,
+                            TimeInterval::default(),
+                            0x01,
+                            0x7f,
+                        ),
+                        DelayMechanism::P2P {
+                            log_min_p_delay_req_interval,
+                            mean_link_delay,
+                        } => (0x7f, mean_link_delay, 0x02, log_min_p_delay_req_interval),
+                        DelayMechanism::CommonP2P { mean_link_delay } => {
+                            (0x7f, mean_link_delay, 0x03, 0x7f)
+                        }
+                        DelayMechanism::Special => (0x7f, TimeInterval::default(), 0x04, 0x7f),
+                        DelayMechanism::NoMechanism => (0x7f, TimeInterval::default(), 0xfe, 0x7f),
+                    };
+                buffer[11] = log_min_delay_req as u8;
+                peer_mean_delay.serialize(&mut buffer[12..20])?;
+                buffer[20] = ds.log_announce_interval as u8;
+                buffer[21] = ds.announce_receipt_timeout;
+                buffer[22] = ds.log_sync_interval as u8;
+                buffer[23] = mechanism;
+                buffer[24] = log_min_pdelay_req as u8;
+                buffer[25] = (ds.minor_version_number << 4) | (ds.version_number & 0x0f);
+            }
+            Self::Priority1(value) | Self::Priority2(value) | Self::Domain(value) => {
+                buffer[0] = *value;
+            }
+            Self::SlaveOnly(value) => {
+                buffer[0] = *value as u8;
+            }
+        }
+
+        Ok(self.wire_size())
+    }
+
+    /// Parse the dataField of a SET request
+    ///
+    /// Only the ids that can be written are supported, for other ids `None` is
+    /// returned.
+    pub(crate) fn deserialize_settable(
+        management_id: ManagementId,
+        buffer: &[u8],
+    ) -> Option<Result<Self, WireFormatError>> {
+        let constructor: fn(u8) -> Self = match management_id {
+            ManagementId::Priority1 => Self::Priority1,
+            ManagementId::Priority2 => Self::Priority2,
+            ManagementId::Domain => Self::Domain,
+            ManagementId::SlaveOnly => |value| Self::SlaveOnly(value & 1 != 0),
+            _ => return None,
+        };
+
+        Some(match buffer {
+            [value, _] => Ok(constructor(*value)),
+            _ => Err(WireFormatError::Invalid),
+        })
+    }
+
+    fn time_properties_flags(ds: &TimePropertiesDS) -> u8 {
+        use crate::config::LeapIndicator;
+
+        let mut flags = 0;
+        if ds.leap_indicator == LeapIndicator::Leap61 {
+            flags |= 1 << 0;
+        }
+        if ds.leap_indicator == LeapIndicator::Leap59 {
+            flags |= 1 << 1;
+        }
+        if ds.current_utc_offset.is_some() {
+            flags |= 1 << 2;
+        }
+        if ds.ptp_timescale {
+            flags |= 1 << 3;
+        }
+        if ds.time_traceable {
+            flags |= 1 << 4;
+        }
+        if ds.frequency_traceable {
+            flags |= 1 << 5;
+        }
+        flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{ClockAccuracy, ClockIdentity, ClockQuality, LeapIndicator, SdoId, TimeSource},
+        observability::port::PortState,
+    };
+
+    #[test]
+    fn management_message_wireformat() {
+        let representation = [
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x00, 0x01, 0x05, 0x03, 0x01, 0x00u8,
+        ];
+        let object = ManagementMessage {
+            target_port_identity: PortIdentity {
+                clock_identity: ClockIdentity([1, 2, 3, 4, 5, 6, 7, 8]),
+                port_number: 1,
+            },
+            starting_boundary_hops: 5,
+            boundary_hops: 3,
+            action: ManagementAction::SET,
+        };
+
+        let mut buffer = [0; 14];
+        object.serialize_content(&mut buffer).unwrap();
+        assert_eq!(buffer, representation);
+
+        let deserialized = ManagementMessage::deserialize_content(&representation).unwrap();
+        assert_eq!(deserialized, object);
+    }
+
+    #[test]
+    fn management_tlv_wireformat() {
+        let representation = [0x20, 0x05, 0x80, 0x00];
+        let tlv = ManagementTlv::deserialize(&representation).unwrap();
+        assert_eq!(tlv.management_id, ManagementId::Priority1);
+        assert_eq!(tlv.data, &[0x80, 0x00]);
+
+        let mut buffer = [0; 4];
+        assert_eq!(tlv.serialize(&mut buffer).unwrap(), 4);
+        assert_eq!(buffer, representation);
+
+        assert!(ManagementTlv::deserialize(&[0x20]).is_err());
+    }
+
+    #[test]
+    fn management_error_status_wireformat() {
+        let representation = [0x00, 0x05, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let object = ManagementErrorStatusTlv {
+            error_id: ManagementErrorId::NotSetable,
+            management_id: ManagementId::DefaultDataSet,
+        };
+
+        let mut buffer = [0xff; 8];
+        object.serialize(&mut buffer).unwrap();
+        assert_eq!(buffer, representation);
+
+        assert_eq!(
+            ManagementErrorStatusTlv::deserialize(&representation).unwrap(),
+            object
+        );
+    }
+
+    #[test]
+    fn default_data_set_wireformat() {
+        let data = ManagementData::DefaultDataSet(DefaultDS {
+            two_step_flag: true,
+            clock_identity: ClockIdentity([1, 2, 3, 4, 5, 6, 7, 8]),
+            number_ports: 2,
+            clock_quality: ClockQuality {
+                clock_class: 248,
+                clock_accuracy: ClockAccuracy::Unknown,
+                offset_scaled_log_variance: 0xffff,
+            },
+            priority_1: 128,
+            priority_2: 127,
+            domain_number: 4,
+            slave_only: true,
+            sdo_id: SdoId::default(),
+            external_port_configuration: false,
+        });
+
+        let mut buffer = [0; 20];
+        assert_eq!(data.serialize(&mut buffer).unwrap(), 20);
+        assert_eq!(
+            buffer,
+            [
+                0x03, 0x00, 0x00, 0x02, 0x80, 0xf8, 0xfe, 0xff, 0xff, 0x7f, 0x01, 0x02, 0x03, 0x04,
+                0x05, 0x06, 0x07, 0x08, 0x04, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn time_properties_data_set_wireformat() {
+        let data = ManagementData::TimePropertiesDataSet(TimePropertiesDS::new_ptp_time(
+            Some(37),
+            LeapIndicator::Leap61,
+            true,
+            false,
+            TimeSource::Gnss,
+        ));
+
+        let mut buffer = [0; 4];
+        assert_eq!(data.serialize(&mut buffer).unwrap(), 4);
+        assert_eq!(buffer, [0x00, 0x25, 0b0001_1101, 0x20]);
+    }
+
+    #[test]
+    fn port_data_set_wireformat() {
+        let data = ManagementData::PortDataSet(PortDS {
+            port_identity: PortIdentity {
+                clock_identity: ClockIdentity([1, 2, 3, 4, 5, 6, 7, 8]),
+                port_number: 1,
+            },
+            port_state: PortState::Slave,
+            log_announce_interval: 1,
+            announce_receipt_timeout: 3,
+            log_sync_interval: -3,
+            delay_mechanism: DelayMechanism::E2E {
+                log_min_delay_req_interval: 0,
+            },
+            version_number: 2,
+            minor_version_number: 1,
+            delay_asymmetry: TimeInterval::default(),
+            master_only: false,
+        });
+
+        let mut buffer = [0; 26];
+        assert_eq!(data.serialize(&mut buffer).unwrap(), 26);
+        assert_eq!(
+            buffer,
+            [
+                0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x00, 0x01, 0x09, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0xfd, 0x01, 0x7f, 0x12
+            ]
+        );
+    }
+
+    #[test]
+    fn settable_data_parsing() {
+        assert!(matches!(
+            ManagementData::deserialize_settable(ManagementId::Priority2, &[12, 0]),
+            Some(Ok(ManagementData::Priority2(12)))
+        ));
+        assert!(matches!(
+            ManagementData::deserialize_settable(ManagementId::SlaveOnly, &[1, 0]),
+            Some(Ok(ManagementData::SlaveOnly(true)))
+        ));
+        assert!(matches!(
+            ManagementData::deserialize_settable(ManagementId::Domain, &[1]),
+            Some(Err(_))
+        ));
+        assert!(
+            ManagementData::deserialize_settable(ManagementId::DefaultDataSet, &[0; 20]).is_none()
+        );
+    }
 }
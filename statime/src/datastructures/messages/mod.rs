@@ -1,28 +1,31 @@
 //! Ptp network messages
 
 pub(crate) use announce::*;
+pub(crate) use authentication::*;
 pub(crate) use delay_req::*;
 pub(crate) use delay_resp::*;
 pub(crate) use follow_up::*;
 pub use header::*;
+pub(crate) use management::*;
 pub(crate) use p_delay_req::*;
 pub(crate) use p_delay_resp::*;
 pub(crate) use p_delay_resp_follow_up::*;
+pub(crate) use signalling::*;
 pub(crate) use sync::*;
 
-use self::{management::ManagementMessage, signalling::SignalingMessage};
 use super::{
-    common::{PortIdentity, TimeInterval, TlvSet, WireTimestamp},
+    common::{ClockIdentity, PortIdentity, TimeInterval, TlvSet, WireTimestamp},
     datasets::InternalDefaultDS,
     WireFormatError,
 };
 use crate::{
-    config::LeapIndicator,
+    config::{LeapIndicator, SecurityAssociation},
     ptp_instance::PtpInstanceState,
     time::{Interval, Time},
 };
 
 mod announce;
+mod authentication;
 mod control_field;
 mod delay_req;
 mod delay_resp;
@@ -246,6 +249,13 @@ pub fn is_compatible(buffer: &[u8]) -> bool {
     (buffer.len() >= 2) && (buffer[1] & 0xf) == 2
 }
 
+/// Sdo id and domain number of the message in `buffer`, without parsing the
+/// rest of the message
+pub(crate) fn message_domain(buffer: &[u8]) -> Option<(SdoId, u8)> {
+    let sdo_id = ((*buffer.first()? as u16 & 0xf0) << 4) | *buffer.get(5)? as u16;
+    Some((SdoId::try_from(sdo_id).ok()?, *buffer.get(4)?))
+}
+
 impl Message<'_> {
     pub(crate) fn sync(
         default_ds: &InternalDefaultDS,
@@ -267,6 +277,27 @@ impl Message<'_> {
         }
     }
 
+    pub(crate) fn one_step_sync(
+        default_ds: &InternalDefaultDS,
+        port_identity: PortIdentity,
+        sequence_id: u16,
+        timestamp: Time,
+        minor_ptp_version: u8,
+    ) -> Self {
+        let header = Header {
+            correction_field: timestamp.subnano(),
+            ..base_header(default_ds, port_identity, sequence_id, minor_ptp_version)
+        };
+
+        Message {
+            header,
+            body: MessageBody::Sync(SyncMessage {
+                origin_timestamp: timestamp.into(),
+            }),
+            suffix: TlvSet::default(),
+        }
+    }
+
     pub(crate) fn follow_up(
         default_ds: &InternalDefaultDS,
         port_identity: PortIdentity,
@@ -444,6 +475,95 @@ impl Message<'_> {
 }
 
 impl<'a> Message<'a> {
+    pub(crate) fn management_response(
+        request_header: Header,
+        request: &ManagementMessage,
+        action: ManagementAction,
+        port_identity: PortIdentity,
+        minor_ptp_version: u8,
+        tlvs: TlvSet<'a>,
+    ) -> Self {
+        let header = Header {
+            sdo_id: request_header.sdo_id,
+            domain_number: request_header.domain_number,
+            source_port_identity: port_identity,
+            sequence_id: request_header.sequence_id,
+            log_message_interval: 0x7f,
+            ..Header::new(minor_ptp_version)
+        };
+
+        // See 15.3.4: the response should travel back the same number of
+        // boundary clocks the request has passed.
+        let hops = request
+            .starting_boundary_hops
+            .saturating_sub(request.boundary_hops);
+
+        Message {
+            header,
+            body: MessageBody::Management(ManagementMessage {
+                target_port_identity: request_header.source_port_identity,
+                starting_boundary_hops: hops,
+                boundary_hops: hops,
+                action,
+            }),
+            suffix: tlvs,
+        }
+    }
+
+    /// Management message carrying the SMPTE ST 2059-2 synchronization
+    /// metadata, addressed to all ports of all clocks on the link
+    pub(crate) fn synchronization_metadata(
+        default_ds: &InternalDefaultDS,
+        port_identity: PortIdentity,
+        sequence_id: u16,
+        minor_ptp_version: u8,
+        tlvs: TlvSet<'a>,
+    ) -> Self {
+        let header = Header {
+            log_message_interval: 0x7f,
+            ..base_header(default_ds, port_identity, sequence_id, minor_ptp_version)
+        };
+
+        Message {
+            header,
+            body: MessageBody::Management(ManagementMessage {
+                target_port_identity: PortIdentity {
+                    clock_identity: ClockIdentity([0xff; 8]),
+                    port_number: 0xffff,
+                },
+                starting_boundary_hops: 0,
+                boundary_hops: 0,
+                action: ManagementAction::COMMAND,
+            }),
+            suffix: tlvs,
+        }
+    }
+
+    pub(crate) fn signaling(
+        default_ds: &InternalDefaultDS,
+        port_identity: PortIdentity,
+        target_port_identity: PortIdentity,
+        sequence_id: u16,
+        minor_ptp_version: u8,
+        tlvs: TlvSet<'a>,
+    ) -> Self {
+        // Signaling is only used for unicast negotiation and slave event
+        // monitoring, both of which are sent unicast, see 16.1 and 16.11
+        let header = Header {
+            unicast_flag: true,
+            log_message_interval: 0x7f,
+            ..base_header(default_ds, port_identity, sequence_id, minor_ptp_version)
+        };
+
+        Message {
+            header,
+            body: MessageBody::Signaling(SignalingMessage {
+                target_port_identity,
+            }),
+            suffix: tlvs,
+        }
+    }
+
     pub(crate) fn header(&self) -> &Header {
         &self.header
     }
@@ -475,6 +595,25 @@ impl<'a> Message<'a> {
         Ok(self.wire_size())
     }
 
+    /// Serializes the object into the PTP wire format, followed by an
+    /// AUTHENTICATION TLV when a security association is given.
+    ///
+    /// Returns the used buffer size that contains the message or an error.
+    pub(crate) fn serialize_authenticated(
+        &self,
+        buffer: &mut [u8],
+        authentication: Option<&SecurityAssociation>,
+    ) -> Result<usize, super::WireFormatError> {
+        let length = self.serialize(buffer)?;
+
+        match authentication {
+            Some(security_association) => {
+                append_authentication_tlv(buffer, length, security_association)
+            }
+            None => Ok(length),
+        }
+    }
+
     /// Deserializes a message from the PTP wire format.
     ///
     /// Returns the message or an error.
@@ -1,8 +1,12 @@
-use crate::datastructures::{common::PortIdentity, WireFormat, WireFormatError};
+use super::MessageType;
+use crate::datastructures::{
+    common::{PortIdentity, TimeInterval, WireTimestamp},
+    WireFormat, WireFormatError,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct SignalingMessage {
-    pub(super) target_port_identity: PortIdentity,
+    pub(crate) target_port_identity: PortIdentity,
 }
 
 impl SignalingMessage {
@@ -31,3 +35,538 @@ impl SignalingMessage {
         })
     }
 }
+
+/// Value of a REQUEST_UNICAST_TRANSMISSION TLV, see 16.1.4.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RequestUnicastTransmissionTlv {
+    pub(crate) message_type: MessageType,
+    pub(crate) log_inter_message_period: i8,
+    pub(crate) duration_field: u32,
+}
+
+impl RequestUnicastTransmissionTlv {
+    pub(crate) fn wire_size(&self) -> usize {
+        6
+    }
+
+    pub(crate) fn serialize(&self, buffer: &mut [u8]) -> Result<usize, WireFormatError> {
+        let buffer = buffer
+            .get_mut(..self.wire_size())
+            .ok_or(WireFormatError::BufferTooShort)?;
+        buffer[0] = (self.message_type as u8) << 4;
+        buffer[1] = self.log_inter_message_period as u8;
+        buffer[2..6].copy_from_slice(&self.duration_field.to_be_bytes());
+        Ok(self.wire_size())
+    }
+
+    pub(crate) fn deserialize(buffer: &[u8]) -> Result<Self, WireFormatError> {
+        if buffer.len() < 6 {
+            return Err(WireFormatError::BufferTooShort);
+        }
+
+        Ok(Self {
+            message_type: MessageType::try_from(buffer[0] >> 4)?,
+            log_inter_message_period: buffer[1] as i8,
+            duration_field: u32::from_be_bytes(buffer[2..6].try_into().unwrap()),
+        })
+    }
+}
+
+/// Value of a GRANT_UNICAST_TRANSMISSION TLV, see 16.1.4.2
+///
+/// A `duration_field` of zero indicates the request was denied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GrantUnicastTransmissionTlv {
+    pub(crate) message_type: MessageType,
+    pub(crate) log_inter_message_period: i8,
+    pub(crate) duration_field: u32,
+    pub(crate) renewal_invited: bool,
+}
+
+impl GrantUnicastTransmissionTlv {
+    pub(crate) fn wire_size(&self) -> usize {
+        8
+    }
+
+    pub(crate) fn serialize(&self, buffer: &mut [u8]) -> Result<usize, WireFormatError> {
+        let buffer = buffer
+            .get_mut(..self.wire_size())
+            .ok_or(WireFormatError::BufferTooShort)?;
+        buffer[0] = (self.message_type as u8) << 4;
+        buffer[1] = self.log_inter_message_period as u8;
+        buffer[2..6].copy_from_slice(&self.duration_field.to_be_bytes());
+        buffer[6] = 0;
+        buffer[7] = self.renewal_invited as u8;
+        Ok(self.wire_size())
+    }
+
+    pub(crate) fn deserialize(buffer: &[u8]) -> Result<Self, WireFormatError> {
+        if buffer.len() < 8 {
+            return Err(WireFormatError::BufferTooShort);
+        }
+
+        Ok(Self {
+            message_type: MessageType::try_from(buffer[0] >> 4)?,
+            log_inter_message_period: buffer[1] as i8,
+            duration_field: u32::from_be_bytes(buffer[2..6].try_into().unwrap()),
+            renewal_invited: buffer[7] & 1 != 0,
+        })
+    }
+}
+
+/// Value of a CANCEL_UNICAST_TRANSMISSION or
+/// ACKNOWLEDGE_CANCEL_UNICAST_TRANSMISSION TLV, see 16.1.4.3 and 16.1.4.4
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CancelUnicastTransmissionTlv {
+    pub(crate) message_type: MessageType,
+}
+
+impl CancelUnicastTransmissionTlv {
+    pub(crate) fn wire_size(&self) -> usize {
+        2
+    }
+
+    pub(crate) fn serialize(&self, buffer: &mut [u8]) -> Result<usize, WireFormatError> {
+        let buffer = buffer
+            .get_mut(..self.wire_size())
+            .ok_or(WireFormatError::BufferTooShort)?;
+        buffer[0] = (self.message_type as u8) << 4;
+        buffer[1] = 0;
+        Ok(self.wire_size())
+    }
+
+    pub(crate) fn deserialize(buffer: &[u8]) -> Result<Self, WireFormatError> {
+        if buffer.len() < 2 {
+            return Err(WireFormatError::BufferTooShort);
+        }
+
+        Ok(Self {
+            message_type: MessageType::try_from(buffer[0] >> 4)?,
+        })
+    }
+}
+
+/// Record of a SLAVE_RX_SYNC_TIMING_DATA TLV, see 16.11.4.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SlaveRxSyncTimingDataRecord {
+    pub(crate) sequence_id: u16,
+    pub(crate) sync_origin_timestamp: WireTimestamp,
+    pub(crate) total_correction_field: TimeInterval,
+    pub(crate) scaled_cumulative_rate_offset: i32,
+    pub(crate) sync_event_ingress_timestamp: WireTimestamp,
+}
+
+impl SlaveRxSyncTimingDataRecord {
+    pub(crate) const WIRE_SIZE: usize = 34;
+
+    fn serialize(&self, buffer: &mut [u8]) -> Result<(), WireFormatError> {
+        buffer[0..2].copy_from_slice(&self.sequence_id.to_be_bytes());
+        self.sync_origin_timestamp.serialize(&mut buffer[2..12])?;
+        self.total_correction_field.serialize(&mut buffer[12..20])?;
+        buffer[20..24].copy_from_slice(&self.scaled_cumulative_rate_offset.to_be_bytes());
+        self.sync_event_ingress_timestamp
+            .serialize(&mut buffer[24..34])?;
+        Ok(())
+    }
+
+    fn deserialize(buffer: &[u8]) -> Result<Self, WireFormatError> {
+        Ok(Self {
+            sequence_id: u16::from_be_bytes([buffer[0], buffer[1]]),
+            sync_origin_timestamp: WireTimestamp::deserialize(&buffer[2..12])?,
+            total_correction_field: TimeInterval::deserialize(&buffer[12..20])?,
+            scaled_cumulative_rate_offset: i32::from_be_bytes(buffer[20..24].try_into().unwrap()),
+            sync_event_ingress_timestamp: WireTimestamp::deserialize(&buffer[24..34])?,
+        })
+    }
+}
+
+/// Value of a SLAVE_RX_SYNC_TIMING_DATA TLV, see 16.11.4.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SlaveRxSyncTimingDataTlv<'a> {
+    pub(crate) sync_source_port_identity: PortIdentity,
+    pub(crate) records: &'a [SlaveRxSyncTimingDataRecord],
+}
+
+impl SlaveRxSyncTimingDataTlv<'_> {
+    pub(crate) fn wire_size(&self) -> usize {
+        10 + self.records.len() * SlaveRxSyncTimingDataRecord::WIRE_SIZE
+    }
+
+    pub(crate) fn serialize(&self, buffer: &mut [u8]) -> Result<usize, WireFormatError> {
+        let buffer = buffer
+            .get_mut(..self.wire_size())
+            .ok_or(WireFormatError::BufferTooShort)?;
+        self.sync_source_port_identity
+            .serialize(&mut buffer[0..10])?;
+        for (record, buffer) in self
+            .records
+            .iter()
+            .zip(buffer[10..].chunks_exact_mut(SlaveRxSyncTimingDataRecord::WIRE_SIZE))
+        {
+            record.serialize(buffer)?;
+        }
+        Ok(self.wire_size())
+    }
+
+    /// Parse the sync source port identity and the records of a TLV value
+    pub(crate) fn deserialize(
+        buffer: &[u8],
+    ) -> Result<
+        (
+            PortIdentity,
+            impl Iterator<Item = SlaveRxSyncTimingDataRecord> + '_,
+        ),
+        WireFormatError,
+    > {
+        let identity_bytes = buffer.get(0..10).ok_or(WireFormatError::BufferTooShort)?;
+        let records = buffer[10..]
+            .chunks_exact(SlaveRxSyncTimingDataRecord::WIRE_SIZE)
+            .filter_map(|record| SlaveRxSyncTimingDataRecord::deserialize(record).ok());
+        Ok((PortIdentity::deserialize(identity_bytes)?, records))
+    }
+}
+
+/// Record of a SLAVE_RX_SYNC_COMPUTED_DATA TLV, see 16.11.4.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SlaveRxSyncComputedDataRecord {
+    pub(crate) sequence_id: u16,
+    pub(crate) offset_from_master: TimeInterval,
+    pub(crate) mean_path_delay: TimeInterval,
+    pub(crate) scaled_neighbor_rate_ratio: i32,
+}
+
+impl SlaveRxSyncComputedDataRecord {
+    pub(crate) const WIRE_SIZE: usize = 22;
+
+    fn serialize(&self, buffer: &mut [u8]) -> Result<(), WireFormatError> {
+        buffer[0..2].copy_from_slice(&self.sequence_id.to_be_bytes());
+        self.offset_from_master.serialize(&mut buffer[2..10])?;
+        self.mean_path_delay.serialize(&mut buffer[10..18])?;
+        buffer[18..22].copy_from_slice(&self.scaled_neighbor_rate_ratio.to_be_bytes());
+        Ok(())
+    }
+
+    fn deserialize(buffer: &[u8]) -> Result<Self, WireFormatError> {
+        Ok(Self {
+            sequence_id: u16::from_be_bytes([buffer[0], buffer[1]]),
+            offset_from_master: TimeInterval::deserialize(&buffer[2..10])?,
+            mean_path_delay: TimeInterval::deserialize(&buffer[10..18])?,
+            scaled_neighbor_rate_ratio: i32::from_be_bytes(buffer[18..22].try_into().unwrap()),
+        })
+    }
+}
+
+/// Value of a SLAVE_RX_SYNC_COMPUTED_DATA TLV, see 16.11.4.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SlaveRxSyncComputedDataTlv<'a> {
+    pub(crate) source_port_identity: PortIdentity,
+    pub(crate) scaled_neighbor_rate_ratio_valid: bool,
+    pub(crate) mean_path_delay_valid: bool,
+    pub(crate) offset_from_master_valid: bool,
+    pub(crate) records: &'a [SlaveRxSyncComputedDataRecord],
+}
+
+impl SlaveRxSyncComputedDataTlv<'_> {
+    pub(crate) fn wire_size(&self) -> usize {
+        12 + self.records.len() * SlaveRxSyncComputedDataRecord::WIRE_SIZE
+    }
+
+    pub(crate) fn serialize(&self, buffer: &mut [u8]) -> Result<usize, WireFormatError> {
+        let buffer = buffer
+            .get_mut(..self.wire_size())
+            .ok_or(WireFormatError::BufferTooShort)?;
+        self.source_port_identity.serialize(&mut buffer[0..10])?;
+        buffer[10] = self.scaled_neighbor_rate_ratio_valid as u8
+            | (self.mean_path_delay_valid as u8) << 1
+            | (self.offset_from_master_valid as u8) << 2;
+        buffer[11] = 0;
+        for (record, buffer) in self
+            .records
+            .iter()
+            .zip(buffer[12..].chunks_exact_mut(SlaveRxSyncComputedDataRecord::WIRE_SIZE))
+        {
+            record.serialize(buffer)?;
+        }
+        Ok(self.wire_size())
+    }
+
+    /// Parse the source port identity and the records of a TLV value, if both
+    /// the offset from master and mean path delay of the records are valid
+    pub(crate) fn deserialize(
+        buffer: &[u8],
+    ) -> Result<
+        Option<(
+            PortIdentity,
+            impl Iterator<Item = SlaveRxSyncComputedDataRecord> + '_,
+        )>,
+        WireFormatError,
+    > {
+        let header = buffer.get(0..12).ok_or(WireFormatError::BufferTooShort)?;
+        let valid = header[10] & 0b110 == 0b110;
+        let records = buffer[12..]
+            .chunks_exact(SlaveRxSyncComputedDataRecord::WIRE_SIZE)
+            .filter_map(|record| SlaveRxSyncComputedDataRecord::deserialize(record).ok());
+        Ok(valid.then_some((PortIdentity::deserialize(&header[0..10])?, records)))
+    }
+}
+
+/// Record of a SLAVE_TX_EVENT_TIMESTAMPS TLV, see 16.11.5.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SlaveTxEventTimestampsRecord {
+    pub(crate) sequence_id: u16,
+    pub(crate) event_egress_timestamp: WireTimestamp,
+}
+
+impl SlaveTxEventTimestampsRecord {
+    pub(crate) const WIRE_SIZE: usize = 12;
+
+    fn serialize(&self, buffer: &mut [u8]) -> Result<(), WireFormatError> {
+        buffer[0..2].copy_from_slice(&self.sequence_id.to_be_bytes());
+        self.event_egress_timestamp.serialize(&mut buffer[2..12])
+    }
+
+    fn deserialize(buffer: &[u8]) -> Result<Self, WireFormatError> {
+        Ok(Self {
+            sequence_id: u16::from_be_bytes([buffer[0], buffer[1]]),
+            event_egress_timestamp: WireTimestamp::deserialize(&buffer[2..12])?,
+        })
+    }
+}
+
+/// Value of a SLAVE_TX_EVENT_TIMESTAMPS TLV, see 16.11.5.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SlaveTxEventTimestampsTlv<'a> {
+    pub(crate) source_port_identity: PortIdentity,
+    pub(crate) event_message_type: MessageType,
+    pub(crate) records: &'a [SlaveTxEventTimestampsRecord],
+}
+
+impl SlaveTxEventTimestampsTlv<'_> {
+    pub(crate) fn wire_size(&self) -> usize {
+        12 + self.records.len() * SlaveTxEventTimestampsRecord::WIRE_SIZE
+    }
+
+    pub(crate) fn serialize(&self, buffer: &mut [u8]) -> Result<usize, WireFormatError> {
+        let buffer = buffer
+            .get_mut(..self.wire_size())
+            .ok_or(WireFormatError::BufferTooShort)?;
+        self.source_port_identity.serialize(&mut buffer[0..10])?;
+        buffer[10] = (self.event_message_type as u8) << 4;
+        buffer[11] = 0;
+        for (record, buffer) in self
+            .records
+            .iter()
+            .zip(buffer[12..].chunks_exact_mut(SlaveTxEventTimestampsRecord::WIRE_SIZE))
+        {
+            record.serialize(buffer)?;
+        }
+        Ok(self.wire_size())
+    }
+
+    /// Parse the source port identity, event message type and the records of a
+    /// TLV value
+    pub(crate) fn deserialize(
+        buffer: &[u8],
+    ) -> Result<
+        (
+            PortIdentity,
+            MessageType,
+            impl Iterator<Item = SlaveTxEventTimestampsRecord> + '_,
+        ),
+        WireFormatError,
+    > {
+        let header = buffer.get(0..12).ok_or(WireFormatError::BufferTooShort)?;
+        let records = buffer[12..]
+            .chunks_exact(SlaveTxEventTimestampsRecord::WIRE_SIZE)
+            .filter_map(|record| SlaveTxEventTimestampsRecord::deserialize(record).ok());
+        Ok((
+            PortIdentity::deserialize(&header[0..10])?,
+            MessageType::try_from(header[10] >> 4)?,
+            records,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_unicast_transmission_wireformat() {
+        let data = [0x00, 0xfd, 0x00, 0x00, 0x01, 0x2c];
+        let tlv = RequestUnicastTransmissionTlv::deserialize(&data).unwrap();
+        assert_eq!(
+            tlv,
+            RequestUnicastTransmissionTlv {
+                message_type: MessageType::Sync,
+                log_inter_message_period: -3,
+                duration_field: 300,
+            }
+        );
+
+        let mut buffer = [0; 6];
+        assert_eq!(tlv.serialize(&mut buffer).unwrap(), 6);
+        assert_eq!(buffer, data);
+
+        // Unknown message types can't be requested
+        assert!(RequestUnicastTransmissionTlv::deserialize(&[0x40, 0, 0, 0, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn grant_unicast_transmission_wireformat() {
+        let tlv = GrantUnicastTransmissionTlv {
+            message_type: MessageType::Announce,
+            log_inter_message_period: 1,
+            duration_field: 60,
+            renewal_invited: true,
+        };
+
+        let mut buffer = [0; 8];
+        assert_eq!(tlv.serialize(&mut buffer).unwrap(), 8);
+        assert_eq!(buffer, [0xb0, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x01]);
+        assert_eq!(
+            GrantUnicastTransmissionTlv::deserialize(&buffer).unwrap(),
+            tlv
+        );
+    }
+
+    #[test]
+    fn cancel_unicast_transmission_wireformat() {
+        let tlv = CancelUnicastTransmissionTlv {
+            message_type: MessageType::DelayResp,
+        };
+
+        let mut buffer = [0xff; 2];
+        assert_eq!(tlv.serialize(&mut buffer).unwrap(), 2);
+        assert_eq!(buffer, [0x90, 0x00]);
+        assert_eq!(
+            CancelUnicastTransmissionTlv::deserialize(&buffer).unwrap(),
+            tlv
+        );
+    }
+
+    #[test]
+    fn slave_rx_sync_timing_data_wireformat() {
+        let source = PortIdentity {
+            clock_identity: crate::config::ClockIdentity([1, 2, 3, 4, 5, 6, 7, 8]),
+            port_number: 9,
+        };
+        let records = [SlaveRxSyncTimingDataRecord {
+            sequence_id: 0x1234,
+            sync_origin_timestamp: WireTimestamp {
+                seconds: 5,
+                nanos: 6,
+            },
+            total_correction_field: TimeInterval(2.into()),
+            scaled_cumulative_rate_offset: -1,
+            sync_event_ingress_timestamp: WireTimestamp {
+                seconds: 7,
+                nanos: 8,
+            },
+        }];
+        let tlv = SlaveRxSyncTimingDataTlv {
+            sync_source_port_identity: source,
+            records: &records,
+        };
+
+        let mut buffer = [0xaa; 50];
+        assert_eq!(tlv.serialize(&mut buffer).unwrap(), 44);
+        assert_eq!(
+            buffer[..44],
+            [
+                1, 2, 3, 4, 5, 6, 7, 8, 0, 9, // source port identity
+                0x12, 0x34, // sequence id
+                0, 0, 0, 0, 0, 5, 0, 0, 0, 6, // origin timestamp
+                0, 0, 0, 0, 0, 2, 0, 0, // total correction
+                0xff, 0xff, 0xff, 0xff, // scaled cumulative rate offset
+                0, 0, 0, 0, 0, 7, 0, 0, 0, 8, // ingress timestamp
+            ]
+        );
+
+        assert!(tlv.serialize(&mut [0; 43]).is_err());
+
+        let (identity, mut parsed) = SlaveRxSyncTimingDataTlv::deserialize(&buffer[..44]).unwrap();
+        assert_eq!(identity, source);
+        assert_eq!(parsed.next(), Some(records[0]));
+        assert_eq!(parsed.next(), None);
+    }
+
+    #[test]
+    fn slave_rx_sync_computed_data_wireformat() {
+        let records = [
+            SlaveRxSyncComputedDataRecord {
+                sequence_id: 1,
+                offset_from_master: TimeInterval((-1).into()),
+                mean_path_delay: TimeInterval(3.into()),
+                scaled_neighbor_rate_ratio: 0,
+            },
+            SlaveRxSyncComputedDataRecord {
+                sequence_id: 2,
+                offset_from_master: TimeInterval(1.into()),
+                mean_path_delay: TimeInterval(3.into()),
+                scaled_neighbor_rate_ratio: 0,
+            },
+        ];
+        let tlv = SlaveRxSyncComputedDataTlv {
+            source_port_identity: Default::default(),
+            scaled_neighbor_rate_ratio_valid: false,
+            mean_path_delay_valid: true,
+            offset_from_master_valid: true,
+            records: &records,
+        };
+
+        let mut buffer = [0xaa; 56];
+        assert_eq!(tlv.serialize(&mut buffer).unwrap(), 56);
+        assert_eq!(buffer[10..12], [0x06, 0x00]);
+        assert_eq!(
+            buffer[12..34],
+            [
+                0, 1, // sequence id
+                0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, // offset from master
+                0, 0, 0, 0, 0, 3, 0, 0, // mean path delay
+                0, 0, 0, 0, // scaled neighbor rate ratio
+            ]
+        );
+        assert_eq!(buffer[34..36], [0, 2]);
+
+        let (_, parsed) = SlaveRxSyncComputedDataTlv::deserialize(&buffer)
+            .unwrap()
+            .unwrap();
+        assert!(parsed.eq(records));
+
+        // Records without a valid offset are not parsed
+        buffer[10] = 0x02;
+        assert!(SlaveRxSyncComputedDataTlv::deserialize(&buffer)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn slave_tx_event_timestamps_wireformat() {
+        let records = [SlaveTxEventTimestampsRecord {
+            sequence_id: 3,
+            event_egress_timestamp: WireTimestamp {
+                seconds: 1,
+                nanos: 2,
+            },
+        }];
+        let tlv = SlaveTxEventTimestampsTlv {
+            source_port_identity: Default::default(),
+            event_message_type: MessageType::DelayReq,
+            records: &records,
+        };
+
+        let mut buffer = [0xaa; 24];
+        assert_eq!(tlv.serialize(&mut buffer).unwrap(), 24);
+        assert_eq!(
+            buffer[10..],
+            [0x10, 0x00, 0, 3, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2]
+        );
+
+        let (_, message_type, mut parsed) =
+            SlaveTxEventTimestampsTlv::deserialize(&buffer).unwrap();
+        assert_eq!(message_type, MessageType::DelayReq);
+        assert_eq!(parsed.next(), Some(records[0]));
+        assert_eq!(parsed.next(), None);
+    }
+}
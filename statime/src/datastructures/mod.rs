@@ -42,7 +42,7 @@ impl From<EnumConversionError> for WireFormatError {
     }
 }
 
-trait WireFormat: Debug + Clone + Eq {
+pub(crate) trait WireFormat: Debug + Clone + Eq {
     /// Serializes the object into the PTP wire format.
     ///
     /// Returns the used buffer size that contains the message or an error.
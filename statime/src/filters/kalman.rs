@@ -67,6 +67,11 @@ pub struct KalmanConfiguration {
     /// peer delay measurements (to compensate for there being multiple path
     /// segments).
     pub peer_delay_factor: f64,
+    /// Amount of recent sync and delay measurements among which only those
+    /// that spent the least time in the network are used (max 32). Larger
+    /// values reject more measurements delayed by queues on paths with much
+    /// packet delay variation. A value of 1 uses all measurements.
+    pub packet_selection_window: usize,
 }
 
 impl Default for KalmanConfiguration {
@@ -87,6 +92,7 @@ impl Default for KalmanConfiguration {
             difference_estimation_boundary: 4,
             statistical_estimation_boundary: 8,
             peer_delay_factor: 2.0,
+            packet_selection_window: 1,
         }
     }
 }
@@ -236,6 +242,80 @@ impl MeasurementErrorEstimator {
     }
 }
 
+#[derive(Debug, Default, Copy, Clone)]
+struct SelectionWindow {
+    data: [f64; 32],
+    next_idx: usize,
+    fill: usize,
+}
+
+impl SelectionWindow {
+    /// Insert an entry, returning whether it is the smallest of the last
+    /// `size` entries
+    fn select(&mut self, entry: f64, size: usize) -> bool {
+        let size = size.clamp(1, self.data.len());
+        self.data[self.next_idx] = entry;
+        self.next_idx = (self.next_idx + 1) % size;
+        self.fill = (self.fill + 1).min(size);
+        self.data
+            .iter()
+            .take(self.fill)
+            .all(|&other| entry <= other)
+    }
+
+    fn absorb_offset_steer(&mut self, steer: f64) {
+        for entry in self.data.iter_mut().take(self.fill) {
+            *entry += steer;
+        }
+    }
+}
+
+/// Selects the measurements least delayed by the network
+///
+/// Queueing delays the arrival of a message, which increases the raw offset
+/// of a sync and decreases that of a delay request.
+#[derive(Debug, Default, Copy, Clone)]
+struct PacketSelector {
+    sync: SelectionWindow,
+    delay: SelectionWindow,
+}
+
+impl PacketSelector {
+    fn select(&mut self, mut m: Measurement, config: &KalmanConfiguration) -> Measurement {
+        if config.packet_selection_window <= 1 {
+            return m;
+        }
+
+        if let Some(sync_offset) = m.raw_sync_offset {
+            if !self
+                .sync
+                .select(sync_offset.seconds(), config.packet_selection_window)
+            {
+                m.raw_sync_offset = None;
+            }
+        }
+
+        if let Some(delay_offset) = m.raw_delay_offset {
+            if !self
+                .delay
+                .select(-delay_offset.seconds(), config.packet_selection_window)
+            {
+                m.raw_delay_offset = None;
+            }
+        }
+
+        m
+    }
+
+    /// Shift the stored offsets by a step of the clock, so measurements after
+    /// the step compare to those before it
+    fn absorb_offset_steer(&mut self, steer: f64) {
+        self.sync.absorb_offset_steer(steer);
+        // The delay window holds negated offsets
+        self.delay.absorb_offset_steer(-steer);
+    }
+}
+
 #[derive(Clone, Debug)]
 struct InnerFilter {
     state: Vector<3>,
@@ -519,6 +599,7 @@ pub struct KalmanFilter {
     wander: f64,
     wander_measurement_error: f64,
     measurement_error_estimator: MeasurementErrorEstimator,
+    packet_selector: PacketSelector,
     cur_frequency: Option<f64>,
 }
 
@@ -536,6 +617,7 @@ impl Filter for KalmanFilter {
                 .measurement_variance(&config)
                 .sqrt(),
             measurement_error_estimator,
+            packet_selector: PacketSelector::default(),
             cur_frequency: None,
             config,
         }
@@ -550,6 +632,8 @@ impl Filter for KalmanFilter {
             return super::FilterUpdate::default();
         }
 
+        let m = self.packet_selector.select(m, &self.config);
+
         self.measurement_error_estimator.absorb_measurement(
             m,
             self.running_filter.freq_offset(),
@@ -632,6 +716,10 @@ impl Filter for KalmanFilter {
             mean_delay: Duration::from_seconds(self.running_filter.mean_delay()),
         }
     }
+
+    fn frequency_uncertainty(&self) -> Option<f64> {
+        Some(self.running_filter.freq_offset_uncertainty(&self.config))
+    }
 }
 
 impl KalmanFilter {
@@ -672,6 +760,7 @@ impl KalmanFilter {
             log::info!("Stepped clock by {}s", -offset);
             self.running_filter.absorb_offset_steer(-offset);
             self.wander_filter.absorb_offset_steer(-offset);
+            self.packet_selector.absorb_offset_steer(-offset);
         }
     }
 
@@ -842,6 +931,7 @@ mod tests {
             wander: KalmanConfiguration::default().initial_wander,
             wander_measurement_error: 1.0,
             measurement_error_estimator: MeasurementErrorEstimator::default(),
+            packet_selector: PacketSelector::default(),
             cur_frequency: Some(0.0),
         };
         let mut clock = TestClock::default();
@@ -868,6 +958,7 @@ mod tests {
             wander: KalmanConfiguration::default().initial_wander,
             wander_measurement_error: 1.0,
             measurement_error_estimator: MeasurementErrorEstimator::default(),
+            packet_selector: PacketSelector::default(),
             cur_frequency: Some(0.0),
         };
         let mut clock = TestClock::default();
@@ -894,6 +985,7 @@ mod tests {
             wander: KalmanConfiguration::default().initial_wander,
             wander_measurement_error: 1.0,
             measurement_error_estimator: MeasurementErrorEstimator::default(),
+            packet_selector: PacketSelector::default(),
             cur_frequency: Some(20.0),
         };
         let mut clock = TestClock::default();
@@ -916,10 +1008,133 @@ mod tests {
             wander: KalmanConfiguration::default().initial_wander,
             wander_measurement_error: 1.0,
             measurement_error_estimator: MeasurementErrorEstimator::default(),
+            packet_selector: PacketSelector::default(),
             cur_frequency: Some(-20.0),
         };
         let mut clock = TestClock::default();
         filter.change_frequency(50.0, &mut clock);
         assert_eq!(clock.last_freq, Some(10.0));
     }
+
+    #[test]
+    fn packet_selection() {
+        let config = KalmanConfiguration {
+            packet_selection_window: 4,
+            ..Default::default()
+        };
+        let mut selector = PacketSelector::default();
+
+        let sync = |nanos| Measurement {
+            raw_sync_offset: Some(Duration::from_nanos(nanos)),
+            ..Default::default()
+        };
+        let delay = |nanos| Measurement {
+            raw_delay_offset: Some(Duration::from_nanos(nanos)),
+            ..Default::default()
+        };
+
+        // The fastest sync of the window is used
+        assert!(selector
+            .select(sync(500), &config)
+            .raw_sync_offset
+            .is_some());
+        assert!(selector
+            .select(sync(800), &config)
+            .raw_sync_offset
+            .is_none());
+        assert!(selector
+            .select(sync(400), &config)
+            .raw_sync_offset
+            .is_some());
+        assert!(selector
+            .select(sync(450), &config)
+            .raw_sync_offset
+            .is_none());
+        assert!(selector
+            .select(sync(600), &config)
+            .raw_sync_offset
+            .is_none());
+        assert!(selector
+            .select(sync(700), &config)
+            .raw_sync_offset
+            .is_none());
+        assert!(selector
+            .select(sync(650), &config)
+            .raw_sync_offset
+            .is_none());
+        // The sync of 400ns has left the window
+        assert!(selector
+            .select(sync(550), &config)
+            .raw_sync_offset
+            .is_some());
+
+        // A delayed delay request has a smaller raw offset
+        assert!(selector
+            .select(delay(-500), &config)
+            .raw_delay_offset
+            .is_some());
+        assert!(selector
+            .select(delay(-800), &config)
+            .raw_delay_offset
+            .is_none());
+        assert!(selector
+            .select(delay(-400), &config)
+            .raw_delay_offset
+            .is_some());
+
+        // A window of 1 uses all measurements
+        let mut selector = PacketSelector::default();
+        let config = KalmanConfiguration::default();
+        assert!(selector
+            .select(sync(500), &config)
+            .raw_sync_offset
+            .is_some());
+        assert!(selector
+            .select(sync(800), &config)
+            .raw_sync_offset
+            .is_some());
+    }
+
+    #[test]
+    fn packet_selection_after_step() {
+        let config = KalmanConfiguration {
+            packet_selection_window: 4,
+            ..Default::default()
+        };
+
+        // The clock is a second behind or ahead, with 500ns of network delay
+        for offset in [-1_000_000_000i64, 1_000_000_000] {
+            let mut selector = PacketSelector::default();
+
+            let m = Measurement {
+                raw_sync_offset: Some(Duration::from_nanos(offset + 500)),
+                raw_delay_offset: Some(Duration::from_nanos(offset - 500)),
+                ..Default::default()
+            };
+            let m = selector.select(m, &config);
+            assert!(m.raw_sync_offset.is_some());
+            assert!(m.raw_delay_offset.is_some());
+
+            // After stepping it, less delayed measurements are still selected
+            selector.absorb_offset_steer(-offset as f64 * 1e-9);
+            let m = Measurement {
+                raw_sync_offset: Some(Duration::from_nanos(400)),
+                raw_delay_offset: Some(Duration::from_nanos(-400)),
+                ..Default::default()
+            };
+            let m = selector.select(m, &config);
+            assert!(m.raw_sync_offset.is_some());
+            assert!(m.raw_delay_offset.is_some());
+
+            // and more delayed ones are not
+            let m = Measurement {
+                raw_sync_offset: Some(Duration::from_nanos(700)),
+                raw_delay_offset: Some(Duration::from_nanos(-700)),
+                ..Default::default()
+            };
+            let m = selector.select(m, &config);
+            assert!(m.raw_sync_offset.is_none());
+            assert!(m.raw_delay_offset.is_none());
+        }
+    }
 }
@@ -64,4 +64,13 @@ pub trait Filter {
     /// Provide estimates for the Current dataset
     /// mean delay and
     fn current_estimates(&self) -> FilterEstimate;
+
+    /// Uncertainty of the estimated frequency of the clock relative to its
+    /// time source, as a fraction (s/s)
+    ///
+    /// This bounds the time error the clock builds up once the time source is
+    /// lost. Filters that do not estimate it return `None`.
+    fn frequency_uncertainty(&self) -> Option<f64> {
+        None
+    }
 }
@@ -0,0 +1,401 @@
+//! Degradation of the clock class of an instance when it loses its time
+//! reference, see *IEEE1588-2019 section 7.6.2.5*
+
+#[allow(unused_imports)]
+use crate::float_polyfill::FloatPolyfill;
+#[cfg(doc)]
+use crate::PtpInstance;
+use crate::{
+    datastructures::datasets::{InternalDefaultDS, TimePropertiesDS},
+    time::Duration,
+};
+
+/// The clock classes announced in each state of the holdover state machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HoldoverClasses {
+    /// Class while locked to an external primary reference
+    pub locked: u8,
+    /// Class while in holdover within the holdover specification
+    pub holdover_in_spec: u8,
+    /// Class after the holdover specification is exceeded
+    pub holdover_out_of_spec: u8,
+    /// Class while running without a reference, also used while following
+    /// the time of an upstream master
+    pub free_running: u8,
+}
+
+impl HoldoverClasses {
+    /// The IEEE1588 classes, degrading to 52 (degradation alternative A)
+    pub const DEGRADATION_A: Self = Self {
+        locked: 6,
+        holdover_in_spec: 7,
+        holdover_out_of_spec: 52,
+        free_running: 248,
+    };
+
+    /// The IEEE1588 classes, degrading to 187 (degradation alternative B),
+    /// which allows the clock to become a slave
+    pub const DEGRADATION_B: Self = Self {
+        locked: 6,
+        holdover_in_spec: 7,
+        holdover_out_of_spec: 187,
+        free_running: 248,
+    };
+
+    /// The classes of a telecom grandmaster in the ITU-T G.8275 profiles,
+    /// degrading to category 1 (140)
+    pub const G8275_GRANDMASTER: Self = Self {
+        locked: 6,
+        holdover_in_spec: 7,
+        holdover_out_of_spec: 140,
+        free_running: 248,
+    };
+
+    /// The classes of a telecom grandmaster in the ITU-T G.8275 profiles,
+    /// degrading to category 2 (150)
+    pub const G8275_GRANDMASTER_CATEGORY_2: Self = Self {
+        locked: 6,
+        holdover_in_spec: 7,
+        holdover_out_of_spec: 150,
+        free_running: 248,
+    };
+
+    /// The classes of a telecom grandmaster in the ITU-T G.8275 profiles,
+    /// degrading to category 3 (160)
+    pub const G8275_GRANDMASTER_CATEGORY_3: Self = Self {
+        locked: 6,
+        holdover_in_spec: 7,
+        holdover_out_of_spec: 160,
+        free_running: 248,
+    };
+
+    /// The classes of a telecom boundary clock in the ITU-T G.8275 profiles
+    pub const G8275_BOUNDARY_CLOCK: Self = Self {
+        locked: 6,
+        holdover_in_spec: 135,
+        holdover_out_of_spec: 165,
+        free_running: 248,
+    };
+}
+
+impl Default for HoldoverClasses {
+    fn default() -> Self {
+        Self::DEGRADATION_A
+    }
+}
+
+/// Configuration of the holdover state machine of a [`PtpInstance`]
+///
+/// See [`PtpInstance::set_holdover`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HoldoverConfig {
+    /// The clock classes to announce in each state
+    pub classes: HoldoverClasses,
+    /// The largest time error the clock may build up during holdover while
+    /// staying within its holdover specification
+    ///
+    /// The time error is estimated from the uncertainty of the frequency of
+    /// the clock at the moment the reference was lost.
+    pub budget: Duration,
+}
+
+/// State of the holdover state machine of a [`PtpInstance`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HoldoverState {
+    /// The clock never had a traceable reference
+    FreeRunning,
+    /// The clock is locked to an external reference, or follows an upstream
+    /// master with a traceable time
+    Locked,
+    /// The external reference is lost, but the estimated time error is still
+    /// within the budget
+    HoldoverInSpec,
+    /// The external reference is lost and the estimated time error exceeds
+    /// the budget
+    HoldoverOutOfSpec,
+}
+
+#[derive(Debug)]
+pub(crate) struct Holdover {
+    config: Option<HoldoverConfig>,
+    external_reference: Option<f64>,
+    state: HoldoverState,
+    locked_externally: bool,
+    frequency_uncertainty: f64,
+    holdover_time: Duration,
+}
+
+impl Holdover {
+    pub(crate) fn new() -> Self {
+        Self {
+            config: None,
+            external_reference: None,
+            state: HoldoverState::FreeRunning,
+            locked_externally: false,
+            frequency_uncertainty: 0.0,
+            holdover_time: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn set_config(&mut self, config: Option<HoldoverConfig>) {
+        self.config = config;
+    }
+
+    pub(crate) fn set_external_reference(&mut self, frequency_uncertainty: Option<f64>) {
+        self.external_reference = frequency_uncertainty;
+    }
+
+    /// The current state, or `None` when holdover is not enabled
+    pub(crate) fn state(&self) -> Option<HoldoverState> {
+        self.config.map(|_| self.state)
+    }
+
+    /// Advance the state machine by `step`
+    ///
+    /// `upstream` is the frequency uncertainty of the clock relative to a
+    /// traceable upstream master, if the instance currently follows one. The
+    /// clock class of `default_ds` is updated to the new state.
+    pub(crate) fn step(
+        &mut self,
+        upstream: Option<f64>,
+        step: Duration,
+        default_ds: &mut InternalDefaultDS,
+    ) {
+        let Some(config) = self.config else {
+            return;
+        };
+
+        let previous = self.state;
+
+        if let Some(frequency_uncertainty) = self.external_reference.or(upstream) {
+            self.state = HoldoverState::Locked;
+            self.locked_externally = self.external_reference.is_some();
+            self.frequency_uncertainty = frequency_uncertainty;
+            self.holdover_time = Duration::ZERO;
+        } else {
+            match self.state {
+                HoldoverState::FreeRunning | HoldoverState::HoldoverOutOfSpec => {}
+                // Without a reference of its own the clock only ever followed an
+                // upstream master, whose loss leaves it running free
+                HoldoverState::Locked if !self.locked_externally => {
+                    self.state = HoldoverState::FreeRunning;
+                }
+                HoldoverState::Locked | HoldoverState::HoldoverInSpec => {
+                    // The reference was lost somewhere during this step, so it counts
+                    // towards the holdover time as well
+                    self.holdover_time += step;
+
+                    let time_error = self.frequency_uncertainty * self.holdover_time.seconds();
+                    self.state = if time_error.abs() > config.budget.seconds() {
+                        HoldoverState::HoldoverOutOfSpec
+                    } else {
+                        HoldoverState::HoldoverInSpec
+                    };
+                }
+            }
+        }
+
+        if self.state != previous {
+            log::info!("Holdover state: {:?} -> {:?}", previous, self.state);
+        }
+
+        default_ds.clock_quality.clock_class = match self.state {
+            // While following an upstream master our own class should not
+            // compete with it
+            HoldoverState::Locked if self.locked_externally => config.classes.locked,
+            HoldoverState::Locked | HoldoverState::FreeRunning => config.classes.free_running,
+            HoldoverState::HoldoverInSpec => config.classes.holdover_in_spec,
+            HoldoverState::HoldoverOutOfSpec => config.classes.holdover_out_of_spec,
+        };
+    }
+
+    /// Update the traceability of the time distributed while the instance is
+    /// the grandmaster
+    pub(crate) fn apply_traceability(&self, time_properties_ds: &mut TimePropertiesDS) {
+        if self.config.is_none() {
+            return;
+        }
+
+        let traceable = match self.state {
+            HoldoverState::Locked => self.locked_externally,
+            HoldoverState::HoldoverInSpec => true,
+            HoldoverState::FreeRunning | HoldoverState::HoldoverOutOfSpec => false,
+        };
+
+        time_properties_ds.time_traceable = traceable;
+        time_properties_ds.frequency_traceable = traceable;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ClockIdentity, ClockQuality, InstanceConfig};
+
+    fn default_ds() -> InternalDefaultDS {
+        InternalDefaultDS::new(InstanceConfig {
+            clock_identity: ClockIdentity([1; 8]),
+            priority_1: 128,
+            priority_2: 128,
+            domain_number: 0,
+            slave_only: false,
+            sdo_id: Default::default(),
+            path_trace: false,
+            external_port_configuration: false,
+            clock_quality: ClockQuality::default(),
+            management_set_policy: Default::default(),
+            dataset_comparison: Default::default(),
+        })
+    }
+
+    fn holdover() -> Holdover {
+        let mut holdover = Holdover::new();
+        holdover.set_config(Some(HoldoverConfig {
+            classes: HoldoverClasses::DEGRADATION_A,
+            budget: Duration::from_micros(1),
+        }));
+        holdover
+    }
+
+    #[test]
+    fn disabled_holdover_keeps_clock_class() {
+        let mut holdover = Holdover::new();
+        let mut default_ds = default_ds();
+        default_ds.clock_quality.clock_class = 13;
+
+        holdover.set_external_reference(Some(1e-9));
+        holdover.step(None, Duration::from_secs(1), &mut default_ds);
+        assert_eq!(holdover.state(), None);
+        assert_eq!(default_ds.clock_quality.clock_class, 13);
+
+        let mut time_properties_ds = TimePropertiesDS::default();
+        holdover.apply_traceability(&mut time_properties_ds);
+        assert!(!time_properties_ds.time_traceable);
+    }
+
+    #[test]
+    fn external_reference_degrades_after_budget() {
+        let mut holdover = holdover();
+        let mut default_ds = default_ds();
+        let mut time_properties_ds = TimePropertiesDS::default();
+        let step = Duration::from_secs(1);
+
+        holdover.step(None, step, &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::FreeRunning));
+        assert_eq!(default_ds.clock_quality.clock_class, 248);
+
+        // 1ppb allows for 1000 seconds of holdover within 1us
+        holdover.set_external_reference(Some(1e-9));
+        holdover.step(None, step, &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::Locked));
+        assert_eq!(default_ds.clock_quality.clock_class, 6);
+        holdover.apply_traceability(&mut time_properties_ds);
+        assert!(time_properties_ds.time_traceable);
+        assert!(time_properties_ds.frequency_traceable);
+
+        holdover.set_external_reference(None);
+        holdover.step(None, step, &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::HoldoverInSpec));
+        assert_eq!(default_ds.clock_quality.clock_class, 7);
+        holdover.apply_traceability(&mut time_properties_ds);
+        assert!(time_properties_ds.time_traceable);
+
+        holdover.step(None, Duration::from_secs(998), &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::HoldoverInSpec));
+
+        holdover.step(None, Duration::from_secs(2), &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::HoldoverOutOfSpec));
+        assert_eq!(default_ds.clock_quality.clock_class, 52);
+        holdover.apply_traceability(&mut time_properties_ds);
+        assert!(!time_properties_ds.time_traceable);
+        assert!(!time_properties_ds.frequency_traceable);
+
+        // Out of spec stays out of spec until the reference returns
+        holdover.step(None, step, &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::HoldoverOutOfSpec));
+
+        holdover.set_external_reference(Some(1e-9));
+        holdover.step(None, step, &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::Locked));
+        assert_eq!(default_ds.clock_quality.clock_class, 6);
+    }
+
+    #[test]
+    fn upstream_master_loss() {
+        let mut holdover = holdover();
+        let mut default_ds = default_ds();
+        let mut time_properties_ds = TimePropertiesDS::default();
+        let step = Duration::from_secs(1);
+
+        // Following a master does not compete with it
+        holdover.step(Some(1e-7), step, &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::Locked));
+        assert_eq!(default_ds.clock_quality.clock_class, 248);
+        holdover.apply_traceability(&mut time_properties_ds);
+        assert!(!time_properties_ds.time_traceable);
+
+        // Without a reference of its own, the clock runs free again
+        holdover.step(None, step, &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::FreeRunning));
+        assert_eq!(default_ds.clock_quality.clock_class, 248);
+        holdover.apply_traceability(&mut time_properties_ds);
+        assert!(!time_properties_ds.time_traceable);
+    }
+
+    #[test]
+    fn boundary_clock_upstream_loss() {
+        let mut holdover = Holdover::new();
+        holdover.set_config(Some(HoldoverConfig {
+            classes: HoldoverClasses::G8275_BOUNDARY_CLOCK,
+            budget: Duration::from_micros(1),
+        }));
+        let mut default_ds = default_ds();
+        let step = Duration::from_secs(1);
+
+        // A boundary clock that only follows its upstream master
+        holdover.step(Some(1e-9), step, &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::Locked));
+        assert_eq!(default_ds.clock_quality.clock_class, 248);
+
+        // Does not announce holdover in spec when it loses that master
+        holdover.step(None, step, &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::FreeRunning));
+        assert_eq!(default_ds.clock_quality.clock_class, 248);
+
+        holdover.step(None, Duration::from_secs(10), &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::FreeRunning));
+        assert_eq!(default_ds.clock_quality.clock_class, 248);
+
+        // Only the loss of its own reference starts holdover
+        holdover.set_external_reference(Some(1e-9));
+        holdover.step(Some(1e-9), step, &mut default_ds);
+        assert_eq!(default_ds.clock_quality.clock_class, 6);
+
+        holdover.set_external_reference(None);
+        holdover.step(Some(1e-9), step, &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::Locked));
+        assert_eq!(default_ds.clock_quality.clock_class, 248);
+
+        holdover.step(None, step, &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::FreeRunning));
+        assert_eq!(default_ds.clock_quality.clock_class, 248);
+    }
+
+    #[test]
+    fn reference_loss_step_counts_towards_holdover() {
+        let mut holdover = holdover();
+        let mut default_ds = default_ds();
+
+        holdover.set_external_reference(Some(1e-7));
+        holdover.step(None, Duration::from_secs(1), &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::Locked));
+
+        // The reference was lost during a step longer than the 10 seconds 100ppb
+        // allows for
+        holdover.set_external_reference(None);
+        holdover.step(None, Duration::from_secs(11), &mut default_ds);
+        assert_eq!(holdover.state(), Some(HoldoverState::HoldoverOutOfSpec));
+        assert_eq!(default_ds.clock_quality.clock_class, 52);
+    }
+}
@@ -89,6 +89,7 @@ pub mod config;
 pub(crate) mod datastructures;
 pub mod filters;
 mod float_polyfill;
+mod holdover;
 pub mod observability;
 mod overlay_clock;
 pub mod port;
@@ -96,12 +97,14 @@ mod ptp_instance;
 #[cfg(feature = "std")]
 mod shared_clock;
 pub mod time;
+pub mod transparent_clock;
 
 pub use clock::Clock;
 pub use overlay_clock::OverlayClock;
 pub use ptp_instance::{PtpInstance, PtpInstanceState, PtpInstanceStateMutex};
 #[cfg(feature = "std")]
 pub use shared_clock::SharedClock;
+pub use transparent_clock::TransparentClock;
 
 /// Helper types used for fuzzing
 ///
@@ -7,6 +7,9 @@ use crate::datastructures::datasets::InternalDefaultDS;
 #[derive(Debug, Copy, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DefaultDS {
+    /// Whether this PTP instance sends two-step sync messages on any of its
+    /// ports. See *IEEE1588-2019 section 8.2.1.2.1*.
+    pub two_step_flag: bool,
     /// The identity of a PTP node.
     /// See *IEEE1588-2019 section 8.2.1.2.2*.
     pub clock_identity: crate::config::ClockIdentity,
@@ -25,11 +28,15 @@ pub struct DefaultDS {
     pub slave_only: bool,
     /// See *IEEE1588-2019 section 7.1.4 table 2*.
     pub sdo_id: crate::config::SdoId,
+    /// Whether the port states are configured externally instead of by the
+    /// BMCA. See *IEEE1588-2019 section 17.6.2*.
+    pub external_port_configuration: bool,
 }
 
 impl From<&InternalDefaultDS> for DefaultDS {
     fn from(v: &InternalDefaultDS) -> Self {
         Self {
+            two_step_flag: v.two_step_flag,
             clock_identity: v.clock_identity,
             number_ports: v.number_ports,
             clock_quality: v.clock_quality,
@@ -38,6 +45,7 @@ impl From<&InternalDefaultDS> for DefaultDS {
             domain_number: v.domain_number,
             slave_only: v.slave_only,
             sdo_id: v.sdo_id,
+            external_port_configuration: v.external_port_configuration,
         }
     }
 }
@@ -12,4 +12,10 @@ pub mod parent;
 /// 8.2.15)
 pub mod port;
 
-pub use crate::datastructures::datasets::PathTraceDS;
+pub use crate::{
+    datastructures::{
+        common::{MasterLockingStatus, PowerProfile, SynchronizationMetadata},
+        datasets::{AlternateTimescaleOffsetsDS, PathTraceDS},
+    },
+    holdover::HoldoverState,
+};
@@ -1,4 +1,7 @@
-use crate::datastructures::common::{PortIdentity, TimeInterval};
+use crate::{
+    config::{ClockIdentity, ClockQuality},
+    datastructures::common::{PortIdentity, TimeInterval},
+};
 
 /// Type for `[PortDS].port_state`, see also *IEEE1588-2019 section 8.2.15.3.1
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -70,3 +73,66 @@ pub struct PortDS {
     /// See *IEEE1588-2019 section 8.2.15.5.2*
     pub master_only: bool,
 }
+
+/// A foreign master known to a port
+///
+/// The grandmaster fields are taken from the most recent announce message
+/// received from the foreign master.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ForeignMaster {
+    /// Identity of the port that sent the announce messages
+    pub foreign_master_port_identity: PortIdentity,
+    /// Number of announce messages from this foreign master within the
+    /// qualification window
+    pub announce_messages: usize,
+    /// Whether enough announce messages were received for this foreign master
+    /// to be considered by the BMCA
+    pub qualified: bool,
+    /// See *IEEE1588-2019 section 8.2.3.6*.
+    pub grandmaster_identity: ClockIdentity,
+    /// See *IEEE1588-2019 section 8.2.3.7*.
+    pub grandmaster_clock_quality: ClockQuality,
+    /// See *IEEE1588-2019 section 8.2.3.8*.
+    pub grandmaster_priority_1: u8,
+    /// See *IEEE1588-2019 section 8.2.3.9*.
+    pub grandmaster_priority_2: u8,
+    /// See *IEEE1588-2019 section 8.2.2.2*.
+    pub steps_removed: u16,
+}
+
+/// Time error of a slave, as reported by its slave event monitoring messages
+///
+/// See also *IEEE1588-2019 section 16.11*
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlaveReport {
+    /// Identity of the slave port that sent the reports
+    pub slave_port_identity: PortIdentity,
+    /// Sequence id of the most recent sync message reported on
+    pub sequence_id: u16,
+    /// Offset of the slave from this master, if it could be computed
+    pub offset_from_master: Option<TimeInterval>,
+    /// Mean path delay between this master and the slave, if it could be
+    /// computed
+    pub mean_path_delay: Option<TimeInterval>,
+}
+
+/// Offset to an alternate master, measured from the sync messages it sends
+/// with the alternateMasterFlag set while this port is slave
+///
+/// The offset assumes the same mean path delay as the current master. See
+/// also *IEEE1588-2019 section 17.3*
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlternateMaster {
+    /// Identity of the port that sent the sync messages
+    pub alternate_master_port_identity: PortIdentity,
+    /// Number of offsets measured to this alternate master
+    pub measurements: u32,
+    /// Most recent offset from the alternate master, if it could be computed
+    pub offset_from_alternate_master: Option<TimeInterval>,
+    /// Moving average of the offset from the alternate master, if it could be
+    /// computed
+    pub mean_offset_from_alternate_master: Option<TimeInterval>,
+}
@@ -3,8 +3,10 @@ use core::iter::Fuse;
 use arrayvec::ArrayVec;
 
 use crate::{
-    datastructures::common::{PortIdentity, Tlv, TlvSetIterator},
+    config::PortAddress,
+    datastructures::common::{PortIdentity, PowerProfile, Tlv, TlvSetIterator},
     filters::FilterUpdate,
+    time::Time,
 };
 
 #[derive(Debug, Clone)]
@@ -48,8 +50,9 @@ impl ForwardedTLVProvider for NoForwardedTLVs {
 
 /// Identification of a packet that should be sent out.
 ///
-/// The caller receives this from a [`PortAction::SendEvent`] and should return
-/// it to the [`Port`](`super::Port`) with
+/// The caller receives this from a [`PortAction::SendEvent`] or
+/// [`PortAction::SendEventUnicast`] and should return it to the
+/// [`Port`](`super::Port`) with
 /// [`Port::handle_send_timestamp`](`super::Port::handle_send_timestamp`) once
 /// the transmit timestamp of that packet is known.
 ///
@@ -66,6 +69,10 @@ pub(super) enum TimestampContextInner {
     Sync {
         id: u16,
     },
+    UnicastSync {
+        id: u16,
+        address: PortAddress,
+    },
     DelayReq {
         id: u16,
     },
@@ -105,6 +112,38 @@ pub enum PortAction<'a> {
     /// for sending peer to peer delay mechanism messages of the relevant
     /// transport specification of PTP.
     SendGeneral { data: &'a [u8], link_local: bool },
+    /// Send a time-critical packet to a single other port
+    ///
+    /// This is handled like [`PortAction::SendEvent`], except that the packet
+    /// is sent to `address` instead of the multicast address of the transport.
+    SendEventUnicast {
+        context: TimestampContext,
+        data: &'a [u8],
+        address: PortAddress,
+    },
+    /// Send a one-step time-critical packet
+    ///
+    /// The packet contains `launch_time` as its origin timestamp. The user
+    /// should either have the hardware replace that timestamp with the actual
+    /// transmit timestamp, or send the packet as close as possible to
+    /// `launch_time` according to the clock of the port. No transmit timestamp
+    /// needs to be returned.
+    ///
+    /// Link local packets are handled as for [`PortAction::SendEvent`].
+    SendEventOneStep {
+        data: &'a [u8],
+        launch_time: Time,
+        link_local: bool,
+    },
+    /// Send a general packet to a single other port
+    ///
+    /// This is handled like [`PortAction::SendGeneral`], except that the
+    /// packet is sent to `address` instead of the multicast address of the
+    /// transport.
+    SendGeneralUnicast {
+        data: &'a [u8],
+        address: PortAddress,
+    },
     /// Call [`Port::handle_announce_timer`](`super::Port::handle_announce_timer`) in `duration` from now
     ResetAnnounceTimer { duration: core::time::Duration },
     /// Call [`Port::handle_sync_timer`](`super::Port::handle_sync_timer`) in
@@ -116,6 +155,12 @@ pub enum PortAction<'a> {
     ResetAnnounceReceiptTimer { duration: core::time::Duration },
     /// Call [`Port::handle_filter_update_timer`](`super::Port::handle_filter_update_timer`) in `duration` from now
     ResetFilterUpdateTimer { duration: core::time::Duration },
+    /// Call [`Port::handle_unicast_timer`](`super::Port::handle_unicast_timer`)
+    /// in `duration` from now
+    ResetUnicastTimer { duration: core::time::Duration },
+    /// Call [`Port::handle_synchronization_metadata_timer`](`super::Port::handle_synchronization_metadata_timer`)
+    /// in `duration` from now
+    ResetSynchronizationMetadataTimer { duration: core::time::Duration },
     /// Forward this TLV to the announce timer call of all other ports.
     /// The receiver must ensure the TLV is yielded only once to the announce
     /// method of a port.
@@ -125,7 +170,7 @@ pub enum PortAction<'a> {
     ForwardTLV { tlv: ForwardedTLV<'a> },
 }
 
-const MAX_ACTIONS: usize = 2;
+const MAX_ACTIONS: usize = 3;
 
 /// An Iterator over [`PortAction`]s
 ///
@@ -187,7 +232,9 @@ impl<'a> Iterator for PortActionIterator<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         self.internal.next().or_else(|| loop {
             let tlv = self.tlvs.next()?;
-            if tlv.tlv_type.announce_propagate() {
+            // IEEE_C37_238 TLVs are passed on by boundary clocks after adding their
+            // own time inaccuracy
+            if tlv.tlv_type.announce_propagate() || PowerProfile::is_power_profile_tlv(&tlv) {
                 return Some(PortAction::ForwardTLV {
                     tlv: ForwardedTLV {
                         tlv,
@@ -0,0 +1,478 @@
+//! Alternate masters that send sync messages next to the master of a link
+//!
+//! See *IEEE1588-2019 section 17.3*
+
+use arrayvec::ArrayVec;
+
+use super::{
+    state::{PortState, SlaveState},
+    InBmca, Port, Running,
+};
+use crate::{
+    config::{AcceptableMasterList, MAX_ALTERNATE_MASTERS},
+    datastructures::{
+        common::PortIdentity,
+        datasets::InternalDefaultDS,
+        messages::{FollowUpMessage, Header, SyncMessage},
+    },
+    filters::Filter,
+    observability::port::AlternateMaster,
+    time::{Duration, Time},
+};
+
+/// Weight of a new offset in the moving average of the offset to an
+/// alternate master
+const MEAN_OFFSET_WEIGHT: i32 = 8;
+
+/// Alternate master status of a port and the alternate masters it measures
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct AlternateMasterState {
+    /// Number of foreign masters on the link that were better than this
+    /// instance during the last BMCA run
+    better_masters: usize,
+    /// Alternate masters measured while slave
+    alternate_masters: ArrayVec<AlternateMasterStatistics, MAX_ALTERNATE_MASTERS>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AlternateMasterStatistics {
+    port_identity: PortIdentity,
+    /// Sequence id, send time and receive time of the last sync message
+    sync: Option<(u16, Option<Time>, Option<Time>)>,
+    /// Time since the last sync message of this alternate master
+    age: Duration,
+    measurements: u32,
+    last_raw_sync_offset: Option<Duration>,
+    offset: Option<Duration>,
+    mean_offset: Option<Duration>,
+}
+
+impl AlternateMasterStatistics {
+    fn new(port_identity: PortIdentity) -> Self {
+        Self {
+            port_identity,
+            sync: None,
+            age: Duration::ZERO,
+            measurements: 0,
+            last_raw_sync_offset: None,
+            offset: None,
+            mean_offset: None,
+        }
+    }
+
+    fn record_offset(&mut self, raw_sync_offset: Duration, mean_delay: Option<Duration>) {
+        self.sync = None;
+        self.measurements = self.measurements.saturating_add(1);
+        self.last_raw_sync_offset = Some(raw_sync_offset);
+
+        if let Some(mean_delay) = mean_delay {
+            let offset = raw_sync_offset - mean_delay;
+            self.offset = Some(offset);
+            self.mean_offset = Some(match self.mean_offset {
+                Some(mean_offset) => mean_offset + (offset - mean_offset) / MEAN_OFFSET_WEIGHT,
+                None => offset,
+            });
+        }
+    }
+
+    fn observable(&self) -> AlternateMaster {
+        AlternateMaster {
+            alternate_master_port_identity: self.port_identity,
+            measurements: self.measurements,
+            offset_from_alternate_master: self.offset.map(Into::into),
+            mean_offset_from_alternate_master: self.mean_offset.map(Into::into),
+        }
+    }
+}
+
+impl AlternateMasterState {
+    pub(super) fn new() -> Self {
+        Self {
+            better_masters: 0,
+            alternate_masters: ArrayVec::new(),
+        }
+    }
+
+    pub(super) fn alternate_masters(&self) -> impl Iterator<Item = AlternateMaster> + '_ {
+        self.alternate_masters
+            .iter()
+            .map(AlternateMasterStatistics::observable)
+    }
+
+    /// Forget the alternate masters that did not send a sync message within
+    /// `timeout`
+    pub(super) fn step_age(&mut self, step: Duration, timeout: Duration) {
+        for alternate_master in &mut self.alternate_masters {
+            alternate_master.age += step;
+        }
+        self.alternate_masters
+            .retain(|alternate_master| alternate_master.age <= timeout);
+    }
+
+    /// Find the statistics of an alternate master, adding it when there is
+    /// room for `max_alternate_masters`
+    fn get_or_insert(
+        &mut self,
+        port_identity: PortIdentity,
+        max_alternate_masters: u8,
+    ) -> Option<&mut AlternateMasterStatistics> {
+        let index = match self
+            .alternate_masters
+            .iter()
+            .position(|alternate_master| alternate_master.port_identity == port_identity)
+        {
+            Some(index) => index,
+            None => {
+                let max = MAX_ALTERNATE_MASTERS.min(max_alternate_masters as usize);
+                if self.alternate_masters.len() >= max {
+                    return None;
+                }
+                self.alternate_masters
+                    .push(AlternateMasterStatistics::new(port_identity));
+                self.alternate_masters.len() - 1
+            }
+        };
+
+        Some(&mut self.alternate_masters[index])
+    }
+
+    fn take(&mut self, port_identity: PortIdentity) -> Option<AlternateMasterStatistics> {
+        let index = self
+            .alternate_masters
+            .iter()
+            .position(|alternate_master| alternate_master.port_identity == port_identity)?;
+        Some(self.alternate_masters.remove(index))
+    }
+}
+
+impl<L, A, R, C, F: Filter, S> Port<'_, L, A, R, C, F, S> {
+    /// Whether this port sends announce, sync and follow up messages as an
+    /// alternate master
+    pub(super) fn is_alternate_master(&self) -> bool {
+        let number_alternate_masters = self.config.number_alternate_masters as usize;
+
+        matches!(self.port_state, PortState::Passive)
+            && self.multiport_disable.is_none()
+            && (1..=number_alternate_masters).contains(&self.alternate_master.better_masters)
+    }
+
+    /// Whether this port is passive, but may become an alternate master at the
+    /// next BMCA run
+    pub(super) fn may_become_alternate_master(&self) -> bool {
+        matches!(self.port_state, PortState::Passive)
+            && self.config.number_alternate_masters > 0
+            && !self.is_alternate_master()
+    }
+
+    /// Switch to a new master without resetting the filter when it was
+    /// measured as an alternate master. The clock is already synchronized to
+    /// the alternate master, so its filter state remains valid.
+    pub(super) fn fail_over_to_alternate_master(&mut self, remote_master: PortIdentity) -> bool {
+        if !matches!(self.port_state, PortState::Slave(_)) {
+            return false;
+        }
+
+        match self.alternate_master.take(remote_master) {
+            Some(AlternateMasterStatistics {
+                measurements: 1..,
+                last_raw_sync_offset,
+                ..
+            }) => {
+                log::info!(
+                    "port {} fails over to alternate master {:?}",
+                    self.port_identity.port_number,
+                    remote_master.clock_identity,
+                );
+
+                let mut state = SlaveState::new(remote_master);
+                state.last_raw_sync_offset = last_raw_sync_offset;
+                self.port_state = PortState::Slave(state);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<A: AcceptableMasterList, R, C, F: Filter, S> Port<'_, InBmca, A, R, C, F, S> {
+    /// Determine the rank of this port among the masters on its link
+    ///
+    /// This uses the qualified announce messages, so it has to happen before
+    /// [`calculate_best_local_announce_message`](`Port::calculate_best_local_announce_message`).
+    pub(crate) fn rank_alternate_master(&mut self, default_ds: &InternalDefaultDS) {
+        self.alternate_master.better_masters = self.bmca.count_better_foreign_masters(default_ds);
+    }
+}
+
+impl<A, R, C, F: Filter, S> Port<'_, InBmca, A, R, C, F, S> {
+    pub(super) fn step_alternate_master_age(&mut self, step: Duration) {
+        let timeout =
+            self.config.announce_interval.as_duration() * self.config.announce_receipt_timeout;
+        self.alternate_master.step_age(step, timeout);
+    }
+}
+
+impl<A, R, C, F: Filter, S> Port<'_, Running, A, R, C, F, S> {
+    /// Measure the offset to an alternate master from its sync message
+    pub(super) fn handle_alternate_sync(
+        &mut self,
+        header: Header,
+        message: SyncMessage,
+        recv_time: Time,
+    ) {
+        let mean_delay = self.mean_delay;
+        let delay_asymmetry = self.config.delay_asymmetry;
+        let Some(alternate_master) = self.alternate_master.get_or_insert(
+            header.source_port_identity,
+            self.config.number_alternate_masters,
+        ) else {
+            return;
+        };
+
+        alternate_master.age = Duration::ZERO;
+        let recv_time = recv_time - Duration::from(header.correction_field);
+
+        if !header.two_step_flag {
+            let send_time = Time::from(message.origin_timestamp);
+            alternate_master.record_offset(recv_time - send_time - delay_asymmetry, mean_delay);
+            return;
+        }
+
+        match alternate_master.sync {
+            Some((id, Some(send_time), None)) if id == header.sequence_id => {
+                alternate_master.record_offset(recv_time - send_time - delay_asymmetry, mean_delay);
+            }
+            _ => alternate_master.sync = Some((header.sequence_id, None, Some(recv_time))),
+        }
+    }
+
+    /// Measure the offset to an alternate master from the follow up of its
+    /// sync message
+    pub(super) fn handle_alternate_follow_up(&mut self, header: Header, message: FollowUpMessage) {
+        let mean_delay = self.mean_delay;
+        let delay_asymmetry = self.config.delay_asymmetry;
+        let Some(alternate_master) = self.alternate_master.get_or_insert(
+            header.source_port_identity,
+            self.config.number_alternate_masters,
+        ) else {
+            return;
+        };
+
+        let send_time =
+            Time::from(message.precise_origin_timestamp) + Duration::from(header.correction_field);
+
+        match alternate_master.sync {
+            Some((id, None, Some(recv_time))) if id == header.sequence_id => {
+                alternate_master.record_offset(recv_time - send_time - delay_asymmetry, mean_delay);
+            }
+            _ => alternate_master.sync = Some((header.sequence_id, Some(send_time), None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        datastructures::{
+            common::{ClockIdentity, TimeInterval},
+            messages::{Message, MessageBody},
+        },
+        port::{
+            actions::TimestampContextInner,
+            tests::{setup_test_port, setup_test_state},
+            NoForwardedTLVs, PortAction,
+        },
+    };
+
+    fn alternate_master(port_number: u16) -> PortIdentity {
+        PortIdentity {
+            clock_identity: ClockIdentity([1; 8]),
+            port_number,
+        }
+    }
+
+    #[test]
+    fn test_alternate_master_statistics() {
+        let mut statistics = AlternateMasterStatistics::new(alternate_master(1));
+
+        // Without a path delay there is no offset yet
+        statistics.record_offset(Duration::from_micros(10), None);
+        assert_eq!(statistics.measurements, 1);
+        assert_eq!(statistics.offset, None);
+
+        statistics.record_offset(Duration::from_micros(10), Some(Duration::from_micros(2)));
+        assert_eq!(statistics.offset, Some(Duration::from_micros(8)));
+        assert_eq!(statistics.mean_offset, Some(Duration::from_micros(8)));
+
+        statistics.record_offset(Duration::from_micros(18), Some(Duration::from_micros(2)));
+        assert_eq!(statistics.measurements, 3);
+        assert_eq!(statistics.offset, Some(Duration::from_micros(16)));
+        assert_eq!(statistics.mean_offset, Some(Duration::from_micros(9)));
+        assert_eq!(
+            statistics.last_raw_sync_offset,
+            Some(Duration::from_micros(18))
+        );
+    }
+
+    #[test]
+    fn test_alternate_master_table() {
+        let mut state = AlternateMasterState::new();
+
+        assert!(state.get_or_insert(alternate_master(1), 0).is_none());
+        assert!(state.get_or_insert(alternate_master(1), 2).is_some());
+        assert!(state.get_or_insert(alternate_master(2), 2).is_some());
+        assert!(state.get_or_insert(alternate_master(3), 2).is_none());
+        assert!(state.get_or_insert(alternate_master(1), 2).is_some());
+        assert_eq!(state.alternate_masters().count(), 2);
+
+        // Alternate masters that stop sending are forgotten
+        state.step_age(Duration::from_secs(4), Duration::from_secs(6));
+        state.get_or_insert(alternate_master(2), 2).unwrap().age = Duration::ZERO;
+        state.step_age(Duration::from_secs(4), Duration::from_secs(6));
+        let remaining: ArrayVec<_, MAX_ALTERNATE_MASTERS> = state
+            .alternate_masters()
+            .map(|alternate_master| alternate_master.alternate_master_port_identity)
+            .collect();
+        assert_eq!(remaining.as_slice(), [alternate_master(2)]);
+
+        assert!(state.take(alternate_master(2)).is_some());
+        assert!(state.take(alternate_master(2)).is_none());
+    }
+
+    #[test]
+    fn test_send_as_alternate_master() {
+        let state = setup_test_state();
+        let mut port = setup_test_port(&state);
+        port.config.number_alternate_masters = 1;
+        port.set_forced_port_state(PortState::Passive);
+
+        // Not an alternate master, but keeps the timers running
+        port.alternate_master.better_masters = 2;
+        let mut actions = port.send_sync();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetSyncTimer { .. })
+        ));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        port.alternate_master.better_masters = 1;
+        let mut actions = port.send_sync();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetSyncTimer { .. })
+        ));
+        let Some(PortAction::SendEvent { context, data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let sync = Message::deserialize(data).unwrap();
+        assert!(matches!(sync.body, MessageBody::Sync(_)));
+        assert!(sync.header.alternate_master_flag);
+
+        let TimestampContextInner::Sync { id } = context.inner else {
+            panic!("Wrong type of context");
+        };
+        let mut actions = port.handle_sync_timestamp(id, Time::from_micros(100));
+        let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let follow_up = Message::deserialize(data).unwrap();
+        assert!(matches!(follow_up.body, MessageBody::FollowUp(_)));
+        assert!(follow_up.header.alternate_master_flag);
+
+        let mut actions = port.send_announce(&mut NoForwardedTLVs);
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetAnnounceTimer { .. })
+        ));
+        let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let announce = Message::deserialize(data).unwrap();
+        assert!(matches!(announce.body, MessageBody::Announce(_)));
+        assert!(announce.header.alternate_master_flag);
+
+        // Without alternate masters a passive port stays silent
+        port.config.number_alternate_masters = 0;
+        assert!(port.send_sync().next().is_none());
+        assert!(port.send_announce(&mut NoForwardedTLVs).next().is_none());
+    }
+
+    #[test]
+    fn test_measure_alternate_master() {
+        let state = setup_test_state();
+        let mut port = setup_test_port(&state);
+        port.config.number_alternate_masters = 1;
+        port.mean_delay = Some(Duration::from_micros(20));
+        port.set_forced_port_state(PortState::Slave(SlaveState::new(alternate_master(1))));
+
+        // Sync messages of other masters are ignored
+        let header = Header {
+            source_port_identity: alternate_master(2),
+            two_step_flag: true,
+            sequence_id: 15,
+            ..Header::new(1)
+        };
+        let sync = SyncMessage {
+            origin_timestamp: Time::from_micros(0).into(),
+        };
+        assert!(port
+            .handle_sync(header, sync, Time::from_micros(60))
+            .next()
+            .is_none());
+        assert_eq!(port.alternate_masters().count(), 0);
+
+        let header = Header {
+            alternate_master_flag: true,
+            ..header
+        };
+        assert!(port
+            .handle_sync(header, sync, Time::from_micros(60))
+            .next()
+            .is_none());
+        let follow_up = FollowUpMessage {
+            precise_origin_timestamp: Time::from_micros(8).into(),
+        };
+        let follow_up_header = Header {
+            correction_field: TimeInterval(2000.into()),
+            ..header
+        };
+        assert!(port
+            .handle_follow_up(follow_up_header, follow_up, None)
+            .next()
+            .is_none());
+
+        let alternate_masters: ArrayVec<_, MAX_ALTERNATE_MASTERS> =
+            port.alternate_masters().collect();
+        assert_eq!(alternate_masters.len(), 1);
+        assert_eq!(
+            alternate_masters[0].alternate_master_port_identity,
+            alternate_master(2)
+        );
+        assert_eq!(alternate_masters[0].measurements, 1);
+        assert_eq!(
+            alternate_masters[0].offset_from_alternate_master,
+            Some(Duration::from_micros(30).into())
+        );
+
+        // Failing over to the measured alternate master
+        assert!(!port.fail_over_to_alternate_master(alternate_master(3)));
+        assert!(port.fail_over_to_alternate_master(alternate_master(2)));
+        let PortState::Slave(state) = &port.port_state else {
+            panic!("Unexpected port state");
+        };
+        assert_eq!(state.remote_master, alternate_master(2));
+        assert_eq!(state.last_raw_sync_offset, Some(Duration::from_micros(50)));
+        assert_eq!(port.alternate_masters().count(), 0);
+    }
+}
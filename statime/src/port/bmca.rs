@@ -2,11 +2,14 @@ use rand::Rng;
 
 use super::{InBmca, Port, PortActionIterator, Running};
 use crate::{
-    bmc::bmca::{BestAnnounceMessage, RecommendedState},
-    config::{AcceptableMasterList, LeapIndicator, TimePropertiesDS, TimeSource},
+    bmc::bmca::{BestAnnounceMessage, Bmca, RecommendedState},
+    config::{AcceptableMasterList, DesiredState, LeapIndicator, TimePropertiesDS, TimeSource},
     datastructures::{
-        common::{ClockIdentity, TlvType},
-        datasets::{InternalCurrentDS, InternalDefaultDS, InternalParentDS, PathTraceDS},
+        common::{AlternateTimescale, ClockIdentity, PowerProfile, TlvType},
+        datasets::{
+            InternalCurrentDS, InternalDefaultDS, InternalParentDS, PathTraceDS,
+            MAX_ALTERNATE_TIMESCALES,
+        },
         messages::Message,
     },
     filters::Filter,
@@ -42,6 +45,12 @@ impl<A: AcceptableMasterList, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMu
 
                 current_ds.steps_removed = announce.steps_removed + 1;
 
+                // Synchronization metadata is only received with management messages,
+                // and does not outlive the grandmaster it came from
+                if parent_ds.grandmaster_identity != announce.grandmaster_identity {
+                    state.current_synchronization_metadata = None;
+                }
+
                 parent_ds.parent_port_identity = announce.header.source_port_identity;
                 parent_ds.grandmaster_identity = announce.grandmaster_identity;
                 parent_ds.grandmaster_clock_quality = announce.grandmaster_clock_quality;
@@ -50,6 +59,32 @@ impl<A: AcceptableMasterList, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMu
 
                 *time_properties_ds = announce.time_properties();
 
+                state.alternate_timescale_offsets_ds.list = message
+                    .suffix
+                    .tlv()
+                    .filter(|tlv| tlv.tlv_type == TlvType::AlternateTimeOffsetIndicator)
+                    .filter_map(|tlv| match AlternateTimescale::from_tlv(&tlv) {
+                        Ok(alternate_timescale) => Some(alternate_timescale),
+                        Err(error) => {
+                            log::warn!("Invalid alternate time offset indicator: {error}");
+                            None
+                        }
+                    })
+                    .take(MAX_ALTERNATE_TIMESCALES)
+                    .collect();
+
+                state.received_power_profile = message
+                    .suffix
+                    .tlv()
+                    .find(PowerProfile::is_power_profile_tlv)
+                    .and_then(|tlv| match PowerProfile::from_tlv(&tlv) {
+                        Ok(power_profile) => Some(power_profile),
+                        Err(error) => {
+                            log::warn!("Invalid IEEE C37.238 TLV: {error}");
+                            None
+                        }
+                    });
+
                 if path_trace_ds.enable {
                     if let Some(tlv) = message
                         .suffix
@@ -123,6 +158,7 @@ impl<A, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'_, InBmca,
         }
 
         self.bmca.step_age(step);
+        self.step_alternate_master_age(step);
     }
 
     pub(crate) fn best_local_announce_message_for_bmca(&self) -> Option<BestAnnounceMessage> {
@@ -130,7 +166,9 @@ impl<A, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'_, InBmca,
         // in the global operation of the best master clock algorithm or in the update
         // of data sets. We still need them during the calculation of the recommended
         // port state though to avoid getting multiple masters in the segment.
-        if self.config.master_only || matches!(self.port_state, PortState::Faulty) {
+        if self.config.master_only
+            || matches!(self.port_state, PortState::Faulty | PortState::Disabled)
+        {
             None
         } else {
             self.lifecycle.local_best
@@ -208,6 +246,67 @@ impl<A, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'_, InBmca,
         }
     }
 
+    /// Move to the desired state of external port configuration, updating the
+    /// data sets from `slave_message`, the best announce message of the
+    /// instance's slave port, if any. A port desired as slave that is not
+    /// `is_slave_port` is passive.
+    ///
+    /// See *IEEE1588-2019 section 17.6.5.3*.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn apply_desired_state(
+        &mut self,
+        slave_message: Option<BestAnnounceMessage>,
+        is_slave_port: bool,
+        path_trace_ds: &mut PathTraceDS,
+        time_properties_ds: &mut TimePropertiesDS,
+        current_ds: &mut InternalCurrentDS,
+        parent_ds: &mut InternalParentDS,
+        default_ds: &InternalDefaultDS,
+    ) {
+        let desired_state = match self.config.desired_state {
+            // Slave only and master only still hold
+            Some(DesiredState::Master) if default_ds.slave_only => DesiredState::Passive,
+            Some(DesiredState::Slave) if self.config.master_only => DesiredState::Passive,
+            // Only one port steers the clock
+            Some(DesiredState::Slave) if !is_slave_port => DesiredState::Passive,
+            Some(desired_state) => desired_state,
+            None => DesiredState::Passive,
+        };
+        let recommended_state = Bmca::<()>::calculate_desired_recommended_state(
+            default_ds,
+            desired_state,
+            slave_message,
+            self.lifecycle.local_best,
+        );
+
+        match recommended_state {
+            Some(recommended_state) => self.set_recommended_state(
+                recommended_state,
+                path_trace_ds,
+                time_properties_ds,
+                current_ds,
+                parent_ds,
+                default_ds,
+            ),
+            // A slave without master waits for one in the listening state
+            None if desired_state == DesiredState::Slave => {
+                if !matches!(self.port_state, PortState::Listening) {
+                    self.set_forced_port_state(PortState::Listening);
+
+                    // consistent with Port<InBmca>::new()
+                    let duration = self.config.announce_duration(&mut self.rng);
+                    let reset_announce = PortAction::ResetAnnounceReceiptTimer { duration };
+                    self.lifecycle.pending_action = actions![reset_announce];
+                }
+            }
+            None => {
+                if !matches!(self.port_state, PortState::Passive) {
+                    self.set_forced_port_state(PortState::Passive);
+                }
+            }
+        }
+    }
+
     fn set_recommended_port_state(
         &mut self,
         recommended_state: &RecommendedState,
@@ -223,27 +322,40 @@ impl<A, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'_, InBmca,
                 let remote_master = announce_message.header.source_port_identity;
 
                 let update_state = match &self.port_state {
-                    PortState::Faulty => false,
+                    PortState::Faulty | PortState::Disabled => false,
                     PortState::Listening | PortState::Master | PortState::Passive => true,
                     PortState::Slave(old_state) => old_state.remote_master() != remote_master,
                 };
 
                 if update_state {
-                    let state = PortState::Slave(SlaveState::new(remote_master));
-                    self.set_forced_port_state(state);
+                    // Failing over to a measured alternate master keeps the filter
+                    if !self.fail_over_to_alternate_master(remote_master) {
+                        let state = PortState::Slave(SlaveState::new(remote_master));
+                        self.set_forced_port_state(state);
+                    }
 
                     let duration = self.config.announce_duration(&mut self.rng);
                     let reset_announce = PortAction::ResetAnnounceReceiptTimer { duration };
                     let reset_delay = PortAction::ResetDelayRequestTimer {
                         duration: core::time::Duration::ZERO,
                     };
-                    self.lifecycle.pending_action = actions![reset_announce, reset_delay];
+                    self.lifecycle.pending_action = if self.unicast_masters.is_empty() {
+                        actions![reset_announce, reset_delay]
+                    } else {
+                        // Request sync and delay responses from the new master
+                        let reset_unicast = PortAction::ResetUnicastTimer {
+                            duration: core::time::Duration::ZERO,
+                        };
+                        actions![reset_announce, reset_delay, reset_unicast]
+                    };
                 }
             }
             RecommendedState::M1(_) | RecommendedState::M2(_) | RecommendedState::M3(_) => {
                 if default_ds.slave_only {
                     match self.port_state {
-                        PortState::Listening | PortState::Faulty => { /* do nothing */ }
+                        PortState::Listening | PortState::Faulty | PortState::Disabled => {
+                            // do nothing
+                        }
                         PortState::Slave(_) | PortState::Passive | PortState::Master => {
                             self.set_forced_port_state(PortState::Listening);
 
@@ -262,22 +374,35 @@ impl<A, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'_, InBmca,
                         PortState::Listening | PortState::Slave(_) | PortState::Passive => {
                             self.set_forced_port_state(PortState::Master);
 
-                            // Immediately start sending announces and syncs
+                            // Immediately start sending announces, syncs and synchronization
+                            // metadata
                             let duration = core::time::Duration::from_secs(0);
                             self.lifecycle.pending_action = actions![
                                 PortAction::ResetAnnounceTimer { duration },
-                                PortAction::ResetSyncTimer { duration }
+                                PortAction::ResetSyncTimer { duration },
+                                PortAction::ResetSynchronizationMetadataTimer { duration }
                             ];
                         }
-                        PortState::Master | PortState::Faulty => { /* do nothing */ }
+                        PortState::Master | PortState::Faulty | PortState::Disabled => {
+                            // do nothing
+                        }
                     }
                 }
             }
             RecommendedState::P1(_) | RecommendedState::P2(_) => match self.port_state {
                 PortState::Listening | PortState::Slave(_) | PortState::Master => {
-                    self.set_forced_port_state(PortState::Passive)
+                    self.set_forced_port_state(PortState::Passive);
+
+                    // Alternate masters keep sending announces and syncs
+                    if self.config.number_alternate_masters > 0 {
+                        let duration = core::time::Duration::from_secs(0);
+                        self.lifecycle.pending_action = actions![
+                            PortAction::ResetAnnounceTimer { duration },
+                            PortAction::ResetSyncTimer { duration }
+                        ];
+                    }
                 }
-                PortState::Passive | PortState::Faulty => {}
+                PortState::Passive | PortState::Faulty | PortState::Disabled => {}
             },
         }
     }
@@ -285,15 +410,24 @@ impl<A, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'_, InBmca,
 
 #[cfg(test)]
 mod tests {
+    use core::cell::RefCell;
+
+    use arrayvec::ArrayString;
+    use rand::rngs::mock::StepRng;
+
     use super::*;
     use crate::{
         bmc::bmca::Bmca,
-        config::{ClockIdentity, ClockQuality, InstanceConfig, SdoId},
+        config::{AcceptAnyMaster, ClockIdentity, ClockQuality, InstanceConfig, SdoId},
         datastructures::{
             common::{PortIdentity, Tlv, TlvSetBuilder},
             messages::{AnnounceMessage, Header, Message, MessageBody, PtpVersion, MAX_DATA_LEN},
         },
-        port::tests::{setup_test_port, setup_test_port_custom_identity, setup_test_state},
+        filters::BasicFilter,
+        port::tests::{
+            setup_test_port, setup_test_port_custom_identity, setup_test_state, TestClock,
+        },
+        ptp_instance::PtpInstanceState,
         time::Time,
     };
 
@@ -398,7 +532,10 @@ mod tests {
             sdo_id: SdoId::default(),
             slave_only: false,
             path_trace: false,
+            external_port_configuration: false,
             clock_quality: ClockQuality::default(),
+            management_set_policy: Default::default(),
+            dataset_comparison: Default::default(),
         };
         let mut port = port.start_bmca();
         port.set_recommended_port_state(
@@ -510,6 +647,122 @@ mod tests {
         assert!(pending_action.next().is_none());
     }
 
+    fn apply_desired_state(
+        port: &mut Port<'_, InBmca, AcceptAnyMaster, StepRng, TestClock, BasicFilter>,
+        state: &RefCell<PtpInstanceState>,
+        slave_message: Option<BestAnnounceMessage>,
+    ) {
+        let mut state = state.borrow_mut();
+        let state = &mut *state;
+        port.apply_desired_state(
+            slave_message,
+            true,
+            &mut state.path_trace_ds,
+            &mut state.time_properties_ds,
+            &mut state.current_ds,
+            &mut state.parent_ds,
+            &state.default_ds,
+        );
+    }
+
+    #[test]
+    fn test_external_port_configuration() {
+        let state = setup_test_state();
+        state.borrow_mut().default_ds.external_port_configuration = true;
+
+        // The local clock is better than the foreign master, which the BMCA would
+        // not pick
+        state.borrow_mut().default_ds.priority_1 = 0;
+
+        let port = setup_test_port(&state);
+
+        let mut foreign_message = default_announce_message();
+        foreign_message.grandmaster_priority_1 = 255;
+        foreign_message.grandmaster_identity = ClockIdentity([1; 8]);
+        foreign_message.header.source_port_identity = PortIdentity {
+            clock_identity: ClockIdentity([2; 8]),
+            port_number: 1,
+        };
+        let announce_message = Message {
+            header: foreign_message.header,
+            body: MessageBody::Announce(foreign_message),
+            suffix: Default::default(),
+        };
+        let mut packet = [0; MAX_DATA_LEN];
+        let packet_len = announce_message.serialize(&mut packet).unwrap();
+        let packet = &packet[..packet_len];
+
+        // Without a desired state the port is passive
+        let mut port = port.start_bmca();
+        apply_desired_state(&mut port, &state, None);
+        assert!(matches!(port.port_state, PortState::Passive));
+
+        port.set_desired_state(Some(DesiredState::Master));
+        apply_desired_state(&mut port, &state, None);
+        assert!(matches!(port.port_state, PortState::Master));
+
+        // A slave without master listens
+        port.set_desired_state(Some(DesiredState::Slave));
+        apply_desired_state(&mut port, &state, None);
+        assert!(matches!(port.port_state, PortState::Listening));
+
+        // Foreign masters qualify after two announce messages
+        let mut port = port.end_bmca().0;
+        for _ in 0..2 {
+            let mut actions = port.handle_general_receive(packet);
+            let Some(PortAction::ResetAnnounceReceiptTimer { .. }) = actions.next() else {
+                panic!("Unexpected action");
+            };
+            assert!(actions.next().is_none());
+        }
+
+        // and follows the master once it is heard
+        let mut port = port.start_bmca();
+        port.calculate_best_local_announce_message();
+        let slave_message = port.best_local_announce_message_for_bmca();
+        assert!(slave_message.is_some());
+        apply_desired_state(&mut port, &state, slave_message);
+        assert!(matches!(port.port_state, PortState::Slave(_)));
+        assert_eq!(
+            state.borrow().parent_ds.grandmaster_identity,
+            ClockIdentity([1; 8])
+        );
+
+        // Losing it does not make the port master
+        let mut port = port.end_bmca().0;
+        let mut actions = port.handle_announce_receipt_timer();
+        let Some(PortAction::ResetAnnounceReceiptTimer { .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+        drop(actions);
+        assert!(matches!(port.port_state, PortState::Listening));
+
+        // Slave only still holds
+        let mut port = port.start_bmca();
+        state.borrow_mut().default_ds.slave_only = true;
+        port.set_desired_state(Some(DesiredState::Master));
+        apply_desired_state(&mut port, &state, None);
+        assert!(matches!(port.port_state, PortState::Passive));
+
+        // Other ports desired as slave are passive next to the slave port
+        port.set_desired_state(Some(DesiredState::Slave));
+        {
+            let mut state = state.borrow_mut();
+            let state = &mut *state;
+            port.apply_desired_state(
+                slave_message,
+                false,
+                &mut state.path_trace_ds,
+                &mut state.time_properties_ds,
+                &mut state.current_ds,
+                &mut state.parent_ds,
+                &state.default_ds,
+            );
+        }
+        assert!(matches!(port.port_state, PortState::Passive));
+    }
+
     #[test]
     fn test_announce_receive() {
         let state = setup_test_state();
@@ -553,6 +806,92 @@ mod tests {
         assert!(port.best_local_announce_message_for_bmca().is_some());
     }
 
+    #[test]
+    fn test_foreign_masters_listed() {
+        let state = setup_test_state();
+
+        let mut port = setup_test_port(&state);
+
+        let mut announce = default_announce_message();
+        announce.header.source_port_identity.clock_identity.0 = [1, 2, 3, 4, 5, 6, 7, 8];
+        announce.grandmaster_priority_1 = 42;
+        let announce_message = Message {
+            header: announce.header,
+            body: MessageBody::Announce(announce),
+            suffix: Default::default(),
+        };
+        let mut packet = [0; MAX_DATA_LEN];
+        let packet_len = announce_message.serialize(&mut packet).unwrap();
+        let packet = &packet[..packet_len];
+
+        assert_eq!(port.foreign_masters().count(), 0);
+
+        drop(port.handle_general_receive(packet));
+        let foreign_master = port.foreign_masters().next().unwrap();
+        assert_eq!(
+            foreign_master.foreign_master_port_identity,
+            announce.header.source_port_identity
+        );
+        assert_eq!(foreign_master.announce_messages, 1);
+        assert!(!foreign_master.qualified);
+        assert_eq!(foreign_master.grandmaster_priority_1, 42);
+
+        drop(port.handle_general_receive(packet));
+        let foreign_master = port.foreign_masters().next().unwrap();
+        assert_eq!(foreign_master.announce_messages, 2);
+        assert!(foreign_master.qualified);
+    }
+
+    #[test]
+    fn test_disabled_port() {
+        let state = setup_test_state();
+
+        let mut port = setup_test_port(&state).start_bmca();
+        port.set_enabled(false);
+        assert!(!port.is_enabled());
+        assert!(matches!(port.port_state, PortState::Disabled));
+
+        let (mut port, mut actions) = port.end_bmca();
+        assert!(actions.next().is_none());
+
+        // A disabled port ignores announce messages
+        let mut announce = default_announce_message();
+        announce.header.source_port_identity.clock_identity.0 = [1, 2, 3, 4, 5, 6, 7, 8];
+        let announce_message = Message {
+            header: announce.header,
+            body: MessageBody::Announce(announce),
+            suffix: Default::default(),
+        };
+        let mut packet = [0; MAX_DATA_LEN];
+        let packet_len = announce_message.serialize(&mut packet).unwrap();
+        let packet = &packet[..packet_len];
+
+        assert!(port.handle_general_receive(packet).next().is_none());
+        assert!(port.handle_general_receive(packet).next().is_none());
+        assert_eq!(port.foreign_masters().count(), 0);
+
+        // and never becomes master on its own
+        assert!(port.handle_announce_receipt_timer().next().is_none());
+        assert!(port.handle_delay_request_timer().next().is_none());
+        assert!(matches!(port.port_state, PortState::Disabled));
+        assert_eq!(
+            port.port_ds().port_state,
+            crate::observability::port::PortState::Disabled
+        );
+
+        let mut port = port.start_bmca();
+        port.set_enabled(true);
+        assert!(port.is_enabled());
+        assert!(matches!(port.port_state, PortState::Listening));
+
+        let (_, mut actions) = port.end_bmca();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetAnnounceReceiptTimer { .. })
+        ));
+        assert!(actions.next().is_none());
+    }
+
     #[test]
     fn test_announce_receive_via_event() {
         let state = setup_test_state();
@@ -663,4 +1002,102 @@ mod tests {
         };
         assert!(actions.next().is_none());
     }
+
+    #[test]
+    fn test_announce_alternate_timescales() {
+        let state = setup_test_state();
+
+        let mut state_ref = state.borrow_mut();
+        state_ref.parent_ds.parent_port_identity.clock_identity.0 = [1, 2, 3, 4, 5, 6, 7, 8];
+        drop(state_ref);
+
+        let mut port = setup_test_port(&state);
+        port.set_forced_port_state(PortState::Slave(SlaveState::new(Default::default())));
+
+        let mut announce = default_announce_message();
+        announce.header.source_port_identity.clock_identity.0 = [1, 2, 3, 4, 5, 6, 7, 8];
+
+        let alternate_timescale = AlternateTimescale {
+            key_field: 3,
+            current_offset: -18000,
+            jump_seconds: 3600,
+            time_of_next_jump: 1710054000,
+            display_name: ArrayString::from("EST").unwrap(),
+        };
+
+        let mut suffix = [0; MAX_DATA_LEN];
+        let mut tlv_builder = TlvSetBuilder::new(&mut suffix);
+        alternate_timescale.with_tlv(|tlv| tlv_builder.add(tlv).unwrap());
+
+        let announce_message = Message {
+            header: announce.header,
+            body: MessageBody::Announce(announce),
+            suffix: tlv_builder.build(),
+        };
+        let mut packet = [0; MAX_DATA_LEN];
+        let packet_len = announce_message.serialize(&mut packet).unwrap();
+        let packet = &packet[..packet_len];
+
+        let mut actions = port.handle_event_receive(packet, Time::from_micros(1));
+        let Some(PortAction::ResetAnnounceReceiptTimer { .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        drop(actions);
+
+        assert_eq!(
+            state
+                .borrow()
+                .alternate_timescale_offsets_ds
+                .list
+                .as_slice(),
+            &[alternate_timescale]
+        );
+    }
+
+    #[test]
+    fn test_announce_power_profile() {
+        let state = setup_test_state();
+
+        let mut state_ref = state.borrow_mut();
+        state_ref.parent_ds.parent_port_identity.clock_identity.0 = [1, 2, 3, 4, 5, 6, 7, 8];
+        drop(state_ref);
+
+        let mut port = setup_test_port(&state);
+        port.set_forced_port_state(PortState::Slave(SlaveState::new(Default::default())));
+
+        let mut announce = default_announce_message();
+        announce.header.source_port_identity.clock_identity.0 = [1, 2, 3, 4, 5, 6, 7, 8];
+
+        let power_profile = PowerProfile {
+            grandmaster_id: 3,
+            total_time_inaccuracy: 250,
+        };
+
+        let mut suffix = [0; MAX_DATA_LEN];
+        let mut tlv_builder = TlvSetBuilder::new(&mut suffix);
+        power_profile.with_tlv(|tlv| tlv_builder.add(tlv).unwrap());
+
+        let announce_message = Message {
+            header: announce.header,
+            body: MessageBody::Announce(announce),
+            suffix: tlv_builder.build(),
+        };
+        let mut packet = [0; MAX_DATA_LEN];
+        let packet_len = announce_message.serialize(&mut packet).unwrap();
+        let packet = &packet[..packet_len];
+
+        // The TLV is passed on to the other ports
+        let mut actions = port.handle_event_receive(packet, Time::from_micros(1));
+        let Some(PortAction::ResetAnnounceReceiptTimer { .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        let Some(PortAction::ForwardTLV { tlv }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(PowerProfile::from_tlv(&tlv.tlv).unwrap(), power_profile);
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        assert_eq!(state.borrow().received_power_profile, Some(power_profile));
+    }
 }
@@ -0,0 +1,494 @@
+//! Collection of the slave event monitoring reports sent to a master, see
+//! *IEEE1588-2019 section 16.11*
+
+use arrayvec::ArrayVec;
+
+use super::{state::PortState, Port, Running};
+use crate::{
+    config::PortAddress,
+    datastructures::{
+        common::{PortIdentity, TlvType},
+        messages::{
+            Message, MessageType, SignalingMessage, SlaveRxSyncComputedDataTlv,
+            SlaveRxSyncTimingDataTlv, SlaveTxEventTimestampsTlv,
+        },
+    },
+    filters::Filter,
+    observability::port::SlaveReport,
+    time::{Duration, Time},
+};
+
+/// Number of recently sent sync messages reports can refer to
+const MAX_SENT_SYNCS: usize = 16;
+
+/// Number of recently received delay requests reports can refer to
+const MAX_RECEIVED_DELAY_REQS: usize = 16;
+
+/// Maximum number of slaves reports are kept for
+const MAX_MONITORED_SLAVES: usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+struct SentSync {
+    /// Address of the unicast grantee, or `None` when sent multicast
+    address: Option<PortAddress>,
+    sequence_id: u16,
+    send_time: Time,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ReceivedDelayReq {
+    requestor: PortIdentity,
+    sequence_id: u16,
+    recv_time: Time,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SlaveEntry {
+    slave: PortIdentity,
+    sequence_id: u16,
+    /// Ingress time at the slave minus origin time of the last matched sync
+    sync_difference: Option<Duration>,
+    /// Receive time minus egress time at the slave of the last matched delay
+    /// request
+    delay_difference: Option<Duration>,
+    /// Offset from master and mean path delay as computed by the slave itself
+    computed: Option<(Duration, Duration)>,
+}
+
+impl SlaveEntry {
+    fn report(&self) -> SlaveReport {
+        let (offset_from_master, mean_path_delay) =
+            match (self.sync_difference, self.delay_difference) {
+                (Some(sync), Some(delay)) => (Some((sync - delay) / 2), Some((sync + delay) / 2)),
+                _ => match self.computed {
+                    Some((offset, delay)) => (Some(offset), Some(delay)),
+                    None => (None, None),
+                },
+            };
+
+        SlaveReport {
+            slave_port_identity: self.slave,
+            sequence_id: self.sequence_id,
+            offset_from_master: offset_from_master.map(Into::into),
+            mean_path_delay: mean_path_delay.map(Into::into),
+        }
+    }
+}
+
+/// Push `item`, dropping the oldest item when `list` is full
+fn push_recent<T, const N: usize>(list: &mut ArrayVec<T, N>, item: T) {
+    if list.is_full() {
+        list.remove(0);
+    }
+    list.push(item);
+}
+
+/// Timestamps of the messages a master port sent to and received from its
+/// slaves, and the reports of those slaves matched against them
+#[derive(Debug)]
+pub(super) struct SlaveCollector {
+    sent_syncs: ArrayVec<SentSync, MAX_SENT_SYNCS>,
+    delay_reqs: ArrayVec<ReceivedDelayReq, MAX_RECEIVED_DELAY_REQS>,
+    slaves: ArrayVec<SlaveEntry, MAX_MONITORED_SLAVES>,
+}
+
+impl SlaveCollector {
+    pub(super) fn new() -> Self {
+        Self {
+            sent_syncs: ArrayVec::new(),
+            delay_reqs: ArrayVec::new(),
+            slaves: ArrayVec::new(),
+        }
+    }
+
+    pub(super) fn record_sync(
+        &mut self,
+        address: Option<PortAddress>,
+        sequence_id: u16,
+        send_time: Time,
+    ) {
+        push_recent(
+            &mut self.sent_syncs,
+            SentSync {
+                address,
+                sequence_id,
+                send_time,
+            },
+        );
+    }
+
+    pub(super) fn record_delay_req(
+        &mut self,
+        requestor: PortIdentity,
+        sequence_id: u16,
+        recv_time: Time,
+    ) {
+        push_recent(
+            &mut self.delay_reqs,
+            ReceivedDelayReq {
+                requestor,
+                sequence_id,
+                recv_time,
+            },
+        );
+    }
+
+    fn slave_mut(&mut self, slave: PortIdentity) -> &mut SlaveEntry {
+        match self.slaves.iter().position(|entry| entry.slave == slave) {
+            Some(index) => &mut self.slaves[index],
+            None => {
+                push_recent(
+                    &mut self.slaves,
+                    SlaveEntry {
+                        slave,
+                        sequence_id: 0,
+                        sync_difference: None,
+                        delay_difference: None,
+                        computed: None,
+                    },
+                );
+                self.slaves.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Match the monitoring TLVs in `message`, sent by a slave of
+    /// `port_identity` from `address`, with the messages exchanged with it
+    fn handle_report(
+        &mut self,
+        port_identity: PortIdentity,
+        message: &Message<'_>,
+        address: Option<PortAddress>,
+    ) {
+        let slave = message.header.source_port_identity;
+
+        for tlv in message.suffix.tlv() {
+            match tlv.tlv_type {
+                TlvType::SlaveRxSyncTimingData => {
+                    let (source, records) = match SlaveRxSyncTimingDataTlv::deserialize(&tlv.value)
+                    {
+                        Ok(result) => result,
+                        Err(error) => {
+                            log::warn!("Could not parse sync timing report: {:?}", error);
+                            continue;
+                        }
+                    };
+                    if source != port_identity {
+                        continue;
+                    }
+
+                    for record in records {
+                        let Some(sync) = self
+                            .sent_syncs
+                            .iter()
+                            .find(|sync| {
+                                sync.sequence_id == record.sequence_id
+                                    && (sync.address.is_none() || sync.address == address)
+                            })
+                            .copied()
+                        else {
+                            continue;
+                        };
+
+                        let difference = Time::from(record.sync_event_ingress_timestamp)
+                            - sync.send_time
+                            - Duration::from(record.total_correction_field);
+                        let entry = self.slave_mut(slave);
+                        entry.sequence_id = record.sequence_id;
+                        entry.sync_difference = Some(difference);
+                    }
+                }
+                TlvType::SlaveRxSyncComputedData => {
+                    let (source, records) =
+                        match SlaveRxSyncComputedDataTlv::deserialize(&tlv.value) {
+                            Ok(Some(result)) => result,
+                            Ok(None) => continue,
+                            Err(error) => {
+                                log::warn!("Could not parse computed data report: {:?}", error);
+                                continue;
+                            }
+                        };
+                    if source != port_identity {
+                        continue;
+                    }
+
+                    if let Some(record) = records.last() {
+                        let entry = self.slave_mut(slave);
+                        entry.sequence_id = record.sequence_id;
+                        entry.computed = Some((
+                            record.offset_from_master.into(),
+                            record.mean_path_delay.into(),
+                        ));
+                    }
+                }
+                TlvType::SlaveTxEventTimestamps => {
+                    let (source, message_type, records) =
+                        match SlaveTxEventTimestampsTlv::deserialize(&tlv.value) {
+                            Ok(result) => result,
+                            Err(error) => {
+                                log::warn!("Could not parse event timestamps report: {:?}", error);
+                                continue;
+                            }
+                        };
+                    if source != slave || message_type != MessageType::DelayReq {
+                        continue;
+                    }
+
+                    for record in records {
+                        let Some(delay_req) = self
+                            .delay_reqs
+                            .iter()
+                            .find(|delay_req| {
+                                delay_req.requestor == slave
+                                    && delay_req.sequence_id == record.sequence_id
+                            })
+                            .copied()
+                        else {
+                            continue;
+                        };
+
+                        let difference =
+                            delay_req.recv_time - Time::from(record.event_egress_timestamp);
+                        self.slave_mut(slave).delay_difference = Some(difference);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub(super) fn reports(&self) -> impl Iterator<Item = SlaveReport> + '_ {
+        self.slaves.iter().map(SlaveEntry::report)
+    }
+}
+
+impl<A, C, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
+    /// Collect the slave event monitoring reports a slave sent to us
+    pub(super) fn handle_slave_reports(
+        &mut self,
+        message: &Message<'_>,
+        signaling: &SignalingMessage,
+        source: Option<PortAddress>,
+    ) {
+        if matches!(self.port_state, PortState::Master)
+            && self.is_target_port(signaling.target_port_identity)
+        {
+            self.slave_collector
+                .handle_report(self.port_identity, message, source);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{ClockIdentity, InstanceConfig},
+        datastructures::{
+            common::{TimeInterval, Tlv, TlvSetBuilder},
+            datasets::InternalDefaultDS,
+            messages::{
+                DelayReqMessage, Header, SlaveRxSyncComputedDataRecord,
+                SlaveRxSyncTimingDataRecord, SlaveTxEventTimestampsRecord, MAX_DATA_LEN,
+            },
+        },
+        port::tests::{setup_test_port_custom_clock, setup_test_state, SettableClock},
+    };
+
+    const SLAVE_ADDRESS: PortAddress = PortAddress::Ipv4([192, 0, 2, 20]);
+
+    fn slave_identity(clock: u8) -> PortIdentity {
+        PortIdentity {
+            clock_identity: ClockIdentity([clock; 8]),
+            port_number: 1,
+        }
+    }
+
+    /// Serialize a signaling message from `slave` with the given TLV values
+    fn report(slave: PortIdentity, tlvs: &[(TlvType, &[u8])], buffer: &mut [u8]) -> usize {
+        let default_ds = InternalDefaultDS::new(InstanceConfig {
+            clock_identity: slave.clock_identity,
+            priority_1: 255,
+            priority_2: 255,
+            domain_number: 0,
+            slave_only: true,
+            sdo_id: Default::default(),
+            path_trace: false,
+            external_port_configuration: false,
+            clock_quality: Default::default(),
+            management_set_policy: Default::default(),
+            dataset_comparison: Default::default(),
+        });
+
+        let mut tlv_buffer = [0; MAX_DATA_LEN];
+        let mut tlv_builder = TlvSetBuilder::new(&mut tlv_buffer);
+        for &(tlv_type, value) in tlvs {
+            tlv_builder
+                .add(Tlv {
+                    tlv_type,
+                    value: value.into(),
+                })
+                .unwrap();
+        }
+
+        Message::signaling(
+            &default_ds,
+            slave,
+            PortIdentity {
+                clock_identity: ClockIdentity([0xff; 8]),
+                port_number: 0xffff,
+            },
+            0,
+            1,
+            tlv_builder.build(),
+        )
+        .serialize(buffer)
+        .unwrap()
+    }
+
+    #[test]
+    fn test_collect_slave_timestamps() {
+        let state = setup_test_state();
+        let mut port = setup_test_port_custom_clock(&state, SettableClock(Time::from_secs(10)));
+        port.set_forced_port_state(PortState::Master);
+        let slave = slave_identity(1);
+
+        let mut actions = port.handle_sync_timestamp(5, Time::from_micros(100));
+        assert!(actions.next().is_some());
+        drop(actions);
+
+        let mut actions = port.handle_delay_req(
+            Header {
+                sequence_id: 7,
+                source_port_identity: slave,
+                correction_field: TimeInterval(2000.into()),
+                ..Header::new(1)
+            },
+            DelayReqMessage {
+                origin_timestamp: Time::from_micros(0).into(),
+            },
+            Time::from_micros(302),
+            None,
+        );
+        assert!(actions.next().is_some());
+        drop(actions);
+
+        let timing = [SlaveRxSyncTimingDataRecord {
+            sequence_id: 5,
+            sync_origin_timestamp: Time::from_micros(100).into(),
+            total_correction_field: TimeInterval(1000.into()),
+            scaled_cumulative_rate_offset: 0,
+            sync_event_ingress_timestamp: Time::from_micros(251).into(),
+        }];
+        let mut timing_value = [0; 64];
+        let timing_length = SlaveRxSyncTimingDataTlv {
+            sync_source_port_identity: port.port_identity,
+            records: &timing,
+        }
+        .serialize(&mut timing_value)
+        .unwrap();
+
+        let tx = [
+            // Unknown delay request
+            SlaveTxEventTimestampsRecord {
+                sequence_id: 6,
+                event_egress_timestamp: Time::from_micros(0).into(),
+            },
+            SlaveTxEventTimestampsRecord {
+                sequence_id: 7,
+                event_egress_timestamp: Time::from_micros(200).into(),
+            },
+        ];
+        let mut tx_value = [0; 64];
+        let tx_length = SlaveTxEventTimestampsTlv {
+            source_port_identity: slave,
+            event_message_type: MessageType::DelayReq,
+            records: &tx,
+        }
+        .serialize(&mut tx_value)
+        .unwrap();
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = report(
+            slave,
+            &[
+                (
+                    TlvType::SlaveRxSyncTimingData,
+                    &timing_value[..timing_length],
+                ),
+                (TlvType::SlaveTxEventTimestamps, &tx_value[..tx_length]),
+            ],
+            &mut buffer,
+        );
+        let mut actions = port.handle_general_receive_from(&buffer[..length], Some(SLAVE_ADDRESS));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        // The sync took 150us and the delay request 100us, so the slave is 25us
+        // ahead of us with a path delay of 125us
+        let reports: ArrayVec<_, 2> = port.slave_reports().collect();
+        assert_eq!(
+            reports.as_slice(),
+            [SlaveReport {
+                slave_port_identity: slave,
+                sequence_id: 5,
+                offset_from_master: Some(Duration::from_micros(25).into()),
+                mean_path_delay: Some(Duration::from_micros(125).into()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_collect_slave_computed_data() {
+        let state = setup_test_state();
+        let mut port = setup_test_port_custom_clock(&state, SettableClock(Time::from_secs(10)));
+        let slave = slave_identity(2);
+
+        let computed = [SlaveRxSyncComputedDataRecord {
+            sequence_id: 9,
+            offset_from_master: Duration::from_micros(-3).into(),
+            mean_path_delay: Duration::from_micros(40).into(),
+            scaled_neighbor_rate_ratio: 0,
+        }];
+        let mut value = [0; 64];
+        let value_length = SlaveRxSyncComputedDataTlv {
+            source_port_identity: port.port_identity,
+            scaled_neighbor_rate_ratio_valid: false,
+            mean_path_delay_valid: true,
+            offset_from_master_valid: true,
+            records: &computed,
+        }
+        .serialize(&mut value)
+        .unwrap();
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = report(
+            slave,
+            &[(TlvType::SlaveRxSyncComputedData, &value[..value_length])],
+            &mut buffer,
+        );
+
+        // Reports are ignored while not master
+        port.set_forced_port_state(PortState::Listening);
+        let mut actions = port.handle_general_receive_from(&buffer[..length], Some(SLAVE_ADDRESS));
+        assert!(actions.next().is_none());
+        drop(actions);
+        assert!(port.slave_reports().next().is_none());
+
+        port.set_forced_port_state(PortState::Master);
+        let mut actions = port.handle_general_receive_from(&buffer[..length], Some(SLAVE_ADDRESS));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let reports: ArrayVec<_, 2> = port.slave_reports().collect();
+        assert_eq!(
+            reports.as_slice(),
+            [SlaveReport {
+                slave_port_identity: slave,
+                sequence_id: 9,
+                offset_from_master: Some(Duration::from_micros(-3).into()),
+                mean_path_delay: Some(Duration::from_micros(40).into()),
+            }]
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! Neighbor rate ratio and asCapable determination for IEEE 802.1AS (gPTP)
+//! ports
+//!
+//! See *IEEE802.1AS-2020 section 11.2.19*
+
+use super::{Port, Running};
+use crate::{
+    config::GptpConfig,
+    datastructures::common::{ClockIdentity, FollowUpInformation, PortIdentity},
+    filters::Filter,
+    time::{Duration, Time},
+};
+
+/// What a gPTP port learned about its neighbor from the peer delay exchanges
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct GptpState {
+    /// Whether the neighbor is capable of running gPTP (asCapable)
+    as_capable: bool,
+    /// Ratio of the frequency of the neighbor to our own frequency
+    neighbor_rate_ratio: f64,
+    /// Responder and its response send time, together with our receive time
+    /// of that response, of the previous completed exchange
+    previous_exchange: Option<(PortIdentity, Time, Time)>,
+    /// Number of consecutive peer delay requests without response
+    lost_responses: u8,
+    /// Rate offset of the grandmaster to the master, from the last follow up
+    cumulative_scaled_rate_offset: i32,
+}
+
+impl GptpState {
+    pub(super) fn new() -> Self {
+        Self {
+            as_capable: false,
+            neighbor_rate_ratio: 1.0,
+            previous_exchange: None,
+            lost_responses: 0,
+            cumulative_scaled_rate_offset: 0,
+        }
+    }
+
+    /// Update the neighbor rate ratio with the response times of a completed
+    /// peer delay exchange, and return it for computing the link delay
+    pub(super) fn record_response(
+        &mut self,
+        responder: PortIdentity,
+        response_send_time: Time,
+        response_recv_time: Time,
+    ) -> f64 {
+        self.lost_responses = 0;
+
+        if let Some((previous_responder, previous_send_time, previous_recv_time)) =
+            self.previous_exchange
+        {
+            let send_interval = response_send_time - previous_send_time;
+            let recv_interval = response_recv_time - previous_recv_time;
+
+            if previous_responder == responder && recv_interval > Duration::ZERO {
+                self.neighbor_rate_ratio = send_interval.seconds() / recv_interval.seconds();
+            }
+        }
+
+        self.previous_exchange = Some((responder, response_send_time, response_recv_time));
+        self.neighbor_rate_ratio
+    }
+
+    /// Determine whether the neighbor is capable of running gPTP after a
+    /// completed peer delay exchange
+    pub(super) fn update_as_capable(
+        &mut self,
+        config: &GptpConfig,
+        own_identity: ClockIdentity,
+        responder: PortIdentity,
+        mean_link_delay: Duration,
+    ) {
+        let as_capable = responder.clock_identity != own_identity
+            && mean_link_delay <= config.neighbor_prop_delay_thresh;
+
+        if as_capable != self.as_capable {
+            log::info!("Neighbor capable of running gPTP: {}", as_capable);
+        }
+        self.as_capable = as_capable;
+    }
+
+    /// Register a peer delay request that was not answered
+    pub(super) fn record_lost_response(&mut self, config: &GptpConfig) {
+        self.lost_responses = self.lost_responses.saturating_add(1);
+
+        if self.lost_responses > config.allowed_lost_responses {
+            if self.as_capable {
+                log::info!("Neighbor stopped responding to peer delay requests");
+            }
+            *self = Self {
+                lost_responses: self.lost_responses,
+                ..Self::new()
+            };
+        }
+    }
+
+    pub(super) fn record_follow_up_information(&mut self, information: FollowUpInformation) {
+        self.cumulative_scaled_rate_offset = information.cumulative_scaled_rate_offset;
+    }
+
+    /// The Follow_Up information to send on our master ports, from the
+    /// information received from our master. The rate offset is relative to
+    /// our own frequency, so it includes the neighbor rate ratio.
+    ///
+    /// See *IEEE802.1AS-2020 section 11.4.4.3*.
+    pub(super) fn forwarded_follow_up_information(
+        &self,
+        information: FollowUpInformation,
+    ) -> FollowUpInformation {
+        let scale = (1u64 << 41) as f64;
+        let rate_ratio = (1.0 + information.cumulative_scaled_rate_offset as f64 / scale)
+            * self.neighbor_rate_ratio;
+
+        FollowUpInformation {
+            cumulative_scaled_rate_offset: ((rate_ratio - 1.0) * scale) as i32,
+            ..information
+        }
+    }
+
+    /// Rate offset of the grandmaster to our master, multiplied by 2^41
+    pub(super) fn cumulative_scaled_rate_offset(&self) -> i32 {
+        self.cumulative_scaled_rate_offset
+    }
+
+    /// Neighbor rate ratio minus one, multiplied by 2^41
+    pub(super) fn scaled_neighbor_rate_ratio(&self) -> i32 {
+        ((self.neighbor_rate_ratio - 1.0) * (1u64 << 41) as f64) as i32
+    }
+}
+
+impl<L, A, R, C, F: Filter, S> Port<'_, L, A, R, C, F, S> {
+    /// Whether sync and announce messages are exchanged with the neighbor.
+    /// Ports not running gPTP always exchange them.
+    pub(super) fn is_as_capable(&self) -> bool {
+        self.config.gptp.is_none() || self.gptp.as_capable
+    }
+}
+
+impl<A, R, C, F: Filter, S> Port<'_, Running, A, R, C, F, S> {
+    /// Whether messages other than peer delay messages are sent to the link
+    /// local address, as gPTP does for all its messages
+    pub(super) fn is_link_local(&self) -> bool {
+        self.config.gptp.is_some() || self.config.link_local
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn responder(port_number: u16) -> PortIdentity {
+        PortIdentity {
+            clock_identity: ClockIdentity([1; 8]),
+            port_number,
+        }
+    }
+
+    #[test]
+    fn test_neighbor_rate_ratio() {
+        let mut state = GptpState::new();
+
+        // The first exchange only provides a reference
+        let ratio = state.record_response(responder(1), Time::from_secs(100), Time::from_secs(10));
+        assert_eq!(ratio, 1.0);
+
+        // Neighbor runs 10 ppm fast
+        let ratio = state.record_response(
+            responder(1),
+            Time::from_secs(101) + Duration::from_micros(10),
+            Time::from_secs(11),
+        );
+        assert!((ratio - 1.000_010).abs() < 1e-9);
+        assert_eq!(state.scaled_neighbor_rate_ratio(), 21_990_232);
+
+        // Another responder does not give a ratio
+        let ratio = state.record_response(responder(2), Time::from_secs(200), Time::from_secs(12));
+        assert!((ratio - 1.000_010).abs() < 1e-9);
+        let ratio = state.record_response(responder(2), Time::from_secs(201), Time::from_secs(13));
+        assert_eq!(ratio, 1.0);
+    }
+
+    #[test]
+    fn test_forwarded_follow_up_information() {
+        let mut state = GptpState::new();
+        let received = FollowUpInformation {
+            cumulative_scaled_rate_offset: 21_990_232,
+            gm_time_base_indicator: 2,
+            ..Default::default()
+        };
+
+        // Without a neighbor rate ratio the information is forwarded unchanged
+        assert_eq!(state.forwarded_follow_up_information(received), received);
+
+        // Grandmaster 10 ppm fast relative to the neighbor, which is 10 ppm fast
+        // relative to us
+        state.record_response(responder(1), Time::from_secs(100), Time::from_secs(10));
+        state.record_response(
+            responder(1),
+            Time::from_secs(101) + Duration::from_micros(10),
+            Time::from_secs(11),
+        );
+        let forwarded = state.forwarded_follow_up_information(received);
+        assert_eq!(forwarded.gm_time_base_indicator, 2);
+        let expected = ((1.000_010f64 * 1.000_010 - 1.0) * (1u64 << 41) as f64) as i32;
+        assert!((forwarded.cumulative_scaled_rate_offset - expected).abs() <= 1);
+    }
+
+    #[test]
+    fn test_as_capable() {
+        let config = GptpConfig::default();
+        let own_identity = ClockIdentity([2; 8]);
+        let mut state = GptpState::new();
+        assert!(!state.as_capable);
+
+        state.update_as_capable(
+            &config,
+            own_identity,
+            responder(1),
+            Duration::from_nanos(500),
+        );
+        assert!(state.as_capable);
+
+        // Link delay too long
+        state.update_as_capable(
+            &config,
+            own_identity,
+            responder(1),
+            Duration::from_nanos(900),
+        );
+        assert!(!state.as_capable);
+
+        // Talking to ourselves
+        let own_port = PortIdentity {
+            clock_identity: own_identity,
+            port_number: 2,
+        };
+        state.update_as_capable(&config, own_identity, own_port, Duration::from_nanos(500));
+        assert!(!state.as_capable);
+
+        // Lost responses
+        state.update_as_capable(
+            &config,
+            own_identity,
+            responder(1),
+            Duration::from_nanos(500),
+        );
+        for _ in 0..config.allowed_lost_responses {
+            state.record_lost_response(&config);
+            assert!(state.as_capable);
+        }
+        state.record_lost_response(&config);
+        assert!(!state.as_capable);
+
+        // A response resets the count
+        state.record_response(responder(1), Time::from_secs(100), Time::from_secs(10));
+        state.update_as_capable(
+            &config,
+            own_identity,
+            responder(1),
+            Duration::from_nanos(500),
+        );
+        state.record_lost_response(&config);
+        assert!(state.as_capable);
+    }
+}
@@ -0,0 +1,662 @@
+use super::{state::PortState, Port, PortActionIterator, Running};
+use crate::{
+    clock::Clock,
+    datastructures::{
+        common::{SynchronizationMetadata, Tlv, TlvSetBuilder, TlvType},
+        messages::{
+            ManagementAction, ManagementData, ManagementErrorId, ManagementErrorStatusTlv,
+            ManagementId, ManagementMessage, ManagementTlv, Message,
+        },
+    },
+    filters::Filter,
+    observability::current::CurrentDS,
+    port::PortAction,
+    ptp_instance::PtpInstanceStateMutex,
+};
+
+// Largest dataField we produce (PARENT_DATA_SET) plus the managementId and
+// tlv header
+const MAX_MANAGEMENT_TLV_SIZE: usize = 4 + 2 + 32;
+
+// Synchronization metadata TLV including its header
+const SYNCHRONIZATION_METADATA_TLV_SIZE: usize = 4 + 48;
+
+impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F, S> {
+    pub(super) fn handle_management<'b>(
+        &'b mut self,
+        message: &Message<'_>,
+        management: &ManagementMessage,
+    ) -> PortActionIterator<'b> {
+        if !self.is_target_port(management.target_port_identity) {
+            return actions![];
+        }
+
+        if let Some(metadata) = message
+            .suffix
+            .tlv()
+            .find_map(|tlv| SynchronizationMetadata::from_tlv(&tlv).ok())
+        {
+            self.handle_synchronization_metadata(message, metadata);
+            return actions![];
+        }
+
+        let Some(reply_action) = management.action.reply() else {
+            // Responses and acknowledgements are meant for management nodes
+            return actions![];
+        };
+
+        let Some(tlv) = message
+            .suffix
+            .tlv()
+            .find(|tlv| tlv.tlv_type == TlvType::Management)
+        else {
+            log::debug!("Received management message without management tlv");
+            return actions![];
+        };
+
+        let request = match ManagementTlv::deserialize(&tlv.value) {
+            Ok(request) => request,
+            Err(error) => {
+                log::warn!("Could not parse management tlv: {:?}", error);
+                return actions![];
+            }
+        };
+
+        log::debug!(
+            "Received management {:?} for {:?}",
+            management.action,
+            request.management_id
+        );
+
+        let result = match management.action {
+            ManagementAction::GET => self.management_get(request.management_id),
+            ManagementAction::SET => self.management_set(request),
+            ManagementAction::COMMAND => match request.management_id {
+                ManagementId::NullPtpManagement => Ok(ManagementData::Null),
+                id if id.is_known() => Err(ManagementErrorId::NotSupported),
+                _ => Err(ManagementErrorId::NoSuchId),
+            },
+            _ => unreachable!(),
+        };
+
+        let mut value_buffer = [0; MAX_MANAGEMENT_TLV_SIZE];
+        let tlv = match result {
+            Ok(data) => {
+                let mut data_buffer = [0; MAX_MANAGEMENT_TLV_SIZE];
+                let Ok(data_length) = data.serialize(&mut data_buffer) else {
+                    log::error!("Statime bug: Could not serialize management data");
+                    return actions![];
+                };
+                let Ok(length) = (ManagementTlv {
+                    management_id: data.management_id(),
+                    data: &data_buffer[..data_length],
+                })
+                .serialize(&mut value_buffer) else {
+                    log::error!("Statime bug: Could not serialize management tlv");
+                    return actions![];
+                };
+                Tlv {
+                    tlv_type: TlvType::Management,
+                    value: value_buffer[..length].into(),
+                }
+            }
+            Err(error_id) => {
+                log::debug!(
+                    "Management request for {:?} failed: {:?}",
+                    request.management_id,
+                    error_id
+                );
+                let Ok(length) = (ManagementErrorStatusTlv {
+                    error_id,
+                    management_id: request.management_id,
+                })
+                .serialize(&mut value_buffer) else {
+                    log::error!("Statime bug: Could not serialize management error status");
+                    return actions![];
+                };
+                Tlv {
+                    tlv_type: TlvType::ManagementErrorStatus,
+                    value: value_buffer[..length].into(),
+                }
+            }
+        };
+
+        let mut tlv_buffer = [0; MAX_MANAGEMENT_TLV_SIZE];
+        let mut tlv_builder = TlvSetBuilder::new(&mut tlv_buffer);
+        if let Err(error) = tlv_builder.add(tlv) {
+            log::error!("Statime bug: Could not build management tlv: {:?}", error);
+            return actions![];
+        }
+
+        let response = Message::management_response(
+            message.header,
+            management,
+            reply_action,
+            self.port_identity,
+            self.config.minor_ptp_version.into(),
+            tlv_builder.build(),
+        );
+
+        let packet_length = match response
+            .serialize_authenticated(&mut self.packet_buffer, self.config.authentication.as_ref())
+        {
+            Ok(length) => length,
+            Err(error) => {
+                log::error!(
+                    "Statime bug: Could not serialize management response: {:?}",
+                    error
+                );
+                return actions![];
+            }
+        };
+
+        actions![PortAction::SendGeneral {
+            data: &self.packet_buffer[..packet_length],
+            link_local: false,
+        }]
+    }
+
+    /// Store the synchronization metadata when it comes from our master
+    fn handle_synchronization_metadata(
+        &mut self,
+        message: &Message<'_>,
+        metadata: SynchronizationMetadata,
+    ) {
+        let PortState::Slave(ref slave_state) = self.port_state else {
+            return;
+        };
+
+        if slave_state.remote_master() != message.header.source_port_identity {
+            log::debug!("Ignoring synchronization metadata from other than our master");
+            return;
+        }
+
+        log::trace!("Received synchronization metadata {:?}", metadata);
+        self.instance_state
+            .with_mut(|state| state.current_synchronization_metadata = Some(metadata));
+    }
+
+    fn management_get(&self, id: ManagementId) -> Result<ManagementData, ManagementErrorId> {
+        // The currentDS offset and delay are only known by the slave port, so
+        // these are reported as zero when the request arrives on another port.
+        let port_contribution = self.port_current_ds_contribution();
+
+        self.instance_state.with_ref(|state| {
+            Ok(match id {
+                ManagementId::NullPtpManagement => ManagementData::Null,
+                ManagementId::DefaultDataSet => {
+                    ManagementData::DefaultDataSet((&state.default_ds).into())
+                }
+                ManagementId::CurrentDataSet => ManagementData::CurrentDataSet(
+                    CurrentDS::from_state(&state.current_ds, port_contribution),
+                ),
+                ManagementId::ParentDataSet => {
+                    ManagementData::ParentDataSet((&state.parent_ds).into())
+                }
+                ManagementId::TimePropertiesDataSet => {
+                    ManagementData::TimePropertiesDataSet(state.time_properties_ds)
+                }
+                ManagementId::PortDataSet => ManagementData::PortDataSet(self.port_ds()),
+                ManagementId::Priority1 => ManagementData::Priority1(state.default_ds.priority_1),
+                ManagementId::Priority2 => ManagementData::Priority2(state.default_ds.priority_2),
+                ManagementId::Domain => ManagementData::Domain(state.default_ds.domain_number),
+                ManagementId::SlaveOnly => ManagementData::SlaveOnly(state.default_ds.slave_only),
+                id if id.is_known() => return Err(ManagementErrorId::NotSupported),
+                _ => return Err(ManagementErrorId::NoSuchId),
+            })
+        })
+    }
+
+    fn management_set(
+        &self,
+        request: ManagementTlv<'_>,
+    ) -> Result<ManagementData, ManagementErrorId> {
+        let data = match ManagementData::deserialize_settable(request.management_id, request.data) {
+            Some(Ok(data)) => data,
+            Some(Err(_)) => return Err(ManagementErrorId::WrongLength),
+            None if request.management_id.is_known() => return Err(ManagementErrorId::NotSetable),
+            None => return Err(ManagementErrorId::NoSuchId),
+        };
+
+        self.instance_state.with_mut(|state| {
+            let policy = state.management_set_policy;
+            match data {
+                ManagementData::Priority1(value) if policy.priority_1 => {
+                    state.default_ds.priority_1 = value
+                }
+                ManagementData::Priority2(value) if policy.priority_2 => {
+                    state.default_ds.priority_2 = value
+                }
+                ManagementData::Domain(value) if policy.domain_number => {
+                    state.default_ds.domain_number = value
+                }
+                ManagementData::SlaveOnly(value) if policy.slave_only => {
+                    state.default_ds.slave_only = value
+                }
+                _ => return Err(ManagementErrorId::NotSetable),
+            }
+
+            log::info!("Applied management SET of {:?}", data);
+            Ok(data)
+        })
+    }
+}
+
+impl<A, C: Clock, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F, S> {
+    /// Send the synchronization metadata of the grandmaster to the slaves of
+    /// this master port, see *SMPTE ST 2059-2:2021 section 9.2*
+    pub(super) fn send_synchronization_metadata(&mut self) -> PortActionIterator<'_> {
+        if !matches!(self.port_state, PortState::Master) {
+            // Restarted when the port becomes master again
+            return actions![];
+        }
+
+        let reset_timer = PortAction::ResetSynchronizationMetadataTimer {
+            duration: self.config.announce_interval.as_core_duration(),
+        };
+
+        let now = self.clock.now().secs();
+        let metadata = self.instance_state.with_mut(|state| {
+            if state.parent_ds.grandmaster_identity == state.default_ds.clock_identity {
+                state.current_synchronization_metadata = state
+                    .synchronization_metadata
+                    .map(|config| config.metadata_at(now));
+            }
+            state.current_synchronization_metadata
+        });
+        let Some(metadata) = metadata.filter(|_| self.is_as_capable()) else {
+            // Keep the timer running for when metadata becomes available
+            return actions![reset_timer];
+        };
+
+        log::trace!("sending synchronization metadata");
+
+        let mut tlv_buffer = [0; SYNCHRONIZATION_METADATA_TLV_SIZE];
+        let mut tlv_builder = TlvSetBuilder::new(&mut tlv_buffer);
+        if let Err(error) = metadata.with_tlv(|tlv| tlv_builder.add(tlv)) {
+            log::error!(
+                "Statime bug: Could not build synchronization metadata tlv: {:?}",
+                error
+            );
+            return actions![reset_timer];
+        }
+
+        let message = self.instance_state.with_ref(|state| {
+            Message::synchronization_metadata(
+                &state.default_ds,
+                self.port_identity,
+                self.management_seq_ids.generate(),
+                self.config.minor_ptp_version.into(),
+                tlv_builder.build(),
+            )
+        });
+
+        let packet_length = match message
+            .serialize_authenticated(&mut self.packet_buffer, self.config.authentication.as_ref())
+        {
+            Ok(length) => length,
+            Err(error) => {
+                log::error!(
+                    "Statime bug: Could not serialize synchronization metadata: {:?}",
+                    error
+                );
+                return actions![reset_timer];
+            }
+        };
+
+        actions![
+            reset_timer,
+            PortAction::SendGeneral {
+                data: &self.packet_buffer[..packet_length],
+                link_local: self.is_link_local(),
+            }
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{ManagementSetPolicy, MasterLockingStatus, SynchronizationMetadataConfig},
+        datastructures::{
+            common::{ClockIdentity, PortIdentity},
+            messages::{Header, MessageBody, MAX_DATA_LEN},
+        },
+        port::{
+            state::SlaveState,
+            tests::{
+                setup_test_port, setup_test_port_custom_clock, setup_test_state, SettableClock,
+            },
+        },
+        time::Time,
+    };
+
+    fn management_request(
+        action: ManagementAction,
+        management_id: ManagementId,
+        data: &[u8],
+        buffer: &mut [u8],
+    ) -> usize {
+        let mut value_buffer = [0; 64];
+        let length = ManagementTlv {
+            management_id,
+            data,
+        }
+        .serialize(&mut value_buffer)
+        .unwrap();
+
+        let mut tlv_buffer = [0; 64];
+        let mut tlv_builder = TlvSetBuilder::new(&mut tlv_buffer);
+        tlv_builder
+            .add(Tlv {
+                tlv_type: TlvType::Management,
+                value: value_buffer[..length].into(),
+            })
+            .unwrap();
+
+        let message = Message {
+            header: Header {
+                source_port_identity: PortIdentity {
+                    clock_identity: ClockIdentity([9; 8]),
+                    port_number: 1,
+                },
+                sequence_id: 42,
+                ..Header::new(1)
+            },
+            body: MessageBody::Management(ManagementMessage {
+                // address all ports of all clocks
+                target_port_identity: PortIdentity {
+                    clock_identity: ClockIdentity([0xff; 8]),
+                    port_number: 0xffff,
+                },
+                starting_boundary_hops: 1,
+                boundary_hops: 1,
+                action,
+            }),
+            suffix: tlv_builder.build(),
+        };
+        message.serialize(buffer).unwrap()
+    }
+
+    fn parse_response(data: &[u8]) -> (ManagementAction, TlvType, [u8; 40], usize) {
+        let message = Message::deserialize(data).unwrap();
+        assert_eq!(message.header.sequence_id, 42);
+        let MessageBody::Management(management) = message.body else {
+            panic!("Expected management message");
+        };
+        assert_eq!(
+            management.target_port_identity,
+            PortIdentity {
+                clock_identity: ClockIdentity([9; 8]),
+                port_number: 1,
+            }
+        );
+        let tlv = message.suffix.tlv().next().unwrap();
+        let mut value = [0; 40];
+        value[..tlv.value.len()].copy_from_slice(&tlv.value);
+        (management.action, tlv.tlv_type, value, tlv.value.len())
+    }
+
+    #[test]
+    fn test_management_get_default_ds() {
+        let state = setup_test_state();
+        let mut port = setup_test_port(&state);
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = management_request(
+            ManagementAction::GET,
+            ManagementId::DefaultDataSet,
+            &[],
+            &mut buffer,
+        );
+
+        let mut actions = port.handle_general_receive(&buffer[..length]);
+        let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+
+        let (action, tlv_type, value, length) = parse_response(data);
+        assert_eq!(action, ManagementAction::RESPONSE);
+        assert_eq!(tlv_type, TlvType::Management);
+        assert_eq!(length, 22);
+        assert_eq!(value[0..2], [0x20, 0x00]);
+        // priority1 and priority2 from the test state
+        assert_eq!(value[6], 255);
+        assert_eq!(value[11], 255);
+    }
+
+    #[test]
+    fn test_management_set_respects_policy() {
+        let state = setup_test_state();
+        let mut port = setup_test_port(&state);
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = management_request(
+            ManagementAction::SET,
+            ManagementId::Priority1,
+            &[12, 0],
+            &mut buffer,
+        );
+
+        let mut actions = port.handle_general_receive(&buffer[..length]);
+        let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        let (action, tlv_type, value, _) = parse_response(data);
+        assert_eq!(action, ManagementAction::RESPONSE);
+        assert_eq!(tlv_type, TlvType::ManagementErrorStatus);
+        assert_eq!(
+            ManagementErrorStatusTlv::deserialize(&value).unwrap(),
+            ManagementErrorStatusTlv {
+                error_id: ManagementErrorId::NotSetable,
+                management_id: ManagementId::Priority1,
+            }
+        );
+        drop(actions);
+        assert_eq!(state.borrow().default_ds.priority_1, 255);
+
+        state.borrow_mut().management_set_policy = ManagementSetPolicy {
+            priority_1: true,
+            ..Default::default()
+        };
+
+        let mut actions = port.handle_general_receive(&buffer[..length]);
+        let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        let (action, tlv_type, value, length) = parse_response(data);
+        assert_eq!(action, ManagementAction::RESPONSE);
+        assert_eq!(tlv_type, TlvType::Management);
+        assert_eq!(value[..length], [0x20, 0x05, 12, 0]);
+        drop(actions);
+        assert_eq!(state.borrow().default_ds.priority_1, 12);
+    }
+
+    #[test]
+    fn test_management_unknown_id() {
+        let state = setup_test_state();
+        let mut port = setup_test_port(&state);
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = management_request(
+            ManagementAction::COMMAND,
+            ManagementId::Other(0x3fff),
+            &[],
+            &mut buffer,
+        );
+
+        let mut actions = port.handle_general_receive(&buffer[..length]);
+        let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        let (action, tlv_type, value, _) = parse_response(data);
+        assert_eq!(action, ManagementAction::ACKNOWLEDGE);
+        assert_eq!(tlv_type, TlvType::ManagementErrorStatus);
+        assert_eq!(
+            ManagementErrorStatusTlv::deserialize(&value)
+                .unwrap()
+                .error_id,
+            ManagementErrorId::NoSuchId
+        );
+    }
+
+    #[test]
+    fn test_management_ignores_other_targets() {
+        let state = setup_test_state();
+        let mut port = setup_test_port(&state);
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = management_request(
+            ManagementAction::GET,
+            ManagementId::DefaultDataSet,
+            &[],
+            &mut buffer,
+        );
+        // Address a different clock
+        buffer[34..42].copy_from_slice(&[1; 8]);
+
+        let mut actions = port.handle_general_receive(&buffer[..length]);
+        assert!(actions.next().is_none());
+    }
+
+    const METADATA: SynchronizationMetadata = SynchronizationMetadata {
+        frame_rate_numerator: 30000,
+        frame_rate_denominator: 1001,
+        master_locking_status: MasterLockingStatus::Locked,
+        drop_frame: true,
+        color_frame_identification: false,
+        current_local_offset: 3600,
+        jump_seconds: 0,
+        time_of_next_jump: 0,
+        time_of_next_jam: 0,
+        time_of_previous_jam: 0,
+        previous_jam_local_offset: 3600,
+        daylight_saving: false,
+        daylight_saving_at_next_jump: false,
+        daylight_saving_at_previous_jam: false,
+        leap_second_jump: false,
+    };
+
+    #[test]
+    fn test_synchronization_metadata_send() {
+        let state = setup_test_state();
+        let mut port =
+            setup_test_port_custom_clock(&state, SettableClock(Time::from_secs(3 * 86400 + 3600)));
+
+        // Nothing to send while not master
+        let mut actions = port.handle_synchronization_metadata_timer();
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        port.set_forced_port_state(PortState::Master);
+
+        // The timer keeps running without metadata
+        let mut actions = port.handle_synchronization_metadata_timer();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetSynchronizationMetadataTimer { .. })
+        ));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        // Jam at 02:00 local time, which is 01:00 PTP time
+        state.borrow_mut().synchronization_metadata = Some(SynchronizationMetadataConfig {
+            metadata: METADATA,
+            daily_jam: Some(7200),
+        });
+        let expected = SynchronizationMetadata {
+            time_of_next_jam: 4 * 86400 + 3600,
+            time_of_previous_jam: 3 * 86400 + 3600,
+            ..METADATA
+        };
+
+        let mut actions = port.handle_synchronization_metadata_timer();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetSynchronizationMetadataTimer { .. })
+        ));
+        let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+
+        let message = Message::deserialize(data).unwrap();
+        let MessageBody::Management(management) = message.body else {
+            panic!("Expected management message");
+        };
+        assert_eq!(management.action, ManagementAction::COMMAND);
+        assert_eq!(
+            management.target_port_identity,
+            PortIdentity {
+                clock_identity: ClockIdentity([0xff; 8]),
+                port_number: 0xffff,
+            }
+        );
+        let tlv = message.suffix.tlv().next().unwrap();
+        assert_eq!(SynchronizationMetadata::from_tlv(&tlv).unwrap(), expected);
+        drop(actions);
+        assert_eq!(
+            state.borrow().current_synchronization_metadata,
+            Some(expected)
+        );
+
+        // The jams advance with the time of sending
+        port.clock.0 = Time::from_secs(4 * 86400 + 3600);
+        let mut actions = port.handle_synchronization_metadata_timer();
+        actions.next();
+        let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        let message = Message::deserialize(data).unwrap();
+        let tlv = message.suffix.tlv().next().unwrap();
+        let metadata = SynchronizationMetadata::from_tlv(&tlv).unwrap();
+        assert_eq!(metadata.time_of_next_jam, 5 * 86400 + 3600);
+        assert_eq!(metadata.time_of_previous_jam, 4 * 86400 + 3600);
+    }
+
+    #[test]
+    fn test_synchronization_metadata_receive() {
+        let state = setup_test_state();
+        let mut port = setup_test_port(&state);
+
+        let master = PortIdentity {
+            clock_identity: ClockIdentity([9; 8]),
+            port_number: 1,
+        };
+        port.set_forced_port_state(PortState::Slave(SlaveState::new(master)));
+
+        let mut tlv_buffer = [0; SYNCHRONIZATION_METADATA_TLV_SIZE];
+        let mut tlv_builder = TlvSetBuilder::new(&mut tlv_buffer);
+        METADATA.with_tlv(|tlv| tlv_builder.add(tlv)).unwrap();
+        let tlvs = tlv_builder.build();
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let default_ds = state.borrow().default_ds;
+
+        // Only our master is listened to
+        let other = PortIdentity {
+            clock_identity: ClockIdentity([8; 8]),
+            port_number: 1,
+        };
+        let length = Message::synchronization_metadata(&default_ds, other, 1, 1, tlvs.clone())
+            .serialize(&mut buffer)
+            .unwrap();
+        let mut actions = port.handle_general_receive(&buffer[..length]);
+        assert!(actions.next().is_none());
+        drop(actions);
+        assert_eq!(state.borrow().current_synchronization_metadata, None);
+
+        let length = Message::synchronization_metadata(&default_ds, master, 2, 1, tlvs)
+            .serialize(&mut buffer)
+            .unwrap();
+        let mut actions = port.handle_general_receive(&buffer[..length]);
+        assert!(actions.next().is_none());
+        drop(actions);
+        assert_eq!(
+            state.borrow().current_synchronization_metadata,
+            Some(METADATA)
+        );
+    }
+}
@@ -2,34 +2,65 @@ use arrayvec::ArrayVec;
 
 use super::{state::PortState, ForwardedTLVProvider, Port, PortActionIterator, Running};
 use crate::{
+    config::PortAddress,
     datastructures::{
-        common::{PortIdentity, Tlv, TlvSetBuilder, TlvType},
+        common::{FollowUpInformation, PortIdentity, PowerProfile, Tlv, TlvSetBuilder, TlvType},
         messages::{DelayReqMessage, Header, Message, MAX_DATA_LEN},
     },
     filters::Filter,
     port::{actions::TimestampContextInner, PortAction, TimestampContext},
     ptp_instance::PtpInstanceStateMutex,
-    time::Time,
+    time::{Duration, Time},
+    Clock,
 };
 
-impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F, S> {
+// Time between creating a one-step sync and sending it, which gives the
+// platform the opportunity to send it exactly at the time it contains.
+const ONE_STEP_LAUNCH_DELAY_MICROS: i64 = 500;
+
+impl<A, C: Clock, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F, S> {
     pub(super) fn send_sync(&mut self) -> PortActionIterator<'_> {
-        if matches!(self.port_state, PortState::Master) {
+        let alternate_master = self.is_alternate_master();
+        // Unicast only ports send to their grantees through the unicast timer
+        let sending = (matches!(self.port_state, PortState::Master) || alternate_master)
+            && !self.config.unicast_only;
+
+        if (sending && !self.is_as_capable()) || self.may_become_alternate_master() {
+            // Keep the timer running for when the neighbor becomes capable again, or
+            // this port becomes an alternate master
+            actions![PortAction::ResetSyncTimer {
+                duration: self.config.sync_interval.as_core_duration(),
+            }]
+        } else if sending {
             log::trace!("sending sync message");
 
             let seq_id = self.sync_seq_ids.generate();
-            let packet_length = match self
-                .instance_state
-                .with_ref(|state| {
-                    Message::sync(
-                        &state.default_ds,
-                        self.port_identity,
-                        seq_id,
-                        self.config.minor_ptp_version.into(),
-                    )
-                })
-                .serialize(&mut self.packet_buffer)
-            {
+            // One-step syncs contain the time they are to be sent at
+            let launch_time = self
+                .config
+                .one_step
+                .then(|| self.clock.now() + Duration::from_micros(ONE_STEP_LAUNCH_DELAY_MICROS));
+            let mut message = self.instance_state.with_ref(|state| match launch_time {
+                Some(launch_time) => Message::one_step_sync(
+                    &state.default_ds,
+                    self.port_identity,
+                    seq_id,
+                    launch_time,
+                    self.config.minor_ptp_version.into(),
+                ),
+                None => Message::sync(
+                    &state.default_ds,
+                    self.port_identity,
+                    seq_id,
+                    self.config.minor_ptp_version.into(),
+                ),
+            });
+            message.header.alternate_master_flag = alternate_master;
+
+            let packet_length = match message.serialize_authenticated(
+                &mut self.packet_buffer,
+                self.config.authentication.as_ref(),
+            ) {
                 Ok(message) => message,
                 Err(error) => {
                     log::error!("Statime bug: Could not serialize sync: {:?}", error);
@@ -37,42 +68,86 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
                 }
             };
 
-            actions![
-                PortAction::ResetSyncTimer {
-                    duration: self.config.sync_interval.as_core_duration(),
-                },
-                PortAction::SendEvent {
-                    context: TimestampContext {
-                        inner: TimestampContextInner::Sync { id: seq_id },
-                    },
-                    data: &self.packet_buffer[..packet_length],
-                    link_local: false,
-                }
-            ]
+            let reset_timer = PortAction::ResetSyncTimer {
+                duration: self.config.sync_interval.as_core_duration(),
+            };
+            if let Some(launch_time) = launch_time {
+                self.slave_collector.record_sync(None, seq_id, launch_time);
+            }
+
+            let link_local = self.is_link_local();
+            let data = &self.packet_buffer[..packet_length];
+
+            match launch_time {
+                Some(launch_time) => actions![
+                    reset_timer,
+                    PortAction::SendEventOneStep {
+                        data,
+                        launch_time,
+                        link_local,
+                    }
+                ],
+                None => actions![
+                    reset_timer,
+                    PortAction::SendEvent {
+                        context: TimestampContext {
+                            inner: TimestampContextInner::Sync { id: seq_id },
+                        },
+                        data,
+                        link_local,
+                    }
+                ],
+            }
         } else {
             actions![]
         }
     }
+}
 
+impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F, S> {
     pub(super) fn handle_sync_timestamp(
         &mut self,
         id: u16,
         timestamp: Time,
     ) -> PortActionIterator<'_> {
-        if matches!(self.port_state, PortState::Master) {
-            let packet_length = match self
-                .instance_state
-                .with_ref(|state| {
-                    Message::follow_up(
-                        &state.default_ds,
-                        self.port_identity,
-                        id,
-                        timestamp,
-                        self.config.minor_ptp_version.into(),
-                    )
-                })
-                .serialize(&mut self.packet_buffer)
-            {
+        let alternate_master = self.is_alternate_master();
+
+        if matches!(self.port_state, PortState::Master) || alternate_master {
+            self.slave_collector.record_sync(None, id, timestamp);
+
+            let mut message = self.instance_state.with_ref(|state| {
+                Message::follow_up(
+                    &state.default_ds,
+                    self.port_identity,
+                    id,
+                    timestamp,
+                    self.config.minor_ptp_version.into(),
+                )
+            });
+            message.header.alternate_master_flag = alternate_master;
+
+            // gPTP masters describe the rate of the grandmaster relative to their
+            // own. The grandmaster sends the default information, boundary clocks
+            // forward what their slave port received.
+            let mut tlv_buffer = [0; MAX_DATA_LEN];
+            if self.config.gptp.is_some() {
+                let information = self.instance_state.with_ref(|state| {
+                    if state.parent_ds.grandmaster_identity == state.default_ds.clock_identity {
+                        FollowUpInformation::default()
+                    } else {
+                        state.received_follow_up_information.unwrap_or_default()
+                    }
+                });
+                let mut tlv_builder = TlvSetBuilder::new(&mut tlv_buffer);
+                // Will not fail as the buffer fits the TLV
+                information.with_tlv(|tlv| tlv_builder.add(tlv)).unwrap();
+                message.suffix = tlv_builder.build();
+            }
+
+            let packet_length = match message.serialize_authenticated(
+                &mut self.packet_buffer,
+                self.config.authentication.as_ref(),
+            ) {
                 Ok(length) => length,
                 Err(error) => {
                     log::error!(
@@ -85,7 +160,7 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
 
             actions![PortAction::SendGeneral {
                 data: &self.packet_buffer[..packet_length],
-                link_local: false,
+                link_local: self.is_link_local(),
             }]
         } else {
             actions![]
@@ -96,7 +171,18 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
         &mut self,
         tlv_provider: &mut impl ForwardedTLVProvider,
     ) -> PortActionIterator<'_> {
-        if matches!(self.port_state, PortState::Master) {
+        let alternate_master = self.is_alternate_master();
+        // Unicast only ports send to their grantees through the unicast timer
+        let sending = (matches!(self.port_state, PortState::Master) || alternate_master)
+            && !self.config.unicast_only;
+
+        if (sending && !self.is_as_capable()) || self.may_become_alternate_master() {
+            // Keep the timer running for when the neighbor becomes capable again, or
+            // this port becomes an alternate master
+            actions![PortAction::ResetAnnounceTimer {
+                duration: self.config.announce_interval.as_core_duration(),
+            }]
+        } else if sending {
             log::trace!("sending announce message");
 
             let mut tlv_buffer = [0; MAX_DATA_LEN];
@@ -110,7 +196,13 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
                     self.config.minor_ptp_version.into(),
                 )
             });
-            let mut tlv_margin = MAX_DATA_LEN - message.wire_size();
+            let mut tlv_margin = MAX_DATA_LEN
+                - message.wire_size()
+                - self
+                    .config
+                    .authentication
+                    .as_ref()
+                    .map_or(0, |security_association| security_association.tlv_size());
 
             let path_trace_enabled = self.instance_state.with_ref(|state| {
                 let default_ds = &state.default_ds;
@@ -141,6 +233,43 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
                 path_trace_ds.enable
             });
 
+            // As grandmaster we distribute our own alternate timescales and power
+            // profile information, otherwise they are forwarded from our parent with
+            // the other TLVs below
+            let power_profile = self.instance_state.with_ref(|state| {
+                if state.parent_ds.grandmaster_identity == state.default_ds.clock_identity {
+                    for alternate_timescale in &state.alternate_timescales {
+                        alternate_timescale.with_tlv(|tlv| {
+                            let tlv_size = tlv.wire_size();
+                            if tlv_margin > tlv_size {
+                                tlv_margin -= tlv_size;
+                                // Will not fail as previous checks ensure sufficient space in
+                                // buffer.
+                                tlv_builder.add(tlv).unwrap();
+                            }
+                        });
+                    }
+
+                    if let Some(config) = state.power_profile {
+                        let power_profile = PowerProfile {
+                            grandmaster_id: config.grandmaster_id,
+                            total_time_inaccuracy: config.time_inaccuracy,
+                        };
+                        power_profile.with_tlv(|tlv| {
+                            let tlv_size = tlv.wire_size();
+                            if tlv_margin > tlv_size {
+                                tlv_margin -= tlv_size;
+                                // Will not fail as previous checks ensure sufficient space in
+                                // buffer.
+                                tlv_builder.add(tlv).unwrap();
+                            }
+                        });
+                    }
+                }
+
+                state.power_profile
+            });
+
             while let Some(tlv) = tlv_provider.next_if_smaller(tlv_margin) {
                 assert!(tlv.size() < tlv_margin);
                 let parent_port_identity = self
@@ -156,14 +285,37 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
                     continue;
                 }
 
+                // Pass on the power profile information with our own time inaccuracy
+                // added, or not at all without the power profile
+                if PowerProfile::is_power_profile_tlv(&tlv.tlv) {
+                    let (Some(config), Ok(received)) =
+                        (power_profile, PowerProfile::from_tlv(&tlv.tlv))
+                    else {
+                        continue;
+                    };
+
+                    received
+                        .add_time_inaccuracy(config.time_inaccuracy)
+                        .with_tlv(|tlv| {
+                            // Fits as it is no larger than the received TLV
+                            tlv_margin -= tlv.wire_size();
+                            tlv_builder.add(tlv).unwrap();
+                        });
+                    continue;
+                }
+
                 tlv_margin -= tlv.size();
                 // Will not fail as previous checks ensure sufficient space in buffer.
                 tlv_builder.add(tlv.tlv).unwrap();
             }
 
             message.suffix = tlv_builder.build();
+            message.header.alternate_master_flag = alternate_master;
 
-            let packet_length = match message.serialize(&mut self.packet_buffer) {
+            let packet_length = match message.serialize_authenticated(
+                &mut self.packet_buffer,
+                self.config.authentication.as_ref(),
+            ) {
                 Ok(length) => length,
                 Err(error) => {
                     log::error!(
@@ -180,7 +332,7 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
                 },
                 PortAction::SendGeneral {
                     data: &self.packet_buffer[..packet_length],
-                    link_local: false,
+                    link_local: self.is_link_local(),
                 }
             ]
         } else {
@@ -193,10 +345,16 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
         header: Header,
         message: DelayReqMessage,
         timestamp: Time,
+        source: Option<PortAddress>,
     ) -> PortActionIterator<'_> {
         if matches!(self.port_state, PortState::Master) {
             log::debug!("Received DelayReq");
-            let delay_resp_message = Message::delay_resp(
+            self.slave_collector.record_delay_req(
+                header.source_port_identity,
+                header.sequence_id,
+                timestamp - Duration::from(header.correction_field),
+            );
+            let mut delay_resp_message = Message::delay_resp(
                 header,
                 message,
                 self.port_identity,
@@ -204,7 +362,19 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
                 timestamp,
             );
 
-            let packet_length = match delay_resp_message.serialize(&mut self.packet_buffer) {
+            // Slaves in hybrid mode send their delay requests unicast and expect a
+            // unicast response, see RFC 8173 section 7
+            let unicast_address = self
+                .unicast_delay_resp_address(header.source_port_identity)
+                .or(source.filter(|_| header.unicast_flag));
+            if unicast_address.is_some() {
+                delay_resp_message.header.unicast_flag = true;
+            }
+
+            let packet_length = match delay_resp_message.serialize_authenticated(
+                &mut self.packet_buffer,
+                self.config.authentication.as_ref(),
+            ) {
                 Ok(length) => length,
                 Err(error) => {
                     log::error!("Could not serialize delay response: {:?}", error);
@@ -212,10 +382,14 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
                 }
             };
 
-            actions![PortAction::SendGeneral {
-                data: &self.packet_buffer[..packet_length],
-                link_local: false,
-            }]
+            let data = &self.packet_buffer[..packet_length];
+            match unicast_address {
+                Some(address) => actions![PortAction::SendGeneralUnicast { data, address }],
+                None => actions![PortAction::SendGeneral {
+                    data,
+                    link_local: self.is_link_local(),
+                }],
+            }
         } else {
             actions![]
         }
@@ -237,7 +411,9 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
             )
         });
 
-        let packet_length = match pdelay_resp_message.serialize(&mut self.packet_buffer) {
+        let packet_length = match pdelay_resp_message
+            .serialize_authenticated(&mut self.packet_buffer, self.config.authentication.as_ref())
+        {
             Ok(length) => length,
             Err(error) => {
                 log::error!("Could not serialize pdelay response: {:?}", error);
@@ -274,7 +450,9 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
             )
         });
 
-        let packet_length = match pdelay_resp_follow_up_messgae.serialize(&mut self.packet_buffer) {
+        let packet_length = match pdelay_resp_follow_up_messgae
+            .serialize_authenticated(&mut self.packet_buffer, self.config.authentication.as_ref())
+        {
             Ok(length) => length,
             Err(error) => {
                 log::error!("Could not serialize pdelay_response_followup: {:?}", error);
@@ -291,19 +469,26 @@ impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F,
 
 #[cfg(test)]
 mod tests {
+    use arrayvec::ArrayString;
     use fixed::types::{I48F16, U96F32};
 
     use super::*;
     use crate::{
-        config::DelayMechanism,
+        config::{
+            AlternateTimescale, ClockIdentity, DelayMechanism, GptpConfig, PowerProfileConfig,
+            PtpMinorVersion, SecurityAssociation, SecurityKey,
+        },
         datastructures::{
             common::{PortIdentity, TimeInterval},
             datasets::PathTraceDS,
             messages::{Header, MessageBody},
         },
         port::{
-            tests::{setup_test_port, setup_test_state},
-            NoForwardedTLVs,
+            tests::{
+                setup_test_port, setup_test_port_custom_clock, setup_test_port_custom_identity,
+                setup_test_state, SettableClock,
+            },
+            ForwardedTLV, NoForwardedTLVs,
         },
         time::Interval,
     };
@@ -334,6 +519,7 @@ mod tests {
                 origin_timestamp: Time::from_micros(0).into(),
             },
             Time::from_fixed_nanos(U96F32::from_bits((200000 << 32) + (500 << 16))),
+            None,
         );
 
         let Some(PortAction::SendGeneral {
@@ -387,6 +573,7 @@ mod tests {
                 origin_timestamp: Time::from_micros(0).into(),
             },
             Time::from_fixed_nanos(U96F32::from_bits((220000 << 32) + (300 << 16))),
+            None,
         );
 
         let Some(PortAction::SendGeneral {
@@ -422,6 +609,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_hybrid_delay_response() {
+        let state = setup_test_state();
+        let mut port = setup_test_port(&state);
+        port.set_forced_port_state(PortState::Master);
+
+        let slave_address = PortAddress::Ipv4([192, 0, 2, 20]);
+        let header = Header {
+            sequence_id: 14,
+            source_port_identity: PortIdentity {
+                port_number: 3,
+                ..Default::default()
+            },
+            unicast_flag: true,
+            ..Header::new(1)
+        };
+        let request = DelayReqMessage {
+            origin_timestamp: Time::from_micros(0).into(),
+        };
+
+        // A unicast request is answered unicast
+        let mut action =
+            port.handle_delay_req(header, request, Time::from_micros(10), Some(slave_address));
+        let Some(PortAction::SendGeneralUnicast { data, address }) = action.next() else {
+            panic!("Unexpected resulting action");
+        };
+        assert_eq!(address, slave_address);
+        let msg = Message::deserialize(data).unwrap();
+        assert!(matches!(msg.body, MessageBody::DelayResp(_)));
+        assert!(msg.header.unicast_flag);
+        assert!(action.next().is_none());
+        drop(action);
+
+        // And a multicast request multicast
+        let header = Header {
+            unicast_flag: false,
+            ..header
+        };
+        let mut action =
+            port.handle_delay_req(header, request, Time::from_micros(10), Some(slave_address));
+        assert!(matches!(
+            action.next(),
+            Some(PortAction::SendGeneral { .. })
+        ));
+        assert!(action.next().is_none());
+    }
+
     #[test]
     fn test_announce() {
         let state = setup_test_state();
@@ -547,6 +781,217 @@ mod tests {
         assert!(tlvs.next().is_none());
     }
 
+    #[test]
+    fn test_announce_alternate_timescales() {
+        let state = setup_test_state();
+
+        let alternate_timescale = AlternateTimescale {
+            key_field: 1,
+            current_offset: 3600,
+            jump_seconds: 3600,
+            time_of_next_jump: 1711846800,
+            display_name: ArrayString::from("CET").unwrap(),
+        };
+        state.borrow_mut().alternate_timescales = [alternate_timescale].into_iter().collect();
+
+        let mut port = setup_test_port(&state);
+
+        port.set_forced_port_state(PortState::Master);
+
+        let mut actions = port.send_announce(&mut NoForwardedTLVs);
+
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetAnnounceTimer { .. })
+        ));
+        let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let msg = Message::deserialize(data).unwrap();
+        let mut tlvs = msg.suffix.tlv();
+        let Some(tlv) = tlvs.next() else {
+            panic!("Missing TLV")
+        };
+        assert_eq!(
+            AlternateTimescale::from_tlv(&tlv).unwrap(),
+            alternate_timescale
+        );
+        assert!(tlvs.next().is_none());
+
+        // Only the grandmaster distributes its own alternate timescales
+        state.borrow_mut().parent_ds.grandmaster_identity = ClockIdentity([1; 8]);
+
+        let mut actions = port.send_announce(&mut NoForwardedTLVs);
+
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetAnnounceTimer { .. })
+        ));
+        let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+
+        let msg = Message::deserialize(data).unwrap();
+        assert_eq!(msg.suffix, Default::default());
+    }
+
+    /// Provides a single forwarded TLV
+    struct SingleForwardedTLV(Option<ForwardedTLV<'static>>);
+
+    impl ForwardedTLVProvider for SingleForwardedTLV {
+        fn next_if_smaller(&mut self, max_size: usize) -> Option<ForwardedTLV<'_>> {
+            self.0.take().filter(|tlv| tlv.size() <= max_size)
+        }
+    }
+
+    #[test]
+    fn test_announce_power_profile() {
+        // Received from the parent with grandmaster id 7 and 250ns inaccuracy
+        const RECEIVED: [u8; 18] = [
+            0x1c, 0x12, 0x9d, 0x00, 0x00, 0x02, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0xfa, 0x00, 0x00,
+        ];
+
+        let state = setup_test_state();
+        state.borrow_mut().power_profile = Some(PowerProfileConfig {
+            grandmaster_id: 3,
+            time_inaccuracy: 100,
+        });
+
+        let mut port = setup_test_port(&state);
+        port.set_forced_port_state(PortState::Master);
+
+        let mut send_announce = |tlv_provider: &mut SingleForwardedTLV| {
+            let mut actions = port.send_announce(tlv_provider);
+            assert!(matches!(
+                actions.next(),
+                Some(PortAction::ResetAnnounceTimer { .. })
+            ));
+            let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+                panic!("Unexpected action");
+            };
+
+            let msg = Message::deserialize(data).unwrap();
+            let power_profiles: ArrayVec<_, 4> = msg
+                .suffix
+                .tlv()
+                .map(|tlv| PowerProfile::from_tlv(&tlv).unwrap())
+                .collect();
+            power_profiles
+        };
+
+        // The grandmaster sends its own information
+        assert_eq!(
+            send_announce(&mut SingleForwardedTLV(None)).as_slice(),
+            [PowerProfile {
+                grandmaster_id: 3,
+                total_time_inaccuracy: 100,
+            }]
+        );
+
+        // A boundary clock adds its time inaccuracy to that of its parent
+        state.borrow_mut().parent_ds.grandmaster_identity = ClockIdentity([1; 8]);
+        let forwarded = || {
+            SingleForwardedTLV(Some(ForwardedTLV {
+                tlv: Tlv {
+                    tlv_type: TlvType::OrganizationExtension,
+                    value: RECEIVED[..].into(),
+                },
+                sender_identity: state.borrow().parent_ds.parent_port_identity,
+            }))
+        };
+        assert_eq!(
+            send_announce(&mut forwarded()).as_slice(),
+            [PowerProfile {
+                grandmaster_id: 7,
+                total_time_inaccuracy: 350,
+            }]
+        );
+
+        // Without the power profile the information is not passed on
+        state.borrow_mut().power_profile = None;
+        assert!(send_announce(&mut forwarded()).is_empty());
+    }
+
+    fn test_security_association() -> SecurityAssociation {
+        SecurityAssociation {
+            spp: 1,
+            keys: [SecurityKey {
+                id: 7,
+                key: b"secret key".iter().copied().collect(),
+                icv_length: 16,
+            }]
+            .into_iter()
+            .collect(),
+            send_key_id: 7,
+        }
+    }
+
+    #[test]
+    fn test_authenticated_announce() {
+        let master_state = setup_test_state();
+        let mut master = setup_test_port_custom_identity(
+            &master_state,
+            PortIdentity {
+                clock_identity: ClockIdentity([1; 8]),
+                port_number: 1,
+            },
+        );
+        master.config.authentication = Some(test_security_association());
+        master.set_forced_port_state(PortState::Master);
+
+        let mut actions = master.send_announce(&mut NoForwardedTLVs);
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetAnnounceTimer { .. })
+        ));
+        let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        let mut packet = [0; MAX_DATA_LEN];
+        packet[..data.len()].copy_from_slice(data);
+        let packet_length = data.len();
+        drop(actions);
+
+        let msg = Message::deserialize(&packet[..packet_length]).unwrap();
+        let Some(tlv) = msg.suffix.tlv().last() else {
+            panic!("Missing TLV");
+        };
+        assert_eq!(tlv.tlv_type, TlvType::Authentication);
+        assert_eq!(tlv.value.len(), 6 + 16);
+
+        // Only authenticated messages are accepted
+        let slave_state = setup_test_state();
+        let mut slave = setup_test_port(&slave_state);
+        slave.config.authentication = Some(test_security_association());
+
+        let mut actions = slave.handle_general_receive(&packet[..packet_length]);
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetAnnounceReceiptTimer { .. })
+        ));
+        drop(actions);
+
+        let mut tampered = packet;
+        tampered[47] ^= 0xff;
+        let mut actions = slave.handle_general_receive(&tampered[..packet_length]);
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let msg = Message::announce(
+            &master_state.borrow(),
+            master.port_identity,
+            0,
+            PtpMinorVersion::One.into(),
+        );
+        let packet_length = msg.serialize(&mut packet).unwrap();
+        let mut actions = slave.handle_general_receive(&packet[..packet_length]);
+        assert!(actions.next().is_none());
+    }
+
     #[test]
     fn test_sync() {
         let state = setup_test_state();
@@ -697,6 +1142,154 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_one_step_sync() {
+        let state = setup_test_state();
+
+        let mut port = setup_test_port_custom_clock(&state, SettableClock(Time::from_secs(10)));
+        port.config.one_step = true;
+        port.set_forced_port_state(PortState::Master);
+
+        let mut actions = port.send_sync();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetSyncTimer { .. })
+        ));
+        let Some(PortAction::SendEventOneStep {
+            data,
+            launch_time,
+            link_local: false,
+        }) = actions.next()
+        else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+        assert_eq!(launch_time, Time::from_micros(10_000_500));
+
+        // The sync carries its own origin timestamp, so no follow up is sent
+        let sync = Message::deserialize(data).unwrap();
+        assert!(!sync.header.two_step_flag);
+        let MessageBody::Sync(sync) = sync.body else {
+            panic!("Unexpected message type");
+        };
+        assert_eq!(sync.origin_timestamp, launch_time.into());
+    }
+
+    #[test]
+    fn test_unicast_only_master() {
+        let state = setup_test_state();
+
+        let mut port = setup_test_port(&state);
+        port.config.unicast_only = true;
+        port.set_forced_port_state(PortState::Master);
+
+        // Neither sync nor announce messages are multicast
+        assert!(port.send_sync().next().is_none());
+        assert!(port.send_announce(&mut NoForwardedTLVs).next().is_none());
+    }
+
+    #[test]
+    fn test_gptp_sync() {
+        let state = setup_test_state();
+
+        let mut port = setup_test_port(&state);
+        let gptp_config = GptpConfig::default();
+        port.config.gptp = Some(gptp_config);
+        port.set_forced_port_state(PortState::Master);
+
+        // Nothing is sent until the neighbor is capable of running gPTP
+        let mut actions = port.send_sync();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetSyncTimer { .. })
+        ));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let mut actions = port.send_announce(&mut NoForwardedTLVs);
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetAnnounceTimer { .. })
+        ));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let neighbor = PortIdentity {
+            clock_identity: ClockIdentity([1; 8]),
+            port_number: 1,
+        };
+        let own_identity = port.port_identity.clock_identity;
+        port.gptp.update_as_capable(
+            &gptp_config,
+            own_identity,
+            neighbor,
+            Duration::from_nanos(100),
+        );
+
+        let mut actions = port.send_sync();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetSyncTimer { .. })
+        ));
+        let Some(PortAction::SendEvent {
+            context,
+            link_local: true,
+            ..
+        }) = actions.next()
+        else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let TimestampContextInner::Sync { id } = context.inner else {
+            panic!("Wrong type of context");
+        };
+
+        // The follow up carries the Follow_Up information TLV
+        let mut actions = port.handle_sync_timestamp(id, Time::from_micros(601));
+        let Some(PortAction::SendGeneral {
+            data,
+            link_local: true,
+        }) = actions.next()
+        else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+
+        let follow_up = Message::deserialize(data).unwrap();
+        assert!(matches!(follow_up.body, MessageBody::FollowUp(_)));
+        let information: ArrayVec<_, 2> = follow_up
+            .suffix
+            .tlv()
+            .map(|tlv| FollowUpInformation::from_tlv(&tlv).unwrap())
+            .collect();
+        assert_eq!(information.as_slice(), &[FollowUpInformation::default()]);
+        drop(actions);
+
+        // A boundary clock forwards the information of the grandmaster
+        let received = FollowUpInformation {
+            cumulative_scaled_rate_offset: 1000,
+            gm_time_base_indicator: 3,
+            last_gm_phase_change: 5,
+            scaled_last_gm_freq_change: 7,
+        };
+        state.borrow_mut().parent_ds.grandmaster_identity = ClockIdentity([3; 8]);
+        state.borrow_mut().received_follow_up_information = Some(received);
+
+        let mut actions = port.handle_sync_timestamp(id, Time::from_micros(601));
+        let Some(PortAction::SendGeneral { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        let follow_up = Message::deserialize(data).unwrap();
+        let information: ArrayVec<_, 2> = follow_up
+            .suffix
+            .tlv()
+            .map(|tlv| FollowUpInformation::from_tlv(&tlv).unwrap())
+            .collect();
+        assert_eq!(information.as_slice(), &[received]);
+    }
+
     #[test]
     fn test_peer_delay() {
         let state = setup_test_state();
@@ -8,11 +8,19 @@ pub use actions::{
     ForwardedTLV, ForwardedTLVProvider, NoForwardedTLVs, PortAction, PortActionIterator,
     TimestampContext,
 };
+use arrayvec::ArrayVec;
 pub use measurement::Measurement;
 use rand::Rng;
 use state::PortState;
 
-use self::sequence_id::SequenceIdGenerator;
+use self::{
+    alternate_master::AlternateMasterState,
+    collector::SlaveCollector,
+    gptp::GptpState,
+    monitoring::SlaveMonitor,
+    sequence_id::SequenceIdGenerator,
+    unicast::{UnicastGrant, UnicastMaster, MAX_UNICAST_GRANTS},
+};
 pub use crate::datastructures::messages::{
     is_compatible as is_message_buffer_compatible, MAX_DATA_LEN,
 };
@@ -24,13 +32,16 @@ use crate::{
         bmca::{BestAnnounceMessage, Bmca},
     },
     clock::Clock,
-    config::PortConfig,
+    config::{DatasetComparison, DesiredState, PortAddress, PortConfig, MAX_UNICAST_MASTERS},
     datastructures::{
-        common::PortIdentity,
-        messages::{Message, MessageBody},
+        common::{ClockIdentity, FollowUpInformation, PortIdentity},
+        messages::{message_domain, verify_authentication, Message, MessageBody},
     },
     filters::{Filter, FilterEstimate},
-    observability::{self, port::PortDS},
+    observability::{
+        self,
+        port::{AlternateMaster, ForeignMaster, PortDS, SlaveReport},
+    },
     ptp_instance::{PtpInstanceState, PtpInstanceStateMutex},
     time::{Duration, Time},
 };
@@ -57,15 +68,30 @@ macro_rules! actions {
             crate::port::PortActionIterator::from(list)
         }
     };
+    [$action1:expr, $action2:expr, $action3:expr] => {
+        {
+            let mut list = ::arrayvec::ArrayVec::new();
+            list.push($action1);
+            list.push($action2);
+            list.push($action3);
+            crate::port::PortActionIterator::from(list)
+        }
+    };
 }
 
 mod actions;
+mod alternate_master;
 mod bmca;
+mod collector;
+mod gptp;
+mod management;
 mod master;
 mod measurement;
-mod sequence_id;
+mod monitoring;
+pub(crate) mod sequence_id;
 mod slave;
 pub(crate) mod state;
+mod unicast;
 
 /// A single port of the PTP instance
 ///
@@ -137,6 +163,18 @@ pub(crate) mod state;
 ///     master_only: false,
 ///     delay_asymmetry: Default::default(),
 ///     minor_ptp_version: PtpMinorVersion::One,
+///     unicast_grantor: None,
+///     unicast_master_table: Default::default(),
+///     one_step: false,
+///     authentication: None,
+///     slave_monitoring: None,
+///     gptp: None,
+///     desired_state: None,
+///     number_alternate_masters: 0,
+///     local_priority: 128,
+///     link_local: false,
+///     hybrid_e2e: false,
+///     unicast_only: false,
 /// };
 /// let filter_config = 1.0;
 /// let clock = system::Clock {};
@@ -171,6 +209,8 @@ pub(crate) mod state;
 /// #     pub struct UdpSocket;
 /// #     impl UdpSocket {
 /// #         pub fn send(&mut self, buf: &[u8], link_local: bool) -> statime::time::Time { unimplemented!() }
+/// #         pub fn send_to(&mut self, buf: &[u8], address: statime::config::PortAddress) -> statime::time::Time { unimplemented!() }
+/// #         pub fn send_at(&mut self, buf: &[u8], link_local: bool, launch_time: statime::time::Time) {}
 /// #     }
 /// # }
 /// struct MyPortResources {
@@ -179,6 +219,8 @@ pub(crate) mod state;
 ///     delay_req_timer: system::Timer,
 ///     announce_receipt_timer: system::Timer,
 ///     filter_update_timer: system::Timer,
+///     unicast_timer: system::Timer,
+///     synchronization_metadata_timer: system::Timer,
 ///     time_critical_socket: system::UdpSocket,
 ///     general_socket: system::UdpSocket,
 ///     send_timestamp: Option<(TimestampContext, Time)>
@@ -194,6 +236,16 @@ pub(crate) mod state;
 ///             PortAction::SendGeneral { data, link_local } => {
 ///                 resources.general_socket.send(data, link_local);
 ///             }
+///             PortAction::SendEventUnicast { context, data, address } => {
+///                 let timestamp = resources.time_critical_socket.send_to(data, address);
+///                 resources.send_timestamp = Some((context, timestamp));
+///             }
+///             PortAction::SendEventOneStep { data, launch_time, link_local } => {
+///                 resources.time_critical_socket.send_at(data, link_local, launch_time);
+///             }
+///             PortAction::SendGeneralUnicast { data, address } => {
+///                 resources.general_socket.send_to(data, address);
+///             }
 ///             PortAction::ResetAnnounceTimer { duration } => {
 ///                 resources.announce_timer.expire_in(duration)
 ///             }
@@ -207,6 +259,12 @@ pub(crate) mod state;
 ///             PortAction::ResetFilterUpdateTimer { duration } => {
 ///                 resources.filter_update_timer.expire_in(duration)
 ///             }
+///             PortAction::ResetUnicastTimer { duration } => {
+///                 resources.unicast_timer.expire_in(duration)
+///             }
+///             PortAction::ResetSynchronizationMetadataTimer { duration } => {
+///                 resources.synchronization_metadata_timer.expire_in(duration)
+///             }
 ///             PortAction::ForwardTLV { .. } => {}
 ///         }
 ///     }
@@ -235,6 +293,8 @@ pub(crate) mod state;
 /// #     delay_req_timer: system::Timer,
 /// #     announce_receipt_timer: system::Timer,
 /// #     filter_update_timer: system::Timer,
+/// #     unicast_timer: system::Timer,
+/// #     synchronization_metadata_timer: system::Timer,
 /// #     time_critical_socket: system::UdpSocket,
 /// #     general_socket: system::UdpSocket,
 /// #     send_timestamp: Option<(statime::port::TimestampContext, statime::time::Time)>
@@ -257,6 +317,10 @@ pub(crate) mod state;
 ///         running_port.handle_announce_receipt_timer()
 ///     } else if resources.filter_update_timer.has_expired() {
 ///         running_port.handle_filter_update_timer()
+///     } else if resources.unicast_timer.has_expired() {
+///         running_port.handle_unicast_timer()
+///     } else if resources.synchronization_metadata_timer.has_expired() {
+///         running_port.handle_synchronization_metadata_timer()
 ///     } else if let Some((data, timestamp)) = resources.time_critical_socket.recv() {
 ///         running_port.handle_event_receive(data, timestamp)
 ///     } else if let Some((data, _timestamp)) = resources.general_socket.recv() {
@@ -295,6 +359,15 @@ pub struct Port<'a, L, A, R, C, F: Filter, S = RefCell<PtpInstanceState>> {
     sync_seq_ids: SequenceIdGenerator,
     delay_seq_ids: SequenceIdGenerator,
     pdelay_seq_ids: SequenceIdGenerator,
+    signaling_seq_ids: SequenceIdGenerator,
+    management_seq_ids: SequenceIdGenerator,
+
+    unicast_grants: ArrayVec<UnicastGrant, MAX_UNICAST_GRANTS>,
+    unicast_masters: ArrayVec<UnicastMaster, MAX_UNICAST_MASTERS>,
+    slave_monitor: SlaveMonitor,
+    slave_collector: SlaveCollector,
+    gptp: GptpState,
+    alternate_master: AlternateMasterState,
 
     filter: F,
     /// Mean delay means either `mean_path_delay` when DelayMechanism is E2E,
@@ -347,6 +420,9 @@ impl<'a, A: AcceptableMasterList, C: Clock, F: Filter, R: Rng, S: PtpInstanceSta
             actions::TimestampContextInner::Sync { id } => {
                 self.handle_sync_timestamp(id, timestamp)
             }
+            actions::TimestampContextInner::UnicastSync { id, address } => {
+                self.handle_unicast_sync_timestamp(id, address, timestamp)
+            }
             actions::TimestampContextInner::DelayReq { id } => {
                 self.handle_delay_timestamp(id, timestamp)
             }
@@ -375,12 +451,30 @@ impl<'a, A: AcceptableMasterList, C: Clock, F: Filter, R: Rng, S: PtpInstanceSta
 
     /// Handle the delay request timer going off
     pub fn handle_delay_request_timer(&mut self) -> PortActionIterator<'_> {
+        if matches!(self.port_state, PortState::Disabled) {
+            return actions![];
+        }
         self.send_delay_request()
     }
 
     /// Handle the announce receipt timer going off
     pub fn handle_announce_receipt_timer(&mut self) -> PortActionIterator<'_> {
-        if self
+        if matches!(self.port_state, PortState::Disabled) {
+            // A disabled port never takes part in the protocol
+            actions![]
+        } else if self
+            .instance_state
+            .with_ref(|state| state.default_ds.external_port_configuration)
+        {
+            // The port state is configured externally, but a slave lost its master. It
+            // waits for a new one in the listening state, like in the BMCA.
+            if matches!(self.port_state, PortState::Slave(_)) {
+                self.set_forced_port_state(PortState::Listening);
+            }
+
+            let duration = self.config.announce_duration(&mut self.rng);
+            actions![PortAction::ResetAnnounceReceiptTimer { duration }]
+        } else if self
             .instance_state
             .with_ref(|state| state.default_ds.slave_only)
         {
@@ -401,18 +495,26 @@ impl<'a, A: AcceptableMasterList, C: Clock, F: Filter, R: Rng, S: PtpInstanceSta
                 _ => self.set_forced_port_state(PortState::Master),
             }
 
-            // Immediately start sending syncs and announces
+            // Immediately start sending syncs, announces and synchronization metadata
             actions![
                 PortAction::ResetAnnounceTimer {
                     duration: core::time::Duration::from_secs(0)
                 },
                 PortAction::ResetSyncTimer {
                     duration: core::time::Duration::from_secs(0)
+                },
+                PortAction::ResetSynchronizationMetadataTimer {
+                    duration: core::time::Duration::from_secs(0)
                 }
             ]
         }
     }
 
+    /// Handle the synchronization metadata timer going off
+    pub fn handle_synchronization_metadata_timer(&mut self) -> PortActionIterator<'_> {
+        self.send_synchronization_metadata()
+    }
+
     /// Handle the filter update timer going off
     pub fn handle_filter_update_timer(&mut self) -> PortActionIterator<'_> {
         let update = self.filter.update(&mut self.clock);
@@ -444,6 +546,14 @@ impl<'a, A: AcceptableMasterList, C: Clock, F: Filter, R: Rng, S: PtpInstanceSta
             sync_seq_ids: self.sync_seq_ids,
             delay_seq_ids: self.delay_seq_ids,
             pdelay_seq_ids: self.pdelay_seq_ids,
+            signaling_seq_ids: self.signaling_seq_ids,
+            management_seq_ids: self.management_seq_ids,
+            unicast_grants: self.unicast_grants,
+            unicast_masters: self.unicast_masters,
+            slave_monitor: self.slave_monitor,
+            slave_collector: self.slave_collector,
+            gptp: self.gptp,
+            alternate_master: self.alternate_master,
 
             filter: self.filter,
             mean_delay: self.mean_delay,
@@ -451,6 +561,18 @@ impl<'a, A: AcceptableMasterList, C: Clock, F: Filter, R: Rng, S: PtpInstanceSta
         }
     }
 
+    /// Whether the message in `data` belongs to the domain of this port
+    ///
+    /// This allows ports of instances in different domains to share a socket,
+    /// by passing each received message to the port of its domain.
+    pub fn is_in_domain(&self, data: &[u8]) -> bool {
+        message_domain(data).map_or(false, |(sdo_id, domain_number)| {
+            self.instance_state.with_ref(|state| {
+                sdo_id == state.default_ds.sdo_id && domain_number == state.default_ds.domain_number
+            })
+        })
+    }
+
     // parse and do basic domain filtering on message
     fn parse_and_filter<'b>(
         &mut self,
@@ -467,6 +589,12 @@ impl<'a, A: AcceptableMasterList, C: Clock, F: Filter, R: Rng, S: PtpInstanceSta
                 return ControlFlow::Break(actions![]);
             }
         };
+        if let Some(security_association) = &self.config.authentication {
+            if let Err(error) = verify_authentication(data, &message, security_association) {
+                log::debug!("Dropping unauthenticated message: {:?}", error);
+                return ControlFlow::Break(actions![]);
+            }
+        }
         let domain_matches = self.instance_state.with_ref(|state| {
             message.header().sdo_id == state.default_ds.sdo_id
                 && message.header().domain_number == state.default_ds.domain_number
@@ -474,6 +602,12 @@ impl<'a, A: AcceptableMasterList, C: Clock, F: Filter, R: Rng, S: PtpInstanceSta
         if !domain_matches {
             return ControlFlow::Break(actions![]);
         }
+        // A disabled port only keeps answering management messages
+        if matches!(self.port_state, PortState::Disabled)
+            && !matches!(message.body, MessageBody::Management(_))
+        {
+            return ControlFlow::Break(actions![]);
+        }
         ControlFlow::Continue(message)
     }
 
@@ -482,6 +616,19 @@ impl<'a, A: AcceptableMasterList, C: Clock, F: Filter, R: Rng, S: PtpInstanceSta
         &'b mut self,
         data: &'b [u8],
         timestamp: Time,
+    ) -> PortActionIterator<'b> {
+        self.handle_event_receive_from(data, timestamp, None)
+    }
+
+    /// Handle a message over the event channel, sent from `source`
+    ///
+    /// Knowing the address of the sender allows the port to answer unicast
+    /// negotiation requests.
+    pub fn handle_event_receive_from<'b>(
+        &'b mut self,
+        data: &'b [u8],
+        timestamp: Time,
+        source: Option<PortAddress>,
     ) -> PortActionIterator<'b> {
         let message = match self.parse_and_filter(data) {
             ControlFlow::Continue(value) => value,
@@ -489,32 +636,69 @@ impl<'a, A: AcceptableMasterList, C: Clock, F: Filter, R: Rng, S: PtpInstanceSta
         };
 
         match message.body {
-            MessageBody::Sync(sync) => self.handle_sync(message.header, sync, timestamp),
+            // Without a capable neighbor, gPTP ports do not synchronize over the link
+            MessageBody::Sync(_) if !self.is_as_capable() => actions![],
+            MessageBody::Sync(sync) => {
+                if let Some(source) = source {
+                    self.learn_master_address(message.header.source_port_identity, source);
+                }
+                self.handle_sync(message.header, sync, timestamp)
+            }
             MessageBody::DelayReq(delay_request) => {
-                self.handle_delay_req(message.header, delay_request, timestamp)
+                self.handle_delay_req(message.header, delay_request, timestamp, source)
             }
             MessageBody::PDelayReq(_) => self.handle_pdelay_req(message.header, timestamp),
             MessageBody::PDelayResp(peer_delay_response) => {
                 self.handle_peer_delay_response(message.header, peer_delay_response, timestamp)
             }
-            _ => self.handle_general_internal(message),
+            _ => self.handle_general_internal(message, source),
         }
     }
 
     /// Handle a general ptp message
     pub fn handle_general_receive<'b>(&'b mut self, data: &'b [u8]) -> PortActionIterator<'b> {
+        self.handle_general_receive_from(data, None)
+    }
+
+    /// Handle a general ptp message, sent from `source`
+    ///
+    /// Knowing the address of the sender allows the port to answer unicast
+    /// negotiation requests.
+    pub fn handle_general_receive_from<'b>(
+        &'b mut self,
+        data: &'b [u8],
+        source: Option<PortAddress>,
+    ) -> PortActionIterator<'b> {
         let message = match self.parse_and_filter(data) {
             ControlFlow::Continue(value) => value,
             ControlFlow::Break(value) => return value,
         };
 
-        self.handle_general_internal(message)
+        self.handle_general_internal(message, source)
     }
 
-    fn handle_general_internal<'b>(&'b mut self, message: Message<'b>) -> PortActionIterator<'b> {
+    fn handle_general_internal<'b>(
+        &'b mut self,
+        message: Message<'b>,
+        source: Option<PortAddress>,
+    ) -> PortActionIterator<'b> {
         match message.body {
-            MessageBody::Announce(announce) => self.handle_announce(&message, announce),
-            MessageBody::FollowUp(follow_up) => self.handle_follow_up(message.header, follow_up),
+            MessageBody::Announce(_) | MessageBody::FollowUp(_) if !self.is_as_capable() => {
+                actions![]
+            }
+            MessageBody::Announce(announce) => {
+                if let Some(source) = source {
+                    self.learn_unicast_master(message.header.source_port_identity, source);
+                }
+                self.handle_announce(&message, announce)
+            }
+            MessageBody::FollowUp(follow_up) => {
+                let information = message
+                    .suffix
+                    .tlv()
+                    .find_map(|tlv| FollowUpInformation::from_tlv(&tlv).ok());
+                self.handle_follow_up(message.header, follow_up, information)
+            }
             MessageBody::DelayResp(delay_response) => {
                 self.handle_delay_resp(message.header, delay_response)
             }
@@ -528,7 +712,11 @@ impl<'a, A: AcceptableMasterList, C: Clock, F: Filter, R: Rng, S: PtpInstanceSta
                 log::warn!("Received event message over general interface");
                 actions![]
             }
-            MessageBody::Management(_) | MessageBody::Signaling(_) => actions![],
+            MessageBody::Management(ref management) => self.handle_management(&message, management),
+            MessageBody::Signaling(ref signaling) => {
+                self.handle_slave_reports(&message, signaling, source);
+                self.handle_signaling(&message, signaling, source)
+            }
         }
     }
 }
@@ -559,6 +747,14 @@ impl<'a, A, C, F: Filter, R, S> Port<'a, InBmca, A, R, C, F, S> {
                 sync_seq_ids: self.sync_seq_ids,
                 delay_seq_ids: self.delay_seq_ids,
                 pdelay_seq_ids: self.pdelay_seq_ids,
+                signaling_seq_ids: self.signaling_seq_ids,
+                management_seq_ids: self.management_seq_ids,
+                unicast_grants: self.unicast_grants,
+                unicast_masters: self.unicast_masters,
+                slave_monitor: self.slave_monitor,
+                slave_collector: self.slave_collector,
+                gptp: self.gptp,
+                alternate_master: self.alternate_master,
                 filter: self.filter,
                 mean_delay: self.mean_delay,
                 peer_delay_state: self.peer_delay_state,
@@ -568,6 +764,53 @@ impl<'a, A, C, F: Filter, R, S> Port<'a, InBmca, A, R, C, F, S> {
     }
 }
 
+impl<A, C: Clock, F: Filter, R: Rng, S> Port<'_, InBmca, A, R, C, F, S> {
+    /// Enable or disable this [`Port`]
+    ///
+    /// A disabled port does not take part in the BMCA, sends no messages and
+    /// ignores everything it receives apart from management messages. Enabling
+    /// the port again moves it to the listening state, after which the BMCA
+    /// decides on its state as usual.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled == self.is_enabled() {
+            return;
+        }
+
+        if enabled {
+            self.set_forced_port_state(PortState::Listening);
+
+            // consistent with Port<InBmca>::new()
+            let duration = self.config.announce_duration(&mut self.rng);
+            let reset_announce = PortAction::ResetAnnounceReceiptTimer { duration };
+            self.lifecycle.pending_action = if self.unicast_masters.is_empty() {
+                actions![reset_announce]
+            } else {
+                let reset_unicast = PortAction::ResetUnicastTimer {
+                    duration: core::time::Duration::ZERO,
+                };
+                actions![reset_announce, reset_unicast]
+            };
+        } else {
+            self.set_forced_port_state(PortState::Disabled);
+            self.multiport_disable = None;
+            self.lifecycle.pending_action = actions![];
+        }
+    }
+}
+
+impl<A, C, F: Filter, R, S> Port<'_, InBmca, A, R, C, F, S> {
+    /// Set the state of this [`Port`] for when the instance uses external
+    /// port configuration, see
+    /// [`PtpInstance::set_external_port_configuration`]
+    ///
+    /// The port moves to this state during the next BMCA run. A port without
+    /// a desired state is passive, as are the ports desired as slave after the
+    /// first one, so only one port steers the clock.
+    pub fn set_desired_state(&mut self, desired_state: Option<DesiredState>) {
+        self.config.desired_state = desired_state;
+    }
+}
+
 impl<L, A, R, C: Clock, F: Filter, S> Port<'_, L, A, R, C, F, S> {
     fn set_forced_port_state(&mut self, mut state: PortState) {
         log::info!(
@@ -598,6 +841,47 @@ impl<L, A, R, C, F: Filter, S> Port<'_, L, A, R, C, F, S> {
         matches!(self.port_state, PortState::Master)
     }
 
+    /// Indicate whether this [`Port`] is enabled, see [`Port::set_enabled`].
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self.port_state, PortState::Disabled)
+    }
+
+    /// The state of this [`Port`] for when the instance uses external port
+    /// configuration, see [`Port::set_desired_state`].
+    pub fn desired_state(&self) -> Option<DesiredState> {
+        self.config.desired_state
+    }
+
+    /// Get the foreign masters from which this [`Port`] has recently received
+    /// announce messages
+    pub fn foreign_masters(&self) -> impl Iterator<Item = ForeignMaster> + '_ {
+        self.bmca.foreign_masters()
+    }
+
+    /// Get the time error of the slaves that sent slave event monitoring
+    /// reports to this [`Port`] while it was master
+    pub fn slave_reports(&self) -> impl Iterator<Item = SlaveReport> + '_ {
+        self.slave_collector.reports()
+    }
+
+    /// Get the offset to the alternate masters this [`Port`] measures while it
+    /// is slave, see [`PortConfig::number_alternate_masters`]
+    pub fn alternate_masters(&self) -> impl Iterator<Item = AlternateMaster> + '_ {
+        self.alternate_master.alternate_masters()
+    }
+
+    /// Whether a message with the given target port identity is meant for
+    /// this port, taking the all ones wildcards into account.
+    fn is_target_port(&self, target: PortIdentity) -> bool {
+        const ALL_CLOCKS: ClockIdentity = ClockIdentity([0xff; 8]);
+        const ALL_PORTS: u16 = 0xffff;
+
+        (target.clock_identity == ALL_CLOCKS
+            || target.clock_identity == self.port_identity.clock_identity)
+            && (target.port_number == ALL_PORTS
+                || target.port_number == self.port_identity.port_number)
+    }
+
     pub(crate) fn state(&self) -> &PortState {
         &self.port_state
     }
@@ -612,6 +896,7 @@ impl<L, A, R, C, F: Filter, S> Port<'_, L, A, R, C, F, S> {
             port_identity: self.port_identity,
             port_state: match self.port_state {
                 PortState::Faulty => observability::port::PortState::Faulty,
+                PortState::Disabled => observability::port::PortState::Disabled,
                 PortState::Listening => observability::port::PortState::Listening,
                 PortState::Master => observability::port::PortState::Master,
                 PortState::Passive => observability::port::PortState::Passive,
@@ -649,6 +934,16 @@ impl<L, A, R, C, F: Filter, S> Port<'_, L, A, R, C, F, S> {
             None
         }
     }
+
+    /// If this port is in the slave state, the uncertainty of the frequency
+    /// of its clock relative to the master
+    pub(crate) fn slave_frequency_uncertainty(&self) -> Option<f64> {
+        if matches!(self.port_state, PortState::Slave(_)) {
+            self.filter.frequency_uncertainty()
+        } else {
+            None
+        }
+    }
 }
 
 impl<'a, A, C, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'a, InBmca, A, R, C, F, S> {
@@ -662,14 +957,40 @@ impl<'a, A, C, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'a, InBmca, A,
         mut rng: R,
     ) -> Self {
         let duration = config.announce_duration(&mut rng);
+        // The alternate BMCA of the telecom profiles compares the localPriority
+        // of the port that received the announce messages
+        let local_priority =
+            instance_state.with_ref(|state| match state.default_ds.dataset_comparison {
+                DatasetComparison::Ieee1588 => None,
+                DatasetComparison::G8275 { .. } => Some(config.local_priority),
+            });
         let bmca = Bmca::new(
             config.acceptable_master_list,
             config.announce_interval.as_duration().into(),
             port_identity,
-        );
+        )
+        .with_local_priority(local_priority);
 
         let filter = F::new(filter_config.clone());
 
+        let unicast_masters: ArrayVec<_, MAX_UNICAST_MASTERS> = config
+            .unicast_master_table
+            .masters
+            .iter()
+            .map(|&address| UnicastMaster::new(address))
+            .collect();
+
+        // Start requesting unicast transmission right away
+        let reset_announce = PortAction::ResetAnnounceReceiptTimer { duration };
+        let pending_action = if unicast_masters.is_empty() {
+            actions![reset_announce]
+        } else {
+            let reset_unicast = PortAction::ResetUnicastTimer {
+                duration: core::time::Duration::ZERO,
+            };
+            actions![reset_announce, reset_unicast]
+        };
+
         Port {
             config: PortConfig {
                 acceptable_master_list: (),
@@ -680,6 +1001,18 @@ impl<'a, A, C, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'a, InBmca, A,
                 master_only: config.master_only,
                 delay_asymmetry: config.delay_asymmetry,
                 minor_ptp_version: config.minor_ptp_version,
+                unicast_grantor: config.unicast_grantor,
+                unicast_master_table: config.unicast_master_table,
+                one_step: config.one_step,
+                authentication: config.authentication,
+                slave_monitoring: config.slave_monitoring,
+                gptp: config.gptp,
+                desired_state: config.desired_state,
+                number_alternate_masters: config.number_alternate_masters,
+                local_priority: config.local_priority,
+                link_local: config.link_local,
+                hybrid_e2e: config.hybrid_e2e,
+                unicast_only: config.unicast_only,
             },
             filter_config,
             clock,
@@ -691,13 +1024,21 @@ impl<'a, A, C, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'a, InBmca, A,
             multiport_disable: None,
             packet_buffer: [0; MAX_DATA_LEN],
             lifecycle: InBmca {
-                pending_action: actions![PortAction::ResetAnnounceReceiptTimer { duration }],
+                pending_action,
                 local_best: None,
             },
             announce_seq_ids: SequenceIdGenerator::new(),
             sync_seq_ids: SequenceIdGenerator::new(),
             delay_seq_ids: SequenceIdGenerator::new(),
             pdelay_seq_ids: SequenceIdGenerator::new(),
+            signaling_seq_ids: SequenceIdGenerator::new(),
+            management_seq_ids: SequenceIdGenerator::new(),
+            unicast_grants: ArrayVec::new(),
+            unicast_masters,
+            slave_monitor: SlaveMonitor::new(),
+            slave_collector: SlaveCollector::new(),
+            gptp: GptpState::new(),
+            alternate_master: AlternateMasterState::new(),
             filter,
             mean_delay: None,
             peer_delay_state: PeerDelayState::Empty,
@@ -717,6 +1058,7 @@ mod tests {
         },
         datastructures::datasets::{InternalDefaultDS, InternalParentDS, PathTraceDS},
         filters::BasicFilter,
+        holdover::Holdover,
         time::{Duration, Interval, Time},
         Clock,
     };
@@ -747,6 +1089,32 @@ mod tests {
         }
     }
 
+    /// Clock that is only changed by the test itself
+    pub(super) struct SettableClock(pub(super) Time);
+
+    impl Clock for SettableClock {
+        type Error = ();
+
+        fn now(&self) -> Time {
+            self.0
+        }
+
+        fn set_frequency(&mut self, _freq: f64) -> Result<Time, Self::Error> {
+            Ok(self.0)
+        }
+
+        fn step_clock(&mut self, _offset: Duration) -> Result<Time, Self::Error> {
+            Ok(self.0)
+        }
+
+        fn set_properties(
+            &mut self,
+            _time_properties_ds: &TimePropertiesDS,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
     pub(super) fn setup_test_port(
         state: &RefCell<PtpInstanceState>,
     ) -> Port<'_, Running, AcceptAnyMaster, rand::rngs::mock::StepRng, TestClock, BasicFilter> {
@@ -763,6 +1131,18 @@ mod tests {
                 master_only: false,
                 delay_asymmetry: Duration::ZERO,
                 minor_ptp_version: PtpMinorVersion::One,
+                unicast_grantor: None,
+                unicast_master_table: Default::default(),
+                one_step: false,
+                authentication: None,
+                slave_monitoring: None,
+                gptp: None,
+                desired_state: None,
+                number_alternate_masters: 0,
+                local_priority: 128,
+                link_local: false,
+                hybrid_e2e: false,
+                unicast_only: false,
             },
             0.25,
             TestClock,
@@ -791,6 +1171,18 @@ mod tests {
                 master_only: false,
                 delay_asymmetry: Duration::ZERO,
                 minor_ptp_version: PtpMinorVersion::One,
+                unicast_grantor: None,
+                unicast_master_table: Default::default(),
+                one_step: false,
+                authentication: None,
+                slave_monitoring: None,
+                gptp: None,
+                desired_state: None,
+                number_alternate_masters: 0,
+                local_priority: 128,
+                link_local: false,
+                hybrid_e2e: false,
+                unicast_only: false,
             },
             0.25,
             TestClock,
@@ -819,6 +1211,18 @@ mod tests {
                 master_only: false,
                 delay_asymmetry: Duration::ZERO,
                 minor_ptp_version: PtpMinorVersion::One,
+                unicast_grantor: None,
+                unicast_master_table: Default::default(),
+                one_step: false,
+                authentication: None,
+                slave_monitoring: None,
+                gptp: None,
+                desired_state: None,
+                number_alternate_masters: 0,
+                local_priority: 128,
+                link_local: false,
+                hybrid_e2e: false,
+                unicast_only: false,
             },
             filter_config,
             TestClock,
@@ -830,6 +1234,46 @@ mod tests {
         port
     }
 
+    pub(super) fn setup_test_port_custom_clock<C: Clock>(
+        state: &RefCell<PtpInstanceState>,
+        clock: C,
+    ) -> Port<'_, Running, AcceptAnyMaster, rand::rngs::mock::StepRng, C, BasicFilter> {
+        let port = Port::<_, _, _, _, BasicFilter>::new(
+            state,
+            PortConfig {
+                acceptable_master_list: AcceptAnyMaster,
+                delay_mechanism: DelayMechanism::E2E {
+                    interval: Interval::from_log_2(1),
+                },
+                announce_interval: Interval::from_log_2(1),
+                announce_receipt_timeout: 3,
+                sync_interval: Interval::from_log_2(0),
+                master_only: false,
+                delay_asymmetry: Duration::ZERO,
+                minor_ptp_version: PtpMinorVersion::One,
+                unicast_grantor: None,
+                unicast_master_table: Default::default(),
+                one_step: false,
+                authentication: None,
+                slave_monitoring: None,
+                gptp: None,
+                desired_state: None,
+                number_alternate_masters: 0,
+                local_priority: 128,
+                link_local: false,
+                hybrid_e2e: false,
+                unicast_only: false,
+            },
+            0.25,
+            clock,
+            Default::default(),
+            rand::rngs::mock::StepRng::new(2, 1),
+        );
+
+        let (port, _) = port.end_bmca();
+        port
+    }
+
     pub(super) fn setup_test_state() -> RefCell<PtpInstanceState> {
         let default_ds = InternalDefaultDS::new(InstanceConfig {
             clock_identity: Default::default(),
@@ -839,7 +1283,10 @@ mod tests {
             slave_only: false,
             sdo_id: Default::default(),
             path_trace: false,
+            external_port_configuration: false,
             clock_quality: ClockQuality::default(),
+            management_set_policy: Default::default(),
+            dataset_comparison: Default::default(),
         });
 
         let parent_ds = InternalParentDS::new(default_ds);
@@ -849,7 +1296,37 @@ mod tests {
             current_ds: Default::default(),
             parent_ds,
             time_properties_ds: Default::default(),
+            local_time_properties_ds: Default::default(),
             path_trace_ds: PathTraceDS::new(false),
+            management_set_policy: Default::default(),
+            alternate_timescales: Default::default(),
+            alternate_timescale_offsets_ds: Default::default(),
+            holdover: Holdover::new(),
+            power_profile: None,
+            received_power_profile: None,
+            synchronization_metadata: None,
+            current_synchronization_metadata: None,
+            received_follow_up_information: None,
         })
     }
+
+    #[test]
+    fn test_is_in_domain() {
+        let state = setup_test_state();
+        state.borrow_mut().default_ds.domain_number = 1;
+        let port = setup_test_port(&state);
+
+        let mut message = [0; 34];
+        message[1] = 0x12;
+        assert!(!port.is_in_domain(&message));
+
+        message[4] = 1;
+        assert!(port.is_in_domain(&message));
+
+        // The major part of the sdo id is in the first octet
+        message[0] = 0x10;
+        assert!(!port.is_in_domain(&message));
+
+        assert!(!port.is_in_domain(&[]));
+    }
 }
@@ -0,0 +1,359 @@
+//! Slave event monitoring, see *IEEE1588-2019 section 16.11*
+
+use arrayvec::ArrayVec;
+
+use super::{state::PortState, Port, PortAction, Running};
+use crate::{
+    config::{DelayMechanism, SlaveMonitoringConfig, MAX_MONITORING_RECORDS},
+    datastructures::{
+        common::{ClockIdentity, PortIdentity, Tlv, TlvSetBuilder, TlvType},
+        messages::{
+            Message, MessageType, SlaveRxSyncComputedDataRecord, SlaveRxSyncComputedDataTlv,
+            SlaveRxSyncTimingDataRecord, SlaveRxSyncTimingDataTlv, SlaveTxEventTimestampsRecord,
+            SlaveTxEventTimestampsTlv,
+        },
+        WireFormatError,
+    },
+    filters::Filter,
+    ptp_instance::PtpInstanceStateMutex,
+};
+
+/// Size of the largest TLV value, the SLAVE_RX_SYNC_TIMING_DATA with the
+/// maximum number of records
+const MAX_MONITORING_TLV_SIZE: usize =
+    10 + MAX_MONITORING_RECORDS * SlaveRxSyncTimingDataRecord::WIRE_SIZE;
+
+/// Size of all three TLVs with the maximum number of records, including the
+/// TLV headers
+const MAX_MONITORING_TLVS_SIZE: usize = 3 * 4
+    + MAX_MONITORING_TLV_SIZE
+    + 12
+    + MAX_MONITORING_RECORDS * SlaveRxSyncComputedDataRecord::WIRE_SIZE
+    + 12
+    + MAX_MONITORING_RECORDS * SlaveTxEventTimestampsRecord::WIRE_SIZE;
+
+/// Records collected by a slave port that are yet to be sent to the collector
+#[derive(Debug)]
+pub(super) struct SlaveMonitor {
+    /// The master the records were received from
+    source: PortIdentity,
+    sync_timing: ArrayVec<SlaveRxSyncTimingDataRecord, MAX_MONITORING_RECORDS>,
+    sync_computed: ArrayVec<SlaveRxSyncComputedDataRecord, MAX_MONITORING_RECORDS>,
+    tx_event_timestamps: ArrayVec<SlaveTxEventTimestampsRecord, MAX_MONITORING_RECORDS>,
+}
+
+impl SlaveMonitor {
+    pub(super) fn new() -> Self {
+        Self {
+            source: Default::default(),
+            sync_timing: ArrayVec::new(),
+            sync_computed: ArrayVec::new(),
+            tx_event_timestamps: ArrayVec::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.sync_timing.clear();
+        self.sync_computed.clear();
+        self.tx_event_timestamps.clear();
+    }
+
+    /// Records of different masters can't be sent in the same message, so drop
+    /// the records of the previous master
+    fn set_source(&mut self, source: PortIdentity) {
+        if self.source != source {
+            self.clear();
+            self.source = source;
+        }
+    }
+
+    /// Record the data of a sync message received from `source`
+    ///
+    /// The computed data is only known once the mean path delay is known.
+    pub(super) fn record_sync(
+        &mut self,
+        config: Option<&SlaveMonitoringConfig>,
+        source: PortIdentity,
+        timing: SlaveRxSyncTimingDataRecord,
+        computed: Option<SlaveRxSyncComputedDataRecord>,
+    ) {
+        let Some(config) = config else {
+            return;
+        };
+
+        self.set_source(source);
+
+        if config.rx_sync_timing_data {
+            // Cannot overflow, the records are sent once the buffer is full
+            let _ = self.sync_timing.try_push(timing);
+        }
+
+        if let (true, Some(computed)) = (config.rx_sync_computed_data, computed) {
+            let _ = self.sync_computed.try_push(computed);
+        }
+    }
+
+    /// Record the transmit timestamp of a (peer) delay request sent while
+    /// synchronizing to `source`
+    pub(super) fn record_tx_event(
+        &mut self,
+        config: Option<&SlaveMonitoringConfig>,
+        source: PortIdentity,
+        record: SlaveTxEventTimestampsRecord,
+    ) {
+        let Some(config) = config else {
+            return;
+        };
+
+        self.set_source(source);
+
+        if config.tx_event_timestamps {
+            let _ = self.tx_event_timestamps.try_push(record);
+        }
+    }
+
+    fn is_full(&self, config: &SlaveMonitoringConfig) -> bool {
+        let records_per_message = config.records_per_message.clamp(1, MAX_MONITORING_RECORDS);
+
+        self.sync_timing.len() >= records_per_message
+            || self.sync_computed.len() >= records_per_message
+            || self.tx_event_timestamps.len() >= records_per_message
+    }
+}
+
+impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F, S> {
+    /// Send the collected monitoring records to the collector once enough have
+    /// been collected
+    pub(super) fn send_slave_monitoring(&mut self) -> Option<PortAction<'_>> {
+        let config = self.config.slave_monitoring?;
+        if !self.slave_monitor.is_full(&config) {
+            return None;
+        }
+
+        let result = self.serialize_slave_monitoring();
+
+        // Whether sent or not, start collecting the next batch of records
+        self.slave_monitor.clear();
+
+        match result {
+            Ok(length) => Some(PortAction::SendGeneralUnicast {
+                data: &self.packet_buffer[..length],
+                address: config.collector,
+            }),
+            Err(error) => {
+                log::error!(
+                    "Statime bug: Could not serialize slave monitoring message: {:?}",
+                    error
+                );
+                None
+            }
+        }
+    }
+
+    fn serialize_slave_monitoring(&mut self) -> Result<usize, WireFormatError> {
+        let source = self.slave_monitor.source;
+        let mut tlv_buffer = [0; MAX_MONITORING_TLVS_SIZE];
+        let mut tlv_builder = TlvSetBuilder::new(&mut tlv_buffer);
+        let mut value_buffer = [0; MAX_MONITORING_TLV_SIZE];
+
+        if !self.slave_monitor.sync_timing.is_empty() {
+            let length = SlaveRxSyncTimingDataTlv {
+                sync_source_port_identity: source,
+                records: &self.slave_monitor.sync_timing,
+            }
+            .serialize(&mut value_buffer)?;
+            tlv_builder.add(Tlv {
+                tlv_type: TlvType::SlaveRxSyncTimingData,
+                value: value_buffer[..length].into(),
+            })?;
+        }
+
+        if !self.slave_monitor.sync_computed.is_empty() {
+            let length = SlaveRxSyncComputedDataTlv {
+                source_port_identity: source,
+                scaled_neighbor_rate_ratio_valid: false,
+                mean_path_delay_valid: true,
+                offset_from_master_valid: true,
+                records: &self.slave_monitor.sync_computed,
+            }
+            .serialize(&mut value_buffer)?;
+            tlv_builder.add(Tlv {
+                tlv_type: TlvType::SlaveRxSyncComputedData,
+                value: value_buffer[..length].into(),
+            })?;
+        }
+
+        if !self.slave_monitor.tx_event_timestamps.is_empty() {
+            let event_message_type = match self.config.delay_mechanism {
+                DelayMechanism::E2E { .. } => MessageType::DelayReq,
+                DelayMechanism::P2P { .. } => MessageType::PDelayReq,
+            };
+            let length = SlaveTxEventTimestampsTlv {
+                source_port_identity: self.port_identity,
+                event_message_type,
+                records: &self.slave_monitor.tx_event_timestamps,
+            }
+            .serialize(&mut value_buffer)?;
+            tlv_builder.add(Tlv {
+                tlv_type: TlvType::SlaveTxEventTimestamps,
+                value: value_buffer[..length].into(),
+            })?;
+        }
+
+        let message = self.instance_state.with_ref(|state| {
+            Message::signaling(
+                &state.default_ds,
+                self.port_identity,
+                PortIdentity {
+                    clock_identity: ClockIdentity([0xff; 8]),
+                    port_number: 0xffff,
+                },
+                self.signaling_seq_ids.generate(),
+                self.config.minor_ptp_version.into(),
+                tlv_builder.build(),
+            )
+        });
+
+        message
+            .serialize_authenticated(&mut self.packet_buffer, self.config.authentication.as_ref())
+    }
+
+    /// Record the transmit timestamp of a peer delay request, which is only
+    /// monitored while the port is slave
+    pub(super) fn record_pdelay_monitoring(&mut self, record: SlaveTxEventTimestampsRecord) {
+        if let PortState::Slave(ref state) = self.port_state {
+            self.slave_monitor.record_tx_event(
+                self.config.slave_monitoring.as_ref(),
+                state.remote_master(),
+                record,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::PortAddress,
+        datastructures::{
+            common::TimeInterval,
+            messages::{Header, MessageBody, SyncMessage},
+        },
+        port::{
+            state::SlaveState,
+            tests::{setup_test_port, setup_test_state},
+        },
+        time::{Duration, Time},
+    };
+
+    const COLLECTOR: PortAddress = PortAddress::Ipv4([192, 0, 2, 10]);
+
+    #[test]
+    fn test_slave_monitoring_sync() {
+        let state = setup_test_state();
+        let mut port = setup_test_port(&state);
+        port.config.slave_monitoring = Some(SlaveMonitoringConfig {
+            collector: COLLECTOR,
+            rx_sync_timing_data: true,
+            rx_sync_computed_data: true,
+            tx_event_timestamps: true,
+            records_per_message: 2,
+        });
+        port.mean_delay = Some(Duration::from_micros(100));
+        port.set_forced_port_state(PortState::Slave(SlaveState::new(Default::default())));
+
+        let send_sync = |port: &mut Port<'_, Running, _, _, _, _, _>, sequence_id| {
+            port.handle_sync(
+                Header {
+                    sequence_id,
+                    two_step_flag: false,
+                    correction_field: TimeInterval(1000.into()),
+                    ..Header::new(1)
+                },
+                SyncMessage {
+                    origin_timestamp: Time::from_micros(0).into(),
+                },
+                Time::from_micros(50),
+            )
+            .filter_map(|action| match action {
+                PortAction::SendGeneralUnicast { data, address } => Some((data.to_vec(), address)),
+                _ => None,
+            })
+            .next()
+        };
+
+        assert!(send_sync(&mut port, 1).is_none());
+        let (data, address) = send_sync(&mut port, 2).unwrap();
+        assert_eq!(address, COLLECTOR);
+
+        let message = Message::deserialize(&data).unwrap();
+        let MessageBody::Signaling(signaling) = message.body else {
+            panic!("Expected signaling message");
+        };
+        assert_eq!(signaling.target_port_identity.port_number, 0xffff);
+
+        let mut tlvs = message.suffix.tlv();
+
+        let timing = tlvs.next().unwrap();
+        assert_eq!(timing.tlv_type, TlvType::SlaveRxSyncTimingData);
+        assert_eq!(timing.value.len(), 10 + 2 * 34);
+        // Sequence ids and total correction of both records
+        assert_eq!(timing.value[10..12], [0, 1]);
+        assert_eq!(timing.value[22..30], [0, 0, 0, 0, 0x03, 0xe8, 0, 0]);
+        assert_eq!(timing.value[44..46], [0, 2]);
+
+        let computed = tlvs.next().unwrap();
+        assert_eq!(computed.tlv_type, TlvType::SlaveRxSyncComputedData);
+        assert_eq!(computed.value.len(), 12 + 2 * 22);
+        assert_eq!(computed.value[10], 0x06);
+        // Mean path delay of 100us
+        assert_eq!(computed.value[22..30], [0, 0, 0, 0x01, 0x86, 0xa0, 0, 0]);
+
+        assert!(tlvs.next().is_none());
+
+        // Records are only sent once
+        assert!(send_sync(&mut port, 3).is_none());
+    }
+
+    #[test]
+    fn test_slave_monitoring_disabled_records() {
+        let state = setup_test_state();
+        let mut port = setup_test_port(&state);
+        port.config.slave_monitoring = Some(SlaveMonitoringConfig {
+            collector: COLLECTOR,
+            rx_sync_timing_data: false,
+            rx_sync_computed_data: true,
+            tx_event_timestamps: false,
+            records_per_message: 1,
+        });
+        port.set_forced_port_state(PortState::Slave(SlaveState::new(Default::default())));
+
+        // Without a mean delay, there is no computed data to send
+        let mut actions = port.handle_sync(
+            Header {
+                two_step_flag: false,
+                ..Header::new(1)
+            },
+            SyncMessage {
+                origin_timestamp: Time::from_micros(0).into(),
+            },
+            Time::from_micros(50),
+        );
+        assert!(!actions.any(|action| matches!(action, PortAction::SendGeneralUnicast { .. })));
+        drop(actions);
+        assert!(port.slave_monitor.sync_timing.is_empty());
+        assert!(port.slave_monitor.sync_computed.is_empty());
+
+        // Disabled records are not collected
+        port.slave_monitor.record_tx_event(
+            port.config.slave_monitoring.as_ref(),
+            Default::default(),
+            SlaveTxEventTimestampsRecord {
+                sequence_id: 1,
+                event_egress_timestamp: Default::default(),
+            },
+        );
+        assert!(port.slave_monitor.tx_event_timestamps.is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use arrayvec::ArrayVec;
 use rand::Rng;
 
 use super::{
@@ -5,10 +6,14 @@ use super::{
     Measurement, PeerDelayState, Port, PortActionIterator, Running,
 };
 use crate::{
-    config::DelayMechanism,
-    datastructures::messages::{
-        DelayRespMessage, FollowUpMessage, Header, Message, PDelayRespFollowUpMessage,
-        PDelayRespMessage, SyncMessage,
+    config::{DelayMechanism, PortAddress},
+    datastructures::{
+        common::{FollowUpInformation, PortIdentity},
+        messages::{
+            DelayRespMessage, FollowUpMessage, Header, Message, PDelayRespFollowUpMessage,
+            PDelayRespMessage, SlaveRxSyncComputedDataRecord, SlaveRxSyncTimingDataRecord,
+            SlaveTxEventTimestampsRecord, SyncMessage,
+        },
     },
     filters::Filter,
     port::{actions::TimestampContextInner, state::SyncState, PortAction, TimestampContext},
@@ -17,8 +22,10 @@ use crate::{
     Clock,
 };
 
-impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
-    pub(super) fn handle_time_measurement<'b>(&mut self) -> PortActionIterator<'b> {
+impl<A, C: Clock, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F, S> {
+    pub(super) fn handle_time_measurement(&mut self) -> PortActionIterator<'_> {
+        let mut actions = ArrayVec::new();
+
         if let Some(measurement) = self.extract_measurement() {
             // If the received message allowed the (slave) state to calculate its offset
             // from the master, update the local clock
@@ -26,10 +33,16 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
             if let Some(mean_delay) = filter_updates.mean_delay {
                 self.mean_delay = Some(mean_delay);
             }
-            PortActionIterator::from_filter(filter_updates)
-        } else {
-            actions![]
+            if let Some(duration) = filter_updates.next_update {
+                actions.push(PortAction::ResetFilterUpdateTimer { duration });
+            }
+        }
+
+        if let Some(action) = self.send_slave_monitoring() {
+            actions.push(action);
         }
+
+        PortActionIterator::from(actions)
     }
 
     pub(super) fn handle_delay_timestamp(
@@ -53,6 +66,14 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
                     ..
                 } if id == timestamp_id => {
                     *send_time = Some(timestamp);
+                    self.slave_monitor.record_tx_event(
+                        self.config.slave_monitoring.as_ref(),
+                        state.remote_master,
+                        SlaveTxEventTimestampsRecord {
+                            sequence_id: id,
+                            event_egress_timestamp: timestamp.into(),
+                        },
+                    );
                     self.handle_time_measurement()
                 }
                 _ => {
@@ -84,6 +105,10 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
                 ..
             } if id == timestamp_id => {
                 *request_send_time = Some(timestamp);
+                self.record_pdelay_monitoring(SlaveTxEventTimestampsRecord {
+                    sequence_id: id,
+                    event_egress_timestamp: timestamp.into(),
+                });
                 self.handle_time_measurement()
             }
             _ => {
@@ -103,6 +128,9 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
             PortState::Slave(ref mut state) => {
                 log::debug!("Received sync {:?}", header.sequence_id);
                 if state.remote_master != header.source_port_identity {
+                    if header.alternate_master_flag {
+                        self.handle_alternate_sync(header, message, recv_time);
+                    }
                     return actions![];
                 }
 
@@ -123,10 +151,12 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
                         }
                         SyncState::Measuring {
                             id,
-                            ref mut recv_time,
+                            recv_time: ref mut sync_recv_time,
+                            ref mut ingress_time,
                             ..
                         } if id == header.sequence_id => {
-                            *recv_time = Some(corrected_recv_time);
+                            *sync_recv_time = Some(corrected_recv_time);
+                            *ingress_time = Some(recv_time);
                             self.handle_time_measurement()
                         }
                         _ => {
@@ -134,6 +164,8 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
                                 id: header.sequence_id,
                                 send_time: None,
                                 recv_time: Some(corrected_recv_time),
+                                origin_timestamp: None,
+                                ingress_time: Some(recv_time),
                             };
                             actions![]
                         }
@@ -150,6 +182,8 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
                                 id: header.sequence_id,
                                 send_time: Some(Time::from(message.origin_timestamp)),
                                 recv_time: Some(corrected_recv_time),
+                                origin_timestamp: Some(Time::from(message.origin_timestamp)),
+                                ingress_time: Some(recv_time),
                             };
                             self.handle_time_measurement()
                         }
@@ -164,16 +198,28 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
         &mut self,
         header: Header,
         message: FollowUpMessage,
+        information: Option<FollowUpInformation>,
     ) -> PortActionIterator<'_> {
         match self.port_state {
             PortState::Slave(ref mut state) => {
                 log::debug!("Received FollowUp {:?}", header.sequence_id);
                 if state.remote_master != header.source_port_identity {
+                    if header.alternate_master_flag {
+                        self.handle_alternate_follow_up(header, message);
+                    }
                     return actions![];
                 }
 
-                let packet_send_time = Time::from(message.precise_origin_timestamp)
-                    + Duration::from(header.correction_field);
+                if let Some(information) = information {
+                    self.gptp.record_follow_up_information(information);
+                    let forwarded = self.gptp.forwarded_follow_up_information(information);
+                    self.instance_state.with_mut(|state| {
+                        state.received_follow_up_information = Some(forwarded);
+                    });
+                }
+
+                let origin_time = Time::from(message.precise_origin_timestamp);
+                let packet_send_time = origin_time + Duration::from(header.correction_field);
 
                 match state.sync_state {
                     SyncState::Measuring {
@@ -188,9 +234,11 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
                     SyncState::Measuring {
                         id,
                         ref mut send_time,
+                        ref mut origin_timestamp,
                         ..
                     } if id == header.sequence_id => {
                         *send_time = Some(packet_send_time);
+                        *origin_timestamp = Some(origin_time);
                         self.handle_time_measurement()
                     }
                     _ => {
@@ -198,6 +246,8 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
                             id: header.sequence_id,
                             send_time: Some(packet_send_time),
                             recv_time: None,
+                            origin_timestamp: Some(origin_time),
+                            ingress_time: None,
                         };
                         self.handle_time_measurement()
                     }
@@ -388,12 +438,32 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
             id,
         } = self.peer_delay_state
         {
+            // gPTP corrects the turnaround time of the neighbor for the difference in
+            // frequency, see IEEE802.1AS-2020 section 11.2.19.3.4
+            let neighbor_rate_ratio = match self.config.gptp {
+                Some(_) => self.gptp.record_response(
+                    responder_identity,
+                    response_send_time,
+                    response_recv_time,
+                ),
+                None => 1.0,
+            };
+            let peer_delay = ((response_recv_time - request_send_time) * neighbor_rate_ratio
+                - (response_send_time - request_recv_time))
+                / 2.0;
+
+            if let Some(gptp_config) = &self.config.gptp {
+                let own_identity = self.port_identity.clock_identity;
+                self.gptp.update_as_capable(
+                    gptp_config,
+                    own_identity,
+                    responder_identity,
+                    peer_delay,
+                );
+            }
+
             result.event_time = response_recv_time;
-            result.peer_delay = Some(
-                ((response_recv_time - request_send_time)
-                    - (response_send_time - request_recv_time))
-                    / 2.0,
-            );
+            result.peer_delay = Some(peer_delay);
             self.peer_delay_state = PeerDelayState::PostMeasurement {
                 id,
                 responder_identity,
@@ -412,9 +482,11 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
         match self.port_state {
             PortState::Slave(ref mut state) => {
                 if let SyncState::Measuring {
+                    id,
                     send_time: Some(send_time),
                     recv_time: Some(recv_time),
-                    ..
+                    origin_timestamp: Some(origin_timestamp),
+                    ingress_time: Some(ingress_time),
                 } = state.sync_state
                 {
                     let raw_sync_offset = recv_time - send_time - self.config.delay_asymmetry;
@@ -425,6 +497,30 @@ impl<A, C: Clock, F: Filter, R, S> Port<'_, Running, A, R, C, F, S> {
                         result.offset = Some(raw_sync_offset - mean_delay);
                     }
 
+                    self.slave_monitor.record_sync(
+                        self.config.slave_monitoring.as_ref(),
+                        state.remote_master,
+                        SlaveRxSyncTimingDataRecord {
+                            sequence_id: id,
+                            sync_origin_timestamp: origin_timestamp.into(),
+                            total_correction_field: ((send_time - origin_timestamp)
+                                + (ingress_time - recv_time))
+                                .into(),
+                            scaled_cumulative_rate_offset: self
+                                .gptp
+                                .cumulative_scaled_rate_offset(),
+                            sync_event_ingress_timestamp: ingress_time.into(),
+                        },
+                        self.mean_delay
+                            .zip(result.offset)
+                            .map(|(mean_delay, offset)| SlaveRxSyncComputedDataRecord {
+                                sequence_id: id,
+                                offset_from_master: offset.into(),
+                                mean_path_delay: mean_delay.into(),
+                                scaled_neighbor_rate_ratio: self.gptp.scaled_neighbor_rate_ratio(),
+                            }),
+                    );
+
                     state.last_raw_sync_offset = Some(raw_sync_offset);
                     state.sync_state = SyncState::Empty;
                 } else if let DelayState::Measuring {
@@ -468,6 +564,13 @@ impl<A, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'_, Running,
         &mut self,
         log_min_pdelay_req_interval: Interval,
     ) -> PortActionIterator<'_> {
+        if let (Some(gptp_config), PeerDelayState::Measuring { .. }) =
+            (&self.config.gptp, self.peer_delay_state)
+        {
+            // The previous request was not answered
+            self.gptp.record_lost_response(gptp_config);
+        }
+
         let pdelay_id = self.pdelay_seq_ids.generate();
 
         let pdelay_req = self.instance_state.with_ref(|state| {
@@ -478,7 +581,9 @@ impl<A, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'_, Running,
                 self.config.minor_ptp_version.into(),
             )
         });
-        let message_length = match pdelay_req.serialize(&mut self.packet_buffer) {
+        let message_length = match pdelay_req
+            .serialize_authenticated(&mut self.packet_buffer, self.config.authentication.as_ref())
+        {
             Ok(length) => length,
             Err(error) => {
                 log::error!("Could not serialize pdelay request: {:?}", error);
@@ -517,12 +622,16 @@ impl<A, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'_, Running,
         &mut self,
         log_min_delay_req_interval: Interval,
     ) -> PortActionIterator<'_> {
+        let unicast_address = self
+            .unicast_delay_req_address()
+            .or_else(|| self.hybrid_delay_req_address());
+
         match self.port_state {
             PortState::Slave(ref mut state) => {
                 log::debug!("Starting new delay measurement");
 
                 let delay_id = self.delay_seq_ids.generate();
-                let delay_req = self.instance_state.with_ref(|state| {
+                let mut delay_req = self.instance_state.with_ref(|state| {
                     Message::delay_req(
                         &state.default_ds,
                         self.port_identity,
@@ -530,8 +639,12 @@ impl<A, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'_, Running,
                         self.config.minor_ptp_version.into(),
                     )
                 });
+                delay_req.header.unicast_flag = unicast_address.is_some();
 
-                let message_length = match delay_req.serialize(&mut self.packet_buffer) {
+                let message_length = match delay_req.serialize_authenticated(
+                    &mut self.packet_buffer,
+                    self.config.authentication.as_ref(),
+                ) {
                     Ok(length) => length,
                     Err(error) => {
                         log::error!("Could not serialize delay request: {:?}", error);
@@ -551,20 +664,47 @@ impl<A, C: Clock, F: Filter, R: Rng, S: PtpInstanceStateMutex> Port<'_, Running,
                     .as_core_duration()
                     .mul_f64(factor);
 
-                actions![
-                    PortAction::ResetDelayRequestTimer { duration },
-                    PortAction::SendEvent {
-                        context: TimestampContext {
-                            inner: TimestampContextInner::DelayReq { id: delay_id },
-                        },
-                        data: &self.packet_buffer[..message_length],
-                        link_local: false,
-                    }
-                ]
+                let context = TimestampContext {
+                    inner: TimestampContextInner::DelayReq { id: delay_id },
+                };
+                let data = &self.packet_buffer[..message_length];
+                let send = match unicast_address {
+                    Some(address) => PortAction::SendEventUnicast {
+                        context,
+                        data,
+                        address,
+                    },
+                    None => PortAction::SendEvent {
+                        context,
+                        data,
+                        link_local: self.is_link_local(),
+                    },
+                };
+
+                actions![PortAction::ResetDelayRequestTimer { duration }, send]
             }
             _ => actions![],
         }
     }
+
+    /// Remember the address of our master when it sends us a sync message,
+    /// for sending it delay requests in hybrid mode
+    pub(super) fn learn_master_address(&mut self, identity: PortIdentity, source: PortAddress) {
+        if let PortState::Slave(ref mut state) = self.port_state {
+            if state.remote_master() == identity {
+                state.remote_master_address = Some(source);
+            }
+        }
+    }
+
+    /// Address to send delay requests to in hybrid mode, until it is known
+    /// they are multicast
+    fn hybrid_delay_req_address(&self) -> Option<PortAddress> {
+        match self.port_state {
+            PortState::Slave(ref state) if self.config.hybrid_e2e => state.remote_master_address,
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -573,10 +713,7 @@ mod tests {
 
     use super::*;
     use crate::{
-        datastructures::{
-            common::{PortIdentity, TimeInterval},
-            messages::MessageBody,
-        },
+        datastructures::{common::TimeInterval, messages::MessageBody},
         filters::FilterUpdate,
         port::{
             state::SlaveState,
@@ -685,6 +822,7 @@ mod tests {
             FollowUpMessage {
                 precise_origin_timestamp: Time::from_micros(1000).into(),
             },
+            None,
         );
 
         assert!(action.next().is_none());
@@ -907,6 +1045,7 @@ mod tests {
             FollowUpMessage {
                 precise_origin_timestamp: Time::from_micros(1000).into(),
             },
+            None,
         );
 
         assert!(action.next().is_none());
@@ -972,6 +1111,7 @@ mod tests {
             FollowUpMessage {
                 precise_origin_timestamp: Time::from_micros(10).into(),
             },
+            None,
         );
 
         assert!(action.next().is_none());
@@ -1044,6 +1184,7 @@ mod tests {
             FollowUpMessage {
                 precise_origin_timestamp: Time::from_micros(10).into(),
             },
+            None,
         );
 
         assert!(action.next().is_none());
@@ -1060,6 +1201,7 @@ mod tests {
             FollowUpMessage {
                 precise_origin_timestamp: Time::from_micros(10).into(),
             },
+            None,
         );
 
         assert!(action.next().is_none());
@@ -1122,6 +1264,7 @@ mod tests {
             FollowUpMessage {
                 precise_origin_timestamp: Time::from_micros(1000).into(),
             },
+            None,
         );
 
         assert!(action.next().is_none());
@@ -1280,6 +1423,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_hybrid_delay_request() {
+        let state = setup_test_state();
+
+        let mut port = setup_test_port_custom_filter::<TestFilter>(&state, ());
+        port.config.hybrid_e2e = true;
+
+        let master = PortIdentity {
+            port_number: 7,
+            ..Default::default()
+        };
+        let master_address = PortAddress::Ipv4([192, 0, 2, 1]);
+        port.set_forced_port_state(PortState::Slave(SlaveState::new(master)));
+
+        // Multicast until the address of the master is known
+        let mut actions = port.send_delay_request();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetDelayRequestTimer { .. })
+        ));
+        assert!(matches!(actions.next(), Some(PortAction::SendEvent { .. })));
+        drop(actions);
+
+        // Only the address of our master is used
+        port.learn_master_address(Default::default(), PortAddress::Ipv4([192, 0, 2, 2]));
+        port.learn_master_address(master, master_address);
+
+        let mut actions = port.send_delay_request();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetDelayRequestTimer { .. })
+        ));
+        let Some(PortAction::SendEventUnicast { data, address, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(address, master_address);
+        let req = Message::deserialize(data).unwrap();
+        assert!(matches!(req.body, MessageBody::DelayReq(_)));
+        assert!(req.header.unicast_flag);
+        drop(actions);
+
+        // Without hybrid mode delay requests are multicast
+        port.config.hybrid_e2e = false;
+        let mut actions = port.send_delay_request();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetDelayRequestTimer { .. })
+        ));
+        assert!(matches!(actions.next(), Some(PortAction::SendEvent { .. })));
+    }
+
     #[test]
     fn test_peer_delay_1step() {
         let state = setup_test_state();
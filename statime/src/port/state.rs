@@ -1,6 +1,7 @@
 use core::fmt::{Display, Formatter};
 
 use crate::{
+    config::PortAddress,
     datastructures::common::PortIdentity,
     time::{Duration, Time},
 };
@@ -11,6 +12,7 @@ use crate::{
 pub(crate) enum PortState {
     #[default]
     Faulty,
+    Disabled,
     Listening,
     Master,
     Passive,
@@ -25,6 +27,7 @@ impl Display for PortState {
             PortState::Passive => write!(f, "Passive"),
             PortState::Slave(_) => write!(f, "Slave"),
             PortState::Faulty => write!(f, "Faulty"),
+            PortState::Disabled => write!(f, "Disabled"),
         }
     }
 }
@@ -32,6 +35,8 @@ impl Display for PortState {
 #[derive(Debug)]
 pub(crate) struct SlaveState {
     pub(super) remote_master: PortIdentity,
+    /// Address the remote master sends its sync messages from
+    pub(super) remote_master_address: Option<PortAddress>,
 
     pub(super) sync_state: SyncState,
     pub(super) delay_state: DelayState,
@@ -52,6 +57,10 @@ pub(super) enum SyncState {
         id: u16,
         send_time: Option<Time>,
         recv_time: Option<Time>,
+        /// Origin timestamp and ingress time without corrections, kept for
+        /// slave event monitoring
+        origin_timestamp: Option<Time>,
+        ingress_time: Option<Time>,
     },
 }
 
@@ -69,6 +78,7 @@ impl SlaveState {
     pub(super) fn new(remote_master: PortIdentity) -> Self {
         SlaveState {
             remote_master,
+            remote_master_address: None,
             sync_state: SyncState::Empty,
             delay_state: DelayState::Empty,
             last_raw_sync_offset: None,
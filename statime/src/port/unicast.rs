@@ -0,0 +1,1447 @@
+//! Unicast message negotiation, see *IEEE1588-2019 section 16.1*
+//!
+//! Other ports can request this port to send them announce, sync and delay
+//! response messages unicast while it is master. In the other direction, a
+//! port with a unicast master table requests announce messages from all
+//! masters in the table, and sync and delay response messages from the one
+//! it selected as its master.
+
+use arrayvec::ArrayVec;
+
+use super::{
+    sequence_id::SequenceIdGenerator, state::PortState, Port, PortActionIterator, Running,
+};
+use crate::{
+    config::{DelayMechanism, PortAddress, MAX_UNICAST_GRANTEES},
+    datastructures::{
+        common::{ClockIdentity, PortIdentity, Tlv, TlvSetBuilder, TlvType},
+        messages::{
+            CancelUnicastTransmissionTlv, GrantUnicastTransmissionTlv, Message, MessageType,
+            RequestUnicastTransmissionTlv, SignalingMessage,
+        },
+    },
+    filters::Filter,
+    port::{actions::TimestampContextInner, PortAction, TimestampContext},
+    ptp_instance::PtpInstanceStateMutex,
+    time::{Duration, Interval, Time},
+    Clock,
+};
+
+/// Every grantee can hold a grant for announce, sync and delay response
+pub(super) const MAX_UNICAST_GRANTS: usize = 3 * MAX_UNICAST_GRANTEES;
+
+// We answer at most this many TLVs of a single signaling message, which
+// bounds the size of the response.
+const MAX_SIGNALING_RESPONSE_TLVS: usize = 8;
+const MAX_SIGNALING_RESPONSE_TLV_SIZE: usize = 4 + 8;
+
+/// Unicast transmission of a single message type granted to another port
+#[derive(Debug, Clone)]
+pub(super) struct UnicastGrant {
+    grantee: PortIdentity,
+    address: PortAddress,
+    message_type: MessageType,
+    log_interval: i8,
+    expires_at: Time,
+    next_message_at: Time,
+    sequence_ids: SequenceIdGenerator,
+}
+
+impl UnicastGrant {
+    // Delay responses are sent in answer to a request, not on a schedule
+    fn is_scheduled(&self) -> bool {
+        matches!(self.message_type, MessageType::Announce | MessageType::Sync)
+    }
+}
+
+// The message types requested from a unicast master
+const REQUESTED_MESSAGE_TYPES: [MessageType; 3] = [
+    MessageType::Announce,
+    MessageType::Sync,
+    MessageType::DelayResp,
+];
+
+/// A master from the unicast master table of the port
+#[derive(Debug, Clone)]
+pub(super) struct UnicastMaster {
+    address: PortAddress,
+    // Learned from its grants and announce messages
+    port_identity: Option<PortIdentity>,
+    announce: RequestedGrant,
+    sync: RequestedGrant,
+    delay_resp: RequestedGrant,
+}
+
+impl UnicastMaster {
+    pub(super) fn new(address: PortAddress) -> Self {
+        Self {
+            address,
+            port_identity: None,
+            announce: Default::default(),
+            sync: Default::default(),
+            delay_resp: Default::default(),
+        }
+    }
+
+    fn grant(&self, message_type: MessageType) -> Option<&RequestedGrant> {
+        match message_type {
+            MessageType::Announce => Some(&self.announce),
+            MessageType::Sync => Some(&self.sync),
+            MessageType::DelayResp => Some(&self.delay_resp),
+            _ => None,
+        }
+    }
+
+    fn grant_mut(&mut self, message_type: MessageType) -> Option<&mut RequestedGrant> {
+        match message_type {
+            MessageType::Announce => Some(&mut self.announce),
+            MessageType::Sync => Some(&mut self.sync),
+            MessageType::DelayResp => Some(&mut self.delay_resp),
+            _ => None,
+        }
+    }
+}
+
+/// Unicast transmission of a single message type requested from a master
+#[derive(Debug, Clone, Default)]
+struct RequestedGrant {
+    // None when not granted (yet)
+    expires_at: Option<Time>,
+    // When to send the next request, either to retry or to renew the grant
+    next_request_at: Time,
+}
+
+impl<A, C: Clock, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F, S> {
+    /// Handle the unicast timer going off
+    ///
+    /// This sends the next message due to a port that was granted unicast
+    /// transmission or the next due request to a unicast master, and removes
+    /// expired grants.
+    pub fn handle_unicast_timer(&mut self) -> PortActionIterator<'_> {
+        let now = self.clock.now();
+
+        self.unicast_grants.retain(|grant| {
+            let active = grant.expires_at > now;
+            if !active {
+                log::debug!(
+                    "Unicast {:?} grant for {:?} expired",
+                    grant.message_type,
+                    grant.address
+                );
+            }
+            active
+        });
+
+        let due = self
+            .unicast_grants
+            .iter()
+            .enumerate()
+            .filter(|(_, grant)| grant.is_scheduled() && grant.next_message_at <= now)
+            .min_by_key(|(_, grant)| grant.next_message_at)
+            .map(|(index, _)| index);
+
+        let mut send = None;
+        if let Some(index) = due {
+            let grant = &mut self.unicast_grants[index];
+            let interval = Interval::from_log_2(grant.log_interval).as_duration();
+            grant.next_message_at += interval;
+            if grant.next_message_at <= now {
+                // Don't try to catch up after falling behind
+                grant.next_message_at = now + interval;
+            }
+
+            // Grants are kept while the port is not master, but only masters
+            // send messages.
+            if matches!(self.port_state, PortState::Master) {
+                send = Some(index);
+            }
+        }
+
+        for master in &mut self.unicast_masters {
+            let address = master.address;
+            for message_type in REQUESTED_MESSAGE_TYPES {
+                let grant = master.grant_mut(message_type).unwrap();
+                if grant
+                    .expires_at
+                    .map_or(false, |expires_at| expires_at <= now)
+                {
+                    log::debug!(
+                        "Unicast {:?} grant from {:?} expired",
+                        message_type,
+                        address
+                    );
+                    grant.expires_at = None;
+                }
+            }
+        }
+
+        // Both sends use the packet buffer, so requests wait for the next
+        // timer if a message to a grantee is due as well.
+        let mut request = None;
+        if send.is_none() {
+            request = self.take_due_unicast_requests(now);
+        }
+
+        let next_grantor_event = self
+            .unicast_grants
+            .iter()
+            .map(|grant| {
+                if grant.is_scheduled() {
+                    grant.next_message_at.min(grant.expires_at)
+                } else {
+                    grant.expires_at
+                }
+            })
+            .min();
+        let next_event = match (next_grantor_event, self.next_unicast_request_event()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        let reset_timer = next_event.map(|next_event| PortAction::ResetUnicastTimer {
+            duration: if next_event > now {
+                (next_event - now).into()
+            } else {
+                core::time::Duration::ZERO
+            },
+        });
+
+        let send_action = match (send, request) {
+            (Some(index), _) => match self.unicast_grants[index].message_type {
+                MessageType::Announce => self.unicast_announce(index),
+                _ => self.unicast_sync(index),
+            },
+            (None, Some((index, message_types))) => self.unicast_request(index, &message_types),
+            (None, None) => None,
+        };
+
+        match (reset_timer, send_action) {
+            (Some(reset_timer), Some(send_action)) => actions![reset_timer, send_action],
+            (Some(action), None) | (None, Some(action)) => actions![action],
+            (None, None) => actions![],
+        }
+    }
+
+    fn unicast_announce(&mut self, index: usize) -> Option<PortAction<'_>> {
+        let grant = &mut self.unicast_grants[index];
+        let address = grant.address;
+
+        let mut message = self.instance_state.with_ref(|state| {
+            Message::announce(
+                state,
+                self.port_identity,
+                grant.sequence_ids.generate(),
+                self.config.minor_ptp_version.into(),
+            )
+        });
+        message.header.unicast_flag = true;
+
+        let packet_length = match message
+            .serialize_authenticated(&mut self.packet_buffer, self.config.authentication.as_ref())
+        {
+            Ok(length) => length,
+            Err(error) => {
+                log::error!(
+                    "Statime bug: Could not serialize unicast announce message {:?}",
+                    error
+                );
+                return None;
+            }
+        };
+
+        Some(PortAction::SendGeneralUnicast {
+            data: &self.packet_buffer[..packet_length],
+            address,
+        })
+    }
+
+    fn unicast_sync(&mut self, index: usize) -> Option<PortAction<'_>> {
+        let grant = &mut self.unicast_grants[index];
+        let address = grant.address;
+        let seq_id = grant.sequence_ids.generate();
+
+        let mut message = self.instance_state.with_ref(|state| {
+            Message::sync(
+                &state.default_ds,
+                self.port_identity,
+                seq_id,
+                self.config.minor_ptp_version.into(),
+            )
+        });
+        message.header.unicast_flag = true;
+
+        let packet_length = match message
+            .serialize_authenticated(&mut self.packet_buffer, self.config.authentication.as_ref())
+        {
+            Ok(length) => length,
+            Err(error) => {
+                log::error!("Statime bug: Could not serialize unicast sync: {:?}", error);
+                return None;
+            }
+        };
+
+        Some(PortAction::SendEventUnicast {
+            context: TimestampContext {
+                inner: TimestampContextInner::UnicastSync {
+                    id: seq_id,
+                    address,
+                },
+            },
+            data: &self.packet_buffer[..packet_length],
+            address,
+        })
+    }
+
+    /// Whether unicast transmission of `message_type` should currently be
+    /// requested from `master`
+    fn wants_unicast_grant(&self, master: &UnicastMaster, message_type: MessageType) -> bool {
+        let is_remote_master = match self.port_state {
+            PortState::Disabled | PortState::Faulty => return false,
+            PortState::Slave(ref state) => master.port_identity == Some(state.remote_master()),
+            _ => false,
+        };
+
+        match message_type {
+            MessageType::Announce => true,
+            MessageType::Sync => is_remote_master,
+            MessageType::DelayResp => {
+                is_remote_master
+                    && matches!(self.config.delay_mechanism, DelayMechanism::E2E { .. })
+            }
+            _ => false,
+        }
+    }
+
+    /// Find the first master with due requests, and schedule a retry of those
+    /// requests in case they are not answered.
+    fn take_due_unicast_requests(
+        &mut self,
+        now: Time,
+    ) -> Option<(usize, ArrayVec<MessageType, 3>)> {
+        let query_interval = self
+            .config
+            .unicast_master_table
+            .query_interval
+            .as_duration();
+
+        for index in 0..self.unicast_masters.len() {
+            let message_types: ArrayVec<MessageType, 3> = REQUESTED_MESSAGE_TYPES
+                .into_iter()
+                .filter(|&message_type| {
+                    let master = &self.unicast_masters[index];
+                    self.wants_unicast_grant(master, message_type)
+                        && master.grant(message_type).unwrap().next_request_at <= now
+                })
+                .collect();
+
+            if !message_types.is_empty() {
+                for &message_type in &message_types {
+                    let grant = self.unicast_masters[index].grant_mut(message_type).unwrap();
+                    grant.next_request_at = now + query_interval;
+                }
+                return Some((index, message_types));
+            }
+        }
+
+        None
+    }
+
+    fn next_unicast_request_event(&self) -> Option<Time> {
+        self.unicast_masters
+            .iter()
+            .flat_map(|master| {
+                REQUESTED_MESSAGE_TYPES
+                    .into_iter()
+                    .filter_map(move |message_type| {
+                        let grant = master.grant(message_type).unwrap();
+                        let next_request = self
+                            .wants_unicast_grant(master, message_type)
+                            .then_some(grant.next_request_at);
+                        match (next_request, grant.expires_at) {
+                            (Some(a), Some(b)) => Some(a.min(b)),
+                            (a, b) => a.or(b),
+                        }
+                    })
+            })
+            .min()
+    }
+
+    fn unicast_request(
+        &mut self,
+        index: usize,
+        message_types: &[MessageType],
+    ) -> Option<PortAction<'_>> {
+        let master = &self.unicast_masters[index];
+        let address = master.address;
+        // Until the master answered, we don't know which port it is
+        let target_port_identity = master.port_identity.unwrap_or(PortIdentity {
+            clock_identity: ClockIdentity([0xff; 8]),
+            port_number: 0xffff,
+        });
+
+        let duration_field = self
+            .config
+            .unicast_master_table
+            .grant_duration
+            .as_secs()
+            .try_into()
+            .unwrap_or(u32::MAX);
+
+        let mut tlv_buffer = [0; REQUESTED_MESSAGE_TYPES.len() * MAX_SIGNALING_RESPONSE_TLV_SIZE];
+        let mut tlv_builder = TlvSetBuilder::new(&mut tlv_buffer);
+        for &message_type in message_types {
+            let interval = match message_type {
+                MessageType::Announce => self.config.announce_interval,
+                MessageType::Sync => self.config.sync_interval,
+                _ => self.config.min_delay_req_interval(),
+            };
+
+            let request = RequestUnicastTransmissionTlv {
+                message_type,
+                log_inter_message_period: interval.as_log_2(),
+                duration_field,
+            };
+
+            let mut value_buffer = [0; MAX_SIGNALING_RESPONSE_TLV_SIZE];
+            let Ok(length) = request.serialize(&mut value_buffer) else {
+                log::error!("Statime bug: Could not serialize unicast request tlv");
+                return None;
+            };
+
+            // Cannot fail, the buffer fits a request for every message type
+            tlv_builder
+                .add(Tlv {
+                    tlv_type: TlvType::RequestUnicastTransmission,
+                    value: value_buffer[..length].into(),
+                })
+                .unwrap();
+        }
+
+        log::debug!("Requesting unicast {:?} from {:?}", message_types, address);
+
+        let message = self.instance_state.with_ref(|state| {
+            Message::signaling(
+                &state.default_ds,
+                self.port_identity,
+                target_port_identity,
+                self.signaling_seq_ids.generate(),
+                self.config.minor_ptp_version.into(),
+                tlv_builder.build(),
+            )
+        });
+
+        let packet_length = match message
+            .serialize_authenticated(&mut self.packet_buffer, self.config.authentication.as_ref())
+        {
+            Ok(length) => length,
+            Err(error) => {
+                log::error!(
+                    "Statime bug: Could not serialize signaling message: {:?}",
+                    error
+                );
+                return None;
+            }
+        };
+
+        Some(PortAction::SendGeneralUnicast {
+            data: &self.packet_buffer[..packet_length],
+            address,
+        })
+    }
+
+    /// Process the answer of a unicast master to one of our requests
+    fn handle_unicast_grant(
+        &mut self,
+        grant: GrantUnicastTransmissionTlv,
+        grantor: PortIdentity,
+        address: PortAddress,
+        now: Time,
+    ) {
+        let query_interval = self
+            .config
+            .unicast_master_table
+            .query_interval
+            .as_duration();
+
+        let Some(master) = self
+            .unicast_masters
+            .iter_mut()
+            .find(|master| master.address == address)
+        else {
+            log::debug!("Ignoring unicast grant from unknown master {:?}", address);
+            return;
+        };
+        master.port_identity = Some(grantor);
+
+        let Some(requested) = master.grant_mut(grant.message_type) else {
+            return;
+        };
+
+        if grant.duration_field == 0 {
+            log::debug!(
+                "Unicast {:?} request denied by {:?}",
+                grant.message_type,
+                address
+            );
+            requested.expires_at = None;
+            requested.next_request_at = now + query_interval;
+        } else {
+            log::debug!(
+                "Unicast {:?} granted by {:?} for {}s",
+                grant.message_type,
+                address,
+                grant.duration_field
+            );
+            let duration = Duration::from_secs(grant.duration_field.into());
+            let expires_at = now + duration;
+            requested.expires_at = Some(expires_at);
+            // Renew halfway through, unless the master does not want us to
+            requested.next_request_at = if grant.renewal_invited {
+                now + duration / 2
+            } else {
+                expires_at
+            };
+        }
+    }
+
+    pub(super) fn handle_signaling<'b>(
+        &'b mut self,
+        message: &Message<'_>,
+        signaling: &SignalingMessage,
+        source: Option<PortAddress>,
+    ) -> PortActionIterator<'b> {
+        if !self.is_target_port(signaling.target_port_identity) {
+            return actions![];
+        }
+
+        let Some(address) = source else {
+            log::debug!("Ignoring signaling message without source address");
+            return actions![];
+        };
+
+        let remote = message.header.source_port_identity;
+        let now = self.clock.now();
+
+        let mut tlv_buffer = [0; MAX_SIGNALING_RESPONSE_TLVS * MAX_SIGNALING_RESPONSE_TLV_SIZE];
+        let mut tlv_builder = TlvSetBuilder::new(&mut tlv_buffer);
+        let mut tlv_count = 0;
+        let mut reschedule = false;
+
+        for tlv in message.suffix.tlv() {
+            if tlv_count == MAX_SIGNALING_RESPONSE_TLVS {
+                log::warn!("Too many tlvs in signaling message, ignoring the rest");
+                break;
+            }
+
+            let mut value_buffer = [0; MAX_SIGNALING_RESPONSE_TLV_SIZE];
+            let (tlv_type, length) = match tlv.tlv_type {
+                TlvType::RequestUnicastTransmission => {
+                    let request = match RequestUnicastTransmissionTlv::deserialize(&tlv.value) {
+                        Ok(request) => request,
+                        Err(error) => {
+                            log::warn!("Could not parse unicast request: {:?}", error);
+                            continue;
+                        }
+                    };
+
+                    let grant = self.grant_unicast(request, remote, address, now);
+                    reschedule |= grant.duration_field != 0;
+                    (
+                        TlvType::GrantUnicastTransmission,
+                        grant.serialize(&mut value_buffer),
+                    )
+                }
+                TlvType::CancelUnicastTransmission => {
+                    let cancel = match CancelUnicastTransmissionTlv::deserialize(&tlv.value) {
+                        Ok(cancel) => cancel,
+                        Err(error) => {
+                            log::warn!("Could not parse unicast cancel: {:?}", error);
+                            continue;
+                        }
+                    };
+
+                    log::debug!(
+                        "Unicast {:?} grant for {:?} cancelled",
+                        cancel.message_type,
+                        address
+                    );
+                    self.unicast_grants.retain(|grant| {
+                        grant.grantee != remote || grant.message_type != cancel.message_type
+                    });
+                    // The cancel can also come from a master that granted us
+                    // transmission, in which case we ask again later
+                    let query_interval = self
+                        .config
+                        .unicast_master_table
+                        .query_interval
+                        .as_duration();
+                    if let Some(requested) = self
+                        .unicast_masters
+                        .iter_mut()
+                        .find(|master| master.address == address)
+                        .and_then(|master| master.grant_mut(cancel.message_type))
+                    {
+                        requested.expires_at = None;
+                        requested.next_request_at = now + query_interval;
+                        reschedule = true;
+                    }
+                    (
+                        TlvType::AcknowledgeCancelUnicastTransmission,
+                        cancel.serialize(&mut value_buffer),
+                    )
+                }
+                TlvType::GrantUnicastTransmission => {
+                    match GrantUnicastTransmissionTlv::deserialize(&tlv.value) {
+                        Ok(grant) => {
+                            self.handle_unicast_grant(grant, remote, address, now);
+                            reschedule = true;
+                        }
+                        Err(error) => log::warn!("Could not parse unicast grant: {:?}", error),
+                    }
+                    // Grants are not answered
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let Ok(length) = length else {
+                log::error!("Statime bug: Could not serialize unicast negotiation tlv");
+                return actions![];
+            };
+
+            // Cannot fail, the buffer fits MAX_SIGNALING_RESPONSE_TLVS of these
+            tlv_builder
+                .add(Tlv {
+                    tlv_type,
+                    value: value_buffer[..length].into(),
+                })
+                .unwrap();
+            tlv_count += 1;
+        }
+
+        // Start sending to a new grantee right away, and account for changes
+        // in our own requests
+        let reset_timer = PortAction::ResetUnicastTimer {
+            duration: core::time::Duration::ZERO,
+        };
+
+        if tlv_count == 0 {
+            return if reschedule {
+                actions![reset_timer]
+            } else {
+                actions![]
+            };
+        }
+
+        let response = self.instance_state.with_ref(|state| {
+            Message::signaling(
+                &state.default_ds,
+                self.port_identity,
+                remote,
+                self.signaling_seq_ids.generate(),
+                self.config.minor_ptp_version.into(),
+                tlv_builder.build(),
+            )
+        });
+
+        let packet_length = match response
+            .serialize_authenticated(&mut self.packet_buffer, self.config.authentication.as_ref())
+        {
+            Ok(length) => length,
+            Err(error) => {
+                log::error!(
+                    "Statime bug: Could not serialize signaling message: {:?}",
+                    error
+                );
+                return actions![];
+            }
+        };
+
+        let send = PortAction::SendGeneralUnicast {
+            data: &self.packet_buffer[..packet_length],
+            address,
+        };
+
+        if reschedule {
+            actions![reset_timer, send]
+        } else {
+            actions![send]
+        }
+    }
+
+    fn grant_unicast(
+        &mut self,
+        request: RequestUnicastTransmissionTlv,
+        grantee: PortIdentity,
+        address: PortAddress,
+        now: Time,
+    ) -> GrantUnicastTransmissionTlv {
+        let denied = GrantUnicastTransmissionTlv {
+            message_type: request.message_type,
+            log_inter_message_period: request.log_inter_message_period,
+            duration_field: 0,
+            renewal_invited: false,
+        };
+
+        let Some(config) = self.config.unicast_grantor else {
+            log::debug!("Denying unicast request, unicast negotiation is disabled");
+            return denied;
+        };
+
+        if self
+            .instance_state
+            .with_ref(|state| state.default_ds.slave_only)
+        {
+            log::debug!("Denying unicast request, instance is slave only");
+            return denied;
+        }
+
+        let supported = match request.message_type {
+            MessageType::Announce | MessageType::Sync => true,
+            MessageType::DelayResp => {
+                matches!(self.config.delay_mechanism, DelayMechanism::E2E { .. })
+            }
+            _ => false,
+        };
+        if !supported {
+            log::debug!(
+                "Denying unicast request for unsupported message type {:?}",
+                request.message_type
+            );
+            return denied;
+        }
+
+        if request.duration_field == 0
+            || request.log_inter_message_period < config.min_interval.as_log_2()
+        {
+            log::debug!("Denying unicast request outside of the configured limits");
+            return denied;
+        }
+
+        let duration_field = request
+            .duration_field
+            .min(config.max_duration.as_secs().try_into().unwrap_or(u32::MAX));
+        let expires_at = now + Duration::from_secs(duration_field.into());
+
+        if let Some(grant) = self
+            .unicast_grants
+            .iter_mut()
+            .find(|grant| grant.grantee == grantee && grant.message_type == request.message_type)
+        {
+            // Renewal of an existing grant
+            grant.address = address;
+            grant.log_interval = request.log_inter_message_period;
+            grant.expires_at = expires_at;
+        } else {
+            let known_grantee = self
+                .unicast_grants
+                .iter()
+                .any(|grant| grant.grantee == grantee);
+            let max_grantees = config.max_grantees.min(MAX_UNICAST_GRANTEES);
+            if !known_grantee && self.unicast_grantee_count() >= max_grantees {
+                log::debug!("Denying unicast request, maximum number of grantees reached");
+                return denied;
+            }
+
+            let grant = UnicastGrant {
+                grantee,
+                address,
+                message_type: request.message_type,
+                log_interval: request.log_inter_message_period,
+                expires_at,
+                next_message_at: now,
+                sequence_ids: SequenceIdGenerator::new(),
+            };
+            if self.unicast_grants.try_push(grant).is_err() {
+                log::debug!("Denying unicast request, no room for more grants");
+                return denied;
+            }
+        }
+
+        log::debug!(
+            "Granted unicast {:?} to {:?} for {}s",
+            request.message_type,
+            address,
+            duration_field
+        );
+
+        GrantUnicastTransmissionTlv {
+            message_type: request.message_type,
+            log_inter_message_period: request.log_inter_message_period,
+            duration_field,
+            renewal_invited: true,
+        }
+    }
+
+    fn unicast_grantee_count(&self) -> usize {
+        let mut grantees = ArrayVec::<PortIdentity, MAX_UNICAST_GRANTS>::new();
+        for grant in &self.unicast_grants {
+            if !grantees.contains(&grant.grantee) {
+                grantees.push(grant.grantee);
+            }
+        }
+        grantees.len()
+    }
+}
+
+impl<A, C, F: Filter, R, S: PtpInstanceStateMutex> Port<'_, Running, A, R, C, F, S> {
+    /// Address to send the delay response to `requester` to, if it was granted
+    /// unicast delay responses.
+    pub(super) fn unicast_delay_resp_address(
+        &self,
+        requester: PortIdentity,
+    ) -> Option<PortAddress> {
+        self.unicast_grants
+            .iter()
+            .find(|grant| {
+                grant.grantee == requester && grant.message_type == MessageType::DelayResp
+            })
+            .map(|grant| grant.address)
+    }
+
+    /// Address to send delay requests to, if our master granted us unicast
+    /// delay responses.
+    pub(super) fn unicast_delay_req_address(&self) -> Option<PortAddress> {
+        let PortState::Slave(ref state) = self.port_state else {
+            return None;
+        };
+
+        self.unicast_masters
+            .iter()
+            .find(|master| {
+                master.port_identity == Some(state.remote_master())
+                    && master.delay_resp.expires_at.is_some()
+            })
+            .map(|master| master.address)
+    }
+
+    /// Remember the identity of a unicast master that sent us an announce
+    pub(super) fn learn_unicast_master(&mut self, identity: PortIdentity, source: PortAddress) {
+        if let Some(master) = self
+            .unicast_masters
+            .iter_mut()
+            .find(|master| master.address == source)
+        {
+            master.port_identity = Some(identity);
+        }
+    }
+
+    pub(super) fn handle_unicast_sync_timestamp(
+        &mut self,
+        id: u16,
+        address: PortAddress,
+        timestamp: Time,
+    ) -> PortActionIterator<'_> {
+        if matches!(self.port_state, PortState::Master) {
+            self.slave_collector
+                .record_sync(Some(address), id, timestamp);
+
+            let mut message = self.instance_state.with_ref(|state| {
+                Message::follow_up(
+                    &state.default_ds,
+                    self.port_identity,
+                    id,
+                    timestamp,
+                    self.config.minor_ptp_version.into(),
+                )
+            });
+            message.header.unicast_flag = true;
+
+            let packet_length = match message.serialize_authenticated(
+                &mut self.packet_buffer,
+                self.config.authentication.as_ref(),
+            ) {
+                Ok(length) => length,
+                Err(error) => {
+                    log::error!(
+                        "Statime bug: Could not serialize unicast sync follow up {:?}",
+                        error
+                    );
+                    return actions![];
+                }
+            };
+
+            actions![PortAction::SendGeneralUnicast {
+                data: &self.packet_buffer[..packet_length],
+                address,
+            }]
+        } else {
+            actions![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::UnicastGrantorConfig,
+        datastructures::{
+            common::ClockIdentity,
+            messages::{DelayReqMessage, Header, MessageBody, MAX_DATA_LEN},
+        },
+        port::{
+            state::SlaveState,
+            tests::{setup_test_port_custom_clock, setup_test_state, SettableClock},
+        },
+    };
+
+    const GRANTEE: PortIdentity = PortIdentity {
+        clock_identity: ClockIdentity([9; 8]),
+        port_number: 1,
+    };
+    const GRANTEE_ADDRESS: PortAddress = PortAddress::Ipv4([192, 0, 2, 1]);
+
+    const GRANTOR_CONFIG: UnicastGrantorConfig = UnicastGrantorConfig {
+        max_grantees: 1,
+        min_interval: Interval::from_log_2(-3),
+        max_duration: core::time::Duration::from_secs(300),
+    };
+
+    fn signaling_message(
+        source_port_identity: PortIdentity,
+        tlvs: &[(TlvType, &[u8])],
+        buffer: &mut [u8],
+    ) -> usize {
+        let mut tlv_buffer = [0; 128];
+        let mut tlv_builder = TlvSetBuilder::new(&mut tlv_buffer);
+        for (tlv_type, value) in tlvs {
+            tlv_builder
+                .add(Tlv {
+                    tlv_type: *tlv_type,
+                    value: (*value).into(),
+                })
+                .unwrap();
+        }
+
+        let message = Message {
+            header: Header {
+                source_port_identity,
+                ..Header::new(1)
+            },
+            body: MessageBody::Signaling(SignalingMessage {
+                target_port_identity: PortIdentity {
+                    clock_identity: ClockIdentity([0xff; 8]),
+                    port_number: 0xffff,
+                },
+            }),
+            suffix: tlv_builder.build(),
+        };
+        message.serialize(buffer).unwrap()
+    }
+
+    fn request(message_type: MessageType, log_interval: i8, duration: u32) -> [u8; 6] {
+        let mut value = [0; 6];
+        RequestUnicastTransmissionTlv {
+            message_type,
+            log_inter_message_period: log_interval,
+            duration_field: duration,
+        }
+        .serialize(&mut value)
+        .unwrap();
+        value
+    }
+
+    fn parse_grants(data: &[u8]) -> ArrayVec<GrantUnicastTransmissionTlv, 4> {
+        let message = Message::deserialize(data).unwrap();
+        assert!(message.header.unicast_flag);
+        let MessageBody::Signaling(signaling) = message.body else {
+            panic!("Expected signaling message");
+        };
+        assert_eq!(signaling.target_port_identity, GRANTEE);
+
+        message
+            .suffix
+            .tlv()
+            .map(|tlv| {
+                assert_eq!(tlv.tlv_type, TlvType::GrantUnicastTransmission);
+                GrantUnicastTransmissionTlv::deserialize(&tlv.value).unwrap()
+            })
+            .collect()
+    }
+
+    const MASTER: PortIdentity = PortIdentity {
+        clock_identity: ClockIdentity([11; 8]),
+        port_number: 1,
+    };
+    const MASTER_ADDRESS: PortAddress = PortAddress::Ipv4([192, 0, 2, 100]);
+
+    fn grant(message_type: MessageType, log_interval: i8, duration: u32) -> [u8; 8] {
+        let mut value = [0; 8];
+        GrantUnicastTransmissionTlv {
+            message_type,
+            log_inter_message_period: log_interval,
+            duration_field: duration,
+            renewal_invited: true,
+        }
+        .serialize(&mut value)
+        .unwrap();
+        value
+    }
+
+    fn parse_requests(data: &[u8]) -> (PortIdentity, ArrayVec<RequestUnicastTransmissionTlv, 4>) {
+        let message = Message::deserialize(data).unwrap();
+        assert!(message.header.unicast_flag);
+        let MessageBody::Signaling(signaling) = message.body else {
+            panic!("Expected signaling message");
+        };
+
+        let requests = message
+            .suffix
+            .tlv()
+            .map(|tlv| {
+                assert_eq!(tlv.tlv_type, TlvType::RequestUnicastTransmission);
+                RequestUnicastTransmissionTlv::deserialize(&tlv.value).unwrap()
+            })
+            .collect();
+        (signaling.target_port_identity, requests)
+    }
+
+    #[test]
+    fn test_unicast_grant_and_send() {
+        let state = setup_test_state();
+        let mut port = setup_test_port_custom_clock(&state, SettableClock(Time::from_secs(10)));
+        port.config.unicast_grantor = Some(GRANTOR_CONFIG);
+        port.set_forced_port_state(PortState::Master);
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = signaling_message(
+            GRANTEE,
+            &[
+                (
+                    TlvType::RequestUnicastTransmission,
+                    &request(MessageType::Announce, 1, 60),
+                ),
+                (
+                    TlvType::RequestUnicastTransmission,
+                    &request(MessageType::Sync, -3, 600),
+                ),
+                (
+                    TlvType::RequestUnicastTransmission,
+                    &request(MessageType::DelayResp, 0, 60),
+                ),
+            ],
+            &mut buffer,
+        );
+
+        let mut actions =
+            port.handle_general_receive_from(&buffer[..length], Some(GRANTEE_ADDRESS));
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetUnicastTimer { duration }) if duration.is_zero()
+        ));
+        let Some(PortAction::SendGeneralUnicast { data, address }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(address, GRANTEE_ADDRESS);
+        assert!(actions.next().is_none());
+
+        let grants = parse_grants(data);
+        assert_eq!(grants.len(), 3);
+        assert_eq!(grants[0].message_type, MessageType::Announce);
+        assert_eq!(grants[0].duration_field, 60);
+        assert!(grants[0].renewal_invited);
+        assert_eq!(grants[1].message_type, MessageType::Sync);
+        assert_eq!(grants[1].log_inter_message_period, -3);
+        // Capped at the maximum duration
+        assert_eq!(grants[1].duration_field, 300);
+        assert_eq!(grants[2].message_type, MessageType::DelayResp);
+        assert_eq!(grants[2].duration_field, 60);
+        drop(actions);
+
+        // Both announce and sync are due immediately
+        let mut actions = port.handle_unicast_timer();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetUnicastTimer { duration }) if duration.is_zero()
+        ));
+        let Some(PortAction::SendGeneralUnicast { data, address }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(address, GRANTEE_ADDRESS);
+        let message = Message::deserialize(data).unwrap();
+        assert!(message.header.unicast_flag);
+        assert!(matches!(message.body, MessageBody::Announce(_)));
+        drop(actions);
+
+        let mut actions = port.handle_unicast_timer();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetUnicastTimer { duration })
+                if duration == core::time::Duration::from_millis(125)
+        ));
+        let Some(PortAction::SendEventUnicast {
+            context,
+            data,
+            address,
+        }) = actions.next()
+        else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(address, GRANTEE_ADDRESS);
+        let message = Message::deserialize(data).unwrap();
+        assert!(message.header.unicast_flag);
+        assert!(matches!(message.body, MessageBody::Sync(_)));
+        drop(actions);
+
+        let mut actions = port.handle_send_timestamp(context, Time::from_secs(10));
+        let Some(PortAction::SendGeneralUnicast { data, address }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(address, GRANTEE_ADDRESS);
+        let message = Message::deserialize(data).unwrap();
+        assert!(matches!(message.body, MessageBody::FollowUp(_)));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        // Delay responses go to the grantee only
+        let mut actions = port.handle_delay_req(
+            Header {
+                source_port_identity: GRANTEE,
+                ..Header::new(1)
+            },
+            DelayReqMessage {
+                origin_timestamp: Default::default(),
+            },
+            Time::from_secs(10),
+            None,
+        );
+        let Some(PortAction::SendGeneralUnicast { data, address }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(address, GRANTEE_ADDRESS);
+        let message = Message::deserialize(data).unwrap();
+        assert!(message.header.unicast_flag);
+        assert!(matches!(message.body, MessageBody::DelayResp(_)));
+    }
+
+    #[test]
+    fn test_unicast_deny() {
+        let state = setup_test_state();
+        let mut port = setup_test_port_custom_clock(&state, SettableClock(Time::from_secs(10)));
+        port.set_forced_port_state(PortState::Master);
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = signaling_message(
+            GRANTEE,
+            &[(
+                TlvType::RequestUnicastTransmission,
+                &request(MessageType::Announce, 1, 60),
+            )],
+            &mut buffer,
+        );
+
+        // Negotiation is disabled by default
+        let mut actions =
+            port.handle_general_receive_from(&buffer[..length], Some(GRANTEE_ADDRESS));
+        let Some(PortAction::SendGeneralUnicast { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+        assert_eq!(parse_grants(data)[0].duration_field, 0);
+        drop(actions);
+
+        // Without a source address there is nobody to answer to
+        let mut actions = port.handle_general_receive(&buffer[..length]);
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        port.config.unicast_grantor = Some(GRANTOR_CONFIG);
+
+        // Too frequent
+        let length = signaling_message(
+            GRANTEE,
+            &[(
+                TlvType::RequestUnicastTransmission,
+                &request(MessageType::Sync, -4, 60),
+            )],
+            &mut buffer,
+        );
+        let mut actions =
+            port.handle_general_receive_from(&buffer[..length], Some(GRANTEE_ADDRESS));
+        let Some(PortAction::SendGeneralUnicast { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(parse_grants(data)[0].duration_field, 0);
+        drop(actions);
+
+        // Peer delay responses are never granted
+        let length = signaling_message(
+            GRANTEE,
+            &[(
+                TlvType::RequestUnicastTransmission,
+                &request(MessageType::PDelayResp, 0, 60),
+            )],
+            &mut buffer,
+        );
+        let mut actions =
+            port.handle_general_receive_from(&buffer[..length], Some(GRANTEE_ADDRESS));
+        let Some(PortAction::SendGeneralUnicast { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(parse_grants(data)[0].duration_field, 0);
+        drop(actions);
+
+        // Fill up the single grantee slot
+        let length = signaling_message(
+            GRANTEE,
+            &[(
+                TlvType::RequestUnicastTransmission,
+                &request(MessageType::Announce, 1, 60),
+            )],
+            &mut buffer,
+        );
+        let mut actions =
+            port.handle_general_receive_from(&buffer[..length], Some(GRANTEE_ADDRESS));
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetUnicastTimer { .. })
+        ));
+        drop(actions);
+
+        let other = PortIdentity {
+            clock_identity: ClockIdentity([10; 8]),
+            port_number: 1,
+        };
+        let length = signaling_message(
+            other,
+            &[(
+                TlvType::RequestUnicastTransmission,
+                &request(MessageType::Announce, 1, 60),
+            )],
+            &mut buffer,
+        );
+        let mut actions = port.handle_general_receive_from(
+            &buffer[..length],
+            Some(PortAddress::Ipv4([192, 0, 2, 2])),
+        );
+        let Some(PortAction::SendGeneralUnicast { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        let message = Message::deserialize(data).unwrap();
+        let tlv = message.suffix.tlv().next().unwrap();
+        assert_eq!(
+            GrantUnicastTransmissionTlv::deserialize(&tlv.value)
+                .unwrap()
+                .duration_field,
+            0
+        );
+    }
+
+    #[test]
+    fn test_unicast_expiry_and_cancel() {
+        let state = setup_test_state();
+        let mut port = setup_test_port_custom_clock(&state, SettableClock(Time::from_secs(10)));
+        port.config.unicast_grantor = Some(GRANTOR_CONFIG);
+        port.set_forced_port_state(PortState::Master);
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = signaling_message(
+            GRANTEE,
+            &[
+                (
+                    TlvType::RequestUnicastTransmission,
+                    &request(MessageType::Announce, 1, 60),
+                ),
+                (
+                    TlvType::RequestUnicastTransmission,
+                    &request(MessageType::Sync, 0, 30),
+                ),
+            ],
+            &mut buffer,
+        );
+        let actions = port.handle_general_receive_from(&buffer[..length], Some(GRANTEE_ADDRESS));
+        drop(actions);
+        assert_eq!(port.unicast_grants.len(), 2);
+
+        // After the sync grant expired only announces are sent
+        port.clock.0 = Time::from_secs(40);
+        let mut actions = port.handle_unicast_timer();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetUnicastTimer { .. })
+        ));
+        let Some(PortAction::SendGeneralUnicast { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        let message = Message::deserialize(data).unwrap();
+        assert!(matches!(message.body, MessageBody::Announce(_)));
+        drop(actions);
+        assert_eq!(port.unicast_grants.len(), 1);
+
+        let mut cancel = [0; 2];
+        CancelUnicastTransmissionTlv {
+            message_type: MessageType::Announce,
+        }
+        .serialize(&mut cancel)
+        .unwrap();
+        let length = signaling_message(
+            GRANTEE,
+            &[(TlvType::CancelUnicastTransmission, &cancel)],
+            &mut buffer,
+        );
+        let mut actions =
+            port.handle_general_receive_from(&buffer[..length], Some(GRANTEE_ADDRESS));
+        let Some(PortAction::SendGeneralUnicast { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+        let message = Message::deserialize(data).unwrap();
+        let tlv = message.suffix.tlv().next().unwrap();
+        assert_eq!(tlv.tlv_type, TlvType::AcknowledgeCancelUnicastTransmission);
+        drop(actions);
+        assert!(port.unicast_grants.is_empty());
+
+        // Nothing left to do
+        let mut actions = port.handle_unicast_timer();
+        assert!(actions.next().is_none());
+    }
+
+    #[test]
+    fn test_unicast_request() {
+        let state = setup_test_state();
+        let mut port = setup_test_port_custom_clock(&state, SettableClock(Time::from_secs(10)));
+        port.unicast_masters
+            .push(UnicastMaster::new(MASTER_ADDRESS));
+
+        // Announce messages are requested from every master
+        let mut actions = port.handle_unicast_timer();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetUnicastTimer { duration })
+                if duration == core::time::Duration::from_secs(2)
+        ));
+        let Some(PortAction::SendGeneralUnicast { data, address }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(address, MASTER_ADDRESS);
+        let (target, requests) = parse_requests(data);
+        assert_eq!(target.port_number, 0xffff);
+        assert_eq!(
+            requests.as_slice(),
+            [RequestUnicastTransmissionTlv {
+                message_type: MessageType::Announce,
+                log_inter_message_period: 1,
+                duration_field: 300,
+            }]
+        );
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        // Not answered, so retried after the query interval
+        let mut actions = port.handle_unicast_timer();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetUnicastTimer { .. })
+        ));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = signaling_message(
+            MASTER,
+            &[(
+                TlvType::GrantUnicastTransmission,
+                &grant(MessageType::Announce, 1, 300),
+            )],
+            &mut buffer,
+        );
+        let mut actions = port.handle_general_receive_from(&buffer[..length], Some(MASTER_ADDRESS));
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetUnicastTimer { duration }) if duration.is_zero()
+        ));
+        assert!(actions.next().is_none());
+        drop(actions);
+        assert_eq!(port.unicast_masters[0].port_identity, Some(MASTER));
+
+        // Renewed halfway through the grant
+        let mut actions = port.handle_unicast_timer();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetUnicastTimer { duration })
+                if duration == core::time::Duration::from_secs(150)
+        ));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        // Once the master is selected, sync and delay responses are requested
+        port.set_forced_port_state(PortState::Slave(SlaveState::new(MASTER)));
+        let mut actions = port.handle_unicast_timer();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetUnicastTimer { .. })
+        ));
+        let Some(PortAction::SendGeneralUnicast { data, address }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(address, MASTER_ADDRESS);
+        let (target, requests) = parse_requests(data);
+        assert_eq!(target, MASTER);
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].message_type, MessageType::Sync);
+        assert_eq!(requests[0].log_inter_message_period, 0);
+        assert_eq!(requests[1].message_type, MessageType::DelayResp);
+        assert_eq!(requests[1].log_inter_message_period, 1);
+        drop(actions);
+
+        // Delay requests are sent unicast only once they are granted
+        let mut actions = port.handle_delay_request_timer();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetDelayRequestTimer { .. })
+        ));
+        assert!(matches!(actions.next(), Some(PortAction::SendEvent { .. })));
+        drop(actions);
+
+        let length = signaling_message(
+            MASTER,
+            &[
+                (
+                    TlvType::GrantUnicastTransmission,
+                    &grant(MessageType::Sync, 0, 0),
+                ),
+                (
+                    TlvType::GrantUnicastTransmission,
+                    &grant(MessageType::DelayResp, 1, 300),
+                ),
+            ],
+            &mut buffer,
+        );
+        let actions = port.handle_general_receive_from(&buffer[..length], Some(MASTER_ADDRESS));
+        drop(actions);
+        assert_eq!(port.unicast_masters[0].sync.expires_at, None);
+        assert_eq!(
+            port.unicast_masters[0].sync.next_request_at,
+            Time::from_secs(12)
+        );
+
+        let mut actions = port.handle_delay_request_timer();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetDelayRequestTimer { .. })
+        ));
+        let Some(PortAction::SendEventUnicast { data, address, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(address, MASTER_ADDRESS);
+        let message = Message::deserialize(data).unwrap();
+        assert!(message.header.unicast_flag);
+        assert!(matches!(message.body, MessageBody::DelayReq(_)));
+        drop(actions);
+
+        // Expired grants are requested again
+        port.clock.0 = Time::from_secs(400);
+        let mut actions = port.handle_unicast_timer();
+        assert!(matches!(
+            actions.next(),
+            Some(PortAction::ResetUnicastTimer { .. })
+        ));
+        let Some(PortAction::SendGeneralUnicast { data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(parse_requests(data).1.len(), 3);
+        drop(actions);
+        assert!(port.unicast_delay_req_address().is_none());
+    }
+}
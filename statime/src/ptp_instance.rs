@@ -4,6 +4,7 @@ use core::{
     sync::atomic::{AtomicI8, Ordering},
 };
 
+use arrayvec::ArrayVec;
 use rand::Rng;
 
 #[allow(unused_imports)]
@@ -11,15 +12,20 @@ use crate::float_polyfill::FloatPolyfill;
 use crate::{
     bmc::{acceptable_master::AcceptableMasterList, bmca::Bmca},
     clock::Clock,
-    config::{ClockQuality, InstanceConfig, PortConfig},
+    config::{
+        AlternateTimescale, ClockQuality, DesiredState, InstanceConfig, ManagementSetPolicy,
+        PortConfig, PowerProfileConfig, SynchronizationMetadata, SynchronizationMetadataConfig,
+    },
     datastructures::{
-        common::PortIdentity,
+        common::{FollowUpInformation, PortIdentity, PowerProfile},
         datasets::{
-            InternalCurrentDS, InternalDefaultDS, InternalParentDS, PathTraceDS, TimePropertiesDS,
+            AlternateTimescaleOffsetsDS, InternalCurrentDS, InternalDefaultDS, InternalParentDS,
+            PathTraceDS, TimePropertiesDS, MAX_ALTERNATE_TIMESCALES,
         },
     },
     filters::{Filter, FilterEstimate},
-    observability::{current::CurrentDS, default::DefaultDS, parent::ParentDS},
+    holdover::{Holdover, HoldoverConfig},
+    observability::{current::CurrentDS, default::DefaultDS, parent::ParentDS, HoldoverState},
     port::{InBmca, Port},
     time::Duration,
 };
@@ -58,7 +64,7 @@ use crate::{
 /// # let rng: rand::rngs::mock::StepRng = unimplemented!();
 /// #
 /// use statime::PtpInstance;
-/// use statime::config::{AcceptAnyMaster, ClockIdentity, ClockQuality, InstanceConfig, TimePropertiesDS, TimeSource};
+/// use statime::config::{AcceptAnyMaster, ClockIdentity, ClockQuality, DatasetComparison, InstanceConfig, ManagementSetPolicy, TimePropertiesDS, TimeSource};
 /// use statime::filters::BasicFilter;
 ///
 /// let instance_config = InstanceConfig {
@@ -69,7 +75,10 @@ use crate::{
 ///     slave_only: false,
 ///     sdo_id: Default::default(),
 ///     path_trace: false,
+///     external_port_configuration: false,
 ///     clock_quality: ClockQuality::default(),
+///     management_set_policy: ManagementSetPolicy::default(),
+///     dataset_comparison: DatasetComparison::Ieee1588,
 /// };
 /// let time_properties_ds = TimePropertiesDS::new_arbitrary_time(false, false, TimeSource::InternalOscillator);
 ///
@@ -101,6 +110,28 @@ pub struct PtpInstanceState {
     pub(crate) parent_ds: InternalParentDS,
     pub(crate) path_trace_ds: PathTraceDS,
     pub(crate) time_properties_ds: TimePropertiesDS,
+    /// Time properties distributed while this instance is the grandmaster
+    pub(crate) local_time_properties_ds: TimePropertiesDS,
+    pub(crate) management_set_policy: ManagementSetPolicy,
+    /// Alternate timescales distributed when this instance is grandmaster
+    pub(crate) alternate_timescales: ArrayVec<AlternateTimescale, MAX_ALTERNATE_TIMESCALES>,
+    /// Alternate timescales received from the grandmaster
+    pub(crate) alternate_timescale_offsets_ds: AlternateTimescaleOffsetsDS,
+    /// Clock class degradation when the time reference is lost
+    pub(crate) holdover: Holdover,
+    /// IEEE C37.238 power profile TLV sent in announce messages
+    pub(crate) power_profile: Option<PowerProfileConfig>,
+    /// IEEE C37.238 power profile TLV received from the parent
+    pub(crate) received_power_profile: Option<PowerProfile>,
+    /// SMPTE ST 2059-2 synchronization metadata sent while this instance is
+    /// grandmaster
+    pub(crate) synchronization_metadata: Option<SynchronizationMetadataConfig>,
+    /// SMPTE ST 2059-2 synchronization metadata last sent as grandmaster, or
+    /// received from the parent
+    pub(crate) current_synchronization_metadata: Option<SynchronizationMetadata>,
+    /// IEEE 802.1AS Follow_Up information received on the slave port,
+    /// combined with the neighbor rate ratio of that port
+    pub(crate) received_follow_up_information: Option<FollowUpInformation>,
 }
 
 impl PtpInstanceState {
@@ -111,10 +142,50 @@ impl PtpInstanceState {
     ) {
         debug_assert_eq!(self.default_ds.number_ports as usize, ports.len());
 
+        // Only a master with a traceable time is a reference worth holding over
+        let upstream = if self.time_properties_ds.time_traceable {
+            ports
+                .iter()
+                .find_map(|port| port.slave_frequency_uncertainty())
+        } else {
+            None
+        };
+        self.holdover
+            .step(upstream, bmca_interval, &mut self.default_ds);
+
         for port in ports.iter_mut() {
+            port.rank_alternate_master(&self.default_ds);
             port.calculate_best_local_announce_message()
         }
 
+        if self.default_ds.external_port_configuration {
+            self.apply_desired_states(ports);
+        } else {
+            self.apply_recommended_states(ports);
+        }
+
+        if self.parent_ds.grandmaster_identity == self.default_ds.clock_identity {
+            self.time_properties_ds = self.local_time_properties_ds;
+            self.holdover
+                .apply_traceability(&mut self.time_properties_ds);
+        }
+
+        // And update announce message ages
+        for port in ports.iter_mut() {
+            port.step_announce_age(bmca_interval);
+        }
+    }
+
+    fn apply_recommended_states<
+        A: AcceptableMasterList,
+        C: Clock,
+        F: Filter,
+        R: Rng,
+        S: PtpInstanceStateMutex,
+    >(
+        &mut self,
+        ports: &mut [&mut Port<'_, InBmca, A, R, C, F, S>],
+    ) {
         let ebest = Bmca::<()>::find_best_announce_message(
             ports
                 .iter()
@@ -122,6 +193,10 @@ impl PtpInstanceState {
         );
 
         for port in ports.iter_mut() {
+            if !port.is_enabled() {
+                continue;
+            }
+
             let recommended_state = Bmca::<()>::calculate_recommended_state(
                 &self.default_ds,
                 ebest,
@@ -145,10 +220,40 @@ impl PtpInstanceState {
                 );
             }
         }
+    }
 
-        // And update announce message ages
-        for port in ports.iter_mut() {
-            port.step_announce_age(bmca_interval);
+    fn apply_desired_states<
+        A: AcceptableMasterList,
+        C: Clock,
+        F: Filter,
+        R: Rng,
+        S: PtpInstanceStateMutex,
+    >(
+        &mut self,
+        ports: &mut [&mut Port<'_, InBmca, A, R, C, F, S>],
+    ) {
+        // Only one port steers the clock, the first enabled port desired as
+        // slave. The data sets follow its master, if there is one.
+        let slave_port = ports.iter().position(|port| {
+            port.is_enabled() && port.desired_state() == Some(DesiredState::Slave)
+        });
+        let slave_message =
+            slave_port.and_then(|index| ports[index].best_local_announce_message_for_bmca());
+
+        for (index, port) in ports.iter_mut().enumerate() {
+            if !port.is_enabled() {
+                continue;
+            }
+
+            port.apply_desired_state(
+                slave_message,
+                Some(index) == slave_port,
+                &mut self.path_trace_ds,
+                &mut self.time_properties_ds,
+                &mut self.current_ds,
+                &mut self.parent_ds,
+                &self.default_ds,
+            );
         }
     }
 }
@@ -156,6 +261,9 @@ impl PtpInstanceState {
 impl<F, S: PtpInstanceStateMutex> PtpInstance<F, S> {
     /// Construct a new [`PtpInstance`] with the given config and time
     /// properties
+    ///
+    /// The time properties are distributed while this instance is the
+    /// grandmaster, see also [`PtpInstance::set_time_properties`].
     pub fn new(config: InstanceConfig, time_properties_ds: TimePropertiesDS) -> Self {
         let default_ds = InternalDefaultDS::new(config);
 
@@ -166,6 +274,16 @@ impl<F, S: PtpInstanceStateMutex> PtpInstance<F, S> {
                 parent_ds: InternalParentDS::new(default_ds),
                 path_trace_ds: PathTraceDS::new(config.path_trace),
                 time_properties_ds,
+                local_time_properties_ds: time_properties_ds,
+                management_set_policy: config.management_set_policy,
+                alternate_timescales: Default::default(),
+                alternate_timescale_offsets_ds: Default::default(),
+                holdover: Holdover::new(),
+                power_profile: None,
+                received_power_profile: None,
+                synchronization_metadata: None,
+                current_synchronization_metadata: None,
+                received_follow_up_information: None,
             }),
             log_bmca_interval: AtomicI8::new(i8::MAX),
             _filter: PhantomData,
@@ -197,6 +315,57 @@ impl<F, S: PtpInstanceStateMutex> PtpInstance<F, S> {
     pub fn path_trace_ds(&self) -> PathTraceDS {
         self.state.with_ref(|s| s.path_trace_ds.clone())
     }
+
+    /// Return IEEE-1588 alternateTimescaleOffsetsDS for introspection
+    ///
+    /// When this instance is the grandmaster these are the alternate
+    /// timescales set with [`PtpInstance::set_alternate_timescales`],
+    /// otherwise those last received from the grandmaster.
+    pub fn alternate_timescale_offsets_ds(&self) -> AlternateTimescaleOffsetsDS {
+        self.state.with_ref(|s| {
+            if s.parent_ds.grandmaster_identity == s.default_ds.clock_identity {
+                AlternateTimescaleOffsetsDS {
+                    list: s.alternate_timescales.clone(),
+                }
+            } else {
+                s.alternate_timescale_offsets_ds.clone()
+            }
+        })
+    }
+
+    /// Return the state of the holdover state machine, or `None` when it is
+    /// not enabled with [`PtpInstance::set_holdover`]
+    pub fn holdover_state(&self) -> Option<HoldoverState> {
+        self.state.with_ref(|s| s.holdover.state())
+    }
+
+    /// Return the IEEE C37.238 power profile information of the time of this
+    /// instance
+    ///
+    /// When this instance is the grandmaster this is the information set with
+    /// [`PtpInstance::set_power_profile`], otherwise that last received from
+    /// the parent, without the time inaccuracy added by this instance.
+    pub fn power_profile(&self) -> Option<PowerProfile> {
+        self.state.with_ref(|s| {
+            if s.parent_ds.grandmaster_identity == s.default_ds.clock_identity {
+                s.power_profile.map(|config| PowerProfile {
+                    grandmaster_id: config.grandmaster_id,
+                    total_time_inaccuracy: config.time_inaccuracy,
+                })
+            } else {
+                s.received_power_profile
+            }
+        })
+    }
+
+    /// Return the SMPTE ST 2059-2 synchronization metadata of the time of this
+    /// instance
+    ///
+    /// When this instance is the grandmaster this is the metadata last sent
+    /// by its master ports, otherwise that last received from the parent.
+    pub fn synchronization_metadata(&self) -> Option<SynchronizationMetadata> {
+        self.state.with_ref(|s| s.current_synchronization_metadata)
+    }
 }
 
 impl<F: Filter, S: PtpInstanceStateMutex> PtpInstance<F, S> {
@@ -218,6 +387,10 @@ impl<F: Filter, S: PtpInstanceStateMutex> PtpInstance<F, S> {
         self.log_bmca_interval
             .fetch_min(config.announce_interval.as_log_2(), Ordering::Relaxed);
         let port_identity = self.state.with_mut(|state| {
+            // Unicast sync messages are always two-step
+            if !config.one_step || config.unicast_grantor.is_some() {
+                state.default_ds.two_step_flag = true;
+            }
             state.default_ds.number_ports += 1;
             PortIdentity {
                 clock_identity: state.default_ds.clock_identity,
@@ -272,6 +445,26 @@ impl<F: Filter, S: PtpInstanceStateMutex> PtpInstance<F, S> {
         });
     }
 
+    /// Set the priority1 of the instance
+    ///
+    /// The new value is used in all subsequent announce messages and BMCA
+    /// decisions.
+    pub fn set_priority_1(&self, priority_1: u8) {
+        self.state.with_mut(|state| {
+            state.default_ds.priority_1 = priority_1;
+        });
+    }
+
+    /// Set the priority2 of the instance
+    ///
+    /// The new value is used in all subsequent announce messages and BMCA
+    /// decisions.
+    pub fn set_priority_2(&self, priority_2: u8) {
+        self.state.with_mut(|state| {
+            state.default_ds.priority_2 = priority_2;
+        });
+    }
+
     /// Set whether this instance is allowed to become a master.
     ///
     /// This allows toggling `slave_only` after the instance has been created.
@@ -283,6 +476,115 @@ impl<F: Filter, S: PtpInstanceStateMutex> PtpInstance<F, S> {
             state.default_ds.slave_only = slave_only;
         })
     }
+
+    /// Set whether the port states are configured externally
+    ///
+    /// When enabled, subsequent BMCA runs move every port to the state set
+    /// with [`Port::set_desired_state`] instead of the state recommended by
+    /// the best master clock algorithm (*IEEE1588-2019 section 17.6*).
+    pub fn set_external_port_configuration(&self, external_port_configuration: bool) {
+        self.state.with_mut(|state| {
+            state.default_ds.external_port_configuration = external_port_configuration;
+        })
+    }
+
+    /// Set the alternate timescales distributed by this instance
+    ///
+    /// These are included as ALTERNATE_TIME_OFFSET_INDICATOR TLVs in the
+    /// announce messages sent while this instance is the grandmaster
+    /// (*IEEE1588-2019 section 16.3*).
+    ///
+    /// # Panics
+    ///
+    /// Panics when more than [`MAX_ALTERNATE_TIMESCALES`] timescales are
+    /// given.
+    pub fn set_alternate_timescales(&self, alternate_timescales: &[AlternateTimescale]) {
+        assert!(alternate_timescales.len() <= MAX_ALTERNATE_TIMESCALES);
+        self.state.with_mut(|state| {
+            state.alternate_timescales = alternate_timescales.iter().copied().collect();
+        })
+    }
+
+    /// Set the time properties distributed while this instance is the
+    /// grandmaster
+    ///
+    /// This replaces the time properties given to [`PtpInstance::new`], for
+    /// example when the UTC offset of the time source becomes known. When the
+    /// holdover state machine is enabled, it still decides the traceability,
+    /// see [`PtpInstance::set_holdover`].
+    pub fn set_time_properties(&self, time_properties_ds: TimePropertiesDS) {
+        self.state.with_mut(|state| {
+            state.local_time_properties_ds = time_properties_ds;
+            if state.parent_ds.grandmaster_identity == state.default_ds.clock_identity {
+                state.time_properties_ds = time_properties_ds;
+                state
+                    .holdover
+                    .apply_traceability(&mut state.time_properties_ds);
+            }
+        })
+    }
+
+    /// Enable or disable the holdover state machine
+    ///
+    /// While enabled, every BMCA run sets the clock class of the instance to
+    /// that of its holdover state, overriding
+    /// [`PtpInstance::set_clock_quality`]. The instance is locked while an
+    /// external reference is reported with
+    /// [`PtpInstance::set_external_reference`], or while one of its ports is
+    /// the slave of a master with a traceable time. Once the external
+    /// reference is lost, the instance stays in holdover within its
+    /// specification until the time error estimated from the frequency
+    /// uncertainty it reported exceeds [`HoldoverConfig::budget`].
+    /// Losing the master runs the instance free again. While the instance is
+    /// the grandmaster, its time is traceable when locked to an external
+    /// reference or within the holdover specification.
+    ///
+    /// See *IEEE1588-2019 section 7.6.2.5* for the clock classes.
+    pub fn set_holdover(&self, config: Option<HoldoverConfig>) {
+        self.state
+            .with_mut(|state| state.holdover.set_config(config))
+    }
+
+    /// Enable the IEEE C37.238 power profile TLV, or disable it with `None`
+    ///
+    /// While this instance is the grandmaster, its announce messages carry an
+    /// IEEE_C37_238 TLV with the configured grandmaster identifier and time
+    /// inaccuracy. Otherwise the TLV received from the parent is passed on by
+    /// the master ports, with the time inaccuracy of this instance added.
+    /// Without the power profile, received IEEE_C37_238 TLVs are not passed
+    /// on.
+    ///
+    /// See *IEEE C37.238-2017 section 5.12*.
+    pub fn set_power_profile(&self, config: Option<PowerProfileConfig>) {
+        self.state.with_mut(|state| state.power_profile = config)
+    }
+
+    /// Set the SMPTE ST 2059-2 synchronization metadata, or stop sending it
+    /// with `None`
+    ///
+    /// While this instance is the grandmaster, its master ports periodically
+    /// send the metadata in a management message, at the rate of their
+    /// announce messages, with the times of the daily jams and the pending
+    /// jump updated to the time of sending. Otherwise the metadata received
+    /// from the parent is passed on by the master ports.
+    ///
+    /// See *SMPTE ST 2059-2:2021 section 9*.
+    pub fn set_synchronization_metadata(&self, config: Option<SynchronizationMetadataConfig>) {
+        self.state
+            .with_mut(|state| state.synchronization_metadata = config)
+    }
+
+    /// Report the state of the external time reference of the instance, such
+    /// as a GNSS receiver
+    ///
+    /// `Some` with the uncertainty of the frequency of the clock relative to
+    /// the reference, as a fraction (s/s), while locked to it, `None` when the
+    /// reference is lost. The holdover state follows during the next BMCA run,
+    /// see [`PtpInstance::set_holdover`].
+    pub fn set_external_reference(&self, frequency_uncertainty: Option<f64>) {
+        self.state
+            .with_mut(|state| state.holdover.set_external_reference(frequency_uncertainty))
+    }
 }
 
 /// A mutex over a [`PtpInstanceState`]
@@ -332,13 +634,16 @@ impl PtpInstanceStateMutex for std::sync::RwLock<PtpInstanceState> {
 
 #[cfg(test)]
 mod tests {
+    use rand::rngs::mock::StepRng;
+
     use super::*;
     use crate::{
         config::{
-            ClockAccuracy, ClockIdentity, ClockQuality, InstanceConfig, TimePropertiesDS,
-            TimeSource,
+            ClockAccuracy, ClockIdentity, ClockQuality, DelayMechanism, InstanceConfig,
+            PtpMinorVersion, TimePropertiesDS, TimeSource,
         },
         filters::BasicFilter,
+        time::Interval,
     };
 
     fn create_test_instance() -> PtpInstance<BasicFilter> {
@@ -350,7 +655,10 @@ mod tests {
             slave_only: false,
             sdo_id: Default::default(),
             path_trace: false,
+            external_port_configuration: false,
             clock_quality: ClockQuality::default(),
+            management_set_policy: Default::default(),
+            dataset_comparison: Default::default(),
         };
         let time_properties_ds =
             TimePropertiesDS::new_arbitrary_time(false, false, TimeSource::InternalOscillator);
@@ -386,6 +694,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_two_step_flag() {
+        let instance = create_test_instance();
+        assert!(!instance.default_ds().two_step_flag);
+
+        let port_config = |one_step| PortConfig {
+            acceptable_master_list: (),
+            delay_mechanism: DelayMechanism::E2E {
+                interval: Interval::ONE_SECOND,
+            },
+            announce_interval: Interval::ONE_SECOND,
+            announce_receipt_timeout: 3,
+            sync_interval: Interval::ONE_SECOND,
+            master_only: false,
+            delay_asymmetry: Duration::ZERO,
+            minor_ptp_version: PtpMinorVersion::One,
+            unicast_grantor: None,
+            unicast_master_table: Default::default(),
+            one_step,
+            authentication: None,
+            slave_monitoring: None,
+            gptp: None,
+            desired_state: None,
+            number_alternate_masters: 0,
+            local_priority: 128,
+            link_local: false,
+            hybrid_e2e: false,
+            unicast_only: false,
+        };
+
+        // Only one-step ports, so the instance is a one-step clock
+        instance.add_port(port_config(true), 0.25, (), StepRng::new(2, 1));
+        assert!(!instance.default_ds().two_step_flag);
+
+        instance.add_port(port_config(false), 0.25, (), StepRng::new(2, 1));
+        assert!(instance.default_ds().two_step_flag);
+    }
+
     #[test]
     fn test_set_slave_only() {
         let instance = create_test_instance();
@@ -396,4 +742,42 @@ mod tests {
         instance.set_slave_only(true);
         assert!(instance.default_ds().slave_only);
     }
+
+    #[test]
+    fn test_set_priorities() {
+        let instance = create_test_instance();
+
+        instance.set_priority_1(10);
+        instance.set_priority_2(20);
+
+        let default_ds = instance.default_ds();
+        assert_eq!(default_ds.priority_1, 10);
+        assert_eq!(default_ds.priority_2, 20);
+    }
+
+    #[test]
+    fn test_set_time_properties() {
+        let instance = create_test_instance();
+
+        // A new instance is its own grandmaster, so this is distributed directly
+        let time_properties_ds = TimePropertiesDS::new_ptp_time(
+            Some(37),
+            crate::config::LeapIndicator::NoLeap,
+            true,
+            true,
+            TimeSource::Gnss,
+        );
+        instance.set_time_properties(time_properties_ds);
+        assert_eq!(instance.time_properties_ds(), time_properties_ds);
+
+        // With holdover enabled, traceability needs a reference
+        instance.set_holdover(Some(HoldoverConfig {
+            classes: Default::default(),
+            budget: Duration::from_micros(1),
+        }));
+        instance.set_time_properties(time_properties_ds);
+        assert!(!instance.time_properties_ds().time_traceable);
+        assert_eq!(instance.time_properties_ds().current_utc_offset, Some(37));
+        assert_eq!(instance.holdover_state(), Some(HoldoverState::FreeRunning));
+    }
 }
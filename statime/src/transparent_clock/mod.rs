@@ -0,0 +1,1023 @@
+//! Transparent clocks, see *IEEE1588-2019 section 10*
+//!
+//! A [`TransparentClock`] forwards all PTP messages received on one of its
+//! ports to all of its other ports. The time the event messages spend inside
+//! the transparent clock (their residence time) is added to the correction
+//! field of the messages, so that ordinary and boundary clocks downstream do
+//! not see the variation in this time as noise.
+//!
+//! This transparent clock is two-step: the residence time of a sync is added to
+//! the matching follow up message, and one-step syncs are converted to two-step
+//! by sending a follow up for them.
+//!
+//! An end-to-end transparent clock ([`TransparentClock::new`], *section 10.2*)
+//! also forwards delay requests. Their residence time is added to the matching
+//! delay response, as allowed by *section 10.2.2.3*. Peer delay messages are
+//! link local, so they are not forwarded.
+//!
+//! A peer-to-peer transparent clock ([`TransparentClock::new_p2p`], *section
+//! 10.3*) measures the delay of the link on each of its ports using peer delay
+//! messages, and answers the peer delay requests of its neighbours. The delay
+//! of the link a sync came in on is added to its correction as well, so
+//! downstream clocks only need to measure the delay of their own link. Delay
+//! requests and responses are not used with the peer delay mechanism, and are
+//! dropped.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # mod system {
+//! #     pub fn receive() -> (usize, Vec<u8>, statime::time::Time) { unimplemented!() }
+//! #     pub fn send_event(port: usize, data: &[u8], link_local: bool) -> statime::time::Time { unimplemented!() }
+//! #     pub fn send_general(port: usize, data: &[u8], link_local: bool) { unimplemented!() }
+//! #     pub fn reset_timer(port: usize, duration: core::time::Duration) { unimplemented!() }
+//! # }
+//! use statime::transparent_clock::{TransparentClock, TransparentClockAction};
+//!
+//! let mut transparent_clock = TransparentClock::new(2);
+//!
+//! loop {
+//!     let (port, data, timestamp) = system::receive();
+//!
+//!     let mut send_timestamps = vec![];
+//!     for action in transparent_clock.handle_event_receive(port, &data, timestamp) {
+//!         match action {
+//!             TransparentClockAction::SendEvent {
+//!                 port,
+//!                 context,
+//!                 data,
+//!                 link_local,
+//!             } => {
+//!                 send_timestamps.push((context, system::send_event(port, data, link_local)));
+//!             }
+//!             TransparentClockAction::SendGeneral {
+//!                 port,
+//!                 data,
+//!                 link_local,
+//!             } => {
+//!                 system::send_general(port, data, link_local);
+//!             }
+//!             TransparentClockAction::ResetPeerDelayTimer { port, duration } => {
+//!                 system::reset_timer(port, duration);
+//!             }
+//!         }
+//!     }
+//!
+//!     for (context, timestamp) in send_timestamps {
+//!         for action in transparent_clock.handle_send_timestamp(context, timestamp) {
+//!             if let TransparentClockAction::SendGeneral {
+//!                 port,
+//!                 data,
+//!                 link_local,
+//!             } = action
+//!             {
+//!                 system::send_general(port, data, link_local);
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use arrayvec::ArrayVec;
+
+use self::peer_delay::PeerDelayPort;
+use crate::{
+    datastructures::{
+        common::{ClockIdentity, PortIdentity, TimeInterval},
+        messages::{
+            DelayRespMessage, FollowUpMessage, Header, Message, MessageBody, MessageType,
+            SyncMessage, MAX_DATA_LEN,
+        },
+        WireFormat,
+    },
+    time::{Duration, Interval, Time},
+};
+
+mod peer_delay;
+
+/// Maximum number of ports of a [`TransparentClock`]
+pub const MAX_TRANSPARENT_CLOCK_PORTS: usize = 16;
+
+// Number of syncs and delay requests for which the residence time is kept
+// until their follow up or delay response passes.
+const MAX_PENDING_MESSAGES: usize = 32;
+
+// Number of forwarded messages remembered to recognize copies of them that
+// come back in, for example through multicast loopback.
+const FORWARDED_HISTORY: usize = 64;
+
+// Follow up messages get a different correction per port, so the buffer
+// needs to fit a copy for each port.
+const PACKET_BUFFER_LEN: usize = 4 * MAX_DATA_LEN;
+
+const HEADER_SIZE: usize = 34;
+const CORRECTION_FIELD: core::ops::Range<usize> = 8..16;
+const TWO_STEP_FLAG: (usize, u8) = (6, 1 << 1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MessageKey {
+    message_type: MessageType,
+    domain_number: u8,
+    source_port_identity: PortIdentity,
+    sequence_id: u16,
+}
+
+impl MessageKey {
+    fn new(header: &Header, message_type: MessageType) -> Self {
+        Self {
+            message_type,
+            domain_number: header.domain_number,
+            source_port_identity: header.source_port_identity,
+            sequence_id: header.sequence_id,
+        }
+    }
+}
+
+/// A forwarded sync or delay request of which we need the residence time
+#[derive(Debug)]
+struct PendingMessage {
+    key: MessageKey,
+    ingress_time: Time,
+    // Added to the correction besides the residence time, the link delay of
+    // the ingress port for peer-to-peer transparent clocks
+    ingress_correction: Duration,
+    residence_times: [Option<Duration>; MAX_TRANSPARENT_CLOCK_PORTS],
+    // Set for one-step syncs, which get their follow up from us
+    one_step_sync: Option<(Header, SyncMessage)>,
+}
+
+/// Identification of a sent event message
+///
+/// The caller receives this from a [`TransparentClockAction::SendEvent`] and
+/// should return it to the [`TransparentClock`] with
+/// [`TransparentClock::handle_send_timestamp`] once the transmit timestamp of
+/// that packet is known.
+#[derive(Debug)]
+pub struct TransparentTimestampContext {
+    inner: TransparentTimestampContextInner,
+}
+
+#[derive(Debug)]
+enum TransparentTimestampContextInner {
+    Forwarded { key: MessageKey, port: usize },
+    PDelayReq { port: usize, id: u16 },
+    PDelayResp { port: usize, request_header: Header },
+}
+
+/// An action the [`TransparentClock`] needs the user to perform
+#[derive(Debug)]
+#[must_use]
+pub enum TransparentClockAction<'a> {
+    /// Send a time-critical packet on the given port
+    ///
+    /// Once the packet is sent and the transmit timestamp known the user
+    /// should return the given [`TransparentTimestampContext`] using
+    /// [`TransparentClock::handle_send_timestamp`].
+    SendEvent {
+        /// Index of the port to send on
+        port: usize,
+        /// Context to return with the transmit timestamp
+        context: TransparentTimestampContext,
+        /// The packet to send
+        data: &'a [u8],
+        /// Whether to send to the peer delay address
+        link_local: bool,
+    },
+    /// Send a general packet on the given port
+    ///
+    /// For a packet sent this way no timestamp needs to be captured.
+    SendGeneral {
+        /// Index of the port to send on
+        port: usize,
+        /// The packet to send
+        data: &'a [u8],
+        /// Whether to send to the peer delay address
+        link_local: bool,
+    },
+    /// Call [`TransparentClock::handle_peer_delay_timer`] for the given port
+    /// in `duration` from now
+    ResetPeerDelayTimer {
+        /// Index of the port the timer is for
+        port: usize,
+        /// Duration until the timer expires
+        duration: core::time::Duration,
+    },
+}
+
+/// An Iterator over [`TransparentClockAction`]s
+///
+/// These are returned by the [`TransparentClock`] when ever the library
+/// needs the user to perform actions to the system.
+#[derive(Debug)]
+#[must_use]
+pub struct TransparentClockActionIterator<'a> {
+    internal: arrayvec::IntoIter<TransparentClockAction<'a>, MAX_TRANSPARENT_CLOCK_PORTS>,
+}
+
+impl<'a> TransparentClockActionIterator<'a> {
+    fn from(list: ArrayVec<TransparentClockAction<'a>, MAX_TRANSPARENT_CLOCK_PORTS>) -> Self {
+        Self {
+            internal: list.into_iter(),
+        }
+    }
+
+    fn empty() -> Self {
+        Self::from(ArrayVec::new())
+    }
+}
+
+impl<'a> Iterator for TransparentClockActionIterator<'a> {
+    type Item = TransparentClockAction<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.internal.next()
+    }
+}
+
+/// A transparent clock
+///
+/// Ports are identified by their index, from 0 up to the number of ports of
+/// the transparent clock. Each message received on one port is forwarded to
+/// all other ports.
+///
+/// The residence time is measured using the receive and transmit timestamps
+/// provided by the user, so these all need to come from the same clock. That
+/// clock is not synchronized, which is fine as long as its frequency error
+/// times the residence time is small.
+#[derive(Debug)]
+pub struct TransparentClock {
+    number_of_ports: usize,
+    // Only for peer-to-peer transparent clocks
+    peer_delay: Option<(
+        ClockIdentity,
+        ArrayVec<PeerDelayPort, MAX_TRANSPARENT_CLOCK_PORTS>,
+    )>,
+    pending: ArrayVec<PendingMessage, MAX_PENDING_MESSAGES>,
+    forwarded: ArrayVec<MessageKey, FORWARDED_HISTORY>,
+    next_forwarded: usize,
+    packet_buffer: [u8; PACKET_BUFFER_LEN],
+}
+
+impl TransparentClock {
+    /// Create a new end-to-end transparent clock with the given number of
+    /// ports
+    ///
+    /// # Panics
+    /// When `number_of_ports` is larger than [`MAX_TRANSPARENT_CLOCK_PORTS`]
+    pub fn new(number_of_ports: usize) -> Self {
+        assert!(
+            number_of_ports <= MAX_TRANSPARENT_CLOCK_PORTS,
+            "A transparent clock supports at most {} ports",
+            MAX_TRANSPARENT_CLOCK_PORTS
+        );
+
+        Self {
+            number_of_ports,
+            peer_delay: None,
+            pending: ArrayVec::new(),
+            forwarded: ArrayVec::new(),
+            next_forwarded: 0,
+            packet_buffer: [0; PACKET_BUFFER_LEN],
+        }
+    }
+
+    /// Create a new peer-to-peer transparent clock, with a port for every
+    /// interval in `pdelay_intervals`
+    ///
+    /// Each port sends peer delay requests at its given interval, identifying
+    /// itself with `clock_identity` and the index of the port plus one as
+    /// port number. The user should call
+    /// [`TransparentClock::handle_peer_delay_timer`] for every port to start
+    /// the measurements.
+    ///
+    /// # Panics
+    /// When more than [`MAX_TRANSPARENT_CLOCK_PORTS`] intervals are given
+    pub fn new_p2p(clock_identity: ClockIdentity, pdelay_intervals: &[Interval]) -> Self {
+        let mut transparent_clock = Self::new(pdelay_intervals.len());
+        transparent_clock.peer_delay = Some((
+            clock_identity,
+            pdelay_intervals
+                .iter()
+                .map(|&interval| PeerDelayPort::new(interval))
+                .collect(),
+        ));
+        transparent_clock
+    }
+
+    /// The mean delay of the link on the given port, as measured by a
+    /// peer-to-peer transparent clock
+    pub fn mean_link_delay(&self, port: usize) -> Option<Duration> {
+        let (_, ports) = self.peer_delay.as_ref()?;
+        ports.get(port)?.mean_link_delay()
+    }
+
+    /// Handle a message received on the event interface of `port`, at
+    /// `timestamp`
+    ///
+    /// General messages received on the event interface are handled as
+    /// general messages.
+    pub fn handle_event_receive<'a>(
+        &'a mut self,
+        port: usize,
+        data: &[u8],
+        timestamp: Time,
+    ) -> TransparentClockActionIterator<'a> {
+        let Some((header, message_type, data)) = self.receive(port, data) else {
+            return TransparentClockActionIterator::empty();
+        };
+
+        match message_type {
+            MessageType::Sync => {
+                let one_step_sync = if header.two_step_flag {
+                    None
+                } else {
+                    match SyncMessage::deserialize_content(&data[HEADER_SIZE..]) {
+                        Ok(sync) => Some((header, sync)),
+                        Err(error) => {
+                            log::warn!("Could not parse sync message: {:?}", error);
+                            return TransparentClockActionIterator::empty();
+                        }
+                    }
+                };
+
+                let ingress_correction = match self.peer_delay {
+                    Some((_, ref ports)) => match ports[port].mean_link_delay() {
+                        Some(link_delay) => link_delay,
+                        None => {
+                            log::debug!("Link delay of port {} not known, dropping sync", port);
+                            return TransparentClockActionIterator::empty();
+                        }
+                    },
+                    None => Duration::ZERO,
+                };
+
+                self.forward_event(
+                    port,
+                    header,
+                    message_type,
+                    data,
+                    timestamp,
+                    ingress_correction,
+                    one_step_sync,
+                )
+            }
+            MessageType::DelayReq => self.forward_event(
+                port,
+                header,
+                message_type,
+                data,
+                timestamp,
+                Duration::ZERO,
+                None,
+            ),
+            MessageType::PDelayReq => self.handle_pdelay_req(port, header, timestamp),
+            MessageType::PDelayResp => self.handle_pdelay_resp(port, header, data, timestamp),
+            _ => self.handle_general(port, header, message_type, data),
+        }
+    }
+
+    /// Handle a message received on the general interface of `port`
+    pub fn handle_general_receive<'a>(
+        &'a mut self,
+        port: usize,
+        data: &[u8],
+    ) -> TransparentClockActionIterator<'a> {
+        let Some((header, message_type, data)) = self.receive(port, data) else {
+            return TransparentClockActionIterator::empty();
+        };
+
+        match message_type {
+            MessageType::Sync
+            | MessageType::DelayReq
+            | MessageType::PDelayReq
+            | MessageType::PDelayResp => {
+                log::warn!("Received event message over general interface");
+                TransparentClockActionIterator::empty()
+            }
+            _ => self.handle_general(port, header, message_type, data),
+        }
+    }
+
+    /// Inform the transparent clock about a transmit timestamp being available
+    ///
+    /// This should be done before handling messages that were received after
+    /// the [`TransparentClockAction::SendEvent`] was performed, as the follow
+    /// up and delay response messages that need the resulting residence time
+    /// can only be forwarded once it is known.
+    pub fn handle_send_timestamp(
+        &mut self,
+        context: TransparentTimestampContext,
+        timestamp: Time,
+    ) -> TransparentClockActionIterator<'_> {
+        match context.inner {
+            TransparentTimestampContextInner::Forwarded { key, port } => {
+                self.handle_forwarded_timestamp(key, port, timestamp)
+            }
+            TransparentTimestampContextInner::PDelayReq { port, id } => {
+                self.handle_pdelay_req_timestamp(port, id, timestamp)
+            }
+            TransparentTimestampContextInner::PDelayResp {
+                port,
+                request_header,
+            } => self.handle_pdelay_resp_timestamp(port, request_header, timestamp),
+        }
+    }
+
+    fn handle_forwarded_timestamp(
+        &mut self,
+        key: MessageKey,
+        port: usize,
+        timestamp: Time,
+    ) -> TransparentClockActionIterator<'_> {
+        let Some(pending) = self.pending.iter_mut().find(|p| p.key == key) else {
+            log::debug!("Send timestamp for a message that is no longer pending");
+            return TransparentClockActionIterator::empty();
+        };
+
+        let residence_time = timestamp - pending.ingress_time;
+        pending.residence_times[port] = Some(residence_time);
+
+        let Some((header, sync)) = pending.one_step_sync else {
+            return TransparentClockActionIterator::empty();
+        };
+
+        // The correction of the sync itself still applies, so the follow up only
+        // carries what we add
+        let follow_up = Message {
+            header: Header {
+                two_step_flag: false,
+                correction_field: (residence_time + pending.ingress_correction).into(),
+                ..header
+            },
+            body: MessageBody::FollowUp(FollowUpMessage {
+                precise_origin_timestamp: sync.origin_timestamp,
+            }),
+            suffix: Default::default(),
+        };
+
+        self.send_general(port, &follow_up)
+    }
+
+    /// Serialize a message we generated ourselves and send it on `port`
+    fn send_general(
+        &mut self,
+        port: usize,
+        message: &Message,
+    ) -> TransparentClockActionIterator<'_> {
+        let length = match message.serialize(&mut self.packet_buffer) {
+            Ok(length) => length,
+            Err(error) => {
+                log::error!("Statime bug: Could not serialize message: {:?}", error);
+                return TransparentClockActionIterator::empty();
+            }
+        };
+
+        let mut actions = ArrayVec::new();
+        actions.push(TransparentClockAction::SendGeneral {
+            port,
+            data: &self.packet_buffer[..length],
+            link_local: matches!(message.body, MessageBody::PDelayRespFollowUp(_)),
+        });
+        TransparentClockActionIterator::from(actions)
+    }
+
+    /// Parse the header of a received message, and drop it if it should not
+    /// be handled
+    fn receive<'b>(
+        &mut self,
+        port: usize,
+        data: &'b [u8],
+    ) -> Option<(Header, MessageType, &'b [u8])> {
+        if port >= self.number_of_ports {
+            log::error!("Received message on unknown port {}", port);
+            return None;
+        }
+
+        let deserialized = match Header::deserialize_header(data) {
+            Ok(deserialized) => deserialized,
+            Err(error) => {
+                log::warn!("Could not parse packet: {:?}", error);
+                return None;
+            }
+        };
+
+        // Drop any padding after the message
+        let data = data.get(..deserialized.message_length as usize)?;
+        if data.len() > MAX_DATA_LEN {
+            log::warn!("Message too long to forward");
+            return None;
+        }
+
+        let header = deserialized.header;
+        let message_type = deserialized.message_type;
+
+        if matches!(
+            message_type,
+            MessageType::PDelayReq | MessageType::PDelayResp | MessageType::PDelayRespFollowUp
+        ) {
+            // Peer delay messages are never forwarded, so they can't loop
+            return match self.peer_delay {
+                Some((clock_identity, _))
+                    if header.source_port_identity.clock_identity != clock_identity =>
+                {
+                    Some((header, message_type, data))
+                }
+                _ => None,
+            };
+        }
+
+        if self.peer_delay.is_some()
+            && matches!(message_type, MessageType::DelayReq | MessageType::DelayResp)
+        {
+            log::trace!("Dropping delay request or response in peer-to-peer mode");
+            return None;
+        }
+
+        let key = MessageKey::new(&header, message_type);
+        if self.forwarded.contains(&key) {
+            log::trace!("Dropping copy of forwarded message");
+            return None;
+        }
+
+        if self.forwarded.is_full() {
+            self.forwarded[self.next_forwarded] = key;
+        } else {
+            self.forwarded.push(key);
+        }
+        self.next_forwarded = (self.next_forwarded + 1) % FORWARDED_HISTORY;
+
+        Some((header, message_type, data))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn forward_event(
+        &mut self,
+        ingress: usize,
+        header: Header,
+        message_type: MessageType,
+        data: &[u8],
+        timestamp: Time,
+        ingress_correction: Duration,
+        one_step_sync: Option<(Header, SyncMessage)>,
+    ) -> TransparentClockActionIterator<'_> {
+        let key = MessageKey::new(&header, message_type);
+
+        if self.pending.is_full() {
+            // The oldest message is the least likely to still be needed
+            self.pending.remove(0);
+        }
+        self.pending.push(PendingMessage {
+            key,
+            ingress_time: timestamp,
+            ingress_correction,
+            residence_times: [None; MAX_TRANSPARENT_CLOCK_PORTS],
+            one_step_sync,
+        });
+
+        let buffer = &mut self.packet_buffer[..data.len()];
+        buffer.copy_from_slice(data);
+        if one_step_sync.is_some() {
+            buffer[TWO_STEP_FLAG.0] |= TWO_STEP_FLAG.1;
+        }
+        let data = &self.packet_buffer[..data.len()];
+
+        let actions = (0..self.number_of_ports)
+            .filter(|&port| port != ingress)
+            .map(|port| TransparentClockAction::SendEvent {
+                port,
+                context: TransparentTimestampContext {
+                    inner: TransparentTimestampContextInner::Forwarded { key, port },
+                },
+                data,
+                link_local: false,
+            })
+            .collect();
+        TransparentClockActionIterator::from(actions)
+    }
+
+    fn handle_general(
+        &mut self,
+        ingress: usize,
+        header: Header,
+        message_type: MessageType,
+        data: &[u8],
+    ) -> TransparentClockActionIterator<'_> {
+        match message_type {
+            MessageType::FollowUp => {
+                let sync_key = MessageKey::new(&header, MessageType::Sync);
+                match self.take_pending(sync_key) {
+                    Some(sync) => self.forward_follow_up(ingress, header, data, &sync),
+                    None => self.forward_general(ingress, data),
+                }
+            }
+            MessageType::DelayResp => {
+                let delay_resp = match DelayRespMessage::deserialize_content(&data[HEADER_SIZE..]) {
+                    Ok(delay_resp) => delay_resp,
+                    Err(error) => {
+                        log::warn!("Could not parse delay response: {:?}", error);
+                        return TransparentClockActionIterator::empty();
+                    }
+                };
+
+                let delay_req_key = MessageKey {
+                    message_type: MessageType::DelayReq,
+                    domain_number: header.domain_number,
+                    source_port_identity: delay_resp.requesting_port_identity,
+                    sequence_id: header.sequence_id,
+                };
+                let Some(delay_req) = self.take_pending(delay_req_key) else {
+                    return self.forward_general(ingress, data);
+                };
+
+                // The delay request went towards the master through the port the
+                // response came in on
+                match delay_req.residence_times[ingress] {
+                    Some(residence_time) => {
+                        let correction = add_correction(header.correction_field, residence_time);
+                        self.forward_general_corrected(ingress, data, correction)
+                    }
+                    None => {
+                        log::warn!("Residence time of delay request not known, dropping response");
+                        TransparentClockActionIterator::empty()
+                    }
+                }
+            }
+            MessageType::PDelayRespFollowUp => {
+                self.handle_pdelay_resp_follow_up(ingress, header, data)
+            }
+            _ => self.forward_general(ingress, data),
+        }
+    }
+
+    fn take_pending(&mut self, key: MessageKey) -> Option<PendingMessage> {
+        let index = self.pending.iter().position(|p| p.key == key)?;
+        Some(self.pending.remove(index))
+    }
+
+    fn forward_general(
+        &mut self,
+        ingress: usize,
+        data: &[u8],
+    ) -> TransparentClockActionIterator<'_> {
+        self.packet_buffer[..data.len()].copy_from_slice(data);
+        let data = &self.packet_buffer[..data.len()];
+
+        let actions = (0..self.number_of_ports)
+            .filter(|&port| port != ingress)
+            .map(|port| TransparentClockAction::SendGeneral {
+                port,
+                data,
+                link_local: false,
+            })
+            .collect();
+        TransparentClockActionIterator::from(actions)
+    }
+
+    fn forward_general_corrected(
+        &mut self,
+        ingress: usize,
+        data: &[u8],
+        correction: TimeInterval,
+    ) -> TransparentClockActionIterator<'_> {
+        let buffer = &mut self.packet_buffer[..data.len()];
+        buffer.copy_from_slice(data);
+        // Cannot fail, the range is within the header
+        correction.serialize(&mut buffer[CORRECTION_FIELD]).unwrap();
+        let data = &self.packet_buffer[..data.len()];
+
+        let actions = (0..self.number_of_ports)
+            .filter(|&port| port != ingress)
+            .map(|port| TransparentClockAction::SendGeneral {
+                port,
+                data,
+                link_local: false,
+            })
+            .collect();
+        TransparentClockActionIterator::from(actions)
+    }
+
+    fn forward_follow_up(
+        &mut self,
+        ingress: usize,
+        header: Header,
+        data: &[u8],
+        sync: &PendingMessage,
+    ) -> TransparentClockActionIterator<'_> {
+        if sync.one_step_sync.is_some() {
+            // We sent our own follow ups for this sync already
+            log::warn!("Dropping follow up for one-step sync");
+            return TransparentClockActionIterator::empty();
+        }
+
+        // Every port has its own residence time, so each gets its own copy
+        let mut actions = ArrayVec::new();
+        let mut buffer: &mut [u8] = &mut self.packet_buffer;
+        for port in (0..self.number_of_ports).filter(|&port| port != ingress) {
+            let Some(residence_time) = sync.residence_times[port] else {
+                log::warn!("Residence time of sync not known, dropping follow up");
+                continue;
+            };
+
+            if buffer.len() < data.len() {
+                log::error!("Follow up too large to forward to all ports");
+                break;
+            }
+            let (copy, rest) = core::mem::take(&mut buffer).split_at_mut(data.len());
+            buffer = rest;
+
+            copy.copy_from_slice(data);
+            let correction = add_correction(
+                header.correction_field,
+                residence_time + sync.ingress_correction,
+            );
+            // Cannot fail, the range is within the header
+            correction.serialize(&mut copy[CORRECTION_FIELD]).unwrap();
+
+            actions.push(TransparentClockAction::SendGeneral {
+                port,
+                data: copy,
+                link_local: false,
+            });
+        }
+
+        TransparentClockActionIterator::from(actions)
+    }
+}
+
+fn add_correction(correction: TimeInterval, residence_time: Duration) -> TimeInterval {
+    (Duration::from(correction) + residence_time).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datastructures::{
+        common::{ClockIdentity, WireTimestamp},
+        messages::DelayReqMessage,
+    };
+
+    const MASTER: PortIdentity = PortIdentity {
+        clock_identity: ClockIdentity([1, 0, 0, 0, 0, 0, 0, 0]),
+        port_number: 1,
+    };
+    const SLAVE: PortIdentity = PortIdentity {
+        clock_identity: ClockIdentity([2, 0, 0, 0, 0, 0, 0, 0]),
+        port_number: 1,
+    };
+
+    fn serialize(
+        source_port_identity: PortIdentity,
+        two_step_flag: bool,
+        correction: Duration,
+        body: MessageBody,
+    ) -> ([u8; MAX_DATA_LEN], usize) {
+        let message = Message {
+            header: Header {
+                source_port_identity,
+                sequence_id: 7,
+                two_step_flag,
+                correction_field: correction.into(),
+                ..Header::new(1)
+            },
+            body,
+            suffix: Default::default(),
+        };
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = message.serialize(&mut buffer).unwrap();
+        (buffer, length)
+    }
+
+    fn sync(two_step_flag: bool) -> ([u8; MAX_DATA_LEN], usize) {
+        let body = MessageBody::Sync(SyncMessage {
+            origin_timestamp: WireTimestamp {
+                seconds: 10,
+                nanos: 20,
+            },
+        });
+        serialize(MASTER, two_step_flag, Duration::from_nanos(5), body)
+    }
+
+    fn correction(data: &[u8]) -> Duration {
+        Message::deserialize(data)
+            .unwrap()
+            .header()
+            .correction_field
+            .into()
+    }
+
+    fn expect_events(
+        actions: TransparentClockActionIterator<'_>,
+    ) -> ArrayVec<TransparentTimestampContext, MAX_TRANSPARENT_CLOCK_PORTS> {
+        actions
+            .map(|action| match action {
+                TransparentClockAction::SendEvent { context, .. } => context,
+                _ => panic!("Unexpected action"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_forward_two_step_sync() {
+        let mut transparent_clock = TransparentClock::new(3);
+
+        let (data, length) = sync(true);
+        let mut ports = ArrayVec::<usize, 2>::new();
+        let mut contexts = ArrayVec::<_, 2>::new();
+        for action in
+            transparent_clock.handle_event_receive(0, &data[..length], Time::from_nanos(1000))
+        {
+            let TransparentClockAction::SendEvent {
+                port,
+                context,
+                data: sent,
+                ..
+            } = action
+            else {
+                panic!("Unexpected action");
+            };
+            assert_eq!(sent, &data[..length]);
+            ports.push(port);
+            contexts.push(context);
+        }
+        assert_eq!(ports.as_slice(), &[1, 2]);
+
+        let mut contexts = contexts.into_iter();
+        let mut actions = transparent_clock
+            .handle_send_timestamp(contexts.next().unwrap(), Time::from_nanos(1500));
+        assert!(actions.next().is_none());
+        drop(actions);
+        let mut actions = transparent_clock
+            .handle_send_timestamp(contexts.next().unwrap(), Time::from_nanos(1700));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let body = MessageBody::FollowUp(FollowUpMessage {
+            precise_origin_timestamp: WireTimestamp {
+                seconds: 10,
+                nanos: 20,
+            },
+        });
+        let (data, length) = serialize(MASTER, false, Duration::from_nanos(10), body);
+
+        let mut corrections = ArrayVec::<_, 2>::new();
+        for action in transparent_clock.handle_general_receive(0, &data[..length]) {
+            let TransparentClockAction::SendGeneral { port, data, .. } = action else {
+                panic!("Unexpected action");
+            };
+            corrections.push((port, correction(data)));
+        }
+        assert_eq!(
+            corrections.as_slice(),
+            &[
+                (1, Duration::from_nanos(510)),
+                (2, Duration::from_nanos(710))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_one_step_sync_gets_follow_up() {
+        let mut transparent_clock = TransparentClock::new(2);
+
+        let (data, length) = sync(false);
+        let mut actions =
+            transparent_clock.handle_event_receive(1, &data[..length], Time::from_nanos(1000));
+        let Some(TransparentClockAction::SendEvent {
+            port,
+            context,
+            data,
+            ..
+        }) = actions.next()
+        else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(port, 0);
+        assert!(actions.next().is_none());
+
+        // Forwarded as two-step, with the correction unchanged
+        let forwarded = Message::deserialize(data).unwrap();
+        assert!(forwarded.header().two_step_flag);
+        assert_eq!(
+            Duration::from(forwarded.header().correction_field),
+            Duration::from_nanos(5)
+        );
+        drop(actions);
+
+        let mut actions = transparent_clock.handle_send_timestamp(context, Time::from_nanos(1300));
+        let Some(TransparentClockAction::SendGeneral { port, data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(port, 0);
+        assert!(actions.next().is_none());
+
+        let follow_up = Message::deserialize(data).unwrap();
+        assert_eq!(follow_up.header().sequence_id, 7);
+        assert_eq!(follow_up.header().source_port_identity, MASTER);
+        assert_eq!(
+            Duration::from(follow_up.header().correction_field),
+            Duration::from_nanos(300)
+        );
+        let MessageBody::FollowUp(body) = follow_up.body else {
+            panic!("Expected follow up");
+        };
+        assert_eq!(
+            body.precise_origin_timestamp,
+            WireTimestamp {
+                seconds: 10,
+                nanos: 20
+            }
+        );
+    }
+
+    #[test]
+    fn test_delay_resp_correction() {
+        let mut transparent_clock = TransparentClock::new(3);
+
+        let body = MessageBody::DelayReq(DelayReqMessage {
+            origin_timestamp: Default::default(),
+        });
+        let (data, length) = serialize(SLAVE, false, Duration::ZERO, body);
+        let contexts = expect_events(transparent_clock.handle_event_receive(
+            1,
+            &data[..length],
+            Time::from_nanos(100),
+        ));
+        assert_eq!(contexts.len(), 2);
+        for (context, timestamp) in contexts.into_iter().zip([300, 600]) {
+            let mut actions =
+                transparent_clock.handle_send_timestamp(context, Time::from_nanos(timestamp));
+            assert!(actions.next().is_none());
+        }
+
+        let body = MessageBody::DelayResp(DelayRespMessage {
+            receive_timestamp: Default::default(),
+            requesting_port_identity: SLAVE,
+        });
+        let (data, length) = serialize(MASTER, false, Duration::from_nanos(3), body);
+
+        // The response came in on port 0, where the request left after 200ns
+        let mut ports = ArrayVec::<usize, 2>::new();
+        for action in transparent_clock.handle_general_receive(0, &data[..length]) {
+            let TransparentClockAction::SendGeneral { port, data, .. } = action else {
+                panic!("Unexpected action");
+            };
+            assert_eq!(correction(data), Duration::from_nanos(203));
+            ports.push(port);
+        }
+        assert_eq!(ports.as_slice(), &[1, 2]);
+    }
+
+    #[test]
+    fn test_drop_looped_and_link_local_messages() {
+        let mut transparent_clock = TransparentClock::new(2);
+
+        let (data, length) = sync(true);
+        let contexts = expect_events(transparent_clock.handle_event_receive(
+            0,
+            &data[..length],
+            Time::from_nanos(100),
+        ));
+        assert_eq!(contexts.len(), 1);
+
+        // The forwarded copy comes back in on the other port
+        let mut actions =
+            transparent_clock.handle_event_receive(1, &data[..length], Time::from_nanos(200));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        // Same layout as a delay request, only the message type differs
+        let body = MessageBody::DelayReq(DelayReqMessage {
+            origin_timestamp: Default::default(),
+        });
+        let (mut data, length) = serialize(SLAVE, false, Duration::ZERO, body);
+        data[0] = (data[0] & 0xf0) | MessageType::PDelayReq as u8;
+        let mut actions =
+            transparent_clock.handle_event_receive(0, &data[..length], Time::from_nanos(300));
+        assert!(actions.next().is_none());
+    }
+
+    #[test]
+    fn test_forward_general() {
+        let mut transparent_clock = TransparentClock::new(3);
+
+        let body = MessageBody::FollowUp(FollowUpMessage {
+            precise_origin_timestamp: Default::default(),
+        });
+        let (data, length) = serialize(MASTER, false, Duration::from_nanos(10), body);
+
+        // Without a matching sync the follow up is forwarded unchanged
+        let mut ports = ArrayVec::<usize, 2>::new();
+        for action in transparent_clock.handle_general_receive(2, &data[..length]) {
+            let TransparentClockAction::SendGeneral {
+                port, data: sent, ..
+            } = action
+            else {
+                panic!("Unexpected action");
+            };
+            assert_eq!(sent, &data[..length]);
+            ports.push(port);
+        }
+        assert_eq!(ports.as_slice(), &[0, 1]);
+    }
+}
@@ -0,0 +1,589 @@
+use arrayvec::ArrayVec;
+
+use super::{
+    TransparentClock, TransparentClockAction, TransparentClockActionIterator,
+    TransparentTimestampContext, TransparentTimestampContextInner,
+};
+use crate::{
+    datastructures::{
+        common::{ClockIdentity, PortIdentity},
+        messages::{
+            Header, Message, MessageBody, PDelayReqMessage, PDelayRespFollowUpMessage,
+            PDelayRespMessage,
+        },
+    },
+    port::sequence_id::SequenceIdGenerator,
+    time::{Duration, Interval, Time},
+};
+
+// Weight of a new measurement in the mean link delay, which smooths out the
+// noise of individual measurements.
+const LINK_DELAY_WEIGHT: f64 = 0.25;
+
+/// Peer delay measurement of a single port of a peer-to-peer transparent
+/// clock
+#[derive(Debug)]
+pub(super) struct PeerDelayPort {
+    interval: Interval,
+    seq_ids: SequenceIdGenerator,
+    measurement: Option<PeerDelayMeasurement>,
+    mean_link_delay: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PeerDelayMeasurement {
+    id: u16,
+    responder_identity: Option<PortIdentity>,
+    request_send_time: Option<Time>,
+    request_recv_time: Option<Time>,
+    response_send_time: Option<Time>,
+    response_recv_time: Option<Time>,
+}
+
+impl PeerDelayPort {
+    pub(super) fn new(interval: Interval) -> Self {
+        Self {
+            interval,
+            seq_ids: SequenceIdGenerator::new(),
+            measurement: None,
+            mean_link_delay: None,
+        }
+    }
+
+    pub(super) fn mean_link_delay(&self) -> Option<Duration> {
+        self.mean_link_delay
+    }
+
+    /// The measurement with the given sequence id, if it is still running
+    fn measurement(&mut self, id: u16) -> Option<&mut PeerDelayMeasurement> {
+        self.measurement.as_mut().filter(|m| m.id == id)
+    }
+
+    fn update_link_delay(&mut self) {
+        let Some(PeerDelayMeasurement {
+            request_send_time: Some(request_send_time),
+            request_recv_time: Some(request_recv_time),
+            response_send_time: Some(response_send_time),
+            response_recv_time: Some(response_recv_time),
+            ..
+        }) = self.measurement
+        else {
+            return;
+        };
+
+        let link_delay = ((response_recv_time - request_send_time)
+            - (response_send_time - request_recv_time))
+            / 2.0;
+        log::debug!("Measured link delay {:?}", link_delay);
+
+        self.mean_link_delay = Some(match self.mean_link_delay {
+            Some(mean) => mean + (link_delay - mean) * LINK_DELAY_WEIGHT,
+            None => link_delay,
+        });
+        self.measurement = None;
+    }
+}
+
+impl TransparentClock {
+    /// Handle the peer delay timer of `port` expiring
+    ///
+    /// This sends a new peer delay request. It does nothing for end-to-end
+    /// transparent clocks.
+    pub fn handle_peer_delay_timer(&mut self, port: usize) -> TransparentClockActionIterator<'_> {
+        let Some((clock_identity, ref mut ports)) = self.peer_delay else {
+            return TransparentClockActionIterator::empty();
+        };
+        let Some(peer_delay) = ports.get_mut(port) else {
+            log::error!("Peer delay timer for unknown port {}", port);
+            return TransparentClockActionIterator::empty();
+        };
+
+        let id = peer_delay.seq_ids.generate();
+        peer_delay.measurement = Some(PeerDelayMeasurement {
+            id,
+            responder_identity: None,
+            request_send_time: None,
+            request_recv_time: None,
+            response_send_time: None,
+            response_recv_time: None,
+        });
+        let duration = peer_delay.interval.as_core_duration();
+
+        let pdelay_req = Message {
+            header: Header {
+                source_port_identity: port_identity(clock_identity, port),
+                sequence_id: id,
+                log_message_interval: 0x7f,
+                ..Header::new(1)
+            },
+            body: MessageBody::PDelayReq(PDelayReqMessage {
+                origin_timestamp: Default::default(),
+            }),
+            suffix: Default::default(),
+        };
+
+        let length = match pdelay_req.serialize(&mut self.packet_buffer) {
+            Ok(length) => length,
+            Err(error) => {
+                log::error!("Could not serialize pdelay request: {:?}", error);
+                return TransparentClockActionIterator::empty();
+            }
+        };
+
+        let mut actions = ArrayVec::new();
+        actions.push(TransparentClockAction::ResetPeerDelayTimer { port, duration });
+        actions.push(TransparentClockAction::SendEvent {
+            port,
+            context: TransparentTimestampContext {
+                inner: TransparentTimestampContextInner::PDelayReq { port, id },
+            },
+            data: &self.packet_buffer[..length],
+            link_local: true,
+        });
+        TransparentClockActionIterator::from(actions)
+    }
+
+    pub(super) fn handle_pdelay_req(
+        &mut self,
+        port: usize,
+        header: Header,
+        timestamp: Time,
+    ) -> TransparentClockActionIterator<'_> {
+        let Some((clock_identity, _)) = self.peer_delay else {
+            return TransparentClockActionIterator::empty();
+        };
+
+        // We implement Option B from IEEE 1588-2019 page 202, like ordinary clocks
+        let pdelay_resp = Message {
+            header: Header {
+                two_step_flag: true,
+                correction_field: header.correction_field,
+                ..response_header(clock_identity, port, &header)
+            },
+            body: MessageBody::PDelayResp(PDelayRespMessage {
+                request_receive_timestamp: timestamp.into(),
+                requesting_port_identity: header.source_port_identity,
+            }),
+            suffix: Default::default(),
+        };
+
+        let length = match pdelay_resp.serialize(&mut self.packet_buffer) {
+            Ok(length) => length,
+            Err(error) => {
+                log::error!("Could not serialize pdelay response: {:?}", error);
+                return TransparentClockActionIterator::empty();
+            }
+        };
+
+        let mut actions = ArrayVec::new();
+        actions.push(TransparentClockAction::SendEvent {
+            port,
+            context: TransparentTimestampContext {
+                inner: TransparentTimestampContextInner::PDelayResp {
+                    port,
+                    request_header: header,
+                },
+            },
+            data: &self.packet_buffer[..length],
+            link_local: true,
+        });
+        TransparentClockActionIterator::from(actions)
+    }
+
+    pub(super) fn handle_pdelay_resp_timestamp(
+        &mut self,
+        port: usize,
+        request_header: Header,
+        timestamp: Time,
+    ) -> TransparentClockActionIterator<'_> {
+        let Some((clock_identity, _)) = self.peer_delay else {
+            return TransparentClockActionIterator::empty();
+        };
+
+        let follow_up = Message {
+            header: response_header(clock_identity, port, &request_header),
+            body: MessageBody::PDelayRespFollowUp(PDelayRespFollowUpMessage {
+                response_origin_timestamp: timestamp.into(),
+                requesting_port_identity: request_header.source_port_identity,
+            }),
+            suffix: Default::default(),
+        };
+
+        self.send_general(port, &follow_up)
+    }
+
+    pub(super) fn handle_pdelay_req_timestamp(
+        &mut self,
+        port: usize,
+        id: u16,
+        timestamp: Time,
+    ) -> TransparentClockActionIterator<'_> {
+        if let Some((_, ref mut ports)) = self.peer_delay {
+            match ports[port].measurement(id) {
+                Some(measurement) => {
+                    measurement.request_send_time = Some(timestamp);
+                    ports[port].update_link_delay();
+                }
+                None => log::warn!("Late timestamp for pdelay request ignored"),
+            }
+        }
+
+        TransparentClockActionIterator::empty()
+    }
+
+    pub(super) fn handle_pdelay_resp(
+        &mut self,
+        port: usize,
+        header: Header,
+        data: &[u8],
+        timestamp: Time,
+    ) -> TransparentClockActionIterator<'_> {
+        let Some((clock_identity, ref mut ports)) = self.peer_delay else {
+            return TransparentClockActionIterator::empty();
+        };
+
+        let message = match Message::deserialize(data) {
+            Ok(message) => message,
+            Err(error) => {
+                log::warn!("Could not parse pdelay response: {:?}", error);
+                return TransparentClockActionIterator::empty();
+            }
+        };
+        let MessageBody::PDelayResp(response) = message.body else {
+            return TransparentClockActionIterator::empty();
+        };
+
+        if response.requesting_port_identity != port_identity(clock_identity, port) {
+            return TransparentClockActionIterator::empty();
+        }
+
+        let Some(measurement) = ports[port].measurement(header.sequence_id) else {
+            log::warn!("Unexpected PDelayResp message");
+            return TransparentClockActionIterator::empty();
+        };
+
+        if !measurement.accept_responder(header.source_port_identity) {
+            return TransparentClockActionIterator::empty();
+        }
+
+        measurement.response_recv_time = Some(timestamp - Duration::from(header.correction_field));
+        measurement.request_recv_time = Some(response.request_receive_timestamp.into());
+        if !header.two_step_flag {
+            measurement.response_send_time = Some(response.request_receive_timestamp.into());
+        }
+        ports[port].update_link_delay();
+
+        TransparentClockActionIterator::empty()
+    }
+
+    pub(super) fn handle_pdelay_resp_follow_up(
+        &mut self,
+        port: usize,
+        header: Header,
+        data: &[u8],
+    ) -> TransparentClockActionIterator<'_> {
+        let Some((clock_identity, ref mut ports)) = self.peer_delay else {
+            return TransparentClockActionIterator::empty();
+        };
+
+        let message = match Message::deserialize(data) {
+            Ok(message) => message,
+            Err(error) => {
+                log::warn!("Could not parse pdelay response follow up: {:?}", error);
+                return TransparentClockActionIterator::empty();
+            }
+        };
+        let MessageBody::PDelayRespFollowUp(follow_up) = message.body else {
+            return TransparentClockActionIterator::empty();
+        };
+
+        if follow_up.requesting_port_identity != port_identity(clock_identity, port) {
+            return TransparentClockActionIterator::empty();
+        }
+
+        let Some(measurement) = ports[port].measurement(header.sequence_id) else {
+            log::warn!("Unexpected PDelayRespFollowUp message");
+            return TransparentClockActionIterator::empty();
+        };
+
+        if !measurement.accept_responder(header.source_port_identity) {
+            return TransparentClockActionIterator::empty();
+        }
+
+        measurement.response_send_time = Some(
+            Time::from(follow_up.response_origin_timestamp)
+                + Duration::from(header.correction_field),
+        );
+        ports[port].update_link_delay();
+
+        TransparentClockActionIterator::empty()
+    }
+}
+
+impl PeerDelayMeasurement {
+    /// Check that all responses to a request come from the same port
+    fn accept_responder(&mut self, identity: PortIdentity) -> bool {
+        match self.responder_identity {
+            Some(responder_identity) if responder_identity != identity => {
+                log::error!("Responses from multiple devices to peer delay request");
+                false
+            }
+            _ => {
+                self.responder_identity = Some(identity);
+                true
+            }
+        }
+    }
+}
+
+/// Header for a response to the peer delay request with `request_header`
+fn response_header(clock_identity: ClockIdentity, port: usize, request_header: &Header) -> Header {
+    Header {
+        sdo_id: request_header.sdo_id,
+        domain_number: request_header.domain_number,
+        source_port_identity: port_identity(clock_identity, port),
+        sequence_id: request_header.sequence_id,
+        log_message_interval: 0x7f,
+        ..Header::new(1)
+    }
+}
+
+fn port_identity(clock_identity: ClockIdentity, port: usize) -> PortIdentity {
+    PortIdentity {
+        clock_identity,
+        port_number: port as u16 + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datastructures::{
+        common::WireTimestamp,
+        messages::{FollowUpMessage, SyncMessage, MAX_DATA_LEN},
+    };
+
+    const OWN_IDENTITY: ClockIdentity = ClockIdentity([1, 0, 0, 0, 0, 0, 0, 0]);
+    const NEIGHBOUR: PortIdentity = PortIdentity {
+        clock_identity: ClockIdentity([2, 0, 0, 0, 0, 0, 0, 0]),
+        port_number: 3,
+    };
+
+    fn serialize(header: Header, body: MessageBody) -> ([u8; MAX_DATA_LEN], usize) {
+        let message = Message {
+            header,
+            body,
+            suffix: Default::default(),
+        };
+
+        let mut buffer = [0; MAX_DATA_LEN];
+        let length = message.serialize(&mut buffer).unwrap();
+        (buffer, length)
+    }
+
+    fn neighbour_header(sequence_id: u16, two_step_flag: bool) -> Header {
+        Header {
+            source_port_identity: NEIGHBOUR,
+            sequence_id,
+            two_step_flag,
+            ..Header::new(1)
+        }
+    }
+
+    fn wire_nanos(nanos: u32) -> WireTimestamp {
+        WireTimestamp { seconds: 0, nanos }
+    }
+
+    /// Measure a link delay of 200ns on port 0
+    fn measure_link_delay(transparent_clock: &mut TransparentClock) {
+        let mut actions = transparent_clock.handle_peer_delay_timer(0);
+        assert!(matches!(
+            actions.next(),
+            Some(TransparentClockAction::ResetPeerDelayTimer { port: 0, .. })
+        ));
+        let Some(TransparentClockAction::SendEvent {
+            port: 0,
+            context,
+            data,
+            link_local: true,
+        }) = actions.next()
+        else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+
+        let request = Message::deserialize(data).unwrap();
+        let id = request.header().sequence_id;
+        assert!(matches!(request.body, MessageBody::PDelayReq(_)));
+        assert_eq!(
+            request.header().source_port_identity,
+            port_identity(OWN_IDENTITY, 0)
+        );
+        drop(actions);
+
+        let mut actions = transparent_clock.handle_send_timestamp(context, Time::from_nanos(1000));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let (data, length) = serialize(
+            neighbour_header(id, true),
+            MessageBody::PDelayResp(PDelayRespMessage {
+                request_receive_timestamp: wire_nanos(5000),
+                requesting_port_identity: port_identity(OWN_IDENTITY, 0),
+            }),
+        );
+        let mut actions =
+            transparent_clock.handle_event_receive(0, &data[..length], Time::from_nanos(1600));
+        assert!(actions.next().is_none());
+        drop(actions);
+        assert_eq!(transparent_clock.mean_link_delay(0), None);
+
+        let (data, length) = serialize(
+            neighbour_header(id, false),
+            MessageBody::PDelayRespFollowUp(PDelayRespFollowUpMessage {
+                response_origin_timestamp: wire_nanos(5200),
+                requesting_port_identity: port_identity(OWN_IDENTITY, 0),
+            }),
+        );
+        let mut actions = transparent_clock.handle_general_receive(0, &data[..length]);
+        assert!(actions.next().is_none());
+    }
+
+    #[test]
+    fn test_link_delay_measurement() {
+        let one_second = Interval::from_log_2(0);
+        let mut transparent_clock = TransparentClock::new_p2p(OWN_IDENTITY, &[one_second; 2]);
+
+        measure_link_delay(&mut transparent_clock);
+        assert_eq!(
+            transparent_clock.mean_link_delay(0),
+            Some(Duration::from_nanos(200))
+        );
+        assert_eq!(transparent_clock.mean_link_delay(1), None);
+    }
+
+    #[test]
+    fn test_pdelay_response() {
+        let one_second = Interval::from_log_2(0);
+        let mut transparent_clock = TransparentClock::new_p2p(OWN_IDENTITY, &[one_second; 2]);
+
+        let (data, length) = serialize(
+            neighbour_header(12, false),
+            MessageBody::PDelayReq(PDelayReqMessage {
+                origin_timestamp: Default::default(),
+            }),
+        );
+        let mut actions =
+            transparent_clock.handle_event_receive(1, &data[..length], Time::from_nanos(700));
+        let Some(TransparentClockAction::SendEvent {
+            port: 1,
+            context,
+            data,
+            link_local: true,
+        }) = actions.next()
+        else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+
+        let response = Message::deserialize(data).unwrap();
+        assert_eq!(response.header().sequence_id, 12);
+        assert!(response.header().two_step_flag);
+        assert_eq!(
+            response.header().source_port_identity,
+            port_identity(OWN_IDENTITY, 1)
+        );
+        let MessageBody::PDelayResp(body) = response.body else {
+            panic!("Expected pdelay response");
+        };
+        assert_eq!(body.request_receive_timestamp, wire_nanos(700));
+        assert_eq!(body.requesting_port_identity, NEIGHBOUR);
+        drop(actions);
+
+        let mut actions = transparent_clock.handle_send_timestamp(context, Time::from_nanos(900));
+        let Some(TransparentClockAction::SendGeneral {
+            port: 1,
+            data,
+            link_local: true,
+        }) = actions.next()
+        else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+
+        let follow_up = Message::deserialize(data).unwrap();
+        assert_eq!(follow_up.header().sequence_id, 12);
+        let MessageBody::PDelayRespFollowUp(body) = follow_up.body else {
+            panic!("Expected pdelay response follow up");
+        };
+        assert_eq!(body.response_origin_timestamp, wire_nanos(900));
+        assert_eq!(body.requesting_port_identity, NEIGHBOUR);
+    }
+
+    #[test]
+    fn test_sync_gets_link_delay() {
+        let one_second = Interval::from_log_2(0);
+        let mut transparent_clock = TransparentClock::new_p2p(OWN_IDENTITY, &[one_second; 2]);
+
+        let master_header = |sequence_id, two_step_flag| Header {
+            source_port_identity: PortIdentity {
+                clock_identity: ClockIdentity([3, 0, 0, 0, 0, 0, 0, 0]),
+                port_number: 1,
+            },
+            sequence_id,
+            two_step_flag,
+            correction_field: Duration::from_nanos(5).into(),
+            ..Header::new(1)
+        };
+        let sync_body = MessageBody::Sync(SyncMessage {
+            origin_timestamp: wire_nanos(0),
+        });
+
+        // Without a link delay the correction can't be computed
+        let (data, length) = serialize(master_header(1, true), sync_body.clone());
+        let mut actions =
+            transparent_clock.handle_event_receive(0, &data[..length], Time::from_nanos(100));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        measure_link_delay(&mut transparent_clock);
+
+        let (data, length) = serialize(master_header(2, true), sync_body);
+        let mut actions =
+            transparent_clock.handle_event_receive(0, &data[..length], Time::from_nanos(10_000));
+        let Some(TransparentClockAction::SendEvent {
+            port: 1,
+            context,
+            link_local: false,
+            ..
+        }) = actions.next()
+        else {
+            panic!("Unexpected action");
+        };
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let mut actions =
+            transparent_clock.handle_send_timestamp(context, Time::from_nanos(10_300));
+        assert!(actions.next().is_none());
+        drop(actions);
+
+        let (data, length) = serialize(
+            master_header(2, false),
+            MessageBody::FollowUp(FollowUpMessage {
+                precise_origin_timestamp: wire_nanos(0),
+            }),
+        );
+        let mut actions = transparent_clock.handle_general_receive(0, &data[..length]);
+        let Some(TransparentClockAction::SendGeneral { port: 1, data, .. }) = actions.next() else {
+            panic!("Unexpected action");
+        };
+
+        // Own correction, residence time and link delay
+        let follow_up = Message::deserialize(data).unwrap();
+        assert_eq!(
+            Duration::from(follow_up.header().correction_field),
+            Duration::from_nanos(505)
+        );
+    }
+}
@@ -4,7 +4,7 @@ set -eo pipefail
 
 docs_dir="docs/man"
 output_dir="${1:-"docs/precompiled/man"}"
-files=("statime.8" "statime-metrics-exporter.8" "statime.toml.5")
+files=("statime.8" "statime-metrics-exporter.8" "statime-ctl.8" "statime.toml.5")
 
 mkdir -p "$output_dir"
 